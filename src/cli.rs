@@ -0,0 +1,422 @@
+use crate::controller::audio_backend_proxy::AudioBackendKind;
+use crate::controller::ribble_controller::HeadlessController;
+use crate::controller::{Progress, RibbleMessage};
+use crate::utils::errors::RibbleError;
+use crate::utils::transcript_formats::TranscriptFormat;
+use std::ffi::OsString;
+use std::io::{ErrorKind, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub(crate) const USAGE: &str = "\
+Usage:
+    ribble [LAUNCH OPTIONS]                  Launch the app.
+    ribble transcribe <FILE> [OPTIONS]       Transcribe an audio file without opening a window.
+
+Launch options:
+    --audio-backend <BACKEND>    Capture audio with sdl2 (the default) or cpal (if built with
+                                 the cpal-backend feature).
+    --audio-file <WAV>           Replay a wav file in real time instead of using a microphone.
+
+Options:
+    -o, --output <PATH>      Write the transcript to PATH instead of stdout.
+    -f, --format <FORMAT>    txt, srt, vtt or json. Defaults to the output file's extension,
+                             otherwise txt.
+    -h, --help               Print this message.
+
+The model and transcription settings are the ones last saved by the app.
+Progress is printed to stderr.";
+
+// NOTE: THIS IS IN MILLISECONDS
+const PROGRESS_REFRESH_RATE: u64 = 100;
+
+pub(crate) enum CliCommand {
+    Help,
+    Launch(LaunchArgs),
+    Transcribe(TranscribeArgs),
+}
+
+pub(crate) struct LaunchArgs {
+    audio_backend: AudioBackendKind,
+}
+
+impl LaunchArgs {
+    pub(crate) fn audio_backend(self) -> AudioBackendKind {
+        self.audio_backend
+    }
+}
+
+pub(crate) struct TranscribeArgs {
+    audio_file: PathBuf,
+    output: Option<PathBuf>,
+    format: Option<TranscriptFormat>,
+}
+
+impl TranscribeArgs {
+    // An explicit format wins, then the output file's extension, then plain text.
+    fn format(&self) -> TranscriptFormat {
+        self.format
+            .or_else(|| self.output.as_deref().and_then(TranscriptFormat::from_path))
+            .unwrap_or_default()
+    }
+}
+
+impl CliCommand {
+    // Returns None if there's no known subcommand, in which case the app should launch as normal.
+    // NOTE: unknown arguments are deliberately ignored here; some platforms (e.g. older macOS)
+    // pass their own flags when launching apps.
+    pub(crate) fn from_args(
+        args: impl IntoIterator<Item = OsString>,
+    ) -> Result<Option<Self>, RibbleError> {
+        let mut args = args.into_iter();
+        let Some(subcommand) = args.next() else {
+            return Ok(None);
+        };
+
+        match subcommand.to_str() {
+            Some("-h" | "--help" | "help") => Ok(Some(CliCommand::Help)),
+            Some("transcribe") => Self::parse_transcribe(args).map(Some),
+            Some(flag) if flag.starts_with("--audio-") => {
+                Self::parse_launch(std::iter::once(subcommand).chain(args)).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // As above, unknown arguments are ignored.
+    fn parse_launch(mut args: impl Iterator<Item = OsString>) -> Result<CliCommand, RibbleError> {
+        let mut audio_backend = AudioBackendKind::default();
+        while let Some(arg) = args.next() {
+            match arg.to_str() {
+                Some("-h" | "--help") => return Ok(CliCommand::Help),
+                Some("--audio-backend") => {
+                    let name = args
+                        .next()
+                        .ok_or_else(|| usage_error("Missing backend after --audio-backend."))?;
+                    audio_backend = name
+                        .to_str()
+                        .and_then(AudioBackendKind::from_name)
+                        .ok_or_else(|| {
+                            let message =
+                                format!("Unknown audio backend: {}", name.to_string_lossy());
+                            usage_error(&message)
+                        })?;
+                }
+                Some("--audio-file") => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| usage_error("Missing wav file after --audio-file."))?;
+                    let path = PathBuf::from(path);
+                    if !path.is_file() {
+                        return Err(usage_error(&format!(
+                            "Audio file: {} does not exist.",
+                            path.display()
+                        )));
+                    }
+                    audio_backend = AudioBackendKind::File(path);
+                }
+                _ => {}
+            }
+        }
+        Ok(CliCommand::Launch(LaunchArgs { audio_backend }))
+    }
+
+    fn parse_transcribe(
+        mut args: impl Iterator<Item = OsString>,
+    ) -> Result<CliCommand, RibbleError> {
+        let mut audio_file = None;
+        let mut output = None;
+        let mut format = None;
+
+        while let Some(arg) = args.next() {
+            match arg.to_str() {
+                Some("-h" | "--help") => return Ok(CliCommand::Help),
+                Some("-o" | "--output") => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| usage_error("Missing path after --output."))?;
+                    output = Some(PathBuf::from(path));
+                }
+                Some("-f" | "--format") => {
+                    let extension = args
+                        .next()
+                        .ok_or_else(|| usage_error("Missing format after --format."))?;
+                    let parsed = extension
+                        .to_str()
+                        .and_then(TranscriptFormat::from_extension)
+                        .ok_or_else(|| {
+                            let message =
+                                format!("Unknown format: {}", extension.to_string_lossy());
+                            usage_error(&message)
+                        })?;
+                    format = Some(parsed);
+                }
+                Some(flag) if flag.starts_with('-') => {
+                    return Err(usage_error(&format!("Unknown option: {flag}")));
+                }
+                _ if audio_file.is_some() => {
+                    return Err(usage_error(
+                        "Only one audio file can be transcribed at a time.",
+                    ));
+                }
+                _ => audio_file = Some(PathBuf::from(arg)),
+            }
+        }
+
+        let audio_file = audio_file.ok_or_else(|| usage_error("Missing audio file."))?;
+        Ok(CliCommand::Transcribe(TranscribeArgs {
+            audio_file,
+            output,
+            format,
+        }))
+    }
+}
+
+fn usage_error(message: &str) -> RibbleError {
+    RibbleError::Core(format!("{message}\n\n{USAGE}"))
+}
+
+pub(crate) fn run_transcribe(
+    controller: HeadlessController,
+    args: TranscribeArgs,
+) -> Result<(), RibbleError> {
+    if !args.audio_file.is_file() {
+        return Err(RibbleError::IOError(std::io::Error::new(
+            ErrorKind::NotFound,
+            format!("Audio file: {} does not exist.", args.audio_file.display()),
+        )));
+    }
+
+    let format = args.format();
+
+    // Run the transcription on a separate thread so that this one can report progress.
+    let worker_controller = controller.clone();
+    let audio_file = args.audio_file.clone();
+    let worker = std::thread::spawn(move || worker_controller.transcribe_file_blocking(audio_file));
+
+    let mut reporter = ProgressReporter::new();
+    while !worker.is_finished() {
+        reporter.report(&controller);
+        std::thread::sleep(Duration::from_millis(PROGRESS_REFRESH_RATE));
+    }
+    reporter.finish();
+
+    let message = worker
+        .join()
+        .map_err(|e| RibbleError::ThreadPanic(format!("{e:#?}")))??;
+    if let RibbleMessage::Console(message) = message {
+        eprintln!("{}", message.message());
+    }
+
+    let transcript = controller.format_transcription(format)?;
+    match args.output {
+        Some(out_path) => {
+            std::fs::write(out_path.as_path(), transcript)?;
+            eprintln!("Transcription saved to: {}", out_path.display());
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(transcript.as_bytes())?;
+            if !transcript.ends_with('\n') {
+                stdout.write_all(b"\n")?;
+            }
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+// Prints the running progress jobs to stderr.
+// On a terminal, this redraws a single line; otherwise (e.g. a log file) it prints a line per
+// change.
+struct ProgressReporter {
+    is_terminal: bool,
+    last_line: String,
+    progress_buffer: Vec<Progress>,
+}
+
+impl ProgressReporter {
+    fn new() -> Self {
+        Self {
+            is_terminal: std::io::stderr().is_terminal(),
+            last_line: String::new(),
+            progress_buffer: vec![],
+        }
+    }
+
+    fn report(&mut self, controller: &HeadlessController) {
+        controller.try_read_progress_metadata(&mut self.progress_buffer);
+        let line = self
+            .progress_buffer
+            .iter()
+            .map(|progress| match progress.progress() {
+                Some(normalized) => {
+                    let percent = (normalized * 100.0).round() as u32;
+                    format!("{}: {percent}%", progress.job_name())
+                }
+                None => format!("{}...", progress.job_name()),
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        if line.is_empty() || line == self.last_line {
+            return;
+        }
+
+        if self.is_terminal {
+            // Carriage return + clear the line.
+            eprint!("\r\x1b[2K{line}");
+        } else {
+            eprintln!("{line}");
+        }
+        self.last_line = line;
+    }
+
+    fn finish(&self) {
+        if self.is_terminal && !self.last_line.is_empty() {
+            eprintln!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<CliCommand>, RibbleError> {
+        CliCommand::from_args(args.iter().map(OsString::from))
+    }
+
+    fn parse_transcribe_args(args: &[&str]) -> TranscribeArgs {
+        match parse(args) {
+            Ok(Some(CliCommand::Transcribe(args))) => args,
+            _ => panic!("Expected a transcribe command from: {args:?}"),
+        }
+    }
+
+    fn parse_error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("Expected an error from: {args:?}"),
+        }
+    }
+
+    #[test]
+    fn no_arguments_launch_the_app() {
+        assert!(matches!(parse(&[]), Ok(None)));
+    }
+
+    #[test]
+    fn unknown_arguments_are_ignored() {
+        assert!(matches!(parse(&["-psn_0_12345"]), Ok(None)));
+        assert!(matches!(
+            parse(&["--audio-backend", "sdl2", "--verbose"]),
+            Ok(Some(CliCommand::Launch(_)))
+        ));
+    }
+
+    #[test]
+    fn help_is_recognized_everywhere() {
+        for args in [
+            &["-h"][..],
+            &["--help"],
+            &["help"],
+            &["transcribe", "--help"],
+            &["transcribe", "audio.wav", "-h"],
+            &["--audio-backend", "sdl2", "--help"],
+        ] {
+            assert!(
+                matches!(parse(args), Ok(Some(CliCommand::Help))),
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn transcribe_with_an_output_and_format() {
+        let args =
+            parse_transcribe_args(&["transcribe", "audio.wav", "-o", "out.txt", "-f", "srt"]);
+        assert_eq!(args.audio_file, PathBuf::from("audio.wav"));
+        assert_eq!(args.output, Some(PathBuf::from("out.txt")));
+        // An explicit format wins over the output's extension.
+        assert_eq!(args.format(), TranscriptFormat::Srt);
+
+        let args = parse_transcribe_args(&[
+            "transcribe",
+            "--format",
+            "json",
+            "--output",
+            "out.txt",
+            "audio.wav",
+        ]);
+        assert_eq!(args.audio_file, PathBuf::from("audio.wav"));
+        assert_eq!(args.format(), TranscriptFormat::Json);
+    }
+
+    #[test]
+    fn the_format_falls_back_to_the_output_extension_then_plain_text() {
+        let args = parse_transcribe_args(&["transcribe", "audio.wav", "-o", "out.vtt"]);
+        assert_eq!(args.format, None);
+        assert_eq!(args.format(), TranscriptFormat::WebVtt);
+
+        let args = parse_transcribe_args(&["transcribe", "audio.wav", "-o", "out"]);
+        assert_eq!(args.format(), TranscriptFormat::PlainText);
+
+        let args = parse_transcribe_args(&["transcribe", "audio.wav"]);
+        assert_eq!(args.output, None);
+        assert_eq!(args.format(), TranscriptFormat::PlainText);
+    }
+
+    #[test]
+    fn transcribe_rejects_bad_arguments() {
+        assert!(
+            parse_error(&["transcribe", "audio.wav", "--verbose"])
+                .contains("Unknown option: --verbose")
+        );
+        assert!(parse_error(&["transcribe", "a.wav", "b.wav"]).contains("Only one audio file"));
+        assert!(parse_error(&["transcribe"]).contains("Missing audio file."));
+        assert!(parse_error(&["transcribe", "-o", "out.txt"]).contains("Missing audio file."));
+        assert!(parse_error(&["transcribe", "audio.wav", "-o"]).contains("Missing path"));
+        assert!(parse_error(&["transcribe", "audio.wav", "-f"]).contains("Missing format"));
+        assert!(
+            parse_error(&["transcribe", "audio.wav", "-f", "docx"])
+                .contains("Unknown format: docx")
+        );
+    }
+
+    #[test]
+    fn usage_errors_include_the_usage() {
+        assert!(parse_error(&["transcribe"]).ends_with(USAGE));
+    }
+
+    #[test]
+    fn launch_with_an_audio_backend() {
+        let Ok(Some(CliCommand::Launch(args))) = parse(&["--audio-backend", "sdl2"]) else {
+            panic!("Expected a launch command.");
+        };
+        assert_eq!(args.audio_backend(), AudioBackendKind::Sdl2);
+
+        assert!(
+            parse_error(&["--audio-backend", "pulse"]).contains("Unknown audio backend: pulse")
+        );
+        assert!(parse_error(&["--audio-backend"]).contains("Missing backend"));
+    }
+
+    #[test]
+    fn launch_with_an_audio_file() {
+        let audio_file = tempfile::NamedTempFile::new().unwrap();
+        let path = audio_file.path().to_str().unwrap();
+        let Ok(Some(CliCommand::Launch(args))) = parse(&["--audio-file", path]) else {
+            panic!("Expected a launch command.");
+        };
+        assert_eq!(
+            args.audio_backend(),
+            AudioBackendKind::File(audio_file.path().to_path_buf())
+        );
+
+        let missing = audio_file.path().with_extension("missing");
+        let missing = missing.to_str().unwrap();
+        assert!(parse_error(&["--audio-file", missing]).contains("does not exist"));
+        assert!(parse_error(&["--audio-file"]).contains("Missing wav file"));
+    }
+}
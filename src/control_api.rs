@@ -0,0 +1,854 @@
+// A small local control API so that other programs (scripts, editor plugins) can drive Ribble.
+//
+// Protocol: newline-delimited JSON over TCP on 127.0.0.1. Each request is a single JSON object
+// on its own line, with a "command" field and a "token" field; each response is a single JSON
+// object on its own line with an "ok" field, and an "error" field if ok is false. e.g.
+//
+//   -> {"token": "...", "command": "set_audio_file", "path": "/home/me/interview.wav"}
+//   <- {"ok": true}
+//   -> {"token": "...", "command": "start_offline"}
+//   <- {"ok": true}
+//   -> {"token": "...", "command": "transcript", "format": "srt"}
+//   <- {"ok": true, "contents": "..."}
+//   -> {"token": "...", "command": "subscribe"}
+//   <- {"ok": true}
+//   <- {"event": "snapshot", "running": true, "confirmed": "...", "segments": ["..."]}
+//   ...
+//
+// Once subscribed, the connection only streams snapshot events until the client disconnects.
+//
+// NOTE: anything that can reach the loopback interface can connect, including web pages (via
+// fetch). So:
+// - The token is regenerated every time the server starts, and written to CONTROL_API_TOKEN_FILE
+//   in the data directory, readable only by the user. Clients read it from there.
+// - A request with a missing/wrong token gets an error, and the connection is closed.
+// - A line that isn't JSON (e.g. an HTTP request line) closes the connection without a reply.
+// - Nothing is written to disk on a client's behalf; transcripts are returned as text.
+use crate::controller::ribble_controller::RibbleController;
+use crate::utils::control_api_configs::ControlApiConfigs;
+use crate::utils::errors::RibbleError;
+use crate::utils::transcript_formats::TranscriptFormat;
+use ribble_whisper::transcriber::TranscriptionSnapshot;
+use serde_json::{Value, json};
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub(crate) const CONTROL_API_TOKEN_FILE: &str = "control_api_token";
+// In bytes; the token itself is hex.
+const TOKEN_LEN: usize = 32;
+// In bytes. Requests are small; a client that sends a longer line is dropped.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+// NOTE: THESE ARE IN MILLISECONDS
+// How often the listener/clients check whether the server is shutting down.
+const SHUTDOWN_POLL_RATE: u64 = 100;
+// How often subscribers are checked for a new snapshot.
+const SNAPSHOT_POLL_RATE: u64 = 100;
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum ControlRequest {
+    Status,
+    StartRealtime,
+    SetAudioFile {
+        path: PathBuf,
+    },
+    // If a path is given, it's set as the audio file first.
+    StartOffline {
+        #[serde(default)]
+        path: Option<PathBuf>,
+    },
+    Stop,
+    Snapshot,
+    // The format is a file extension (txt, srt, vtt, json); plain text if it's missing.
+    Transcript {
+        #[serde(default)]
+        format: Option<String>,
+    },
+    Subscribe,
+}
+
+// What the control API drives: the RibbleController in the app, a stub in the tests.
+pub(crate) trait ControlTarget: Clone + Send + 'static {
+    fn read_current_audio_file_path(&self) -> Arc<Option<PathBuf>>;
+    fn set_audio_file_path(&self, path: PathBuf);
+    fn realtime_running(&self) -> bool;
+    fn offline_running(&self) -> bool;
+    fn batch_running(&self) -> bool;
+    fn recorder_running(&self) -> bool;
+    fn transcriber_running(&self) -> bool;
+    fn start_realtime_transcription(&self);
+    fn start_offline_transcription(&self);
+    fn stop_transcription(&self);
+    fn read_transcription_snapshot(&self) -> Arc<TranscriptionSnapshot>;
+    fn format_transcription(&self, format: TranscriptFormat) -> Result<String, RibbleError>;
+}
+
+impl ControlTarget for RibbleController {
+    fn read_current_audio_file_path(&self) -> Arc<Option<PathBuf>> {
+        RibbleController::read_current_audio_file_path(self)
+    }
+    fn set_audio_file_path(&self, path: PathBuf) {
+        RibbleController::set_audio_file_path(self, path);
+    }
+    fn realtime_running(&self) -> bool {
+        RibbleController::realtime_running(self)
+    }
+    fn offline_running(&self) -> bool {
+        RibbleController::offline_running(self)
+    }
+    fn batch_running(&self) -> bool {
+        RibbleController::batch_running(self)
+    }
+    fn recorder_running(&self) -> bool {
+        RibbleController::recorder_running(self)
+    }
+    fn transcriber_running(&self) -> bool {
+        RibbleController::transcriber_running(self)
+    }
+    fn start_realtime_transcription(&self) {
+        RibbleController::start_realtime_transcription(self);
+    }
+    fn start_offline_transcription(&self) {
+        RibbleController::start_offline_transcription(self);
+    }
+    fn stop_transcription(&self) {
+        RibbleController::stop_transcription(self);
+    }
+    fn read_transcription_snapshot(&self) -> Arc<TranscriptionSnapshot> {
+        RibbleController::read_transcription_snapshot(self)
+    }
+    fn format_transcription(&self, format: TranscriptFormat) -> Result<String, RibbleError> {
+        RibbleController::format_transcription(self, format)
+    }
+}
+
+pub(crate) struct ControlServer {
+    configs: ControlApiConfigs,
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    listener_thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    // The token is written to the token_directory (i.e. the data directory).
+    pub(crate) fn start<C: ControlTarget>(
+        controller: C,
+        configs: ControlApiConfigs,
+        token_directory: &Path,
+    ) -> Result<Self, RibbleError> {
+        let listener = TcpListener::bind(configs.socket_addr())?;
+        Self::from_listener(controller, configs, listener, token_directory)
+    }
+
+    fn from_listener<C: ControlTarget>(
+        controller: C,
+        configs: ControlApiConfigs,
+        listener: TcpListener,
+        token_directory: &Path,
+    ) -> Result<Self, RibbleError> {
+        let token: Arc<str> = Arc::from(generate_token()?);
+        write_token_file(
+            token_directory.join(CONTROL_API_TOKEN_FILE).as_path(),
+            &token,
+        )?;
+
+        // Non-blocking so that the listener can notice a shutdown.
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread_shutdown = Arc::clone(&shutdown);
+        let listener_thread =
+            std::thread::spawn(move || run_listener(listener, controller, token, thread_shutdown));
+
+        log::info!("Control API listening on: {local_addr}");
+        Ok(Self {
+            configs,
+            local_addr,
+            shutdown,
+            listener_thread: Some(listener_thread),
+        })
+    }
+
+    pub(crate) fn configs(&self) -> ControlApiConfigs {
+        self.configs
+    }
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        log::info!("Stopping control API.");
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(handle) = self.listener_thread.take() {
+            if handle.join().is_err() {
+                log::error!("Control API listener thread panicked.");
+            }
+            log::info!("Control API listener thread joined.");
+        }
+    }
+}
+
+fn generate_token() -> Result<String, RibbleError> {
+    let mut bytes = [0u8; TOKEN_LEN];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| RibbleError::Core(format!("Failed to generate a control API token: {e}")))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+// NOTE: on Windows, the data directory is already private to the user.
+fn write_token_file(path: &Path, token: &str) -> Result<(), RibbleError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // The mode only applies to new files; an old token file could have been left readable.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(token.as_bytes())?;
+    Ok(())
+}
+
+// Compares the whole token, so that the time taken doesn't leak how much of it matched.
+fn token_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn run_listener<C: ControlTarget>(
+    listener: TcpListener,
+    controller: C,
+    token: Arc<str>,
+    shutdown: Arc<AtomicBool>,
+) {
+    let mut clients: Vec<JoinHandle<()>> = vec![];
+    while !shutdown.load(Ordering::Acquire) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                log::info!("Control API client connected: {peer}");
+                let client_controller = controller.clone();
+                let client_token = Arc::clone(&token);
+                let client_shutdown = Arc::clone(&shutdown);
+                clients.push(std::thread::spawn(move || {
+                    if let Err(e) =
+                        handle_client(stream, client_controller, &client_token, client_shutdown)
+                    {
+                        log::warn!(
+                            "Control API client error.\nError: {e}\nError source: {:#?}",
+                            e.source()
+                        );
+                    }
+                }));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(SHUTDOWN_POLL_RATE));
+            }
+            Err(e) => {
+                log::warn!(
+                    "Control API failed to accept connection.\nError: {e}\nError source: {:#?}",
+                    e.source()
+                );
+            }
+        }
+        clients.retain(|client| !client.is_finished());
+    }
+
+    // The clients poll the same shutdown flag, so these should all finish quickly.
+    for client in clients {
+        if client.join().is_err() {
+            log::error!("Control API client thread panicked.");
+        }
+    }
+}
+
+fn handle_client<C: ControlTarget>(
+    stream: TcpStream,
+    controller: C,
+    token: &str,
+    shutdown: Arc<AtomicBool>,
+) -> Result<(), RibbleError> {
+    // Accepted sockets may inherit non-blocking mode from the listener on some platforms.
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_millis(SHUTDOWN_POLL_RATE)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+
+    while !shutdown.load(Ordering::Acquire) {
+        // NOTE: the limit counts any partial line already read, so that a client can't make this
+        // buffer an unbounded line (the token hasn't been checked yet).
+        let limit = MAX_LINE_LEN.saturating_sub(line.len()) as u64;
+        match reader.by_ref().take(limit).read_line(&mut line) {
+            // The client hung up.
+            Ok(0) => return Ok(()),
+            Ok(_) if !line.ends_with('\n') && line.len() >= MAX_LINE_LEN => {
+                log::warn!("Control API client sent an oversized request; closing the connection.");
+                return Ok(());
+            }
+            Ok(_) => {}
+            // NOTE: a timed-out read keeps any partial line in the buffer; it gets completed on
+            // the next read.
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        }
+
+        if line.trim().is_empty() {
+            line.clear();
+            continue;
+        }
+
+        // Not a control API client (most likely HTTP); drop it without answering.
+        let Ok(mut message) = serde_json::from_str::<Value>(line.trim()) else {
+            log::warn!("Control API client sent a non-JSON request; closing the connection.");
+            return Ok(());
+        };
+        line.clear();
+
+        let given_token = message.get("token").and_then(Value::as_str).unwrap_or("");
+        if !token_matches(token, given_token) {
+            log::warn!("Control API client sent a missing/invalid token; closing the connection.");
+            let response = json!({ "ok": false, "error": "Invalid or missing token." });
+            return write_message(&mut writer, &response);
+        }
+        if let Value::Object(fields) = &mut message {
+            fields.remove("token");
+        }
+
+        match serde_json::from_value::<ControlRequest>(message) {
+            Ok(ControlRequest::Subscribe) => {
+                write_message(&mut writer, &json!({ "ok": true }))?;
+                return stream_snapshots(&mut writer, &controller, &shutdown);
+            }
+            Ok(request) => {
+                let response = match handle_request(request, &controller) {
+                    Ok(response) => response,
+                    Err(e) => json!({ "ok": false, "error": e.to_string() }),
+                };
+                write_message(&mut writer, &response)?;
+            }
+            Err(e) => {
+                let response = json!({ "ok": false, "error": format!("Invalid request: {e}") });
+                write_message(&mut writer, &response)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_request<C: ControlTarget>(
+    request: ControlRequest,
+    controller: &C,
+) -> Result<Value, RibbleError> {
+    match request {
+        ControlRequest::Status => {
+            let audio_file = controller.read_current_audio_file_path();
+            Ok(json!({
+                "ok": true,
+                "realtime_running": controller.realtime_running(),
+                "offline_running": controller.offline_running(),
+                "batch_running": controller.batch_running(),
+                "recorder_running": controller.recorder_running(),
+                "audio_file": audio_file.as_ref().as_ref().map(|path| path.display().to_string()),
+            }))
+        }
+        ControlRequest::StartRealtime => {
+            ensure_transcriber_idle(controller)?;
+            // The recorder already has the microphone open.
+            if controller.recorder_running() {
+                return Err(RibbleError::Core(
+                    "Cannot start real-time transcription while recording.".to_string(),
+                ));
+            }
+            controller.start_realtime_transcription();
+            Ok(json!({ "ok": true }))
+        }
+        ControlRequest::SetAudioFile { path } => {
+            ensure_audio_file(&path)?;
+            controller.set_audio_file_path(path);
+            Ok(json!({ "ok": true }))
+        }
+        ControlRequest::StartOffline { path } => {
+            ensure_transcriber_idle(controller)?;
+            match path {
+                Some(path) => {
+                    ensure_audio_file(&path)?;
+                    controller.set_audio_file_path(path);
+                }
+                None if controller.read_current_audio_file_path().is_none() => {
+                    return Err(RibbleError::Core("Audio file path not loaded.".to_string()));
+                }
+                None => {}
+            }
+            controller.start_offline_transcription();
+            Ok(json!({ "ok": true }))
+        }
+        ControlRequest::Stop => {
+            controller.stop_transcription();
+            Ok(json!({ "ok": true }))
+        }
+        ControlRequest::Snapshot => {
+            let mut response = snapshot_json(&controller.read_transcription_snapshot());
+            response["ok"] = json!(true);
+            response["running"] = json!(controller.transcriber_running());
+            Ok(response)
+        }
+        ControlRequest::Transcript { format } => {
+            let format = match format {
+                Some(extension) => {
+                    TranscriptFormat::from_extension(&extension).ok_or_else(|| {
+                        RibbleError::Core(format!("Unknown transcript format: {extension}"))
+                    })?
+                }
+                None => TranscriptFormat::default(),
+            };
+            let contents = controller.format_transcription(format)?;
+            Ok(json!({ "ok": true, "contents": contents }))
+        }
+        ControlRequest::Subscribe => unreachable!("Subscriptions are handled by the client loop."),
+    }
+}
+
+fn ensure_transcriber_idle<C: ControlTarget>(controller: &C) -> Result<(), RibbleError> {
+    if controller.transcriber_running() {
+        Err(RibbleError::Core(
+            "Transcriber already running.".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn ensure_audio_file(path: &Path) -> Result<(), RibbleError> {
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(RibbleError::IOError(std::io::Error::new(
+            ErrorKind::NotFound,
+            format!("Audio file: {} does not exist.", path.display()),
+        )))
+    }
+}
+
+fn snapshot_json(snapshot: &TranscriptionSnapshot) -> Value {
+    let segments = snapshot
+        .string_segments()
+        .iter()
+        .map(|segment| segment.to_string())
+        .collect::<Vec<_>>();
+    json!({
+        "confirmed": snapshot.confirmed().to_string(),
+        "segments": segments,
+    })
+}
+
+// Sends a snapshot event whenever the snapshot (or the running state) changes.
+fn stream_snapshots<C: ControlTarget>(
+    writer: &mut TcpStream,
+    controller: &C,
+    shutdown: &AtomicBool,
+) -> Result<(), RibbleError> {
+    let mut last_snapshot: Option<Arc<TranscriptionSnapshot>> = None;
+    let mut last_running = None;
+
+    while !shutdown.load(Ordering::Acquire) {
+        let snapshot = controller.read_transcription_snapshot();
+        let running = controller.transcriber_running();
+        let snapshot_changed = last_snapshot
+            .as_ref()
+            .is_none_or(|last| !Arc::ptr_eq(last, &snapshot));
+
+        if snapshot_changed || last_running != Some(running) {
+            let mut event = snapshot_json(&snapshot);
+            event["event"] = json!("snapshot");
+            event["running"] = json!(running);
+            // A failed write means the client has gone away.
+            if let Err(e) = write_message(writer, &event) {
+                log::info!("Control API subscriber disconnected: {e}");
+                return Ok(());
+            }
+            last_snapshot = Some(snapshot);
+            last_running = Some(running);
+        }
+
+        std::thread::sleep(Duration::from_millis(SNAPSHOT_POLL_RATE));
+    }
+    Ok(())
+}
+
+fn write_message(writer: &mut TcpStream, message: &Value) -> Result<(), RibbleError> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arc_swap::ArcSwap;
+    use std::sync::atomic::AtomicUsize;
+
+    const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[derive(Default)]
+    struct StubState {
+        audio_file: ArcSwap<Option<PathBuf>>,
+        snapshot: ArcSwap<TranscriptionSnapshot>,
+        running: AtomicBool,
+        recording: AtomicBool,
+        num_realtime_starts: AtomicUsize,
+        num_offline_starts: AtomicUsize,
+        num_stops: AtomicUsize,
+    }
+
+    #[derive(Clone, Default)]
+    struct StubTarget(Arc<StubState>);
+
+    impl ControlTarget for StubTarget {
+        fn read_current_audio_file_path(&self) -> Arc<Option<PathBuf>> {
+            self.0.audio_file.load_full()
+        }
+        fn set_audio_file_path(&self, path: PathBuf) {
+            self.0.audio_file.store(Arc::new(Some(path)));
+        }
+        fn realtime_running(&self) -> bool {
+            false
+        }
+        fn offline_running(&self) -> bool {
+            self.0.running.load(Ordering::Acquire)
+        }
+        fn batch_running(&self) -> bool {
+            false
+        }
+        fn recorder_running(&self) -> bool {
+            self.0.recording.load(Ordering::Acquire)
+        }
+        fn transcriber_running(&self) -> bool {
+            self.0.running.load(Ordering::Acquire)
+        }
+        fn start_realtime_transcription(&self) {
+            self.0.num_realtime_starts.fetch_add(1, Ordering::AcqRel);
+        }
+        fn start_offline_transcription(&self) {
+            self.0.num_offline_starts.fetch_add(1, Ordering::AcqRel);
+            self.0.running.store(true, Ordering::Release);
+        }
+        fn stop_transcription(&self) {
+            self.0.num_stops.fetch_add(1, Ordering::AcqRel);
+            self.0.running.store(false, Ordering::Release);
+        }
+        fn read_transcription_snapshot(&self) -> Arc<TranscriptionSnapshot> {
+            self.0.snapshot.load_full()
+        }
+        fn format_transcription(&self, format: TranscriptFormat) -> Result<String, RibbleError> {
+            Ok(format!(
+                "{}: {}",
+                format.as_ref(),
+                self.0.snapshot.load().confirmed()
+            ))
+        }
+    }
+
+    struct TestServer {
+        target: StubTarget,
+        token: String,
+        // Dropped after the server, so the token file outlives it.
+        server: ControlServer,
+        _token_directory: tempfile::TempDir,
+    }
+
+    impl TestServer {
+        fn start() -> Self {
+            let token_directory = tempfile::tempdir().unwrap();
+            let target = StubTarget::default();
+            // Port 0: the OS picks a free port, so the tests can run in parallel.
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let server = ControlServer::from_listener(
+                target.clone(),
+                ControlApiConfigs::default(),
+                listener,
+                token_directory.path(),
+            )
+            .unwrap();
+            let token =
+                std::fs::read_to_string(token_directory.path().join(CONTROL_API_TOKEN_FILE))
+                    .unwrap();
+            Self {
+                target,
+                token,
+                server,
+                _token_directory: token_directory,
+            }
+        }
+
+        fn connect(&self) -> Client {
+            let stream = TcpStream::connect(self.server.local_addr()).unwrap();
+            stream.set_read_timeout(Some(CLIENT_TIMEOUT)).unwrap();
+            Client {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            }
+        }
+
+        fn request(&self, request: Value) -> Value {
+            let mut request = request;
+            request["token"] = json!(self.token);
+            request
+        }
+    }
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn send_line(&mut self, line: &str) {
+            self.writer.write_all(line.as_bytes()).unwrap();
+            self.writer.write_all(b"\n").unwrap();
+        }
+        fn send(&mut self, request: &Value) {
+            self.send_line(&request.to_string());
+        }
+        // None if the server closed the connection.
+        fn receive(&mut self) -> Option<Value> {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => None,
+                Ok(_) => Some(serde_json::from_str(&line).unwrap()),
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => None,
+                Err(e) => panic!("Control API client read failed: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn status_reports_the_transcriber_state() {
+        let server = TestServer::start();
+        let mut client = server.connect();
+
+        client.send(&server.request(json!({ "command": "status" })));
+        let response = client.receive().unwrap();
+        assert_eq!(response["ok"], json!(true));
+        assert_eq!(response["offline_running"], json!(false));
+        assert_eq!(response["audio_file"], Value::Null);
+
+        let audio_file = tempfile::NamedTempFile::new().unwrap();
+        client.send(&server.request(json!({
+            "command": "start_offline",
+            "path": audio_file.path(),
+        })));
+        assert_eq!(client.receive().unwrap(), json!({ "ok": true }));
+
+        client.send(&server.request(json!({ "command": "status" })));
+        let response = client.receive().unwrap();
+        assert_eq!(response["offline_running"], json!(true));
+        assert_eq!(
+            response["audio_file"],
+            json!(audio_file.path().display().to_string())
+        );
+        assert_eq!(
+            server.target.0.num_offline_starts.load(Ordering::Acquire),
+            1
+        );
+    }
+
+    #[test]
+    fn bad_requests_get_error_replies() {
+        let server = TestServer::start();
+        let mut client = server.connect();
+
+        client.send(&server.request(json!({ "command": "launch_rockets" })));
+        let response = client.receive().unwrap();
+        assert_eq!(response["ok"], json!(false));
+        assert!(
+            response["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid request")
+        );
+
+        client.send(&server.request(json!({ "command": "start_offline" })));
+        let response = client.receive().unwrap();
+        assert_eq!(response["ok"], json!(false));
+        assert!(
+            response["error"]
+                .as_str()
+                .unwrap()
+                .contains("Audio file path not loaded.")
+        );
+
+        client.send(&server.request(json!({
+            "command": "set_audio_file",
+            "path": "/this/file/does/not/exist.wav",
+        })));
+        assert_eq!(client.receive().unwrap()["ok"], json!(false));
+
+        client.send(&server.request(json!({ "command": "transcript", "format": "docx" })));
+        let response = client.receive().unwrap();
+        assert!(
+            response["error"]
+                .as_str()
+                .unwrap()
+                .contains("Unknown transcript format")
+        );
+
+        // None of those close the connection.
+        client.send(&server.request(json!({ "command": "status" })));
+        assert_eq!(client.receive().unwrap()["ok"], json!(true));
+        assert_eq!(
+            server.target.0.num_offline_starts.load(Ordering::Acquire),
+            0
+        );
+    }
+
+    #[test]
+    fn requests_without_the_token_are_refused() {
+        let server = TestServer::start();
+
+        let mut client = server.connect();
+        client.send(&json!({ "command": "stop" }));
+        let response = client.receive().unwrap();
+        assert_eq!(response["ok"], json!(false));
+        assert!(client.receive().is_none());
+
+        let mut client = server.connect();
+        client.send(&json!({ "command": "stop", "token": "not the token" }));
+        assert_eq!(client.receive().unwrap()["ok"], json!(false));
+        assert!(client.receive().is_none());
+
+        assert_eq!(server.target.0.num_stops.load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn http_requests_are_dropped_without_a_reply() {
+        let server = TestServer::start();
+        let mut client = server.connect();
+
+        // What a browser's fetch() sends; the body is a well-formed command.
+        client.send_line("POST / HTTP/1.1\r");
+        client.send_line("Host: 127.0.0.1\r");
+        client.send_line("Content-Type: text/plain\r");
+        client.send_line("\r");
+        client.send(&server.request(json!({ "command": "stop" })));
+
+        assert!(client.receive().is_none());
+        assert_eq!(server.target.0.num_stops.load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn realtime_is_refused_while_recording() {
+        let server = TestServer::start();
+        server.target.0.recording.store(true, Ordering::Release);
+        let mut client = server.connect();
+
+        client.send(&server.request(json!({ "command": "start_realtime" })));
+        let response = client.receive().unwrap();
+        assert_eq!(response["ok"], json!(false));
+        assert!(response["error"].as_str().unwrap().contains("recording"));
+
+        server.target.0.recording.store(false, Ordering::Release);
+        client.send(&server.request(json!({ "command": "start_realtime" })));
+        assert_eq!(client.receive().unwrap(), json!({ "ok": true }));
+        assert_eq!(
+            server.target.0.num_realtime_starts.load(Ordering::Acquire),
+            1
+        );
+    }
+
+    #[test]
+    fn oversized_requests_close_the_connection() {
+        let server = TestServer::start();
+        let mut client = server.connect();
+
+        // No newline: the server would otherwise keep buffering it.
+        let oversized = format!("{{\"command\": \"{}", "a".repeat(MAX_LINE_LEN));
+        // The server may hang up before the client finishes writing.
+        let _ = client.writer.write_all(oversized.as_bytes());
+        assert!(client.receive().is_none());
+    }
+
+    #[test]
+    fn transcript_returns_the_contents() {
+        let server = TestServer::start();
+        server
+            .target
+            .0
+            .snapshot
+            .store(Arc::new(TranscriptionSnapshot::new(
+                Arc::from("Hello there."),
+                Default::default(),
+            )));
+        let mut client = server.connect();
+
+        client.send(&server.request(json!({ "command": "transcript", "format": "srt" })));
+        let response = client.receive().unwrap();
+        assert_eq!(response["ok"], json!(true));
+        let expected = format!("{}: Hello there.", TranscriptFormat::Srt.as_ref());
+        assert_eq!(response["contents"], json!(expected));
+    }
+
+    #[test]
+    fn subscribers_get_snapshot_updates() {
+        let server = TestServer::start();
+        let mut client = server.connect();
+
+        client.send(&server.request(json!({ "command": "subscribe" })));
+        assert_eq!(client.receive().unwrap(), json!({ "ok": true }));
+
+        let first = client.receive().unwrap();
+        assert_eq!(first["event"], json!("snapshot"));
+        assert_eq!(first["running"], json!(false));
+        assert_eq!(first["confirmed"], json!(""));
+
+        server
+            .target
+            .0
+            .snapshot
+            .store(Arc::new(TranscriptionSnapshot::new(
+                Arc::from("Confirmed text."),
+                Default::default(),
+            )));
+        let update = client.receive().unwrap();
+        assert_eq!(update["event"], json!("snapshot"));
+        assert_eq!(update["confirmed"], json!("Confirmed text."));
+    }
+
+    #[test]
+    fn the_token_is_fresh_and_private() {
+        let first = TestServer::start();
+        let second = TestServer::start();
+        assert_eq!(first.token.len(), TOKEN_LEN * 2);
+        assert_ne!(first.token, second.token);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = first._token_directory.path().join(CONTROL_API_TOKEN_FILE);
+            let mode = std::fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -1,44 +1,86 @@
+#[cfg(feature = "cpal-backend")]
+use crate::controller::cpal_capture::CpalCapture;
+use crate::controller::file_capture::FileCapture;
+use arc_swap::ArcSwap;
 use ribble_whisper::audio::audio_backend::AudioBackend;
 use ribble_whisper::audio::audio_backend::CaptureSpec;
-use ribble_whisper::audio::microphone::{MicCapture, Sdl2Capture};
+use ribble_whisper::audio::microphone::{MicCapture, RibbleAudioFormat, Sdl2Capture};
 use ribble_whisper::audio::recorder::{ArcChannelSink, SampleSink};
+use ribble_whisper::sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired, AudioStatus};
+use ribble_whisper::sdl2::AudioSubsystem;
 use ribble_whisper::utils::errors::RibbleWhisperError;
 use ribble_whisper::utils::{get_channel, Sender};
 use std::error::Error;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+// Which backend captures audio; this is chosen at startup (see the cli) and fixed for the session.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum AudioBackendKind {
+    // Captures are opened on the main thread, through SDL2.
+    #[default]
+    Sdl2,
+    #[cfg(feature = "cpal-backend")]
+    Cpal,
+    // Replays a wav file in real time, as though it were a microphone.
+    File(PathBuf),
+}
+
+impl AudioBackendKind {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sdl2" | "sdl" => Some(Self::Sdl2),
+            #[cfg(feature = "cpal-backend")]
+            "cpal" => Some(Self::Cpal),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) enum AudioCaptureRequest {
+    // The device name is None for the system default device.
     Open(
         CaptureSpec,
+        Option<Arc<str>>,
         ArcChannelSink<f32>,
-        Sender<Result<SharedSdl2Capture<ArcChannelSink<f32>>, RibbleWhisperError>>,
+        Sender<Result<SharedSdl2Capture, RibbleWhisperError>>,
     ),
     Close(usize),
 }
 
 pub(crate) struct AudioBackendProxy {
+    kind: AudioBackendKind,
+    // Only the SDL2 backend goes through the main thread.
     request_sender: Sender<AudioCaptureRequest>,
+    // The device to capture from; this is kept in sync with the recording configs.
+    input_device: ArcSwap<Option<Arc<str>>>,
 }
 
 impl AudioBackendProxy {
-    pub(crate) fn new(request_sender: Sender<AudioCaptureRequest>) -> Self {
-        Self { request_sender }
+    pub(crate) fn new(kind: AudioBackendKind, request_sender: Sender<AudioCaptureRequest>) -> Self {
+        Self {
+            kind,
+            request_sender,
+            input_device: ArcSwap::new(Arc::new(None)),
+        }
     }
-}
 
-// For all intents and purposes, concrete types are fine.
-// Until that requirement changes, avoid generics.
-impl AudioBackend<ArcChannelSink<f32>> for AudioBackendProxy {
-    type Capture = SharedSdl2Capture<ArcChannelSink<f32>>;
+    pub(crate) fn kind(&self) -> &AudioBackendKind {
+        &self.kind
+    }
 
-    fn open_capture(
+    pub(crate) fn set_input_device(&self, input_device: Option<Arc<str>>) {
+        self.input_device.store(Arc::new(input_device));
+    }
+
+    fn open_sdl2_capture(
         &self,
         spec: CaptureSpec,
+        input_device: Option<Arc<str>>,
         sink: ArcChannelSink<f32>,
-    ) -> Result<Self::Capture, RibbleWhisperError> {
+    ) -> Result<SharedSdl2Capture, RibbleWhisperError> {
         let (capture_sender, capture_receiver) = get_channel(1);
-
-        let request = AudioCaptureRequest::Open(spec, sink, capture_sender);
+        let request = AudioCaptureRequest::Open(spec, input_device, sink, capture_sender);
 
         if let Err(e) = self.request_sender.send(request) {
             log::error!("Cannot send audio capture request to main thread.\n\
@@ -55,8 +97,38 @@ impl AudioBackend<ArcChannelSink<f32>> for AudioBackendProxy {
             )
         })?
     }
+}
+
+// The engines only ever see the proxy; the backend behind it is picked at startup.
+// An enum (rather than generics) keeps the engines' types the same whichever backend is in use.
+impl AudioBackend<ArcChannelSink<f32>> for AudioBackendProxy {
+    type Capture = RibbleCapture;
+
+    fn open_capture(
+        &self,
+        spec: CaptureSpec,
+        sink: ArcChannelSink<f32>,
+    ) -> Result<Self::Capture, RibbleWhisperError> {
+        let input_device = self.input_device.load().as_ref().clone();
+        match &self.kind {
+            AudioBackendKind::Sdl2 => self
+                .open_sdl2_capture(spec, input_device, sink)
+                .map(RibbleCapture::Sdl2),
+            #[cfg(feature = "cpal-backend")]
+            AudioBackendKind::Cpal => {
+                CpalCapture::open(input_device, spec, sink).map(RibbleCapture::Cpal)
+            }
+            AudioBackendKind::File(path) => {
+                FileCapture::open(path, spec, sink).map(RibbleCapture::File)
+            }
+        }
+    }
 
     fn close_capture(&self, capture: Self::Capture) {
+        // The other backends close their streams when dropped.
+        let RibbleCapture::Sdl2(capture) = capture else {
+            return;
+        };
         let id = capture.device_id;
         let request = AudioCaptureRequest::Close(id);
 
@@ -70,19 +142,207 @@ impl AudioBackend<ArcChannelSink<f32>> for AudioBackendProxy {
     }
 }
 
+// Maps interleaved audio from one channel count to another: down-mixes to mono by averaging, and
+// otherwise repeats/drops channels.
+pub(crate) fn remap_channels(samples: &[f32], from_channels: usize, to_channels: usize) -> Vec<f32> {
+    if from_channels == to_channels || from_channels == 0 || to_channels == 0 {
+        return samples.to_vec();
+    }
+
+    let frames = samples.chunks_exact(from_channels);
+    if to_channels == 1 {
+        frames
+            .map(|frame| frame.iter().sum::<f32>() / from_channels as f32)
+            .collect()
+    } else {
+        frames
+            .flat_map(|frame| (0..to_channels).map(move |channel| frame[channel % from_channels]))
+            .collect()
+    }
+}
+
+// A capture from whichever backend is in use.
+pub(crate) enum RibbleCapture {
+    Sdl2(SharedSdl2Capture),
+    #[cfg(feature = "cpal-backend")]
+    Cpal(CpalCapture),
+    File(FileCapture),
+}
+
+// NOTE: MicCapture isn't necessarily object-safe, so this matches instead of going through dyn.
+macro_rules! with_capture {
+    ($capture:expr, $inner:ident => $body:expr) => {
+        match $capture {
+            RibbleCapture::Sdl2($inner) => $body,
+            #[cfg(feature = "cpal-backend")]
+            RibbleCapture::Cpal($inner) => $body,
+            RibbleCapture::File($inner) => $body,
+        }
+    };
+}
+
+impl MicCapture for RibbleCapture {
+    fn play(&self) {
+        with_capture!(self, capture => capture.play())
+    }
+
+    fn pause(&self) {
+        with_capture!(self, capture => capture.pause())
+    }
+
+    fn sample_rate(&self) -> usize {
+        with_capture!(self, capture => capture.sample_rate())
+    }
+
+    fn format(&self) -> RibbleAudioFormat {
+        with_capture!(self, capture => capture.format())
+    }
+
+    fn channels(&self) -> u8 {
+        with_capture!(self, capture => capture.channels())
+    }
+
+    fn buffer_size(&self) -> usize {
+        with_capture!(self, capture => capture.buffer_size())
+    }
+}
+
+// The capture device names, in the order SDL reports them.
+// NOTE: this must be called on the main thread.
+pub(crate) fn list_input_devices(audio_subsystem: &AudioSubsystem) -> Vec<Arc<str>> {
+    let num_devices = audio_subsystem.num_audio_capture_devices().unwrap_or(0);
+    (0..num_devices)
+        .filter_map(|index| audio_subsystem.audio_capture_device_name(index).ok())
+        .filter(|name| !name.is_empty())
+        .map(Arc::from)
+        .collect()
+}
+
+// Forwards captured audio from a (user-chosen) device into the sink.
+pub(crate) struct InputDeviceCallback {
+    sink: ArcChannelSink<f32>,
+}
+
+impl AudioCallback for InputDeviceCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, input: &mut [f32]) {
+        self.sink.push(input);
+    }
+}
+
+pub(crate) enum SdlCapture {
+    // Opened through ribble_whisper, on the system default device.
+    Default(Sdl2Capture<ArcChannelSink<f32>>),
+    // ribble_whisper's backend only opens the default device, so named devices are opened here.
+    Device {
+        name: Arc<str>,
+        device: AudioDevice<InputDeviceCallback>,
+    },
+}
+
+impl SdlCapture {
+    // Fails (with a device error) if the device isn't connected; it never falls back to another
+    // device.
+    // NOTE: this must be called on the main thread.
+    pub(crate) fn open_device(
+        audio_subsystem: &AudioSubsystem,
+        name: Arc<str>,
+        spec: CaptureSpec,
+        sink: ArcChannelSink<f32>,
+    ) -> Result<Self, RibbleWhisperError> {
+        if !list_input_devices(audio_subsystem).contains(&name) {
+            return Err(RibbleWhisperError::DeviceError(format!(
+                "Input device not found: {name}. Reconnect it, or choose another input device."
+            )));
+        }
+
+        let desired_spec = AudioSpecDesired {
+            freq: spec.sample_rate().map(|sample_rate| sample_rate as i32),
+            channels: spec.channels(),
+            samples: spec.period().map(|period| period as u16),
+        };
+        let device = audio_subsystem
+            .open_capture(Some(name.as_ref()), &desired_spec, |_| {
+                InputDeviceCallback { sink }
+            })
+            .map_err(|e| {
+                RibbleWhisperError::DeviceError(format!(
+                    "Failed to open input device: {name}.\nError: {e}"
+                ))
+            })?;
+        Ok(Self::Device { name, device })
+    }
+
+    // The name of a named device that has been disconnected.
+    // SDL stops a capture device once it's been unplugged.
+    pub(crate) fn disconnected_device(&self) -> Option<Arc<str>> {
+        match self {
+            SdlCapture::Default(_) => None,
+            SdlCapture::Device { name, device } => {
+                (device.status() == AudioStatus::Stopped).then(|| Arc::clone(name))
+            }
+        }
+    }
+}
+
+impl MicCapture for SdlCapture {
+    fn play(&self) {
+        match self {
+            SdlCapture::Default(capture) => capture.play(),
+            SdlCapture::Device { device, .. } => device.resume(),
+        }
+    }
+
+    fn pause(&self) {
+        match self {
+            SdlCapture::Default(capture) => capture.pause(),
+            SdlCapture::Device { device, .. } => device.pause(),
+        }
+    }
+
+    fn sample_rate(&self) -> usize {
+        match self {
+            SdlCapture::Default(capture) => capture.sample_rate(),
+            SdlCapture::Device { device, .. } => device.spec().freq as usize,
+        }
+    }
+
+    fn format(&self) -> RibbleAudioFormat {
+        match self {
+            SdlCapture::Default(capture) => capture.format(),
+            SdlCapture::Device { .. } => RibbleAudioFormat::F32,
+        }
+    }
+
+    fn channels(&self) -> u8 {
+        match self {
+            SdlCapture::Default(capture) => capture.channels(),
+            SdlCapture::Device { device, .. } => device.spec().channels,
+        }
+    }
+
+    fn buffer_size(&self) -> usize {
+        match self {
+            SdlCapture::Default(capture) => capture.buffer_size(),
+            SdlCapture::Device { device, .. } => device.spec().samples as usize,
+        }
+    }
+}
+
 // Since SDL2 uses a Mutex to guard calls to pause/resume audio capture, for all intents and
 // purposes, the inner Sdl2 capture should be considered Sync.
 //
 // To guarantee thread-safety, a copy of this capture should always exist on the main thread and
 // must only be dropped on the main thread.
 #[derive(Clone)]
-pub(crate) struct SharedSdl2Capture<S: SampleSink> {
+pub(crate) struct SharedSdl2Capture {
     device_id: usize,
-    inner: Arc<Sdl2Capture<S>>,
+    inner: Arc<SdlCapture>,
 }
 
-impl<S: SampleSink> SharedSdl2Capture<S> {
-    pub(crate) fn new(device_id: usize, sdl_capture: Arc<Sdl2Capture<S>>) -> Self {
+impl SharedSdl2Capture {
+    pub(crate) fn new(device_id: usize, sdl_capture: Arc<SdlCapture>) -> Self {
         Self {
             device_id,
             inner: sdl_capture,
@@ -96,10 +356,10 @@ impl<S: SampleSink> SharedSdl2Capture<S> {
     }
 }
 
-unsafe impl<S: SampleSink> Sync for SharedSdl2Capture<S> {}
-unsafe impl<S: SampleSink> Send for SharedSdl2Capture<S> {}
+unsafe impl Sync for SharedSdl2Capture {}
+unsafe impl Send for SharedSdl2Capture {}
 
-impl<S: SampleSink> MicCapture for SharedSdl2Capture<S> {
+impl MicCapture for SharedSdl2Capture {
     fn play(&self) {
         self.inner.play()
     }
@@ -112,7 +372,7 @@ impl<S: SampleSink> MicCapture for SharedSdl2Capture<S> {
         self.inner.sample_rate()
     }
 
-    fn format(&self) -> ribble_whisper::audio::microphone::RibbleAudioFormat {
+    fn format(&self) -> RibbleAudioFormat {
         self.inner.format()
     }
 
@@ -124,3 +384,55 @@ impl<S: SampleSink> MicCapture for SharedSdl2Capture<S> {
         self.inner.buffer_size()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::remap_channels;
+
+    #[test]
+    fn mono_is_copied_to_both_stereo_channels() {
+        let remapped = remap_channels(&[0.1, -0.2, 0.3], 1, 2);
+        assert_eq!(remapped, vec![0.1, 0.1, -0.2, -0.2, 0.3, 0.3]);
+    }
+
+    #[test]
+    fn stereo_is_averaged_down_to_mono() {
+        let remapped = remap_channels(&[0.2, 0.4, -1.0, 1.0, 0.5, 0.5], 2, 1);
+        assert_eq!(remapped.len(), 3);
+        assert!((remapped[0] - 0.3).abs() < f32::EPSILON);
+        assert_eq!(remapped[1], 0.0);
+        assert_eq!(remapped[2], 0.5);
+    }
+
+    #[test]
+    fn more_channels_repeat_the_source_channels() {
+        let remapped = remap_channels(&[1.0, 2.0, 3.0, 4.0], 2, 4);
+        assert_eq!(remapped, vec![1.0, 2.0, 1.0, 2.0, 3.0, 4.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn fewer_channels_drop_the_extra_channels() {
+        let remapped = remap_channels(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 3, 2);
+        assert_eq!(remapped, vec![1.0, 2.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn many_channels_are_averaged_down_to_mono() {
+        let remapped = remap_channels(&[1.0, 2.0, 3.0, 4.0, 0.0, 0.0, 0.0, 0.0], 4, 1);
+        assert_eq!(remapped, vec![2.5, 0.0]);
+    }
+
+    #[test]
+    fn a_partial_trailing_frame_is_dropped() {
+        let remapped = remap_channels(&[1.0, 2.0, 3.0], 2, 1);
+        assert_eq!(remapped, vec![1.5]);
+    }
+
+    #[test]
+    fn matching_or_zero_channel_counts_pass_through() {
+        let samples = [0.1, 0.2, 0.3, 0.4];
+        assert_eq!(remap_channels(&samples, 2, 2), samples.to_vec());
+        assert_eq!(remap_channels(&samples, 0, 2), samples.to_vec());
+        assert_eq!(remap_channels(&samples, 2, 0), samples.to_vec());
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use strum::{AsRefStr, Display};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, AsRefStr)]
+pub(crate) enum BatchJobStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+impl BatchJobStatus {
+    // Finished, failed and cancelled jobs can be cleared out of the queue.
+    pub(crate) fn is_done(&self) -> bool {
+        matches!(
+            self,
+            BatchJobStatus::Finished | BatchJobStatus::Failed | BatchJobStatus::Cancelled
+        )
+    }
+}
+
+// A single audio file in the batch transcription queue.
+// This is a cheap clone so that it can be copied out to the UI.
+#[derive(Clone, Debug)]
+pub(crate) struct BatchJob {
+    audio_path: Arc<Path>,
+    // Overrides the output directory in the batch configs; e.g. the watch folder outbox.
+    output_directory: Option<Arc<Path>>,
+    status: BatchJobStatus,
+    output_path: Option<Arc<Path>>,
+    error: Option<Arc<str>>,
+}
+
+impl BatchJob {
+    pub(super) fn new(audio_path: PathBuf, output_directory: Option<PathBuf>) -> Self {
+        Self {
+            audio_path: Arc::from(audio_path),
+            output_directory: output_directory.map(Arc::from),
+            status: BatchJobStatus::Queued,
+            output_path: None,
+            error: None,
+        }
+    }
+
+    pub(crate) fn audio_path(&self) -> &Path {
+        self.audio_path.as_ref()
+    }
+
+    pub(crate) fn file_name(&self) -> String {
+        self.audio_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.audio_path.display().to_string())
+    }
+
+    pub(crate) fn output_directory(&self) -> Option<&Path> {
+        self.output_directory.as_deref()
+    }
+
+    pub(crate) fn status(&self) -> BatchJobStatus {
+        self.status
+    }
+    pub(crate) fn output_path(&self) -> Option<&Path> {
+        self.output_path.as_deref()
+    }
+    pub(crate) fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub(super) fn set_running(&mut self) {
+        self.status = BatchJobStatus::Running;
+        self.error = None;
+    }
+
+    pub(super) fn set_finished(&mut self, output_path: PathBuf) {
+        self.status = BatchJobStatus::Finished;
+        self.output_path = Some(Arc::from(output_path));
+    }
+
+    pub(super) fn set_failed(&mut self, error: &str) {
+        self.status = BatchJobStatus::Failed;
+        self.error = Some(Arc::from(error));
+    }
+
+    pub(super) fn set_cancelled(&mut self) {
+        self.status = BatchJobStatus::Cancelled;
+    }
+
+    // Puts a failed/cancelled job back into the queue so that it runs on the next batch.
+    pub(super) fn requeue(&mut self) {
+        self.status = BatchJobStatus::Queued;
+        self.output_path = None;
+        self.error = None;
+    }
+}
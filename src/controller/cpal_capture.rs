@@ -0,0 +1,378 @@
+// Capture through cpal (ALSA on Linux, CoreAudio, WASAPI), for systems where SDL2's capture
+// doesn't work well.
+// cpal streams aren't Send on every platform, so each stream lives on (and is dropped on) its own
+// thread; the capture is a handle to that thread.
+//
+// NOTE: unlike SDL, cpal doesn't convert between formats. If the device can't capture at the
+// requested spec, its audio is remapped and resampled on the stream thread.
+use crate::controller::audio_backend_proxy::remap_channels;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    Device, FromSample, Sample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig,
+    StreamError,
+};
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender};
+use ribble_whisper::audio::audio_backend::CaptureSpec;
+use ribble_whisper::audio::microphone::{MicCapture, RibbleAudioFormat};
+use ribble_whisper::audio::recorder::{ArcChannelSink, SampleSink};
+use ribble_whisper::utils::errors::RibbleWhisperError;
+use rubato::{FftFixedIn, Resampler};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// In frames.
+const DEFAULT_PERIOD: usize = 1024;
+const RESAMPLER_CHUNK_SIZE: usize = 1024;
+// The number of device buffers that can queue up before audio gets dropped.
+const STREAM_QUEUE_SIZE: usize = 64;
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+enum StreamCommand {
+    Play,
+    Pause,
+}
+
+// The names cpal reports for the default host's capture devices.
+pub(crate) fn list_input_devices() -> Vec<Arc<str>> {
+    match cpal::default_host().input_devices() {
+        Ok(devices) => devices
+            .filter_map(|device| device.name().ok())
+            .filter(|name| !name.is_empty())
+            .map(Arc::from)
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to list cpal input devices: {e}");
+            vec![]
+        }
+    }
+}
+
+pub(crate) struct CpalCapture {
+    sample_rate: usize,
+    channels: u8,
+    period: usize,
+    // Dropping this ends the stream thread.
+    commands: Option<Sender<StreamCommand>>,
+    stream_thread: Option<JoinHandle<()>>,
+}
+
+impl CpalCapture {
+    // Fails (with a device error) if the device isn't connected; it never falls back to another
+    // device.
+    pub(crate) fn open(
+        input_device: Option<Arc<str>>,
+        spec: CaptureSpec,
+        sink: ArcChannelSink<f32>,
+    ) -> Result<Self, RibbleWhisperError> {
+        let period = spec.period().unwrap_or(DEFAULT_PERIOD);
+        let (command_sender, command_receiver) = crossbeam::channel::unbounded();
+        let (ready_sender, ready_receiver) = crossbeam::channel::bounded(1);
+
+        let stream_thread = std::thread::spawn(move || match open_stream(input_device, &spec) {
+            Ok(opened) => {
+                let opened_spec = (opened.converter.sample_rate, opened.converter.channels);
+                if ready_sender.send(Ok(opened_spec)).is_ok() {
+                    run_stream(opened, command_receiver, sink);
+                }
+            }
+            Err(e) => {
+                let _ = ready_sender.send(Err(e));
+            }
+        });
+
+        let (sample_rate, channels) = ready_receiver.recv().map_err(|_| {
+            RibbleWhisperError::DeviceError("Audio stream thread closed unexpectedly.".to_string())
+        })??;
+
+        Ok(Self {
+            sample_rate,
+            channels,
+            period,
+            commands: Some(command_sender),
+            stream_thread: Some(stream_thread),
+        })
+    }
+
+    fn send_command(&self, command: StreamCommand) {
+        if let Some(commands) = self.commands.as_ref()
+            && commands.send(command).is_err()
+        {
+            log::warn!("Audio stream thread closed, cannot play/pause capture.");
+        }
+    }
+}
+
+struct OpenedStream {
+    stream: Stream,
+    stream_receiver: Receiver<Vec<f32>>,
+    error_receiver: Receiver<StreamError>,
+    converter: StreamConverter,
+}
+
+fn open_stream(
+    input_device: Option<Arc<str>>,
+    spec: &CaptureSpec,
+) -> Result<OpenedStream, RibbleWhisperError> {
+    let host = cpal::default_host();
+    let device = match input_device.as_deref() {
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| RibbleWhisperError::DeviceError(e.to_string()))?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+            .ok_or(RibbleWhisperError::DeviceError(format!(
+                "Input device not found: {name}. Reconnect it, or choose another input device."
+            )))?,
+        None => host
+            .default_input_device()
+            .ok_or(RibbleWhisperError::DeviceError(
+                "No input device available.".to_string(),
+            ))?,
+    };
+
+    let default_config = device
+        .default_input_config()
+        .map_err(|e| RibbleWhisperError::DeviceError(e.to_string()))?;
+    let device_channels = default_config.channels();
+    let device_rate = default_config.sample_rate().0 as usize;
+    let channels = spec.channels().unwrap_or(device_channels as u8);
+    let sample_rate = spec.sample_rate().unwrap_or(device_rate);
+
+    // Prefer capturing at the requested spec directly; otherwise convert from the default.
+    let (config, sample_format) = device
+        .supported_input_configs()
+        .ok()
+        .and_then(|mut configs| {
+            configs.find(|config| {
+                config.channels() == channels as u16
+                    && config.min_sample_rate().0 as usize <= sample_rate
+                    && sample_rate <= config.max_sample_rate().0 as usize
+            })
+        })
+        .map(|config| config.with_sample_rate(SampleRate(sample_rate as u32)))
+        .map(|config| (config.config(), config.sample_format()))
+        .unwrap_or((default_config.config(), default_config.sample_format()));
+
+    let (stream_sender, stream_receiver) = crossbeam::channel::bounded(STREAM_QUEUE_SIZE);
+    let (error_sender, error_receiver) = crossbeam::channel::bounded(1);
+    let stream = match sample_format {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, stream_sender, error_sender),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, stream_sender, error_sender),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, stream_sender, error_sender),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, stream_sender, error_sender),
+        format => {
+            return Err(RibbleWhisperError::DeviceError(format!(
+                "Unsupported input sample format: {format:?}"
+            )));
+        }
+    }?;
+
+    let converter = StreamConverter::new(
+        config.channels as usize,
+        config.sample_rate.0 as usize,
+        channels,
+        sample_rate,
+    )?;
+    Ok(OpenedStream {
+        stream,
+        stream_receiver,
+        error_receiver,
+        converter,
+    })
+}
+
+fn build_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    stream_sender: Sender<Vec<f32>>,
+    error_sender: Sender<StreamError>,
+) -> Result<Stream, RibbleWhisperError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let samples = data
+                    .iter()
+                    .map(|sample| f32::from_sample(*sample))
+                    .collect();
+                // NOTE: if the consumer can't keep up, the audio is dropped rather than blocking
+                // the audio callback.
+                let _ = stream_sender.try_send(samples);
+            },
+            move |e| {
+                let _ = error_sender.try_send(e);
+            },
+            None,
+        )
+        .map_err(|e| RibbleWhisperError::DeviceError(e.to_string()))
+}
+
+fn run_stream(
+    opened: OpenedStream,
+    command_receiver: Receiver<StreamCommand>,
+    mut sink: ArcChannelSink<f32>,
+) {
+    let OpenedStream {
+        stream,
+        stream_receiver,
+        error_receiver,
+        mut converter,
+    } = opened;
+    loop {
+        loop {
+            match command_receiver.try_recv() {
+                Ok(StreamCommand::Play) => {
+                    if let Err(e) = stream.play() {
+                        log::error!("Failed to start audio stream: {e}");
+                    }
+                }
+                Ok(StreamCommand::Pause) => {
+                    if let Err(e) = stream.pause() {
+                        log::warn!("Failed to pause audio stream: {e}");
+                    }
+                }
+                Err(crossbeam::channel::TryRecvError::Empty) => break,
+                // The capture has been closed.
+                Err(crossbeam::channel::TryRecvError::Disconnected) => return,
+            }
+        }
+
+        // e.g. the device has been unplugged; dropping the sink ends the capture.
+        if let Ok(e) = error_receiver.try_recv() {
+            log::error!("Audio stream error, closing the capture: {e}");
+            return;
+        }
+
+        match stream_receiver.recv_timeout(STREAM_POLL_INTERVAL) {
+            Ok(samples) => match converter.convert(&samples) {
+                Ok(converted) if converted.is_empty() => {}
+                Ok(converted) => sink.push(&converted),
+                Err(e) => {
+                    log::error!("Failed to convert captured audio, closing the capture: {e}");
+                    return;
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+// Converts the device's audio to the requested spec, a buffer at a time.
+struct StreamConverter {
+    device_channels: usize,
+    channels: u8,
+    sample_rate: usize,
+    resampler: Option<FftFixedIn<f32>>,
+    // Deinterleaved frames waiting for a full resampler chunk.
+    pending: Vec<Vec<f32>>,
+}
+
+impl StreamConverter {
+    fn new(
+        device_channels: usize,
+        device_rate: usize,
+        channels: u8,
+        sample_rate: usize,
+    ) -> Result<Self, RibbleWhisperError> {
+        let resampler = if device_rate == sample_rate {
+            None
+        } else {
+            let resampler = FftFixedIn::<f32>::new(
+                device_rate,
+                sample_rate,
+                RESAMPLER_CHUNK_SIZE,
+                2,
+                channels as usize,
+            )
+            .map_err(|e| RibbleWhisperError::DeviceError(e.to_string()))?;
+            Some(resampler)
+        };
+
+        Ok(Self {
+            device_channels,
+            channels,
+            sample_rate,
+            resampler,
+            pending: vec![vec![]; channels as usize],
+        })
+    }
+
+    // Interleaved in, interleaved out. This may return nothing while the resampler fills up.
+    fn convert(&mut self, samples: &[f32]) -> Result<Vec<f32>, RibbleWhisperError> {
+        let channels = self.channels as usize;
+        let samples = remap_channels(samples, self.device_channels, channels);
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(samples);
+        };
+
+        for (channel, pending) in self.pending.iter_mut().enumerate() {
+            pending.extend(samples.iter().skip(channel).step_by(channels));
+        }
+
+        let mut interleaved = vec![];
+        while self.pending[0].len() >= resampler.input_frames_next() {
+            let chunk_size = resampler.input_frames_next();
+            let chunk = self
+                .pending
+                .iter()
+                .map(|pending| &pending[..chunk_size])
+                .collect::<Vec<_>>();
+            let resampled = resampler
+                .process(&chunk, None)
+                .map_err(|e| RibbleWhisperError::DeviceError(e.to_string()))?;
+            for pending in self.pending.iter_mut() {
+                pending.drain(..chunk_size);
+            }
+
+            let num_frames = resampled.first().map_or(0, Vec::len);
+            interleaved.reserve(num_frames * channels);
+            for frame in 0..num_frames {
+                interleaved.extend(resampled.iter().map(|channel| channel[frame]));
+            }
+        }
+        Ok(interleaved)
+    }
+}
+
+impl Drop for CpalCapture {
+    fn drop(&mut self) {
+        // Closing the command channel ends the stream thread, which drops the stream.
+        self.commands.take();
+        if let Some(stream_thread) = self.stream_thread.take()
+            && stream_thread.join().is_err()
+        {
+            log::error!("Audio stream thread panicked.");
+        }
+    }
+}
+
+impl MicCapture for CpalCapture {
+    fn play(&self) {
+        self.send_command(StreamCommand::Play);
+    }
+
+    fn pause(&self) {
+        self.send_command(StreamCommand::Pause);
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn format(&self) -> RibbleAudioFormat {
+        RibbleAudioFormat::F32
+    }
+
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.period
+    }
+}
@@ -1,78 +1,241 @@
-#[cfg(debug_assertions)]
-use crate::controller::AtomicProgress;
+use crate::controller::resumable_download::{DownloadOutcome, ResumableDownload};
 use crate::controller::{
-    AmortizedDownloadProgress, Bus, ConsoleMessage, DownloadRequest, FileDownload,
-    Progress, ProgressMessage, RibbleMessage, WorkRequest,
+    AmortizedDownloadProgress, AtomicProgress, Bus, ConsoleMessage, DownloadRequest, DownloadState,
+    FileDownload, ModelVerifyRequest, Progress, ProgressMessage, ProgressView, RibbleMessage,
+    WorkRequest,
 };
-
+use crate::utils::download_configs::DownloadConfigs;
 use crate::utils::errors::RibbleError;
-use parking_lot::RwLock;
-use ribble_whisper::downloader::downloaders::sync_download_request;
-use ribble_whisper::downloader::SyncDownload;
-use ribble_whisper::utils::callback::{RibbleAbortCallback, RibbleWhisperCallback};
-use ribble_whisper::utils::errors::RibbleWhisperError;
+use arc_swap::ArcSwap;
+use parking_lot::{Mutex, RwLock};
+use reqwest::StatusCode;
 use ribble_whisper::utils::{get_channel, Receiver, Sender};
 use slab::Slab;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
+use std::io::ErrorKind;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
-
-const FALLBACK_NAME: &str = "invalid_download";
+use std::time::{Duration, Instant};
+
+// Retries back off exponentially: 2s, 4s, 8s... up to a minute.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+// How often a waiting retry checks whether it's been paused or cancelled.
+const RETRY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Everything needed to (re)start a download.
+#[derive(Clone)]
+struct DownloadJob {
+    url: String,
+    directory: PathBuf,
+    verify_sender: Option<Sender<ModelVerifyRequest>>,
+}
 
 struct DownloadEngineState {
     // NOTE: if hashing is required, implment hash on FileDownload and use an IndexSet/ pre-hash
     // the content_name and use it as key in IndexMap.
     // Vectors might be a little fragile and Slab insert/remove is going to be way faster.
     file_downloads: RwLock<Slab<FileDownload>>,
+    // Keyed by download id (i.e. the slab key); kept until the download is finished or cancelled
+    // so that it can be retried.
+    download_jobs: Mutex<HashMap<usize, DownloadJob>>,
+    // Download ids, in the order they're to be started.
+    queue: Mutex<VecDeque<usize>>,
+    // The downloads with a running thread.
+    // NOTE: a paused download's thread can still be winding down when it gets resumed; it isn't
+    // started again until the old thread is done with the .part file.
+    active_downloads: Mutex<HashSet<usize>>,
+    configs: ArcSwap<DownloadConfigs>,
+    shutting_down: AtomicBool,
     incoming_jobs: Receiver<DownloadRequest>,
     worker_sender: Sender<WorkRequest>,
     progress_sender: Sender<ProgressMessage>,
 }
 
 impl DownloadEngineState {
-    fn new(incoming_jobs: Receiver<DownloadRequest>, bus: &Bus) -> Self {
+    fn new(incoming_jobs: Receiver<DownloadRequest>, configs: DownloadConfigs, bus: &Bus) -> Self {
         let file_downloads = RwLock::new(Slab::new());
         Self {
             file_downloads,
+            download_jobs: Mutex::new(HashMap::new()),
+            queue: Mutex::new(VecDeque::new()),
+            active_downloads: Mutex::new(HashSet::new()),
+            configs: ArcSwap::new(Arc::new(configs)),
+            shutting_down: AtomicBool::new(false),
             incoming_jobs,
             worker_sender: bus.work_request_sender(),
             progress_sender: bus.progress_message_sender(),
         }
     }
 
-    // NOTE: these just take in the resources by value -> they're owned by the DownloadRequest::DownloadJob
-    // There's no real need to take it in via reference.
-    fn run_download(&self, url: String, dest_dir: PathBuf) -> Result<RibbleMessage, RibbleError> {
-        let sync_downloader = sync_download_request(&url, FALLBACK_NAME)?;
-        let content_name = sync_downloader.content_name();
-        if content_name == FALLBACK_NAME {
-            return Err(RibbleWhisperError::ParameterError(format!(
-                "File not found, likely invalid url.\nURL:{url}"
-            ))
-                .into());
+    fn enqueue_download(self: &Arc<Self>, job: DownloadJob) {
+        if self.shutting_down.load(Ordering::Acquire) {
+            return;
+        }
+        // The real name (and size) are only known once the download starts.
+        let file_download = FileDownload::new(
+            queued_name(job.url.as_str()),
+            placeholder_progress(),
+            Arc::new(AtomicBool::new(false)),
+        )
+        .with_state(DownloadState::Queued);
+
+        let download_id = self.file_downloads.write().insert(file_download);
+        self.download_jobs.lock().insert(download_id, job);
+        self.queue.lock().push_back(download_id);
+        self.pump_queue();
+    }
+
+    // Starts queued downloads until the concurrency limit is hit.
+    fn pump_queue(self: &Arc<Self>) {
+        loop {
+            let download_id = {
+                let mut queue = self.queue.lock();
+                let mut active_downloads = self.active_downloads.lock();
+                if self.shutting_down.load(Ordering::Acquire)
+                    || active_downloads.len() >= self.configs.load().max_concurrent_downloads()
+                {
+                    return;
+                }
+                let Some(position) = queue
+                    .iter()
+                    .position(|download_id| !active_downloads.contains(download_id))
+                else {
+                    return;
+                };
+                let download_id = queue
+                    .remove(position)
+                    .expect("The position is expected to be in bounds.");
+                active_downloads.insert(download_id);
+                download_id
+            };
+
+            let thread_inner = Arc::clone(self);
+            let worker = std::thread::spawn(move || {
+                let result = thread_inner.run_queued_download(download_id);
+                thread_inner.active_downloads.lock().remove(&download_id);
+                thread_inner.pump_queue();
+                result
+            });
+
+            let work_request = WorkRequest::Short(worker);
+            if let Err(e) = self.worker_sender.send(work_request) {
+                log::warn!(
+                    "Worker Engine closed. Can no longer send requests.\nError source: {:#?}",
+                    e.source()
+                );
+                return;
+            }
+        }
+    }
+
+    // Runs the download (retrying transient failures) until it finishes, fails, or is stopped.
+    fn run_queued_download(&self, download_id: usize) -> Result<RibbleMessage, RibbleError> {
+        let no_work = Ok(RibbleMessage::BackgroundWork(Ok(())));
+        let Some(job) = self.download_jobs.lock().get(&download_id).cloned() else {
+            return no_work;
+        };
+
+        // Each run gets a fresh abort flag; the previous one (if any) was used to pause it.
+        let should_abort = Arc::new(AtomicBool::new(false));
+        let started = self.update_download(download_id, |download| {
+            if download.state != DownloadState::Queued {
+                return false;
+            }
+            download.should_abort = Arc::clone(&should_abort);
+            download.state = DownloadState::Downloading;
+            true
+        });
+        if started != Some(true) {
+            return no_work;
+        }
+
+        let max_retries = self.configs.load().max_retries();
+        let mut attempt = 0;
+        loop {
+            let result = self.run_download(download_id, &job, &should_abort);
+            let name = self.download_name(download_id, job.url.as_str());
+            match result {
+                Ok(DownloadOutcome::Finished(download_path)) => {
+                    self.remove_download(download_id);
+                    if let Some(sender) = job.verify_sender.as_ref()
+                        && let Err(e) =
+                            sender.send(ModelVerifyRequest::Verify(download_path.clone()))
+                    {
+                        log::warn!(
+                            "Model bank closed, cannot verify download.\nError source: {:#?}",
+                            e.source()
+                        );
+                    }
+
+                    // Print both the content name and the fully returned path in the Console message.
+                    let console_message = ConsoleMessage::Status(format!(
+                        "Successfully downloaded {name} to {}",
+                        download_path.display()
+                    ));
+                    return Ok(RibbleMessage::Console(console_message));
+                }
+                // Paused or cancelled; the state was already set by whichever it was.
+                Ok(DownloadOutcome::Aborted) => {
+                    let console_message = ConsoleMessage::Status(format!(
+                        "Stopped downloading {name}. The partial download was kept."
+                    ));
+                    return Ok(RibbleMessage::Console(console_message));
+                }
+                Err(_) if should_abort.load(Ordering::Acquire) => return no_work,
+                Err(e) if attempt < max_retries && is_transient(&e) => {
+                    attempt += 1;
+                    let delay = retry_delay(attempt);
+                    log::warn!(
+                        "Download failed: {name}, retrying in {}s ({attempt}/{max_retries}).\n\
+                        Error: {e}",
+                        delay.as_secs()
+                    );
+                    self.set_download_state(download_id, DownloadState::Retrying { attempt });
+                    if !self.wait_for_retry(delay, &should_abort) {
+                        return no_work;
+                    }
+                    self.set_download_state(download_id, DownloadState::Downloading);
+                }
+                Err(e) => {
+                    self.set_download_state(
+                        download_id,
+                        DownloadState::Failed(Arc::from(e.to_string())),
+                    );
+                    return Err(e);
+                }
+            }
         }
+    }
 
-        // If the content-length is missing, sync_downloader defaults to "1" as an "indeterminate"
+    fn run_download(
+        &self,
+        download_id: usize,
+        job: &DownloadJob,
+        should_abort: &AtomicBool,
+    ) -> Result<DownloadOutcome, RibbleError> {
+        let download = ResumableDownload::new(job.url.as_str())?;
+        let content_name = download.content_name();
+
+        // If the content-length is missing, fall back to "1" as an "indeterminate" size
         // There is a mechanism to set maybe_indeterminate to keep the total_size() as current() + 1
         // At the moment, this fits better with the GUI.
-        let progress_job =
-            Progress::new_determinate("Downloading model.", sync_downloader.total_size() as u64)
-                .maybe_indeterminate(sync_downloader.total_size() == 1);
+        let total_size = download.total_size();
+        let progress_job = Progress::new_determinate("Downloading model.", total_size.unwrap_or(1))
+            .maybe_indeterminate(total_size.is_none());
 
         let progress_view = progress_job
             .progress_view()
             .expect("This method always returns some with a determinate progress job");
-        let abort_download = Arc::new(AtomicBool::new(false));
-
-        // Make a FileDownload to store in the slab -> for exposing in the UI.
-        let file_download =
-            FileDownload::new(content_name, progress_view, Arc::clone(&abort_download));
 
-        // Place it in the bank of downloads -> the progress updated by the progress_callback is
-        // shared, so the state will be accessible from the UI (outside progress-bars, that is).
-        let download_id = self.file_downloads.write().insert(file_download);
+        // Now that the name and size are known, swap out the placeholders.
+        self.update_download(download_id, |file_download| {
+            file_download.name = Arc::from(content_name);
+            file_download.progress = progress_view;
+        });
 
         let (id_sender, id_receiver) = get_channel(1);
         let progress_message = ProgressMessage::Request {
@@ -98,17 +261,12 @@ impl DownloadEngineState {
             }
         };
 
-        let callback_progress_sender = self.progress_sender.clone();
-
-        // NOTE: Downloads -accumulate- progress
-        let progress_closure = move |n: usize| {
+        // NOTE: Downloads -accumulate- progress; a resumed download starts part-way through.
+        let progress_closure = |pos: u64| {
             if let Some(id) = progress_id {
-                let update = ProgressMessage::Set {
-                    job_id: id,
-                    pos: n as u64,
-                };
+                let update = ProgressMessage::Set { job_id: id, pos };
 
-                if let Err(e) = callback_progress_sender.try_send(update) {
+                if let Err(e) = self.progress_sender.try_send(update) {
                     log::warn!(
                         "Cannot send update request, channel either closed or too small.\nError: {}\nError source: {:#?}",
                         &e,
@@ -117,41 +275,13 @@ impl DownloadEngineState {
                 }
             }
         };
-
-        let progress_callback = RibbleWhisperCallback::new(progress_closure);
-        let abort_closure = move || abort_download.load(Ordering::Acquire);
-        let abort_callback = RibbleAbortCallback::new(abort_closure);
-
-        let mut sync_downloader = sync_downloader
-            .with_progress_callback(progress_callback)
-            .with_abort_callback(abort_callback);
+        let abort_closure = || should_abort.load(Ordering::Acquire);
 
         // NOTE: This is a blocking call; download the file.
-        let download_path = sync_downloader.download(dest_dir.as_path());
-        // Regardless of whether  it ends because of an error, the download needs to get
-        // removed--there's no "resume"/"pause" because it's a blocking download, so there's no way
-        // to expose that feature right now.
-        // This may change at a later date, but for now it's good enough.
-
-        // Try to remove the FileDownload struct -> since the download is done, this thread
-        // shouldn't panic.
-        // It -should- be impossible for this to panic, because the DownloadEngineState owns its
-        // file_downloads; if it is gone, log the error to diagnose issues.
-        if self
-            .file_downloads
-            .write()
-            .try_remove(download_id)
-            .is_none()
-        {
-            log::warn!("File download metadata missing after download.");
-        };
-
-        let download_path = download_path?;
-
-        // Re-bind the content_name to avoid borrowing issues.
-        let content_name = sync_downloader.content_name();
+        let outcome = download.download(job.directory.as_path(), progress_closure, abort_closure);
 
-        // Remove the progress job now that the file's downloaded.
+        // Regardless of how it ends, the progress job needs to get removed; the download metadata
+        // is kept around for anything that can be resumed.
         if let Some(id) = progress_id {
             let finished = ProgressMessage::Remove { job_id: id };
             if let Err(e) = self.progress_sender.send(finished) {
@@ -162,39 +292,160 @@ impl DownloadEngineState {
             }
         }
 
-        // Print both the content name and the fully returned path in the Console message.
-        let console_message = ConsoleMessage::Status(format!(
-            "Successfully downloaded {content_name} to {}",
-            download_path.display()
-        ));
+        outcome
+    }
 
-        let ribble_message = RibbleMessage::Console(console_message);
+    // Returns false if the download was paused or cancelled while waiting.
+    fn wait_for_retry(&self, delay: Duration, should_abort: &AtomicBool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < delay {
+            if should_abort.load(Ordering::Acquire) || self.shutting_down.load(Ordering::Acquire) {
+                return false;
+            }
+            std::thread::sleep(RETRY_POLL_INTERVAL);
+        }
+        !should_abort.load(Ordering::Acquire)
+    }
+
+    // Returns None if the download is gone (i.e. it was cancelled).
+    fn update_download<T, F>(&self, download_id: usize, update: F) -> Option<T>
+    where
+        F: FnOnce(&mut FileDownload) -> T,
+    {
+        self.file_downloads.write().get_mut(download_id).map(update)
+    }
 
-        Ok(ribble_message)
+    fn set_download_state(&self, download_id: usize, state: DownloadState) {
+        self.update_download(download_id, |download| download.state = state);
+    }
+
+    fn download_name(&self, download_id: usize, url: &str) -> Arc<str> {
+        self.file_downloads
+            .read()
+            .get(download_id)
+            .map_or_else(|| Arc::from(queued_name(url)), FileDownload::name)
+    }
+
+    fn remove_download(&self, download_id: usize) {
+        self.queue.lock().retain(|queued| *queued != download_id);
+        self.download_jobs.lock().remove(&download_id);
+        // It -should- be impossible for this to fail, unless the download was just cancelled.
+        if self
+            .file_downloads
+            .write()
+            .try_remove(download_id)
+            .is_none()
+        {
+            log::info!("File download metadata already removed for id: {download_id}");
+        }
     }
 
     // Since the download is already happening on a thread, only the abort flag needs to be set;
-    // The metadata will be removed automatically and does not need to be manually removed.
-    // NOTE: this will still block at the moment -> it's tricky to coordinate another thread with
-    // the download thread in place; if this becomes an issue, then look at spawning another
-    // thread.
+    // the metadata is removed here so that it disappears from the UI immediately.
+    // Any .part file is left for the next time the same file is downloaded.
     fn abort_download(&self, download_id: usize) {
-        let read_guard = self.file_downloads.read();
-        if let Some(download) = read_guard.get(download_id) {
-            download.abort_download();
-        } else {
-            log::warn!("Download metadata missing for id: {download_id}");
+        let removed = self.file_downloads.write().try_remove(download_id);
+        match removed {
+            Some(download) => download.abort_download(),
+            None => log::warn!("Download metadata missing for id: {download_id}"),
+        }
+        self.queue.lock().retain(|queued| *queued != download_id);
+        self.download_jobs.lock().remove(&download_id);
+    }
+
+    fn pause_download(&self, download_id: usize) {
+        let paused = self.update_download(download_id, |download| {
+            let can_pause = download.state.is_active() || download.state == DownloadState::Queued;
+            if can_pause {
+                download.state = DownloadState::Paused;
+                download.abort_download();
+            }
+            can_pause
+        });
+        if paused == Some(true) {
+            self.queue.lock().retain(|queued| *queued != download_id);
+        }
+    }
+
+    // Resumes a paused download, or retries a failed one.
+    fn resume_download(self: &Arc<Self>, download_id: usize) {
+        let resumed = self.update_download(download_id, |download| {
+            let can_resume = download.state.can_resume();
+            if can_resume {
+                download.state = DownloadState::Queued;
+            }
+            can_resume
+        });
+        if resumed == Some(true) {
+            self.queue.lock().push_back(download_id);
+            self.pump_queue();
         }
     }
 
     // These will get removed if they happen to be downloading on a background thread.
     fn stop_downloads(&self) {
+        self.shutting_down.store(true, Ordering::Release);
+        self.queue.lock().clear();
         for (_, download) in self.file_downloads.read().iter() {
             download.should_abort.store(true, Ordering::Release);
         }
     }
 }
 
+// The file name in the url, until the server says otherwise.
+fn queued_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or(url)
+}
+
+fn placeholder_progress() -> ProgressView {
+    ProgressView::new(Arc::new(
+        AtomicProgress::new()
+            .with_capacity(1)
+            .with_maybe_indeterminate(true),
+    ))
+}
+
+fn retry_delay(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    RETRY_BASE_DELAY.saturating_mul(factor).min(RETRY_MAX_DELAY)
+}
+
+// Dropped connections, timeouts and server-side errors are worth retrying; anything else (a bad
+// url, a full disk) would just fail again.
+fn is_transient(error: &RibbleError) -> bool {
+    fn is_transient_http(error: &reqwest::Error) -> bool {
+        error.is_timeout()
+            || error.is_connect()
+            || error.is_body()
+            || error.status().is_some_and(|status| {
+                status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+            })
+    }
+
+    match error {
+        RibbleError::Http(e) => is_transient_http(e),
+        // NOTE: errors reading the response body surface as io errors wrapping the http error.
+        RibbleError::IOError(e) => {
+            matches!(
+                e.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::Interrupted
+            ) || e
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+                .is_some_and(is_transient_http)
+        }
+        _ => false,
+    }
+}
+
 pub(super) struct DownloadEngine {
     inner: Arc<DownloadEngineState>,
     work_thread: Option<JoinHandle<()>>,
@@ -207,26 +458,29 @@ impl DownloadEngine {
 }
 
 impl DownloadEngine {
-    pub(super) fn new(incoming_jobs: Receiver<DownloadRequest>, bus: &Bus) -> Self {
-        let inner = Arc::new(DownloadEngineState::new(incoming_jobs, bus));
+    pub(super) fn new(
+        incoming_jobs: Receiver<DownloadRequest>,
+        start_configs: Option<DownloadConfigs>,
+        bus: &Bus,
+    ) -> Self {
+        let configs = start_configs.unwrap_or_default();
+        let inner = Arc::new(DownloadEngineState::new(incoming_jobs, configs, bus));
         let thread_inner = Arc::clone(&inner);
 
+        // Requests are queued here; the queue decides when they start.
         let worker = std::thread::spawn(move || {
             while let Ok(download_job) = thread_inner.incoming_jobs.recv() {
                 match download_job {
-                    DownloadRequest::DownloadJob { url, directory } => {
-                        let download_inner = Arc::clone(&thread_inner);
-                        let start_download =
-                            std::thread::spawn(move || download_inner.run_download(url, directory));
-
-                        let work_request = WorkRequest::Short(start_download);
-                        if let Err(e) = thread_inner.worker_sender.send(work_request) {
-                            log::warn!(
-                                "Worker Engine closed. Can no longer send requests.\nError source: {:#?}",
-                                e.source()
-                            );
-                            break;
-                        }
+                    DownloadRequest::DownloadJob {
+                        url,
+                        directory,
+                        verify_sender,
+                    } => {
+                        thread_inner.enqueue_download(DownloadJob {
+                            url,
+                            directory,
+                            verify_sender,
+                        });
                     }
                     DownloadRequest::Shutdown => break,
                 }
@@ -237,6 +491,15 @@ impl DownloadEngine {
         Self { inner, work_thread }
     }
 
+    pub(super) fn read_download_configs(&self) -> Arc<DownloadConfigs> {
+        self.inner.configs.load_full()
+    }
+    pub(super) fn write_download_configs(&self, new_configs: DownloadConfigs) {
+        self.inner.configs.store(Arc::new(new_configs));
+        // The limit might have gone up.
+        self.inner.pump_queue();
+    }
+
     // FileDownload is a cheap clone (mostly copy); this should be harmlesss to call in the UI.
     pub(super) fn try_read_current_download_metadata(
         &self,
@@ -244,13 +507,7 @@ impl DownloadEngine {
     ) {
         if let Some(guard) = self.inner.file_downloads.try_read() {
             copy_buffer.clear();
-            // Filter out aborted downloads and return a cloned list.
-            copy_buffer.extend(
-                guard
-                    .iter()
-                    .filter(|(_, download)| !download.should_abort.load(Ordering::Acquire))
-                    .map(|(key, val)| (key, val.clone())),
-            );
+            copy_buffer.extend(guard.iter().map(|(key, val)| (key, val.clone())));
         }
     }
 
@@ -264,8 +521,10 @@ impl DownloadEngine {
             let download_progress: AmortizedDownloadProgress = jobs
                 .iter()
                 .fold((0usize, 0usize), |(current, total), (_, file_download)| {
-                    // If the download is aborted, don't accumulated it.
-                    if file_download.should_abort.load(Ordering::Acquire) {
+                    // Only accumulate the downloads that are actually running.
+                    if !file_download.state().is_active()
+                        || file_download.should_abort.load(Ordering::Acquire)
+                    {
                         (current, total)
                     } else {
                         let progress = file_download.progress();
@@ -287,10 +546,17 @@ impl DownloadEngine {
         self.inner.abort_download(download_id);
     }
 
+    pub(super) fn pause_download(&self, download_id: usize) {
+        self.inner.pause_download(download_id);
+    }
+
+    pub(super) fn resume_download(&self, download_id: usize) {
+        self.inner.resume_download(download_id);
+    }
+
     // NOTE: this will block and should be called with care (i.e. from the debug menu)
     #[cfg(debug_assertions)]
     pub(super) fn add_fake_download(&self) -> usize {
-        use crate::controller::ProgressView;
         let progress = Arc::new(AtomicProgress::new().with_capacity(100));
         progress.set(50);
         let fake_progress = ProgressView::new(progress);
@@ -302,7 +568,6 @@ impl DownloadEngine {
 
     #[cfg(debug_assertions)]
     pub(super) fn add_fake_indeterminate_download(&self) -> usize {
-        use crate::controller::ProgressView;
         let progress = Arc::new(
             AtomicProgress::new()
                 .with_capacity(1)
@@ -343,3 +608,212 @@ impl Drop for DownloadEngine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::resumable_download::tests::{
+        FILE_NAME, Ranges, SeenRequest, file_contents, serve_held,
+    };
+    use crate::controller::test_harness::{CapturedBus, TEST_TIMEOUT};
+    use reqwest::blocking::Client;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+    fn wait_until<F: FnMut() -> bool>(mut condition: F) -> bool {
+        let deadline = Instant::now() + TEST_TIMEOUT;
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        false
+    }
+
+    // A real http error, from a server that answers everything with the status.
+    fn status_error(status: u16) -> RibbleError {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let response = format!(
+                "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+            let _ = stream.write_all(response.as_bytes());
+        });
+        let response = Client::new().get(url).send().unwrap();
+        RibbleError::from(response.error_for_status().unwrap_err())
+    }
+
+    fn new_engine(max_concurrent_downloads: usize, bus: &CapturedBus) -> Arc<DownloadEngineState> {
+        let (_, incoming_jobs) = get_channel(1);
+        let configs = DownloadConfigs::new()
+            .with_max_concurrent_downloads(max_concurrent_downloads)
+            .with_max_retries(0);
+        Arc::new(DownloadEngineState::new(incoming_jobs, configs, bus.bus()))
+    }
+
+    // Each download gets its own directory, so that they don't share a .part file.
+    fn enqueue(
+        engine: &Arc<DownloadEngineState>,
+        url: &str,
+        directory: &TempDir,
+        name: &str,
+    ) -> PathBuf {
+        let job_directory = directory.path().join(name);
+        std::fs::create_dir_all(job_directory.as_path()).unwrap();
+        engine.enqueue_download(DownloadJob {
+            url: url.to_string(),
+            directory: job_directory.clone(),
+            verify_sender: None,
+        });
+        job_directory
+    }
+
+    fn state(engine: &DownloadEngineState, download_id: usize) -> Option<DownloadState> {
+        engine
+            .file_downloads
+            .read()
+            .get(download_id)
+            .map(|download| download.state().clone())
+    }
+
+    // Lets one held response body through.
+    fn release_one(release: &Sender<()>) {
+        release
+            .send_timeout((), TEST_TIMEOUT)
+            .expect("No download is waiting on the server.");
+    }
+
+    fn gets(seen: &std::sync::Mutex<Vec<SeenRequest>>) -> Vec<Option<String>> {
+        seen.lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == "GET")
+            .map(|request| request.range.clone())
+            .collect()
+    }
+
+    fn downloaded(directory: &Path) -> bool {
+        std::fs::read(directory.join(FILE_NAME)).is_ok_and(|file| file == file_contents())
+    }
+
+    #[test]
+    fn retries_back_off_up_to_a_minute() {
+        assert_eq!(retry_delay(1), Duration::from_secs(2));
+        assert_eq!(retry_delay(2), Duration::from_secs(4));
+        assert_eq!(retry_delay(3), Duration::from_secs(8));
+        assert_eq!(retry_delay(6), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        assert!(is_transient(&status_error(500)));
+        assert!(is_transient(&status_error(503)));
+        assert!(is_transient(&status_error(429)));
+        assert!(!is_transient(&status_error(404)));
+        assert!(!is_transient(&status_error(403)));
+        assert!(!is_transient(&status_error(416)));
+
+        // Nothing's listening: the connection is refused.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let refused = Client::new().get(format!("http://{address}/")).send();
+        assert!(is_transient(&RibbleError::from(refused.unwrap_err())));
+
+        let io_error = |kind| RibbleError::IOError(std::io::Error::from(kind));
+        assert!(is_transient(&io_error(ErrorKind::ConnectionReset)));
+        assert!(is_transient(&io_error(ErrorKind::UnexpectedEof)));
+        assert!(!is_transient(&io_error(ErrorKind::PermissionDenied)));
+        assert!(!is_transient(&io_error(ErrorKind::NotFound)));
+        assert!(!is_transient(&RibbleError::Core("Bad url.".to_string())));
+    }
+
+    #[test]
+    fn downloads_past_the_limit_wait_their_turn() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let (release, release_receiver) = get_channel(0);
+        let (url, seen) = serve_held("\"v1\"", Ranges::Supported, Some(release_receiver));
+        let engine = new_engine(2, &bus);
+
+        let directories =
+            ["first", "second", "third"].map(|name| enqueue(&engine, &url, &directory, name));
+
+        assert!(wait_until(|| gets(&seen).len() == 2));
+        assert_eq!(state(&engine, 0), Some(DownloadState::Downloading));
+        assert_eq!(state(&engine, 1), Some(DownloadState::Downloading));
+        assert_eq!(state(&engine, 2), Some(DownloadState::Queued));
+        assert_eq!(engine.active_downloads.lock().len(), 2);
+
+        // One finishes, and the queued download takes its place.
+        release_one(&release);
+        assert!(wait_until(|| gets(&seen).len() == 3));
+        assert!(engine.queue.lock().is_empty());
+        assert!(engine.active_downloads.lock().len() <= 2);
+
+        release_one(&release);
+        release_one(&release);
+        assert!(wait_until(|| engine.file_downloads.read().is_empty()));
+        assert!(directories.iter().all(|directory| downloaded(directory)));
+    }
+
+    #[test]
+    fn paused_downloads_resume_from_the_back_of_the_queue() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let (release, release_receiver) = get_channel(0);
+        let (url, seen) = serve_held("\"v1\"", Ranges::Supported, Some(release_receiver));
+        let engine = new_engine(1, &bus);
+
+        let first = enqueue(&engine, &url, &directory, "first");
+        let second = enqueue(&engine, &url, &directory, "second");
+        assert!(wait_until(|| gets(&seen).len() == 1));
+
+        // Pausing a queued download takes it out of the queue.
+        engine.pause_download(1);
+        assert_eq!(state(&engine, 1), Some(DownloadState::Paused));
+        assert!(engine.queue.lock().is_empty());
+
+        // The running download stops once it gets its next chunk; nothing starts in its place.
+        engine.pause_download(0);
+        release_one(&release);
+        assert!(wait_until(|| engine.active_downloads.lock().is_empty()));
+        assert_eq!(state(&engine, 0), Some(DownloadState::Paused));
+        assert_eq!(gets(&seen).len(), 1);
+        let part_len = std::fs::metadata(first.join(format!("{FILE_NAME}.part")))
+            .unwrap()
+            .len();
+
+        // Resuming goes back through the queue, and picks up from the .part file.
+        engine.resume_download(1);
+        engine.resume_download(0);
+        assert!(wait_until(|| gets(&seen).len() == 2));
+        assert_eq!(state(&engine, 1), Some(DownloadState::Downloading));
+        assert_eq!(state(&engine, 0), Some(DownloadState::Queued));
+        assert_eq!(*engine.queue.lock(), [0]);
+
+        release_one(&release);
+        assert!(wait_until(|| gets(&seen).len() == 3));
+        release_one(&release);
+        assert!(wait_until(|| engine.file_downloads.read().is_empty()));
+
+        // NOTE: the pause can land before the first chunk; there's nothing to resume then.
+        let gets = gets(&seen);
+        assert_eq!(gets[1], None);
+        assert_eq!(
+            gets[2],
+            (part_len > 0).then(|| format!("bytes={part_len}-"))
+        );
+        assert!(downloaded(&first));
+        assert!(downloaded(&second));
+    }
+}
@@ -0,0 +1,443 @@
+// Compressed export formats for recordings.
+// The recordings are kept as (interleaved) f32 wav; these pull the samples through the encoders a
+// block at a time, so memory use doesn't grow with the length of the recording.
+use crate::utils::errors::RibbleError;
+use crate::utils::recorder_configs::RibbleOpusBitrate;
+use crate::utils::resampler::{ChunkResampler, RESAMPLER_CHUNK_SIZE};
+use flacenc::component::{BitRepr, StreamInfo};
+use flacenc::error::Verify;
+use flacenc::source::{Fill, FrameBuf};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// FLAC is written at 24 bits so the quiet end of the (f32) recording isn't thrown away.
+const FLAC_BITS_PER_SAMPLE: usize = 24;
+const FLAC_SAMPLE_SCALE: f32 = ((1 << (FLAC_BITS_PER_SAMPLE - 1)) - 1) as f32;
+const FLAC_MAGIC: &[u8; 4] = b"fLaC";
+const FLAC_STREAMINFO_LEN: usize = 34;
+// Opus only takes these rates; anything else is resampled to 48kHz first.
+const OPUS_SAMPLE_RATES: [usize; 5] = [8000, 12000, 16000, 24000, 48000];
+// Opus timestamps (granule positions) are always counted at 48kHz.
+const OPUS_GRANULE_RATE: usize = 48000;
+// 20ms frames.
+const OPUS_FRAMES_PER_SECOND: usize = 50;
+// Recommended max packet size, from the libopus docs.
+const OPUS_MAX_PACKET_SIZE: usize = 4000;
+const OPUS_VENDOR: &str = concat!("ribble ", env!("CARGO_PKG_VERSION"));
+
+// NOTE: the STREAMINFO block isn't known until the last frame is written, so a placeholder goes
+// out first and is filled in at the end.
+pub(super) fn encode_flac<I, F>(
+    out_path: &Path,
+    samples: I,
+    channels: usize,
+    sample_rate: usize,
+    mut on_progress: F,
+) -> Result<(), RibbleError>
+where
+    I: Iterator<Item = Result<f32, RibbleError>>,
+    F: FnMut(u64),
+{
+    let flac_error =
+        |e: &dyn std::fmt::Display| RibbleError::Core(format!("Failed to encode FLAC: {e}"));
+
+    let configs = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| RibbleError::Core(format!("Invalid FLAC encoder configs: {e}")))?;
+    let block_size = configs.block_size;
+    let stream_info =
+        StreamInfo::new(sample_rate, channels, FLAC_BITS_PER_SAMPLE).map_err(|e| flac_error(&e))?;
+    let mut framebuf = FrameBuf::with_size(channels, block_size).map_err(|e| flac_error(&e))?;
+
+    let mut header = FlacHeader::new(sample_rate, channels, block_size);
+    let mut writer = BufWriter::new(File::create(out_path)?);
+    writer.write_all(&header.to_bytes())?;
+
+    let mut samples = samples;
+    let mut block = Vec::with_capacity(block_size * channels);
+    let mut frame_number = 0;
+    loop {
+        block.clear();
+        for sample in samples.by_ref().take(block_size * channels) {
+            block.push(quantize_flac_sample(sample?));
+        }
+        // A wav file never ends partway through a frame, but don't encode one if it does.
+        block.truncate(block.len() - block.len() % channels);
+        if block.is_empty() {
+            break;
+        }
+
+        framebuf
+            .fill_interleaved(&block)
+            .map_err(|e| flac_error(&e))?;
+        let frame =
+            flacenc::encode_fixed_size_frame(&configs, &framebuf, frame_number, &stream_info)
+                .map_err(|e| flac_error(&e))?;
+        let mut sink = flacenc::bitsink::ByteSink::new();
+        frame.write(&mut sink).map_err(|e| flac_error(&e))?;
+        writer.write_all(sink.as_slice())?;
+
+        header.add_frame(block.len() / channels, sink.as_slice().len());
+        on_progress(block.len() as u64);
+        frame_number += 1;
+    }
+
+    let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header.to_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+fn quantize_flac_sample(sample: f32) -> i32 {
+    (sample.clamp(-1.0, 1.0) * FLAC_SAMPLE_SCALE).round() as i32
+}
+
+// The stream marker and a STREAMINFO metadata block (the only one written).
+struct FlacHeader {
+    sample_rate: usize,
+    channels: usize,
+    block_size: usize,
+    min_frame_len: Option<usize>,
+    max_frame_len: usize,
+    num_frames: u64,
+}
+
+impl FlacHeader {
+    fn new(sample_rate: usize, channels: usize, block_size: usize) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            block_size,
+            min_frame_len: None,
+            max_frame_len: 0,
+            num_frames: 0,
+        }
+    }
+
+    fn add_frame(&mut self, num_frames: usize, frame_len: usize) {
+        self.min_frame_len = Some(
+            self.min_frame_len
+                .map_or(frame_len, |min| min.min(frame_len)),
+        );
+        self.max_frame_len = self.max_frame_len.max(frame_len);
+        self.num_frames += num_frames as u64;
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FLAC_MAGIC.len() + 4 + FLAC_STREAMINFO_LEN);
+        bytes.extend_from_slice(FLAC_MAGIC);
+        // Block header: last-metadata-block flag, type 0 (STREAMINFO), then a 24-bit length.
+        bytes.push(0x80);
+        bytes.extend_from_slice(&(FLAC_STREAMINFO_LEN as u32).to_be_bytes()[1..]);
+
+        // Fixed block size: the min and max are the same (the last block is allowed to be short).
+        bytes.extend_from_slice(&(self.block_size as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.block_size as u16).to_be_bytes());
+        // 0 means unknown.
+        let min_frame_len = self.min_frame_len.unwrap_or_default() as u32;
+        bytes.extend_from_slice(&min_frame_len.to_be_bytes()[1..]);
+        bytes.extend_from_slice(&(self.max_frame_len as u32).to_be_bytes()[1..]);
+
+        // Sample rate (20 bits), channels - 1 (3 bits), bits per sample - 1 (5 bits),
+        // total samples per channel (36 bits).
+        let packed = ((self.sample_rate as u64) << 44)
+            | ((self.channels as u64 - 1) << 41)
+            | ((FLAC_BITS_PER_SAMPLE as u64 - 1) << 36)
+            | (self.num_frames & ((1 << 36) - 1));
+        bytes.extend_from_slice(&packed.to_be_bytes());
+        // The MD5 signature of the audio is optional; zeroes mean it wasn't computed.
+        bytes.extend_from_slice(&[0u8; 16]);
+        bytes
+    }
+}
+
+// Writes an Ogg/Opus file (RFC 7845).
+// NOTE: num_samples is needed up front to know which packet ends the stream.
+pub(super) fn encode_opus<I, F>(
+    out_path: &Path,
+    samples: I,
+    num_samples: u64,
+    channels: usize,
+    sample_rate: usize,
+    bitrate: RibbleOpusBitrate,
+    mut on_progress: F,
+) -> Result<(), RibbleError>
+where
+    I: Iterator<Item = Result<f32, RibbleError>>,
+    F: FnMut(u64),
+{
+    let opus_channels = match channels {
+        1 => opus::Channels::Mono,
+        2 => opus::Channels::Stereo,
+        _ => {
+            return Err(RibbleError::Core(format!(
+                "Opus export supports mono or stereo audio, got {channels} channels."
+            )));
+        }
+    };
+
+    let num_input_frames = num_samples as usize / channels;
+    let mut resampler = if OPUS_SAMPLE_RATES.contains(&sample_rate) {
+        None
+    } else {
+        Some(ChunkResampler::new(
+            channels,
+            sample_rate,
+            OPUS_GRANULE_RATE,
+            num_input_frames,
+        )?)
+    };
+    let (encoder_rate, num_frames) = match resampler.as_ref() {
+        Some(resampler) => (OPUS_GRANULE_RATE, resampler.output_len()),
+        None => (sample_rate, num_input_frames),
+    };
+
+    let mut encoder =
+        opus::Encoder::new(encoder_rate as u32, opus_channels, opus::Application::Audio)?;
+    encoder.set_bitrate(opus::Bitrate::Bits(bitrate.bits_per_second()))?;
+
+    let granule_scale = (OPUS_GRANULE_RATE / encoder_rate) as u64;
+    let lookahead = encoder.get_lookahead()? as usize;
+    let pre_skip = lookahead as u64 * granule_scale;
+    let frame_size = encoder_rate / OPUS_FRAMES_PER_SECOND;
+    // The encoder delays its output by the lookahead; keep encoding (silence) until it's flushed.
+    let num_packets = (num_frames + lookahead).div_ceil(frame_size);
+    let end_granule = pre_skip + num_frames as u64 * granule_scale;
+
+    let mut writer = PacketWriter::new(BufWriter::new(File::create(out_path)?));
+    let serial = std::process::id();
+
+    writer.write_packet(
+        opus_head(channels as u8, pre_skip as u16, sample_rate as u32),
+        serial,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    writer.write_packet(opus_tags(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+    let mut samples = samples;
+    let input_block_len = RESAMPLER_CHUNK_SIZE * channels;
+    let mut input_block = Vec::with_capacity(input_block_len);
+    let mut input_done = false;
+    // Encoder-rate samples that haven't made it into a packet yet.
+    let mut pending = Vec::new();
+    let frame_len = frame_size * channels;
+    let mut frame = vec![0.0f32; frame_len];
+    for packet_no in 0..num_packets {
+        // Top up until there's a whole opus frame, or the input runs out.
+        while pending.len() < frame_len && !input_done {
+            input_block.clear();
+            for sample in samples.by_ref().take(input_block_len) {
+                input_block.push(sample?);
+            }
+            // A short block is the last one.
+            input_done = input_block.len() < input_block_len;
+            match resampler.as_mut() {
+                Some(resampler) => resampler.process(&input_block, &mut pending)?,
+                None => pending.extend_from_slice(&input_block),
+            }
+            on_progress(input_block.len() as u64);
+        }
+
+        let num_taken = frame_len.min(pending.len());
+        frame[..num_taken].copy_from_slice(&pending[..num_taken]);
+        frame[num_taken..].fill(0.0);
+        pending.drain(..num_taken);
+
+        let packet = encoder.encode_vec_float(&frame, OPUS_MAX_PACKET_SIZE)?;
+        let last_packet = packet_no + 1 == num_packets;
+        let (end_info, granule) = if last_packet {
+            // The last granule trims off the padding.
+            (PacketWriteEndInfo::EndStream, end_granule)
+        } else {
+            let granule = ((packet_no + 1) * frame_size) as u64 * granule_scale;
+            (PacketWriteEndInfo::NormalPacket, granule)
+        };
+        writer.write_packet(packet, serial, end_info, granule)?;
+    }
+
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+fn opus_head(channels: u8, pre_skip: u16, input_sample_rate: u32) -> Vec<u8> {
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    // Version
+    head.push(1);
+    head.push(channels);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    // Output gain
+    head.extend_from_slice(&0i16.to_le_bytes());
+    // Channel mapping family 0: mono/stereo.
+    head.push(0);
+    head
+}
+
+fn opus_tags() -> Vec<u8> {
+    let mut tags = Vec::with_capacity(16 + OPUS_VENDOR.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(OPUS_VENDOR.len() as u32).to_le_bytes());
+    tags.extend_from_slice(OPUS_VENDOR.as_bytes());
+    // No user comments.
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(num_frames: usize, channels: usize, sample_rate: usize) -> Vec<f32> {
+        (0..num_frames)
+            .flat_map(|frame| {
+                let t = frame as f32 / sample_rate as f32;
+                (0..channels).map(move |channel| {
+                    0.5 * (std::f32::consts::TAU * (440.0 + 110.0 * channel as f32) * t).sin()
+                })
+            })
+            .collect()
+    }
+
+    fn as_results(samples: &[f32]) -> impl Iterator<Item = Result<f32, RibbleError>> + '_ {
+        samples.iter().copied().map(Ok)
+    }
+
+    #[test]
+    fn flac_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("round_trip.flac");
+        // Not a whole number of blocks, so the last frame is short.
+        let (num_frames, channels, sample_rate) = (10_000, 2, 44100);
+        let samples = sine(num_frames, channels, sample_rate);
+
+        let mut num_reported = 0;
+        encode_flac(
+            path.as_path(),
+            as_results(&samples),
+            channels,
+            sample_rate,
+            |delta| num_reported += delta,
+        )
+        .unwrap();
+        assert_eq!(num_reported, samples.len() as u64);
+
+        let mut reader = claxon::FlacReader::open(path.as_path()).unwrap();
+        let info = reader.streaminfo();
+        assert_eq!(info.sample_rate, sample_rate as u32);
+        assert_eq!(info.channels, channels as u32);
+        assert_eq!(info.bits_per_sample, FLAC_BITS_PER_SAMPLE as u32);
+        assert_eq!(info.samples, Some(num_frames as u64));
+
+        let decoded = reader
+            .samples()
+            .map(|sample| sample.unwrap() as f32 / FLAC_SAMPLE_SCALE)
+            .collect::<Vec<_>>();
+        assert_eq!(decoded.len(), samples.len());
+        // Within a 24-bit step (rounding to the step, plus some room for f32 precision).
+        let tolerance = 1.5 / FLAC_SAMPLE_SCALE;
+        for (decoded, original) in decoded.iter().zip(samples.iter()) {
+            assert!(
+                (decoded - original).abs() <= tolerance,
+                "{decoded} vs {original}"
+            );
+        }
+    }
+
+    #[test]
+    fn flac_empty_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.flac");
+        encode_flac(path.as_path(), as_results(&[]), 1, 16000, |_| {}).unwrap();
+
+        let mut reader = claxon::FlacReader::open(path.as_path()).unwrap();
+        assert_eq!(reader.streaminfo().samples, Some(0));
+        assert_eq!(reader.samples().count(), 0);
+    }
+
+    // Checks the Ogg framing: the header packets, then audio packets with increasing granule
+    // positions, the last of which marks the end of the stream at the right length.
+    fn check_ogg_opus(path: &Path, channels: usize, input_rate: usize, num_output_frames: u64) {
+        let mut reader = ogg::reading::PacketReader::new(File::open(path).unwrap());
+
+        let head = reader.read_packet().unwrap().unwrap();
+        assert!(head.first_in_stream());
+        assert_eq!(&head.data[0..8], b"OpusHead");
+        assert_eq!(head.data[9] as usize, channels);
+        let pre_skip = u16::from_le_bytes([head.data[10], head.data[11]]) as u64;
+        let rate = u32::from_le_bytes(head.data[12..16].try_into().unwrap());
+        assert_eq!(rate as usize, input_rate);
+
+        let tags = reader.read_packet().unwrap().unwrap();
+        assert_eq!(&tags.data[0..8], b"OpusTags");
+
+        let mut decoder = opus::Decoder::new(48000, opus::Channels::Mono).unwrap();
+        let mut decoded = vec![0.0f32; 48000];
+        let mut last_granule = 0;
+        let mut num_decoded = 0;
+        let mut last_packet = None;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            let granule = packet.absgp_page();
+            assert!(granule >= last_granule);
+            last_granule = granule;
+            num_decoded += decoder
+                .decode_float(&packet.data, &mut decoded, false)
+                .unwrap() as u64;
+            last_packet = Some(packet);
+        }
+
+        let last_packet = last_packet.unwrap();
+        assert!(last_packet.last_in_stream());
+        assert_eq!(last_granule, pre_skip + num_output_frames);
+        // Every (48kHz) frame the granule accounts for was actually encoded.
+        assert!(num_decoded >= last_granule);
+    }
+
+    #[test]
+    fn opus_ogg_framing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("framing.opus");
+        let (num_frames, channels, sample_rate) = (12_345, 1, 16000);
+        let samples = sine(num_frames, channels, sample_rate);
+
+        let mut num_reported = 0;
+        encode_opus(
+            path.as_path(),
+            as_results(&samples),
+            samples.len() as u64,
+            channels,
+            sample_rate,
+            RibbleOpusBitrate::default(),
+            |delta| num_reported += delta,
+        )
+        .unwrap();
+        assert_eq!(num_reported, samples.len() as u64);
+        // Granules count at 48kHz.
+        check_ogg_opus(path.as_path(), channels, sample_rate, num_frames as u64 * 3);
+    }
+
+    #[test]
+    fn opus_ogg_framing_resampled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resampled.opus");
+        let (num_frames, channels, sample_rate) = (44100, 2, 44100);
+        let samples = sine(num_frames, channels, sample_rate);
+
+        encode_opus(
+            path.as_path(),
+            as_results(&samples),
+            samples.len() as u64,
+            channels,
+            sample_rate,
+            RibbleOpusBitrate::default(),
+            |_| {},
+        )
+        .unwrap();
+        // One second in, one second out.
+        check_ogg_opus(path.as_path(), channels, sample_rate, 48000);
+    }
+}
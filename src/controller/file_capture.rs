@@ -0,0 +1,293 @@
+// A "microphone" that replays a wav file in real time.
+// The file is converted up front to the requested spec (channels, sample rate), and then fed to
+// the sink one period at a time at the rate a sound card would. Once the file runs out, the sink
+// is dropped, which ends the recording/transcription the same way a closed device would.
+//
+// This is mainly for testing realtime transcription deterministically, without a sound card.
+// NOTE: the replay starts when the capture starts playing; the realtime transcriber drops audio
+// until its model is loaded, so leave some leading silence in the file.
+use crate::controller::audio_backend_proxy::remap_channels;
+use crate::utils::resampler::resample;
+use hound::{SampleFormat, WavReader};
+use ribble_whisper::audio::audio_backend::CaptureSpec;
+use ribble_whisper::audio::microphone::{MicCapture, RibbleAudioFormat};
+use ribble_whisper::audio::recorder::{ArcChannelSink, SampleSink};
+use ribble_whisper::utils::errors::RibbleWhisperError;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// In frames; SDL's default is in the same ballpark.
+const DEFAULT_PERIOD: usize = 1024;
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub(crate) struct FileCapture {
+    sample_rate: usize,
+    channels: u8,
+    period: usize,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+    replay_thread: Option<JoinHandle<()>>,
+}
+
+impl FileCapture {
+    pub(crate) fn open(
+        path: &Path,
+        spec: CaptureSpec,
+        sink: ArcChannelSink<f32>,
+    ) -> Result<Self, RibbleWhisperError> {
+        let wav_error = |e: hound::Error| {
+            RibbleWhisperError::DeviceError(format!(
+                "Failed to read audio file: {}\nError: {e}",
+                path.display()
+            ))
+        };
+
+        let reader = WavReader::open(path).map_err(wav_error)?;
+        let wav_spec = reader.spec();
+        let samples = match wav_spec.sample_format {
+            SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>(),
+            SampleFormat::Int => {
+                let scale = (1i64 << (wav_spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 / scale))
+                    .collect::<Result<Vec<_>, _>>()
+            }
+        }
+        .map_err(wav_error)?;
+
+        let file_rate = wav_spec.sample_rate as usize;
+        let file_channels = wav_spec.channels as usize;
+        let sample_rate = spec.sample_rate().unwrap_or(file_rate);
+        let channels = spec.channels().unwrap_or(wav_spec.channels as u8);
+        let period = spec.period().unwrap_or(DEFAULT_PERIOD);
+
+        let samples = remap_channels(&samples, file_channels, channels as usize);
+        let samples = if sample_rate == file_rate {
+            samples
+        } else {
+            resample(&samples, channels as usize, file_rate, sample_rate)
+                .map_err(|e| RibbleWhisperError::DeviceError(e.to_string()))?
+        };
+
+        let playing = Arc::new(AtomicBool::new(false));
+        let closed = Arc::new(AtomicBool::new(false));
+        let thread_playing = Arc::clone(&playing);
+        let thread_closed = Arc::clone(&closed);
+        let chunk_len = period * channels as usize;
+        let chunk_duration = Duration::from_secs_f64(period as f64 / sample_rate as f64);
+
+        let replay_thread = std::thread::spawn(move || {
+            replay(
+                samples,
+                sink,
+                chunk_len,
+                chunk_duration,
+                thread_playing,
+                thread_closed,
+            )
+        });
+
+        Ok(Self {
+            sample_rate,
+            channels,
+            period,
+            playing,
+            closed,
+            replay_thread: Some(replay_thread),
+        })
+    }
+}
+
+fn replay(
+    samples: Vec<f32>,
+    mut sink: ArcChannelSink<f32>,
+    chunk_len: usize,
+    chunk_duration: Duration,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+) {
+    let mut chunks = samples.chunks(chunk_len);
+    // When the next chunk is due; None while paused, so that a resume doesn't try to catch up.
+    let mut next_chunk: Option<Instant> = None;
+    while !closed.load(Ordering::Acquire) {
+        if !playing.load(Ordering::Acquire) {
+            next_chunk = None;
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+            continue;
+        }
+
+        let due = *next_chunk.get_or_insert_with(Instant::now);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+
+        let Some(chunk) = chunks.next() else {
+            log::info!("Audio file finished; closing the capture.");
+            break;
+        };
+        sink.push(chunk);
+        // Scheduled from the last deadline (not now), so the feed doesn't drift.
+        next_chunk = Some(due + chunk_duration);
+    }
+}
+
+impl Drop for FileCapture {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+        if let Some(replay_thread) = self.replay_thread.take()
+            && replay_thread.join().is_err()
+        {
+            log::error!("Audio file replay thread panicked.");
+        }
+    }
+}
+
+impl MicCapture for FileCapture {
+    fn play(&self) {
+        self.playing.store(true, Ordering::Release);
+    }
+
+    fn pause(&self) {
+        self.playing.store(false, Ordering::Release);
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn format(&self) -> RibbleAudioFormat {
+        RibbleAudioFormat::F32
+    }
+
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::test_harness::{TEST_TIMEOUT, drain_write_job};
+    use hound::{WavSpec, WavWriter};
+    use ribble_whisper::utils::{Receiver, get_channel};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    const FILE_NAME: &str = "replay.wav";
+
+    // A mono 16-bit file holding the given samples.
+    fn write_wav(directory: &TempDir, sample_rate: u32, samples: &[i16]) -> PathBuf {
+        let path = directory.path().join(FILE_NAME);
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    fn open(path: &Path, spec: CaptureSpec) -> (FileCapture, Receiver<Arc<[f32]>>) {
+        let (sender, receiver) = get_channel(64);
+        let capture = FileCapture::open(path, spec, ArcChannelSink::new(sender)).unwrap();
+        (capture, receiver)
+    }
+
+    #[test]
+    fn replays_at_the_requested_rate_and_channels() {
+        let directory = tempfile::tempdir().unwrap();
+        // A quarter second at 8kHz.
+        let path = write_wav(&directory, 8000, &[8192; 2000]);
+        let spec = CaptureSpec::new()
+            .with_sample_rate(Some(16000))
+            .with_num_channels(Some(2))
+            .with_period(Some(256));
+        let (capture, receiver) = open(&path, spec);
+
+        assert_eq!(capture.sample_rate(), 16000);
+        assert_eq!(capture.channels(), 2);
+        assert_eq!(capture.buffer_size(), 256);
+
+        let started = Instant::now();
+        capture.play();
+        let chunks = drain_write_job(&receiver, TEST_TIMEOUT);
+        let elapsed = started.elapsed();
+
+        // The file runs out, so the sink closes without the capture being dropped.
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 256 * 2));
+        let samples: Vec<f32> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.iter().copied())
+            .collect();
+        assert_eq!(samples.len(), 4000 * 2);
+        for frame in samples.chunks_exact(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+        // Fed in real time: the last chunk is due 15 periods (240ms) in.
+        assert!(
+            elapsed >= Duration::from_millis(200),
+            "elapsed: {elapsed:?}"
+        );
+        drop(capture);
+    }
+
+    #[test]
+    fn an_open_spec_replays_the_file_as_is() {
+        let directory = tempfile::tempdir().unwrap();
+        let samples = [0, 16384, -16384, i16::MIN];
+        let path = write_wav(&directory, 16000, &samples);
+        let (capture, receiver) = open(&path, CaptureSpec::new());
+
+        assert_eq!(capture.sample_rate(), 16000);
+        assert_eq!(capture.channels(), 1);
+        assert_eq!(capture.buffer_size(), DEFAULT_PERIOD);
+
+        capture.play();
+        let chunks = drain_write_job(&receiver, TEST_TIMEOUT);
+        let replayed: Vec<f32> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.iter().copied())
+            .collect();
+        assert_eq!(replayed, vec![0.0, 0.5, -0.5, -1.0]);
+    }
+
+    #[test]
+    fn nothing_is_fed_until_it_plays() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = write_wav(&directory, 16000, &[0; 1600]);
+        let (capture, receiver) = open(&path, CaptureSpec::new());
+
+        let waited = receiver.recv_timeout(Duration::from_millis(50));
+        assert!(waited.is_err_and(|e| e.is_timeout()));
+
+        capture.play();
+        let chunks = drain_write_job(&receiver, TEST_TIMEOUT);
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).sum::<usize>(), 1600);
+    }
+
+    #[test]
+    fn dropping_the_capture_closes_the_sink() {
+        let directory = tempfile::tempdir().unwrap();
+        // A minute long; the replay can't finish on its own during the test.
+        let path = write_wav(&directory, 8000, &vec![0; 8000 * 60]);
+        let (capture, receiver) = open(&path, CaptureSpec::new());
+
+        capture.play();
+        assert!(receiver.recv_timeout(TEST_TIMEOUT).is_ok());
+        drop(capture);
+        drain_write_job(&receiver, TEST_TIMEOUT);
+    }
+}
@@ -1,29 +1,47 @@
+use crate::controller::batch_queue::BatchJob;
 use crate::controller::console::ConsoleEngine;
 use crate::controller::downloader::DownloadEngine;
-use crate::controller::model_bank::RibbleModelBank;
+use crate::controller::model_bank::{ModelLease, RibbleModelBank};
+use crate::controller::model_catalog::{CatalogModel, ModelCatalog};
+use crate::controller::model_header::ModelHeader;
+use crate::controller::model_integrity::ModelIntegrity;
+use crate::controller::playback::{PlaybackEngine, PlaybackHandle};
 use crate::controller::progress::ProgressEngine;
 use crate::controller::recorder::RecorderEngine;
+use crate::controller::session_checkpoint::SessionCheckpointer;
 use crate::controller::transcriber::TranscriberEngine;
+use crate::controller::transcript::{Transcript, TranscriptEdit, TranscriptionSource};
 use crate::controller::visualizer::VisualizerEngine;
+use crate::controller::watch_folder::WatchFolderEngine;
 use crate::controller::worker::WorkerEngine;
 use crate::controller::writer::WriterEngine;
 use crate::controller::{
     AmortizedDownloadProgress, AmortizedProgress, Bus, CompletedRecordingJobs, ConsoleMessage,
-    DEFAULT_PROGRESS_SLAB_CAPACITY, LatestError, ModelFile, NUM_VISUALIZER_BUCKETS,
-    OfflineTranscriberFeedback, Progress, RotationDirection, SMALL_UTILITY_QUEUE_SIZE,
-    UTILITY_QUEUE_SIZE,
+    DEFAULT_PROGRESS_SLAB_CAPACITY, DownloadRequest, LatestError, ModelDiskUsage, ModelFile,
+    NUM_VISUALIZER_BUCKETS, OfflineTranscriberFeedback, Progress, RibbleMessage, RotationDirection,
+    SMALL_UTILITY_QUEUE_SIZE, UTILITY_QUEUE_SIZE, VisualizerPacket, WriteRequest,
 };
 use crate::controller::{AnalysisType, FileDownload};
 use crate::utils::audio_gain::AudioGainConfigs;
+use crate::utils::batch_configs::BatchConfigs;
+use crate::utils::control_api_configs::ControlApiConfigs;
+use crate::utils::download_configs::DownloadConfigs;
 use crate::utils::errors::RibbleError;
+use crate::utils::live_sink_configs::LiveSinkConfigs;
+use crate::utils::noise_suppression::NoiseSuppressionConfigs;
 use crate::utils::preferences::UserPreferences;
-use crate::utils::recorder_configs::{RibbleExportFormat, RibbleRecordingConfigs};
+use crate::utils::recorder_configs::{
+    RibbleExportFormat, RibbleOpusBitrate, RibbleRecordingConfigs,
+};
+use crate::utils::recording_library_configs::RecordingLibraryConfigs;
+use crate::utils::transcript_formats::{SubtitleConfigs, TranscriptFormat, format_rfc3339};
 use crate::utils::vad_configs::VadConfigs;
+use crate::utils::watch_folder_configs::WatchFolderConfigs;
 
-use crate::controller::audio_backend_proxy::AudioBackendProxy;
+use crate::controller::audio_backend_proxy::{AudioBackendKind, AudioBackendProxy};
 use arc_swap::ArcSwap;
 use ribble_whisper::transcriber::{TranscriptionSnapshot, WhisperControlPhrase};
-use ribble_whisper::utils::get_channel;
+use ribble_whisper::utils::{Receiver, get_channel};
 use ribble_whisper::whisper::configs::WhisperRealtimeConfigs;
 use ribble_whisper::whisper::model::ModelId;
 use ron::ser::PrettyConfig;
@@ -31,11 +49,15 @@ use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(super) struct Kernel {
     data_directory: PathBuf,
     user_preferences: ArcSwap<UserPreferences>,
+    control_api_configs: ArcSwap<ControlApiConfigs>,
     audio_backend: Arc<AudioBackendProxy>,
+    // The capture devices, as last listed by the main thread.
+    input_devices: ArcSwap<Vec<Arc<str>>>,
     transcriber_engine: TranscriberEngine,
     recorder_engine: RecorderEngine,
     console_engine: ConsoleEngine,
@@ -44,9 +66,12 @@ pub(super) struct Kernel {
     worker_engine: WorkerEngine,
     writer_engine: WriterEngine,
     download_engine: DownloadEngine,
+    watch_folder_engine: WatchFolderEngine,
+    playback_engine: PlaybackEngine,
     // Since model bank needs to be accessed elsewhere (e.g. TranscriberEngine), this needs to be
     // in a shared pointer.
     model_bank: Arc<RibbleModelBank>,
+    session_checkpointer: Arc<SessionCheckpointer>,
     bus: Bus,
 }
 
@@ -57,10 +82,24 @@ impl Kernel {
 
     // NOTE: this needs to take in the audio capture request sender from the app (main thread)
     // to uphold SDL invariants.
+    // NOTE: if the last session didn't exit cleanly, the runner has already asked whether to
+    // restore it.
     pub(super) fn new(
         data_directory: &Path,
         audio_backend: AudioBackendProxy,
+        restore_session: bool,
     ) -> Result<Self, RibbleError> {
+        let kernel = Self::build(data_directory, audio_backend)?;
+        if restore_session {
+            kernel.restore_session();
+        } else {
+            kernel.discard_session();
+        }
+        kernel.watch_folder_engine.start_watching();
+        Ok(kernel)
+    }
+
+    fn build(data_directory: &Path, audio_backend: AudioBackendProxy) -> Result<Self, RibbleError> {
         if !data_directory.is_absolute() {
             return Err(RibbleError::Core(format!(
                 "Data directory not canonicalized: {data_directory:#?}"
@@ -71,12 +110,31 @@ impl Kernel {
             transcriber_configs,
             offline_transcriber_feedback,
             transcriber_gain_settings,
+            noise_suppression_configs,
+            subtitle_configs,
+            batch_configs,
+            watch_folder_configs,
+            live_sink_configs,
             vad_configs,
             recording_configs,
+            recording_library_configs,
             visualizer_analysis_type,
             export_format,
+            opus_bitrate,
             user_preferences,
+            control_api_configs,
+            download_configs,
         } = Self::deserialize_user_data(data_directory);
+        let transcriber_state = TranscriberState {
+            transcriber_configs,
+            offline_transcriber_feedback,
+            transcriber_gain_settings,
+            noise_suppression_configs,
+            subtitle_configs,
+            batch_configs,
+            live_sink_configs,
+            vad_configs,
+        };
         let (console_sender, console_receiver) = get_channel(UTILITY_QUEUE_SIZE);
         // NOTE: at the moment, it seems like 16 messages is too small for the progress channel
         // Download updates fire at the -byte- level, so perhaps this needs to batch the update.
@@ -94,9 +152,14 @@ impl Kernel {
             download_sender,
         );
 
+        audio_backend.set_input_device(recording_configs.input_device().cloned());
         // TODO: take the configs objects/export formats as options
-        let recorder_engine =
-            RecorderEngine::new(Some(recording_configs), Some(export_format), &bus);
+        let recorder_engine = RecorderEngine::new(
+            Some(recording_configs),
+            Some(export_format),
+            Some(opus_bitrate),
+            &bus,
+        );
         let console_engine = ConsoleEngine::new(
             console_receiver,
             user_preferences.console_message_size(),
@@ -107,50 +170,49 @@ impl Kernel {
         let visualizer_engine =
             VisualizerEngine::new(visualizer_receiver, visualizer_analysis_type);
         let worker_engine = WorkerEngine::new(work_receiver, &bus)?;
+        let session_checkpointer = Arc::new(SessionCheckpointer::new(data_directory, true));
 
         let recording_directory = data_directory.join(Self::TEMP_AUDIO_DIR_SLUG);
         // CREATE the recording directory if it doesn't exist.
         std::fs::create_dir_all(&recording_directory)?;
 
-        let writer_engine = WriterEngine::new(recording_directory, write_receiver, &bus);
-        let download_engine = DownloadEngine::new(download_receiver, &bus);
+        // NOTE: if the recording library is enabled, this reloads the previous recordings.
+        let writer_engine = WriterEngine::new(
+            recording_directory,
+            write_receiver,
+            Some(recording_library_configs),
+            Arc::clone(&session_checkpointer),
+            &bus,
+        );
+        let model_catalog = download_configs.model_catalog().map(Path::to_path_buf);
+        let download_engine = DownloadEngine::new(download_receiver, Some(download_configs), &bus);
 
         let model_directory = data_directory.join(Self::MODEL_BANK_DIR_SLUG);
         // CREATE the model directory if it doesn't exist
         std::fs::create_dir_all(&model_directory)?;
 
         let model_bank = Arc::new(RibbleModelBank::new(model_directory.as_path(), &bus)?);
+        model_bank.load_model_catalog(model_catalog);
 
-        // In case the user has mucked around with the model directory and the previous ID is
-        // invalid in the configs, catch it before constructing the TranscriberEngine and set the
-        // configs ID to None.
-        let model_id = transcriber_configs
-            .model_id()
-            .as_ref()
-            .and_then(|model_id| {
-                if !model_bank.contains_model(*model_id) {
-                    None
-                } else {
-                    Some(*model_id)
-                }
-            });
+        let transcriber_engine =
+            transcriber_state.build_engine(&model_bank, Arc::clone(&session_checkpointer), &bus);
 
-        let transcriber_configs = transcriber_configs.with_model_id(model_id);
-
-        // NOTE: to avoid already modifying the transcriber engine, just construct it last after
-        // the ID check has been run.
-        let transcriber_engine = TranscriberEngine::new(
-            Some(transcriber_configs),
-            Some(vad_configs),
-            Some(offline_transcriber_feedback),
-            Some(transcriber_gain_settings),
+        // The watch folder feeds the batch queue, so it needs its own handle to the transcriber.
+        let watch_folder_engine = WatchFolderEngine::new(
+            Some(watch_folder_configs),
+            transcriber_engine.clone(),
+            Arc::clone(&model_bank),
             &bus,
         );
 
+        let playback_engine = PlaybackEngine::new(&bus);
+
         Ok(Self {
             data_directory: data_directory.to_path_buf(),
             user_preferences: ArcSwap::from(Arc::new(user_preferences)),
+            control_api_configs: ArcSwap::from(Arc::new(control_api_configs)),
             audio_backend: Arc::new(audio_backend),
+            input_devices: ArcSwap::from(Arc::new(vec![])),
             transcriber_engine,
             recorder_engine,
             console_engine,
@@ -159,11 +221,57 @@ impl Kernel {
             worker_engine,
             writer_engine,
             download_engine,
+            watch_folder_engine,
+            playback_engine,
             model_bank,
+            session_checkpointer,
             bus,
         })
     }
 
+    // Restores whatever was checkpointed before the last session crashed.
+    fn restore_session(&self) {
+        let mut restored = Vec::new();
+        if let Some(checkpoint) = self.session_checkpointer.read_transcript_checkpoint() {
+            let saved_at = format_rfc3339(checkpoint.saved_at());
+            let (snapshot, transcript, audio_file) = checkpoint.into_parts();
+            self.transcriber_engine
+                .restore_transcript(snapshot, transcript, audio_file);
+            restored.push(format!("Restored the unsaved transcript (last saved: {saved_at})."));
+        }
+
+        // This also picks up recordings that finished, but were never cleaned up.
+        let in_progress = self.session_checkpointer.read_recording_checkpoint();
+        let num_recovered = self
+            .writer_engine
+            .recover_orphaned_recordings(in_progress.as_ref());
+        self.session_checkpointer.clear_recording();
+        if num_recovered > 0 {
+            restored.push(format!("Recovered {num_recovered} unfinished recording(s)."));
+        }
+
+        let console_sender = self.bus.console_message_sender();
+        for message in restored {
+            log::info!("{message}");
+            if let Err(e) = console_sender.try_send(ConsoleMessage::Status(message)) {
+                log::warn!(
+                    "Cannot send session restore message, channel is too small or closed.\n\
+                    Error: {e}"
+                );
+            }
+        }
+    }
+
+    // Removes whatever was left behind by the last session, so that none of it turns up later.
+    fn discard_session(&self) {
+        self.session_checkpointer.clear_transcript();
+        self.session_checkpointer.clear_recording();
+        let num_discarded = self.writer_engine.discard_orphaned_recordings();
+        if num_discarded > 0 {
+            log::info!("Discarded {num_discarded} unfinished recording(s).");
+        }
+    }
+
     // This is mainly in service to the UI, which needs to know whether major (long) jobs are being
     // run. This includes recording, transcribing, etc -> anything which would suggest state
     // changes which merit refreshing UI.
@@ -175,6 +283,9 @@ impl Kernel {
     // If the app is being closed and there are background loops running, the program can deadlock.
     // This is a "kill everything" so that cleanup can happen on drop.
     pub(super) fn stop_work(&self) {
+        // Stop watching first so that nothing new gets queued while shutting down.
+        self.watch_folder_engine.stop_watching();
+        self.playback_engine.pause();
         self.transcriber_engine.stop_offline();
         self.transcriber_engine.stop_realtime();
         self.recorder_engine.stop_recording();
@@ -197,6 +308,13 @@ impl Kernel {
         }
     }
 
+    pub(super) fn read_control_api_configs(&self) -> Arc<ControlApiConfigs> {
+        self.control_api_configs.load_full()
+    }
+    pub(super) fn write_control_api_configs(&self, new_configs: ControlApiConfigs) {
+        self.control_api_configs.store(Arc::new(new_configs));
+    }
+
     pub(super) fn read_app_theme(&self) -> Option<catppuccin_egui::Theme> {
         self.user_preferences.load().system_theme().app_theme()
     }
@@ -226,10 +344,63 @@ impl Kernel {
         self.model_bank.create_model_key(model_key)
     }
 
+    pub(super) fn verify_all_models(&self) {
+        self.console_engine.clear_latest_error();
+        self.model_bank.verify_all_models();
+    }
+
+    pub(super) fn read_model_integrity(&self, model_id: ModelId) -> ModelIntegrity {
+        self.model_bank.model_integrity(model_id)
+    }
+
+    pub(super) fn read_model_header(&self, model_id: ModelId) -> Option<ModelHeader> {
+        self.model_bank.model_header(model_id)
+    }
+
+    pub(super) fn verifying_model(&self) -> Option<Arc<str>> {
+        self.model_bank.verifying_model()
+    }
+
     pub(super) fn get_model_directory(&self) -> &Path {
         self.model_bank.model_directory()
     }
 
+    pub(super) fn read_model_size(&self, model_id: ModelId) -> Option<u64> {
+        self.model_bank.model_size(model_id)
+    }
+
+    pub(super) fn model_disk_usage(&self) -> ModelDiskUsage {
+        self.model_bank.disk_usage()
+    }
+
+    pub(super) fn get_model_path(&self, model_id: ModelId) -> Option<PathBuf> {
+        self.model_bank.model_path(model_id)
+    }
+
+    // NOTE: the model bank refuses to delete (or rename) a model that's in use; the transcribers
+    // are handed a lease on their model (see: ModelLease), rather than the bank.
+    pub(super) fn delete_model(&self, model_id: ModelId) -> Result<(), RibbleError> {
+        let file_name = self.model_bank.delete_model(model_id)?;
+
+        let configs = *self.read_transcription_configs();
+        if *configs.model_id() == Some(model_id) {
+            self.write_transcription_configs(configs.with_model_id(None));
+        }
+        log::info!("Deleted model: {file_name}");
+        Ok(())
+    }
+
+    pub(super) fn rename_model(&self, model_id: ModelId, new_name: &str) -> Result<(), RibbleError> {
+        let new_id = self.model_bank.rename_model(model_id, new_name)?;
+
+        // Keep the selection pointing at the same model.
+        let configs = *self.read_transcription_configs();
+        if *configs.model_id() == Some(model_id) {
+            self.write_transcription_configs(configs.with_model_id(Some(new_id)));
+        }
+        Ok(())
+    }
+
     // (ID, File name)
     pub(super) fn try_read_model_list(&self, copy_buffer: &mut Vec<(ModelId, ModelFile)>) {
         self.model_bank.try_read_model_list(copy_buffer);
@@ -267,12 +438,40 @@ impl Kernel {
         self.transcriber_engine
             .write_audio_gain_configs(new_settings);
     }
+    pub(super) fn read_noise_suppression_configs(&self) -> Arc<NoiseSuppressionConfigs> {
+        self.transcriber_engine.read_noise_suppression_configs()
+    }
+    pub(super) fn write_noise_suppression_configs(&self, new_configs: NoiseSuppressionConfigs) {
+        self.transcriber_engine
+            .write_noise_suppression_configs(new_configs);
+    }
+    pub(super) fn read_subtitle_configs(&self) -> Arc<SubtitleConfigs> {
+        self.transcriber_engine.read_subtitle_configs()
+    }
+    pub(super) fn write_subtitle_configs(&self, new_configs: SubtitleConfigs) {
+        self.transcriber_engine.write_subtitle_configs(new_configs);
+    }
+    pub(super) fn read_batch_configs(&self) -> Arc<BatchConfigs> {
+        self.transcriber_engine.read_batch_configs()
+    }
+    pub(super) fn write_batch_configs(&self, new_configs: BatchConfigs) {
+        self.transcriber_engine.write_batch_configs(new_configs);
+    }
+    pub(super) fn read_live_sink_configs(&self) -> Arc<LiveSinkConfigs> {
+        self.transcriber_engine.read_live_sink_configs()
+    }
+    pub(super) fn write_live_sink_configs(&self, new_configs: LiveSinkConfigs) {
+        self.transcriber_engine.write_live_sink_configs(new_configs);
+    }
     pub(super) fn realtime_running(&self) -> bool {
         self.transcriber_engine.realtime_running()
     }
     pub(super) fn offline_running(&self) -> bool {
         self.transcriber_engine.offline_running()
     }
+    pub(super) fn batch_running(&self) -> bool {
+        self.transcriber_engine.batch_running()
+    }
     pub(super) fn transcriber_running(&self) -> bool {
         self.transcriber_engine.transcriber_running()
     }
@@ -295,6 +494,28 @@ impl Kernel {
     pub(super) fn read_transcription_snapshot(&self) -> Arc<TranscriptionSnapshot> {
         self.transcriber_engine.read_transcription_snapshot()
     }
+    pub(super) fn read_transcript(&self) -> Arc<Transcript> {
+        self.transcriber_engine.read_transcript()
+    }
+    // NOTE: the watch folder can start a batch (which replaces the transcript) at any time, so
+    // the transcript is left alone while it's active.
+    pub(super) fn edit_transcript(&self, edit: &TranscriptEdit) -> Result<(), RibbleError> {
+        if self.watch_folder_active() {
+            return Err(RibbleError::Core(
+                "The transcript cannot be edited while the watch folder is active.".to_string(),
+            ));
+        }
+        self.transcriber_engine.edit_transcript(edit)
+    }
+    pub(super) fn revert_transcript_edits(&self) {
+        if self.watch_folder_active() {
+            return;
+        }
+        self.transcriber_engine.revert_transcript_edits();
+    }
+    pub(super) fn transcript_edited(&self) -> bool {
+        self.transcriber_engine.transcript_edited()
+    }
     pub(super) fn read_latest_control_phrase(&self) -> Arc<WhisperControlPhrase> {
         self.transcriber_engine.read_latest_control_phrase()
     }
@@ -303,8 +524,14 @@ impl Kernel {
         self.transcriber_engine.read_current_audio_file_path()
     }
 
+    // The model is leased from the bank for the length of the run.
+    fn lease_selected_model(&self) -> Arc<ModelLease> {
+        let model_id = *self.read_transcription_configs().model_id();
+        Arc::new(self.model_bank.lease_model(model_id))
+    }
+
     pub(super) fn start_realtime_transcription(&self) {
-        let bank = Arc::clone(&self.model_bank);
+        let bank = self.lease_selected_model();
         let backend = Arc::clone(&self.audio_backend);
 
         // Clear the latest error before starting background work.
@@ -320,27 +547,146 @@ impl Kernel {
     pub(super) fn clear_audio_file_path(&self) {
         self.transcriber_engine.clear_current_audio_file_path();
     }
+    // For recordings: the first segment stands in as the audio file path.
+    pub(super) fn set_audio_file_segments(&self, segments: Vec<PathBuf>) {
+        self.transcriber_engine.set_current_audio_segments(segments);
+    }
+    pub(super) fn read_current_audio_files(&self) -> Vec<PathBuf> {
+        self.transcriber_engine.read_current_audio_files()
+    }
 
     // NOTE: The WriterEngine will update its own state if its recording cache is empty.
     // NOTE TWICE: This does not guarantee there won't be a file issue if the recording is missing.
     pub(super) fn try_retranscribe_latest(&self) {
-        if let Some(path) = self.try_get_latest_recording() {
-            self.set_audio_file_path(path);
+        if let Some(segments) = self.try_get_latest_recording() {
+            self.set_audio_file_segments(segments);
             self.start_offline_transcription();
         }
     }
 
     pub(super) fn start_offline_transcription(&self) {
-        let bank = Arc::clone(&self.model_bank);
+        let bank = self.lease_selected_model();
         // Clear the latest error before starting background work.
         self.console_engine.clear_latest_error();
         self.transcriber_engine.start_offline_transcription(bank);
     }
 
-    pub(super) fn save_transcription(&self, out_path: PathBuf) {
+    // BATCH TRANSCRIPTION
+    pub(super) fn start_batch_transcription(&self) {
+        let bank = self.lease_selected_model();
+        // The batch runs with the current transcription configs.
+        let model_file = self
+            .transcriber_engine
+            .read_transcription_configs()
+            .model_id()
+            .and_then(|model_id| self.model_bank.get_model_file(model_id));
+        // Clear the latest error before starting background work.
+        self.console_engine.clear_latest_error();
+        self.transcriber_engine
+            .start_batch_transcription(bank, model_file);
+    }
+    pub(super) fn enqueue_batch_files(&self, audio_file_paths: Vec<PathBuf>) {
+        self.transcriber_engine
+            .enqueue_batch_files(audio_file_paths, None);
+    }
+    pub(super) fn remove_batch_job(&self, job_id: usize) {
+        self.transcriber_engine.remove_batch_job(job_id);
+    }
+    pub(super) fn requeue_batch_job(&self, job_id: usize) {
+        self.transcriber_engine.requeue_batch_job(job_id);
+    }
+    pub(super) fn clear_finished_batch_jobs(&self) {
+        self.transcriber_engine.clear_finished_batch_jobs();
+    }
+    pub(super) fn try_read_batch_queue(&self, copy_buffer: &mut Vec<(usize, BatchJob)>) {
+        self.transcriber_engine.try_read_batch_queue(copy_buffer);
+    }
+
+    // WATCH FOLDER
+    pub(super) fn read_watch_folder_configs(&self) -> Arc<WatchFolderConfigs> {
+        self.watch_folder_engine.read_watch_folder_configs()
+    }
+    pub(super) fn write_watch_folder_configs(&self, new_configs: WatchFolderConfigs) {
+        self.watch_folder_engine
+            .write_watch_folder_configs(new_configs);
+    }
+    pub(super) fn watch_folder_active(&self) -> bool {
+        self.watch_folder_engine.watch_folder_active()
+    }
+
+    // PLAYBACK
+    pub(super) fn playback_handle(&self) -> PlaybackHandle {
+        self.playback_engine.playback_handle()
+    }
+    pub(super) fn load_playback_audio(&self, audio_files: Vec<PathBuf>) {
         // Clear the latest error before starting background work.
         self.console_engine.clear_latest_error();
-        self.transcriber_engine.save_transcription(out_path);
+        self.playback_engine.load_audio(audio_files);
+    }
+    pub(super) fn playback_loading(&self) -> bool {
+        self.playback_engine.playback_loading()
+    }
+    pub(super) fn playback_playing(&self) -> bool {
+        self.playback_engine.playback_playing()
+    }
+    pub(super) fn playback_path(&self) -> Option<PathBuf> {
+        self.playback_engine.playback_path()
+    }
+    pub(super) fn playback_position(&self) -> Duration {
+        self.playback_engine.playback_position()
+    }
+    pub(super) fn playback_duration(&self) -> Duration {
+        self.playback_engine.playback_duration()
+    }
+    pub(super) fn play_playback(&self) {
+        self.playback_engine.play();
+    }
+    pub(super) fn pause_playback(&self) {
+        self.playback_engine.pause();
+    }
+    pub(super) fn seek_playback(&self, position: Duration) {
+        self.playback_engine.seek(position);
+    }
+
+    pub(super) fn save_transcription(&self, out_path: PathBuf, format: TranscriptFormat) {
+        // Clear the latest error before starting background work.
+        self.console_engine.clear_latest_error();
+        let model_file = self.last_run_model_file();
+        self.link_transcript_to_recording(out_path.as_path());
+        self.transcriber_engine
+            .save_transcription(out_path, format, model_file);
+    }
+
+    // If the transcript came from a recording, remember where it was saved so that the recording
+    // library can find it again.
+    fn link_transcript_to_recording(&self, transcript_path: &Path) {
+        let run_info = self.transcriber_engine.read_last_run_info();
+        let Some(run_info) = run_info.as_ref() else {
+            return;
+        };
+        let recording = match run_info.source() {
+            TranscriptionSource::AudioFile(path) => self.writer_engine.recording_file_name(path),
+            TranscriptionSource::Microphone => self
+                .writer_engine
+                .first_recording_since(run_info.started_at()),
+        };
+        if let Some(file_name) = recording {
+            self.writer_engine
+                .link_transcript(&file_name, transcript_path);
+        }
+    }
+
+    pub(super) fn format_transcription(
+        &self,
+        format: TranscriptFormat,
+    ) -> Result<String, RibbleError> {
+        let model_file = self.last_run_model_file();
+        self.transcriber_engine
+            .format_transcription(format, model_file)
+    }
+
+    fn last_run_model_file(&self) -> Option<ModelFile> {
+        last_run_model_file(&self.transcriber_engine, &self.model_bank)
     }
 
     // RECORDER
@@ -352,21 +698,76 @@ impl Kernel {
         self.recorder_engine.read_recorder_configs()
     }
     pub(super) fn write_recorder_configs(&self, new_configs: RibbleRecordingConfigs) {
+        // NOTE: this only takes effect the next time a device is opened.
+        self.audio_backend
+            .set_input_device(new_configs.input_device().cloned());
         self.recorder_engine.write_recorder_configs(new_configs);
     }
 
+    // INPUT DEVICES
+    pub(super) fn audio_backend_kind(&self) -> &AudioBackendKind {
+        self.audio_backend.kind()
+    }
+    pub(super) fn read_input_devices(&self) -> Arc<Vec<Arc<str>>> {
+        self.input_devices.load_full()
+    }
+    pub(super) fn set_input_devices(&self, input_devices: Vec<Arc<str>>) {
+        self.input_devices.store(Arc::new(input_devices));
+    }
+
+    // The device has stopped sending audio, so whatever is capturing from it is stopped; it's an
+    // error rather than a silent switch to another device.
+    pub(super) fn input_device_disconnected(&self, name: Arc<str>) {
+        let recording = self.recorder_running();
+        let transcribing = self.realtime_running();
+        if recording {
+            self.recorder_engine.stop_recording();
+        }
+        if transcribing {
+            self.transcriber_engine.stop_realtime();
+        }
+
+        let stopped = match (recording, transcribing) {
+            (true, true) => " Recording and transcription stopped.",
+            (true, false) => " Recording stopped.",
+            (false, true) => " Transcription stopped.",
+            (false, false) => "",
+        };
+        let error = RibbleError::InputDevice(format!(
+            "{name} was disconnected.{stopped} Reconnect it, or choose another input device."
+        ));
+        if let Err(e) = self
+            .bus
+            .console_message_sender()
+            .try_send(ConsoleMessage::Error(error))
+        {
+            log::warn!(
+                "Cannot send input device error, channel is too small or closed.\n\
+                Error: {e}"
+            );
+        }
+    }
+
     pub(super) fn read_export_format(&self) -> RibbleExportFormat {
         self.recorder_engine.read_export_format()
     }
     pub(super) fn write_export_format(&self, export_format: RibbleExportFormat) {
         self.recorder_engine.write_export_format(export_format);
     }
+    pub(super) fn read_opus_bitrate(&self) -> RibbleOpusBitrate {
+        self.recorder_engine.read_opus_bitrate()
+    }
+    pub(super) fn write_opus_bitrate(&self, opus_bitrate: RibbleOpusBitrate) {
+        self.recorder_engine.write_opus_bitrate(opus_bitrate);
+    }
 
     pub(super) fn start_recording(&self) {
         let backend = Arc::clone(&self.audio_backend);
         // Clear the latest error before starting background work.
         self.console_engine.clear_latest_error();
-        self.recorder_engine.start_recording(backend);
+        // NOTE: the recording shares the transcriber's noise suppression settings.
+        let noise_suppression = *self.transcriber_engine.read_noise_suppression_configs();
+        self.recorder_engine.start_recording(backend, noise_suppression);
     }
     pub(super) fn stop_recording(&self) {
         self.recorder_engine.stop_recording();
@@ -386,7 +787,7 @@ impl Kernel {
     pub(super) fn latest_recording_exists(&self) -> bool {
         self.writer_engine.latest_exists()
     }
-    pub(super) fn try_get_latest_recording(&self) -> Option<PathBuf> {
+    pub(super) fn try_get_latest_recording(&self) -> Option<Vec<PathBuf>> {
         self.writer_engine.try_get_latest()
     }
 
@@ -401,8 +802,19 @@ impl Kernel {
     }
 
     // NOTE: this consumes a shared string -> clone higher up and consume it
-    pub(super) fn try_get_recording_path(&self, file_name: Arc<str>) -> Option<PathBuf> {
-        self.writer_engine.get_recording_path(file_name)
+    pub(super) fn try_get_recording_segments(&self, file_name: Arc<str>) -> Option<Vec<PathBuf>> {
+        self.writer_engine.get_recording_segments(file_name)
+    }
+
+    pub(super) fn read_recording_library_configs(&self) -> Arc<RecordingLibraryConfigs> {
+        self.writer_engine.read_library_configs()
+    }
+    pub(super) fn write_recording_library_configs(&self, new_configs: RecordingLibraryConfigs) {
+        self.writer_engine.write_library_configs(new_configs);
+    }
+    pub(super) fn set_recording_label(&self, recording_file_name: &str, label: &str) {
+        self.writer_engine
+            .set_recording_label(recording_file_name, label);
     }
 
     // NOTE: recording_file_name is internal -- It's the left-half of the (String, CompletedRecordingJobs) tuple.
@@ -414,9 +826,14 @@ impl Kernel {
     ) {
         // Clear the latest error before starting background work.
         self.console_engine.clear_latest_error();
+        let opus_bitrate = self.recorder_engine.read_opus_bitrate();
 
-        self.writer_engine
-            .export_recording(out_path, recording_file_name, output_format);
+        self.writer_engine.export_recording(
+            out_path,
+            recording_file_name,
+            output_format,
+            opus_bitrate,
+        );
     }
 
     // CONSOLE
@@ -479,6 +896,43 @@ impl Kernel {
     pub(super) fn abort_download(&self, download_id: usize) {
         self.download_engine.abort_download(download_id);
     }
+    pub(super) fn pause_download(&self, download_id: usize) {
+        self.download_engine.pause_download(download_id);
+    }
+    pub(super) fn resume_download(&self, download_id: usize) {
+        // Clear the latest error before restarting bg work
+        self.console_engine.clear_latest_error();
+        self.download_engine.resume_download(download_id);
+    }
+
+    pub(super) fn read_download_configs(&self) -> Arc<DownloadConfigs> {
+        self.download_engine.read_download_configs()
+    }
+    pub(super) fn write_download_configs(&self, new_configs: DownloadConfigs) {
+        let old_configs = self.download_engine.read_download_configs();
+        if old_configs.model_catalog() != new_configs.model_catalog() {
+            self.console_engine.clear_latest_error();
+            self.model_bank
+                .load_model_catalog(new_configs.model_catalog().map(Path::to_path_buf));
+        }
+        self.download_engine.write_download_configs(new_configs);
+    }
+
+    // MODEL CATALOG
+    pub(super) fn refresh_model_catalog(&self) {
+        self.console_engine.clear_latest_error();
+        let configs = self.download_engine.read_download_configs();
+        self.model_bank
+            .load_model_catalog(configs.model_catalog().map(Path::to_path_buf));
+    }
+    pub(super) fn read_model_catalog(&self) -> Option<Arc<ModelCatalog>> {
+        self.model_bank.model_catalog()
+    }
+    pub(super) fn install_catalog_model(&self, model: CatalogModel) {
+        // Clear the latest error before starting bg work
+        self.console_engine.clear_latest_error();
+        self.model_bank.install_catalog_model(model);
+    }
 
     // VISUALIZER
     pub(super) fn set_visualizer_visibility(&self, is_visible: bool) {
@@ -508,21 +962,40 @@ impl Kernel {
         let offline_transcriber_feedback =
             self.transcriber_engine.read_offline_transcriber_feedback();
         let transcriber_gain_settings = *self.transcriber_engine.read_audio_gain_configs();
+        let noise_suppression_configs =
+            *self.transcriber_engine.read_noise_suppression_configs();
+        let subtitle_configs = *self.transcriber_engine.read_subtitle_configs();
+        let batch_configs = (*self.transcriber_engine.read_batch_configs()).clone();
+        let watch_folder_configs = (*self.watch_folder_engine.read_watch_folder_configs()).clone();
+        let live_sink_configs = (*self.transcriber_engine.read_live_sink_configs()).clone();
         let vad_configs = *self.transcriber_engine.read_vad_configs();
-        let recording_configs = *self.recorder_engine.read_recorder_configs();
+        let recording_configs = (*self.recorder_engine.read_recorder_configs()).clone();
+        let recording_library_configs = *self.writer_engine.read_library_configs();
         let export_format = self.recorder_engine.read_export_format();
+        let opus_bitrate = self.recorder_engine.read_opus_bitrate();
         let visualizer_analysis_type = self.visualizer_engine.read_visualizer_analysis_type();
         let user_preferences = *self.user_preferences.load_full();
+        let control_api_configs = *self.control_api_configs.load_full();
+        let download_configs = (*self.download_engine.read_download_configs()).clone();
 
         let state = KernelState {
             transcriber_configs,
             offline_transcriber_feedback,
             transcriber_gain_settings,
+            noise_suppression_configs,
+            subtitle_configs,
+            batch_configs,
+            watch_folder_configs,
+            live_sink_configs,
             vad_configs,
             recording_configs,
+            recording_library_configs,
             export_format,
+            opus_bitrate,
             visualizer_analysis_type,
             user_preferences,
+            control_api_configs,
+            download_configs,
         };
 
         let canonicalized = self.data_directory.to_path_buf().join(Self::CONFIGS_FILE);
@@ -573,10 +1046,190 @@ impl Drop for Kernel {
         self.bus.try_close_bus();
         log::info!("Serializing user data.");
         self.serialize_user_data();
+        // This was a clean exit; there's nothing to restore next time.
+        self.session_checkpointer.close();
         log::info!("Kernel cleanup completed.");
     }
 }
 
+// The transcriber only knows the model id, so resolve the file here for exporting.
+fn last_run_model_file(
+    transcriber_engine: &TranscriberEngine,
+    model_bank: &RibbleModelBank,
+) -> Option<ModelFile> {
+    transcriber_engine
+        .read_last_run_info()
+        .as_ref()
+        .as_ref()
+        .and_then(|run_info| run_info.model_id())
+        .and_then(|model_id| model_bank.get_model_file(model_id))
+}
+
+// For running without a window (e.g. the CLI): just offline transcription, with the models and
+// the configs the app last saved.
+// NOTE: none of the app's other engines are started (there's no audio capture, recording,
+// watch folder, etc.), the session isn't checkpointed, and the configs are never saved back; the
+// app owns all of that, and may well be running at the same time.
+pub(super) struct HeadlessKernel {
+    transcriber_engine: TranscriberEngine,
+    model_bank: Arc<RibbleModelBank>,
+    progress_engine: ProgressEngine,
+    _console_engine: ConsoleEngine,
+    _worker_engine: WorkerEngine,
+    // Nothing is recorded, visualized or downloaded; these are kept so that sends don't fail.
+    _write_receiver: Receiver<WriteRequest>,
+    _visualizer_receiver: Receiver<VisualizerPacket>,
+    _download_receiver: Receiver<DownloadRequest>,
+    bus: Bus,
+}
+
+impl HeadlessKernel {
+    pub(super) fn new(data_directory: &Path) -> Result<Self, RibbleError> {
+        if !data_directory.is_absolute() {
+            return Err(RibbleError::Core(format!(
+                "Data directory not canonicalized: {data_directory:#?}"
+            )));
+        }
+
+        let state = Kernel::deserialize_user_data(data_directory);
+        let console_message_size = state.user_preferences.console_message_size();
+        let (console_sender, console_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (progress_sender, progress_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (work_sender, work_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (write_sender, write_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (visualizer_sender, visualizer_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (download_sender, download_receiver) = get_channel(SMALL_UTILITY_QUEUE_SIZE);
+        let bus = Bus::new(
+            console_sender,
+            progress_sender,
+            work_sender,
+            write_sender,
+            visualizer_sender,
+            download_sender,
+        );
+
+        let console_engine = ConsoleEngine::new(console_receiver, console_message_size, &bus);
+        let progress_engine =
+            ProgressEngine::new(DEFAULT_PROGRESS_SLAB_CAPACITY, progress_receiver);
+        let worker_engine = WorkerEngine::new(work_receiver, &bus)?;
+
+        let model_directory = data_directory.join(Kernel::MODEL_BANK_DIR_SLUG);
+        std::fs::create_dir_all(&model_directory)?;
+        let model_bank = Arc::new(RibbleModelBank::new(model_directory.as_path(), &bus)?);
+
+        let session_checkpointer = Arc::new(SessionCheckpointer::new(data_directory, false));
+        let transcriber_engine =
+            TranscriberState::from(state).build_engine(&model_bank, session_checkpointer, &bus);
+
+        Ok(Self {
+            transcriber_engine,
+            model_bank,
+            progress_engine,
+            _console_engine: console_engine,
+            _worker_engine: worker_engine,
+            _write_receiver: write_receiver,
+            _visualizer_receiver: visualizer_receiver,
+            _download_receiver: download_receiver,
+            bus,
+        })
+    }
+
+    pub(super) fn transcribe_file_blocking(
+        &self,
+        audio_file_path: PathBuf,
+    ) -> Result<RibbleMessage, RibbleError> {
+        let bank = Arc::clone(&self.model_bank);
+        self.transcriber_engine
+            .transcribe_file_blocking(bank, audio_file_path)
+    }
+
+    pub(super) fn format_transcription(
+        &self,
+        format: TranscriptFormat,
+    ) -> Result<String, RibbleError> {
+        let model_file = last_run_model_file(&self.transcriber_engine, &self.model_bank);
+        self.transcriber_engine
+            .format_transcription(format, model_file)
+    }
+
+    pub(super) fn try_read_progress_metadata(&self, copy_buffer: &mut Vec<Progress>) {
+        self.progress_engine.try_read_progress_metadata(copy_buffer);
+    }
+}
+
+impl Drop for HeadlessKernel {
+    fn drop(&mut self) {
+        log::info!("Dropping headless kernel.");
+        self.bus.try_close_bus();
+    }
+}
+
+// The transcriber's share of the saved state.
+struct TranscriberState {
+    transcriber_configs: WhisperRealtimeConfigs,
+    offline_transcriber_feedback: OfflineTranscriberFeedback,
+    transcriber_gain_settings: AudioGainConfigs,
+    noise_suppression_configs: NoiseSuppressionConfigs,
+    subtitle_configs: SubtitleConfigs,
+    batch_configs: BatchConfigs,
+    live_sink_configs: LiveSinkConfigs,
+    vad_configs: VadConfigs,
+}
+
+impl TranscriberState {
+    fn build_engine(
+        self,
+        model_bank: &RibbleModelBank,
+        session_checkpointer: Arc<SessionCheckpointer>,
+        bus: &Bus,
+    ) -> TranscriberEngine {
+        // In case the user has mucked around with the model directory and the previous ID is
+        // invalid in the configs, catch it before constructing the TranscriberEngine and set the
+        // configs ID to None.
+        let model_id = self
+            .transcriber_configs
+            .model_id()
+            .as_ref()
+            .and_then(|model_id| {
+                if !model_bank.contains_model(*model_id) {
+                    None
+                } else {
+                    Some(*model_id)
+                }
+            });
+
+        let transcriber_configs = self.transcriber_configs.with_model_id(model_id);
+
+        TranscriberEngine::new(
+            Some(transcriber_configs),
+            Some(self.vad_configs),
+            Some(self.offline_transcriber_feedback),
+            Some(self.transcriber_gain_settings),
+            Some(self.noise_suppression_configs),
+            Some(self.subtitle_configs),
+            Some(self.batch_configs),
+            Some(self.live_sink_configs),
+            session_checkpointer,
+            bus,
+        )
+    }
+}
+
+impl From<KernelState> for TranscriberState {
+    fn from(state: KernelState) -> Self {
+        Self {
+            transcriber_configs: state.transcriber_configs,
+            offline_transcriber_feedback: state.offline_transcriber_feedback,
+            transcriber_gain_settings: state.transcriber_gain_settings,
+            noise_suppression_configs: state.noise_suppression_configs,
+            subtitle_configs: state.subtitle_configs,
+            batch_configs: state.batch_configs,
+            live_sink_configs: state.live_sink_configs,
+            vad_configs: state.vad_configs,
+        }
+    }
+}
+
 // Basic serializable/deserializable app state.
 // At this time, there isn't much to keep track of, but this may
 // start to grow as features get added.
@@ -592,13 +1245,31 @@ struct KernelState {
     #[serde(default)]
     transcriber_gain_settings: AudioGainConfigs,
     #[serde(default)]
+    noise_suppression_configs: NoiseSuppressionConfigs,
+    #[serde(default)]
+    subtitle_configs: SubtitleConfigs,
+    #[serde(default)]
+    batch_configs: BatchConfigs,
+    #[serde(default)]
+    watch_folder_configs: WatchFolderConfigs,
+    #[serde(default)]
+    live_sink_configs: LiveSinkConfigs,
+    #[serde(default)]
     vad_configs: VadConfigs,
     #[serde(default)]
     recording_configs: RibbleRecordingConfigs,
     #[serde(default)]
+    recording_library_configs: RecordingLibraryConfigs,
+    #[serde(default)]
     export_format: RibbleExportFormat,
     #[serde(default)]
+    opus_bitrate: RibbleOpusBitrate,
+    #[serde(default)]
     visualizer_analysis_type: AnalysisType,
     #[serde(default)]
     user_preferences: UserPreferences,
+    #[serde(default)]
+    control_api_configs: ControlApiConfigs,
+    #[serde(default)]
+    download_configs: DownloadConfigs,
 }
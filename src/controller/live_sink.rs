@@ -0,0 +1,344 @@
+// Streams the real-time transcript out of the app as it's being transcribed, e.g. to pipe live
+// captions into other tools.
+//
+// Format: newline-delimited JSON, one event per line.
+//
+//   {"event": "start"}
+//   {"event": "partial", "text": "..."}                               <- only if enabled
+//   {"event": "segment", "start_secs": 1.2, "end_secs": 3.4, "text": "..."}
+//   {"event": "reset"}                                                <- see below
+//   {"event": "end"}
+//
+// Segments are only sent once they're confirmed, so they never change after being sent.
+// If the transcriber ever rewrites its confirmed output, a "reset" is sent and the confirmed
+// segments are re-sent from the start.
+use crate::controller::transcript::Transcript;
+use crate::controller::{ConsoleMessage, UTILITY_QUEUE_SIZE};
+use crate::utils::errors::RibbleError;
+use crate::utils::live_sink_configs::{LiveSinkConfigs, LiveSinkTarget};
+use crossbeam::channel::TrySendError;
+use ribble_whisper::utils::{Receiver, Sender, get_channel};
+use serde_json::{Value, json};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::net::TcpStream;
+use std::path::Path;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+// How long to wait for a reader to open the other end of a named pipe.
+#[cfg(unix)]
+const PIPE_READER_TIMEOUT: Duration = Duration::from_secs(30);
+#[cfg(unix)]
+const PIPE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(super) struct LiveTranscriptSink {
+    line_sender: Option<Sender<String>>,
+    include_partial: bool,
+    num_sent_segments: usize,
+    last_partial: String,
+}
+
+impl LiveTranscriptSink {
+    // Returns None if the sink is disabled.
+    // NOTE: the target is opened on the sink's own thread; a named pipe may have to wait for
+    // something to open the other end, and that shouldn't hold up the transcriber.
+    pub(super) fn open(
+        configs: &LiveSinkConfigs,
+        console_message_sender: Sender<ConsoleMessage>,
+    ) -> Option<Self> {
+        if !configs.enabled() {
+            return None;
+        }
+
+        let (line_sender, line_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let thread_configs = configs.clone();
+        // NOTE: this thread is detached rather than joined; waiting on a slow reader shouldn't
+        // hold up the end of a run. It exits once the sink (and its sender) is dropped, or once
+        // it gives up waiting for a named pipe's reader (see open_pipe).
+        std::thread::spawn(move || {
+            if let Err(e) = run_sink(&thread_configs, line_receiver) {
+                log::warn!(
+                    "Live transcript sink stopped.\nError: {e}\nError source: {:#?}",
+                    e.source()
+                );
+                if let Err(e) = console_message_sender.try_send(ConsoleMessage::Error(e)) {
+                    log::warn!(
+                        "Cannot send live sink error, channel is too small or closed.\nError source: {:#?}",
+                        e.source()
+                    );
+                }
+            }
+        });
+
+        let mut sink = Self {
+            line_sender: Some(line_sender),
+            include_partial: configs.include_partial(),
+            num_sent_segments: 0,
+            last_partial: String::new(),
+        };
+        sink.send(json!({ "event": "start" }));
+        Some(sink)
+    }
+
+    // Sends any newly confirmed segments, and the in-progress text if it has changed.
+    pub(super) fn update(&mut self, transcript: &Transcript) {
+        self.send_confirmed(transcript);
+        if !self.include_partial {
+            return;
+        }
+
+        let partial = transcript
+            .segments()
+            .iter()
+            .filter(|segment| !segment.is_confirmed())
+            .map(|segment| segment.text().trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if partial != self.last_partial && self.send(json!({ "event": "partial", "text": partial }))
+        {
+            self.last_partial = partial;
+        }
+    }
+
+    // Sends whatever was confirmed when the transcriber finished.
+    pub(super) fn finish(mut self, transcript: &Transcript) {
+        self.send_confirmed(transcript);
+        if self.include_partial && !self.last_partial.is_empty() {
+            self.send(json!({ "event": "partial", "text": "" }));
+        }
+        self.send(json!({ "event": "end" }));
+    }
+
+    // NOTE: segments are only counted as sent once they're queued. If the queue is full, the rest
+    // are sent (in order) on a later update, so a slow reader never misses a segment.
+    fn send_confirmed(&mut self, transcript: &Transcript) {
+        let confirmed = transcript
+            .segments()
+            .iter()
+            .filter(|segment| segment.is_confirmed())
+            .collect::<Vec<_>>();
+
+        if confirmed.len() < self.num_sent_segments {
+            if !self.send(json!({ "event": "reset" })) {
+                return;
+            }
+            self.num_sent_segments = 0;
+        }
+
+        for segment in confirmed.iter().skip(self.num_sent_segments) {
+            let text = segment.text().trim();
+            let queued = text.is_empty()
+                || self.send(json!({
+                    "event": "segment",
+                    "start_secs": segment.start().as_secs_f64(),
+                    "end_secs": segment.end().as_secs_f64(),
+                    "text": text,
+                }));
+            if !queued {
+                return;
+            }
+            self.num_sent_segments += 1;
+        }
+    }
+
+    // Returns whether the event was queued.
+    fn send(&mut self, event: Value) -> bool {
+        let Some(sender) = self.line_sender.as_ref() else {
+            return false;
+        };
+        match sender.try_send(event.to_string()) {
+            Ok(()) => true,
+            // The reader (or the disk) is too slow; don't stall transcription waiting on it.
+            Err(TrySendError::Full(_)) => {
+                log::warn!("Live transcript sink is falling behind, holding back an event.");
+                false
+            }
+            // The sink thread has stopped (and reported why); stop sending.
+            Err(TrySendError::Disconnected(_)) => {
+                self.line_sender = None;
+                false
+            }
+        }
+    }
+}
+
+fn run_sink(configs: &LiveSinkConfigs, lines: Receiver<String>) -> Result<(), RibbleError> {
+    let writer: Box<dyn Write + Send> = match configs.target() {
+        LiveSinkTarget::Stdout => Box::new(std::io::stdout()),
+        LiveSinkTarget::File => {
+            let Some(path) = configs.file_path() else {
+                return Err(RibbleError::Core(
+                    "Live transcript sink has no output file.".to_string(),
+                ));
+            };
+            let file = open_file(path)?;
+            log::info!("Streaming live transcript to: {}", path.display());
+            Box::new(file)
+        }
+        LiveSinkTarget::Socket => {
+            let addr = configs.socket_addr();
+            let stream = TcpStream::connect(addr).map_err(|e| {
+                RibbleError::IOError(std::io::Error::new(
+                    e.kind(),
+                    format!("Cannot connect live transcript sink to {addr}: {e}"),
+                ))
+            })?;
+            log::info!("Streaming live transcript to: {addr}");
+            Box::new(stream)
+        }
+    };
+
+    let mut writer = BufWriter::new(writer);
+    while let Ok(line) = lines.recv() {
+        match writeln!(writer, "{line}").and_then(|_| writer.flush()) {
+            Ok(()) => {}
+            // The reader went away (e.g. the other end of the pipe was closed); not an error.
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                log::info!("Live transcript reader disconnected.");
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+fn open_file(path: &Path) -> Result<File, RibbleError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo()) {
+            return open_pipe(path);
+        }
+    }
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+// NOTE: a blocking open on a named pipe doesn't return until something opens the other end,
+// which might be never. Instead, open without blocking (which fails with ENXIO while there's no
+// reader) and retry for a while, then give up and report it.
+#[cfg(unix)]
+fn open_pipe(path: &Path) -> Result<File, RibbleError> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let deadline = Instant::now() + PIPE_READER_TIMEOUT;
+    loop {
+        let opened = OpenOptions::new()
+            .append(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path);
+        match opened {
+            Ok(file) => {
+                // Only the open shouldn't block; the writes should, like they do for a file.
+                let fd = file.as_raw_fd();
+                // SAFETY: fd belongs to file, which is open for the duration of both calls.
+                let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+                if flags < 0
+                    || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0
+                {
+                    return Err(std::io::Error::last_os_error().into());
+                }
+                return Ok(file);
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                if Instant::now() >= deadline {
+                    return Err(RibbleError::IOError(std::io::Error::new(
+                        ErrorKind::NotConnected,
+                        format!(
+                            "Nothing opened the live transcript pipe within {}s: {}",
+                            PIPE_READER_TIMEOUT.as_secs(),
+                            path.display()
+                        ),
+                    )));
+                }
+                std::thread::sleep(PIPE_RETRY_INTERVAL);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::transcript::{SegmentState, TranscriptSegment};
+    use std::time::Duration;
+
+    fn confirmed(texts: &[&str]) -> Transcript {
+        let segments = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let start = Duration::from_secs(i as u64);
+                TranscriptSegment::new(
+                    start,
+                    start + Duration::from_secs(1),
+                    text,
+                    SegmentState::Confirmed,
+                )
+            })
+            .collect();
+        Transcript::from_segments(segments)
+    }
+
+    fn segment_texts(lines: &Receiver<String>) -> Vec<String> {
+        lines
+            .try_iter()
+            .map(|line| serde_json::from_str::<Value>(&line).unwrap())
+            .map(|event| {
+                assert_eq!(event["event"], "segment");
+                event["text"].as_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_full_queue_holds_segments_back_until_there_is_room() {
+        let (line_sender, lines) = get_channel(2);
+        let mut sink = LiveTranscriptSink {
+            line_sender: Some(line_sender),
+            include_partial: false,
+            num_sent_segments: 0,
+            last_partial: String::new(),
+        };
+
+        let transcript = confirmed(&["One", "Two", "Three", "Four", "Five"]);
+        sink.update(&transcript);
+        assert_eq!(segment_texts(&lines), ["One", "Two"]);
+        sink.update(&transcript);
+        assert_eq!(segment_texts(&lines), ["Three", "Four"]);
+        sink.update(&transcript);
+        assert_eq!(segment_texts(&lines), ["Five"]);
+        sink.update(&transcript);
+        assert!(segment_texts(&lines).is_empty());
+    }
+
+    #[test]
+    fn a_reset_is_only_counted_once_it_is_queued() {
+        let (line_sender, lines) = get_channel(1);
+        let mut sink = LiveTranscriptSink {
+            line_sender: Some(line_sender),
+            include_partial: false,
+            num_sent_segments: 0,
+            last_partial: String::new(),
+        };
+
+        sink.update(&confirmed(&["One"]));
+        assert_eq!(segment_texts(&lines), ["One"]);
+        sink.update(&confirmed(&["One", "Two"]));
+        // The queue is full, so the reset can't go out yet; nothing else should either.
+        sink.update(&confirmed(&["Uno"]));
+        assert_eq!(segment_texts(&lines), ["Two"]);
+
+        sink.update(&confirmed(&["Uno"]));
+        let reset: Value = serde_json::from_str(&lines.try_recv().unwrap()).unwrap();
+        assert_eq!(reset["event"], "reset");
+        sink.update(&confirmed(&["Uno"]));
+        assert_eq!(segment_texts(&lines), ["Uno"]);
+    }
+}
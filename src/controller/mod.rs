@@ -9,19 +9,36 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use strum::{AsRefStr, Display, EnumIter, EnumString, IntoStaticStr};
 
 pub(crate) mod audio_backend_proxy;
+pub(crate) mod batch_queue;
 mod console;
+#[cfg(feature = "cpal-backend")]
+pub(crate) mod cpal_capture;
 mod downloader;
+mod export_encoders;
+pub(crate) mod file_capture;
 mod kernel;
+mod live_sink;
 mod model_bank;
+pub(crate) mod model_catalog;
+pub(crate) mod model_header;
+pub(crate) mod model_integrity;
+pub(crate) mod playback;
 mod progress;
 mod recorder;
+mod recording_segments;
+mod resumable_download;
 pub(crate) mod ribble_controller;
+pub(crate) mod session_checkpoint;
+#[cfg(test)]
+mod test_harness;
+pub(crate) mod transcript;
 mod transcriber;
 mod visualizer;
+mod watch_folder;
 mod worker;
 mod writer;
 
@@ -34,11 +51,20 @@ pub const SMALL_UTILITY_QUEUE_SIZE: usize = 16;
 pub const UI_UPDATE_QUEUE_SIZE: usize = 8;
 
 const DEFAULT_PROGRESS_SLAB_CAPACITY: usize = 8;
+// How long the capture loops wait on the audio feed before re-checking whether they should stop.
+// NOTE: an unplugged device never closes the feed; it just stops sending.
+const AUDIO_FEED_TIMEOUT: Duration = Duration::from_millis(250);
 // CONSOLE CONSTANTS
 pub const DEFAULT_NUM_CONSOLE_MESSAGES: usize = 32;
 
 pub const MIN_NUM_CONSOLE_MESSAGES: usize = 16;
 pub const MAX_NUM_CONSOLE_MESSAGES: usize = 64;
+
+// The audio file extensions that the offline transcriber can load.
+pub(crate) const SUPPORTED_AUDIO_FILE_EXTENSIONS: [&str; 14] = [
+    "wav", "mpa", "mp2", "mp3", "mp4", "m4v", "ogg", "mkv", "aif", "aiff", "aifc", "caf", "alac",
+    "flac",
+];
 type RibbleWorkerHandle = JoinHandle<Result<RibbleMessage, RibbleError>>;
 
 pub(crate) enum RibbleMessage {
@@ -223,11 +249,36 @@ enum WorkRequest {
     Shutdown,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DownloadState {
+    Queued,
+    Downloading,
+    // Waiting to retry after a transient error.
+    Retrying { attempt: u32 },
+    Paused,
+    Failed(Arc<str>),
+}
+
+impl DownloadState {
+    // Whether the download is (or is about to be) transferring data.
+    pub(crate) fn is_active(&self) -> bool {
+        matches!(
+            self,
+            DownloadState::Downloading | DownloadState::Retrying { .. }
+        )
+    }
+    // Paused and failed downloads can be queued again; they resume from their .part file.
+    pub(crate) fn can_resume(&self) -> bool {
+        matches!(self, DownloadState::Paused | DownloadState::Failed(_))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct FileDownload {
     name: Arc<str>,
     progress: ProgressView,
     should_abort: Arc<AtomicBool>,
+    state: DownloadState,
 }
 
 impl FileDownload {
@@ -236,15 +287,24 @@ impl FileDownload {
             name: Arc::from(name),
             progress,
             should_abort,
+            state: DownloadState::Downloading,
         }
     }
 
+    fn with_state(mut self, state: DownloadState) -> Self {
+        self.state = state;
+        self
+    }
+
     pub(crate) fn name(&self) -> Arc<str> {
         Arc::clone(&self.name)
     }
     pub(crate) fn progress(&self) -> ProgressView {
         self.progress.clone()
     }
+    pub(crate) fn state(&self) -> &DownloadState {
+        &self.state
+    }
 
     fn abort_download(&self) {
         self.should_abort.store(true, Ordering::Release);
@@ -254,7 +314,12 @@ impl FileDownload {
 // NOTE: if it somehow becomes necessary to send information (e.g. the returned PathBuf) back from the DownloadRequest to the
 // requester, then use a queue.
 enum DownloadRequest {
-    DownloadJob { url: String, directory: PathBuf },
+    DownloadJob {
+        url: String,
+        directory: PathBuf,
+        // The downloaded file is sent here to be verified.
+        verify_sender: Option<Sender<ModelVerifyRequest>>,
+    },
     Shutdown,
 }
 
@@ -263,8 +328,25 @@ impl DownloadRequest {
         Self::DownloadJob {
             url: url.to_string(),
             directory: directory.to_path_buf(),
+            verify_sender: None,
         }
     }
+
+    fn with_verify_sender(self, sender: Sender<ModelVerifyRequest>) -> Self {
+        match self {
+            Self::DownloadJob { url, directory, .. } => Self::DownloadJob {
+                url,
+                directory,
+                verify_sender: Some(sender),
+            },
+            Self::Shutdown => Self::Shutdown,
+        }
+    }
+}
+
+enum ModelVerifyRequest {
+    Verify(PathBuf),
+    Shutdown,
 }
 
 enum ProgressMessage {
@@ -562,13 +644,22 @@ impl Progress {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub(crate) struct CompletedRecordingJobs {
     // This can probably just be accumulated.
     file_size_estimate: usize,
     total_duration: Duration,
     channels: usize,
     sample_rate: usize,
+    // When the recording was started.
+    created_at: SystemTime,
+    // User-facing name; these are only kept around if the recording library is enabled.
+    label: Option<Arc<str>>,
+    // The last transcript saved from this recording.
+    transcript_path: Option<Arc<Path>>,
+    // The files this recording was written to, in order; the first is the key.
+    // Long recordings (that can't be joined back into one file) span multiple segments.
+    segments: Arc<[Arc<str>]>,
 }
 
 impl CompletedRecordingJobs {
@@ -577,15 +668,30 @@ impl CompletedRecordingJobs {
         total_duration: Duration,
         channels: usize,
         sample_rate: usize,
+        created_at: SystemTime,
+        segments: Arc<[Arc<str>]>,
     ) -> Self {
         Self {
             file_size_estimate,
             total_duration,
             channels,
             sample_rate,
+            created_at,
+            label: None,
+            transcript_path: None,
+            segments,
         }
     }
 
+    pub(crate) fn with_label(mut self, label: Option<Arc<str>>) -> Self {
+        self.label = label;
+        self
+    }
+    pub(crate) fn with_transcript_path(mut self, transcript_path: Option<Arc<Path>>) -> Self {
+        self.transcript_path = transcript_path;
+        self
+    }
+
     pub(crate) fn file_size_estimate(&self) -> usize {
         self.file_size_estimate
     }
@@ -598,6 +704,18 @@ impl CompletedRecordingJobs {
     pub(crate) fn sample_rate(&self) -> usize {
         self.sample_rate
     }
+    pub(crate) fn created_at(&self) -> SystemTime {
+        self.created_at
+    }
+    pub(crate) fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub(crate) fn transcript_path(&self) -> Option<&Path> {
+        self.transcript_path.as_deref()
+    }
+    pub(crate) fn num_segments(&self) -> usize {
+        self.segments.len()
+    }
 }
 
 pub(crate) enum RotationDirection {
@@ -670,6 +788,22 @@ pub(crate) enum ModelFile {
 impl ModelFile {
     #[cfg(any(debug_assertions, feature = "pack-in-models"))]
     pub(crate) const PACKED_NAMES: [&'static str; 2] = ["ggml-tiny.q5_1.bin", "ggml-base.q5_1.bin"];
+
+    pub(crate) fn file_name(&self) -> &str {
+        match self {
+            #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+            ModelFile::Packed(idx) => Self::PACKED_NAMES[*idx],
+            ModelFile::File(name) => name.as_ref(),
+        }
+    }
+
+    pub(crate) fn is_packed(&self) -> bool {
+        match self {
+            #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+            ModelFile::Packed(_) => true,
+            ModelFile::File(_) => false,
+        }
+    }
 }
 
 impl Display for ModelFile {
@@ -686,6 +820,28 @@ impl Display for ModelFile {
     }
 }
 
+// The models in the model bank's directory.
+#[derive(Copy, Clone, Default)]
+pub(crate) struct ModelDiskUsage {
+    num_models: usize,
+    total_bytes: u64,
+}
+
+impl ModelDiskUsage {
+    pub(crate) fn new(num_models: usize, total_bytes: u64) -> Self {
+        Self {
+            num_models,
+            total_bytes,
+        }
+    }
+    pub(crate) fn num_models(&self) -> usize {
+        self.num_models
+    }
+    pub(crate) fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+}
+
 pub(in crate::controller) enum WriteRequest {
     WriteJob {
         receiver: Receiver<Arc<[f32]>>,
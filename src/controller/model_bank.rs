@@ -1,18 +1,29 @@
+use crate::controller::model_catalog::{install_catalog_model, CatalogModel, ModelCatalog};
+#[cfg(any(debug_assertions, feature = "pack-in-models"))]
+use crate::controller::model_header::parse_model_header;
+use crate::controller::model_header::{read_model_header, ModelHeader};
+use crate::controller::model_integrity::{
+    load_integrity_records, save_integrity_records, verify_model_file, IntegrityRecord,
+    ModelIntegrity, ModelManifest,
+};
 use crate::controller::{
-    Bus, ConsoleMessage, DownloadRequest, ModelFile, Progress, ProgressMessage, RibbleMessage,
-    WorkRequest, SMALL_UTILITY_QUEUE_SIZE,
+    Bus, ConsoleMessage, DownloadRequest, ModelDiskUsage, ModelFile, ModelVerifyRequest, Progress,
+    ProgressMessage, RibbleMessage, WorkRequest, SMALL_UTILITY_QUEUE_SIZE, UTILITY_QUEUE_SIZE,
 };
 use crate::utils::errors::RibbleError;
+use arc_swap::ArcSwap;
 use indexmap::IndexMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, MutexGuard, RwLock};
 use ribble_whisper::utils::errors::RibbleWhisperError;
 use ribble_whisper::utils::{get_channel, Receiver, Sender};
 use ribble_whisper::whisper::model::{ModelId, ModelLocation, ModelRetriever};
+use std::collections::HashMap;
 use std::error::Error;
 use std::ffi::OsStr;
 use std::fs;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
 use twox_hash::XxHash3_64;
@@ -30,17 +41,40 @@ const MODEL_FILE_EXTENSION: &str = "bin";
 const MAX_DEBOUNCE_TIME: u64 = 2000;
 
 #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+// NOTE: this has to be in the same order as ModelFile::PACKED_NAMES.
 const PACKED_MODELS: [&[u8]; 2] = [
-    include_bytes!("../models/ggml-base-q5_1.bin"),
     include_bytes!("../models/ggml-tiny-q5_1.bin"),
+    include_bytes!("../models/ggml-base-q5_1.bin"),
 ];
 
+#[derive(Copy, Clone)]
+struct ModelDetails {
+    // None if the header couldn't be read.
+    header: Option<ModelHeader>,
+    size: u64,
+}
+
 struct RibbleModelBankState {
     model_directory: PathBuf,
     // NOTE: this isn't using ribble_whisper's model/ConcurrentModelBank abstraction.
     // It's way, way easier and cheaper to use Arc<str>
     model_map: RwLock<IndexMap<ModelId, ModelFile>>,
     model_directory_watcher: Receiver<DebounceEventResult>,
+    // Read whenever the bank is (re)filled.
+    model_details: RwLock<HashMap<ModelId, ModelDetails>>,
+    // File name -> the last verification result.
+    integrity_records: RwLock<HashMap<String, IntegrityRecord>>,
+    // The model currently being verified.
+    verifying: ArcSwap<Option<Arc<str>>>,
+    // The manifest is only re-fetched once per session; after that, the cached copy is used.
+    manifest_refreshed: AtomicBool,
+    // The offline model catalog, if one is set (and it loaded).
+    catalog: ArcSwap<Option<Arc<ModelCatalog>>>,
+    // Model -> the number of leases on it (see: ModelLease).
+    // NOTE: this is held across deleting/renaming a model, and across handing out its location,
+    // so that a model can't be removed between a transcriber resolving it and loading it.
+    models_in_use: Mutex<HashMap<ModelId, usize>>,
+    shutting_down: AtomicBool,
 }
 
 impl RibbleModelBankState {
@@ -51,6 +85,7 @@ impl RibbleModelBankState {
         watcher: Receiver<DebounceEventResult>,
     ) -> Result<Self, RibbleError> {
         let model_map = RwLock::new(IndexMap::with_capacity(Self::DEFAULT_MODEL_MAP_SIZE));
+        let integrity_records = RwLock::new(load_integrity_records(model_directory));
         let model_directory = model_directory.to_path_buf();
 
         // Test to make sure model_directory exists and is.
@@ -64,6 +99,13 @@ impl RibbleModelBankState {
                 model_directory,
                 model_map,
                 model_directory_watcher: watcher,
+                model_details: RwLock::new(HashMap::new()),
+                integrity_records,
+                verifying: ArcSwap::new(Arc::new(None)),
+                manifest_refreshed: AtomicBool::new(false),
+                catalog: ArcSwap::new(Arc::new(None)),
+                models_in_use: Mutex::new(HashMap::new()),
+                shutting_down: AtomicBool::new(false),
             }
                 .init()
         }
@@ -148,31 +190,368 @@ impl RibbleModelBankState {
         // Sort by file size.
         entries.sort_by(|(_, size1), (_, size2)| size1.cmp(size2));
 
+        let entries = entries
+            .into_iter()
+            .map(|(model_file, size)| {
+                let model_key = match &model_file {
+                    #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+                    ModelFile::Packed(idx) => XxHash3_64::oneshot_with_seed(
+                        MODEL_ID_SEED,
+                        ModelFile::PACKED_NAMES[*idx].as_bytes(),
+                    ),
+                    ModelFile::File(file_name) => {
+                        XxHash3_64::oneshot_with_seed(MODEL_ID_SEED, file_name.as_ref().as_bytes())
+                    }
+                };
+                (model_key, model_file, size)
+            })
+            .collect::<Vec<(ModelId, ModelFile, u64)>>();
+
+        // Read the headers before taking the lock; this is file io.
+        let model_details = entries
+            .iter()
+            .map(|(model_key, model_file, size)| {
+                let details = ModelDetails {
+                    header: self.read_model_header(model_file),
+                    size: *size,
+                };
+                (*model_key, details)
+            })
+            .collect::<HashMap<ModelId, ModelDetails>>();
+        *self.model_details.write() = model_details;
+
         // Get a write lock to fill the map.
         let mut map_lock = self.model_map.write();
 
         // Fill the model bank.
-        for (model_file, _) in entries {
-            let model_key = match &model_file {
-                #[cfg(any(debug_assertions, feature = "pack-in-models"))]
-                ModelFile::Packed(idx) => XxHash3_64::oneshot_with_seed(
-                    MODEL_ID_SEED,
-                    ModelFile::PACKED_NAMES[*idx].as_bytes(),
-                ),
-                ModelFile::File(file_name) => {
-                    XxHash3_64::oneshot_with_seed(MODEL_ID_SEED, file_name.as_ref().as_bytes())
-                }
-            };
+        for (model_key, model_file, _) in entries {
             map_lock.insert(model_key, model_file);
         }
+        drop(map_lock);
 
+        self.prune_integrity_records();
         Ok(())
     }
 
+    fn read_model_header(&self, model_file: &ModelFile) -> Option<ModelHeader> {
+        let result = match model_file {
+            #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+            ModelFile::Packed(idx) => parse_model_header(&mut &PACKED_MODELS[*idx][..]),
+            ModelFile::File(file_name) => {
+                read_model_header(self.model_directory.join(file_name.as_ref()).as_path())
+            }
+        };
+        result
+            .map_err(|e| log::warn!("Failed to read model header: {model_file}\nError: {e}"))
+            .ok()
+    }
+
+    fn model_header(&self, model_id: ModelId) -> Option<ModelHeader> {
+        self.model_details
+            .read()
+            .get(&model_id)
+            .and_then(|details| details.header)
+    }
+
+    fn model_size(&self, model_id: ModelId) -> Option<u64> {
+        self.model_details
+            .read()
+            .get(&model_id)
+            .map(|details| details.size)
+    }
+
+    // Packed models are part of the binary, so they don't count.
+    fn disk_usage(&self) -> ModelDiskUsage {
+        let model_map = self.model_map.read();
+        let model_details = self.model_details.read();
+        model_map
+            .iter()
+            .filter(|(_, model)| !model.is_packed())
+            .fold(ModelDiskUsage::default(), |usage, (model_id, _)| {
+                let size = model_details.get(model_id).map_or(0, |details| details.size);
+                ModelDiskUsage::new(usage.num_models() + 1, usage.total_bytes() + size)
+            })
+    }
+
+    fn model_path(&self, model_id: ModelId) -> Option<PathBuf> {
+        match self.model_map.read().get(&model_id)? {
+            ModelFile::File(file_name) => Some(self.model_directory.join(file_name.as_ref())),
+            #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+            ModelFile::Packed(_) => None,
+        }
+    }
+
+    fn delete_model(&self, model_id: ModelId) -> Result<Arc<str>, RibbleError> {
+        let models_in_use = self.models_in_use.lock();
+        if models_in_use.contains_key(&model_id) {
+            return Err(RibbleError::Core(
+                "Cannot delete the model while it's in use. Stop transcribing first.".to_string(),
+            ));
+        }
+
+        let file_name = match self.get_model_file(model_id) {
+            Some(ModelFile::File(file_name)) => file_name,
+            #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+            Some(ModelFile::Packed(_)) => {
+                return Err(RibbleError::Core(
+                    "Built-in models cannot be deleted.".to_string(),
+                ));
+            }
+            None => return Err(RibbleError::Core("Model not found.".to_string())),
+        };
+
+        self.handle_removal(model_id, false)?;
+        self.model_details.write().remove(&model_id);
+        self.forget_integrity_record(file_name.as_ref(), None);
+        Ok(file_name)
+    }
+
+    // Keeps the model (by file name) from being leased while the guard is held; this fails if it's
+    // already in use.
+    fn claim_unused_model(
+        &self,
+        file_name: &str,
+    ) -> Result<MutexGuard<'_, HashMap<ModelId, usize>>, RibbleError> {
+        let models_in_use = self.models_in_use.lock();
+        if models_in_use.contains_key(&self.create_model_key(file_name)) {
+            return Err(RibbleError::Core(format!(
+                "Cannot replace {file_name} while it's in use. Stop transcribing first."
+            )));
+        }
+        Ok(models_in_use)
+    }
+
+    // The new name gets the model extension if it's missing. Returns the new id.
+    fn rename_model(&self, model_id: ModelId, new_name: &str) -> Result<ModelId, RibbleError> {
+        let new_name = new_name.trim();
+        let invalid = new_name.is_empty()
+            || new_name.starts_with('.')
+            || new_name.contains(['/', '\\'])
+            || Path::new(new_name).file_name() != Some(OsStr::new(new_name));
+        if invalid {
+            return Err(RibbleError::Core(format!("Invalid model name: {new_name}")));
+        }
+        let new_name = if Path::new(new_name).extension() == Some(OsStr::new(MODEL_FILE_EXTENSION))
+        {
+            new_name.to_string()
+        } else {
+            format!("{new_name}.{MODEL_FILE_EXTENSION}")
+        };
+
+        let models_in_use = self.models_in_use.lock();
+        if models_in_use.contains_key(&model_id) {
+            return Err(RibbleError::Core(
+                "Cannot rename the model while it's in use. Stop transcribing first.".to_string(),
+            ));
+        }
+
+        let old_name = match self.get_model_file(model_id) {
+            Some(ModelFile::File(file_name)) => file_name,
+            #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+            Some(ModelFile::Packed(_)) => {
+                return Err(RibbleError::Core(
+                    "Built-in models cannot be renamed.".to_string(),
+                ));
+            }
+            None => return Err(RibbleError::Core("Model not found.".to_string())),
+        };
+        if old_name.as_ref() == new_name {
+            return Ok(model_id);
+        }
+
+        let new_path = self.model_directory.join(new_name.as_str());
+        if new_path.exists() {
+            return Err(RibbleError::Core(format!(
+                "A model named {new_name} already exists."
+            )));
+        }
+        fs::rename(self.model_directory.join(old_name.as_ref()), new_path)?;
+
+        // NOTE: the debouncer refreshes the bank anyway, but update it now so that the ids line
+        // up before the next repaint.
+        let new_id = self.create_model_key(new_name.as_str());
+        {
+            let mut model_map = self.model_map.write();
+            if let Some(index) = model_map.get_index_of(&model_id) {
+                model_map.insert(new_id, ModelFile::File(Arc::from(new_name.as_str())));
+                let last = model_map.len() - 1;
+                model_map.move_index(last, index);
+                model_map.shift_remove(&model_id);
+            }
+        }
+        {
+            let mut model_details = self.model_details.write();
+            if let Some(details) = model_details.remove(&model_id) {
+                model_details.insert(new_id, details);
+            }
+        }
+        self.forget_integrity_record(old_name.as_ref(), Some(new_name.as_str()));
+        Ok(new_id)
+    }
+
+    // Drops (or moves, on a rename) a model's verification result.
+    fn forget_integrity_record(&self, file_name: &str, new_name: Option<&str>) {
+        let mut records = self.integrity_records.write();
+        let Some(record) = records.remove(file_name) else {
+            return;
+        };
+        if let Some(new_name) = new_name {
+            records.insert(new_name.to_string(), record);
+        }
+        if let Err(e) = save_integrity_records(self.model_directory(), &records) {
+            log::warn!("Failed to save model integrity results.\nError: {e}");
+        }
+    }
+
+    // For models that were already verified elsewhere (i.e. installed from a catalog).
+    fn record_integrity(&self, model_path: &Path, sha256: String) -> Result<(), RibbleError> {
+        let file_name = model_path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .ok_or(RibbleError::Core(format!(
+                "Invalid model path: {}",
+                model_path.display()
+            )))?;
+        let record = IntegrityRecord::verified(model_path, sha256)?;
+        let mut records = self.integrity_records.write();
+        records.insert(file_name.to_string(), record);
+        save_integrity_records(self.model_directory(), &records)
+    }
+
+    // Drops the results for models that have been removed or changed since they were verified.
+    fn prune_integrity_records(&self) {
+        let mut records = self.integrity_records.write();
+        let num_records = records.len();
+        records.retain(|file_name, record| {
+            fs::metadata(self.model_directory.join(file_name))
+                .is_ok_and(|metadata| !record.is_stale(&metadata))
+        });
+
+        // NOTE: the index lives in the watched directory; only write it when something changed,
+        // otherwise every refresh would trigger another.
+        if records.len() != num_records
+            && let Err(e) = save_integrity_records(self.model_directory(), &records)
+        {
+            log::warn!("Failed to save model integrity results.\nError: {e}");
+        }
+    }
+
+    fn model_integrity(&self, model_id: ModelId) -> ModelIntegrity {
+        match self.model_map.read().get(&model_id) {
+            Some(ModelFile::File(file_name)) => self
+                .integrity_records
+                .read()
+                .get(file_name.as_ref())
+                .map(IntegrityRecord::status)
+                .unwrap_or_default(),
+            // Packed models are part of the binary.
+            _ => ModelIntegrity::Unchecked,
+        }
+    }
+
+    // Uses the cached manifest, unless the model isn't in it (or it's missing), in which case
+    // it's re-fetched (once).
+    // NOTE: a catalog means the models come from a local mirror (i.e. the machine is offline), so
+    // the manifest is never fetched while one is set.
+    fn load_manifest(&self, file_name: &str) -> Option<ModelManifest> {
+        let cached = ModelManifest::load(self.model_directory());
+        let needs_refresh = cached
+            .as_ref()
+            .is_none_or(|manifest| !manifest.contains(file_name));
+        if !needs_refresh
+            || self.catalog.load().is_some()
+            || self.manifest_refreshed.swap(true, Ordering::AcqRel)
+        {
+            return cached;
+        }
+
+        match ModelManifest::fetch() {
+            Ok(manifest) => {
+                if let Err(e) = manifest.save(self.model_directory()) {
+                    log::warn!("Failed to cache model manifest.\nError: {e}");
+                }
+                Some(manifest)
+            }
+            Err(e) => {
+                log::warn!("Failed to fetch model manifest, using the cached copy.\nError: {e}");
+                cached
+            }
+        }
+    }
+
+    fn verify_model(
+        &self,
+        model_path: &Path,
+        progress_sender: &Sender<ProgressMessage>,
+    ) -> Result<ModelIntegrity, RibbleError> {
+        let file_name = model_path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .ok_or(RibbleError::Core(format!(
+                "Invalid model path: {}",
+                model_path.display()
+            )))?;
+        let file_size = fs::metadata(model_path)?.len();
+        let manifest = self.load_manifest(file_name);
+
+        let (id_sender, id_receiver) = get_channel(1);
+        let progress_message = ProgressMessage::Request {
+            job: Progress::new_determinate("Verifying model", file_size),
+            id_return_sender: id_sender,
+        };
+        if let Err(e) = progress_sender.send(progress_message) {
+            log::warn!(
+                "Progress channel closed, cannot send verify model job.\nError source: {:#?}",
+                e.source()
+            );
+        }
+        let progress_id = id_receiver.recv().ok();
+
+        self.verifying.store(Arc::new(Some(Arc::from(file_name))));
+        let result = verify_model_file(
+            model_path,
+            manifest.as_ref(),
+            |delta| {
+                if let Some(id) = progress_id {
+                    let increment = ProgressMessage::Increment { job_id: id, delta };
+                    if let Err(e) = progress_sender.send(increment) {
+                        log::warn!(
+                            "Progress channel closed, cannot send verify progress.\n\
+                            Error source: {:#?}",
+                            e.source()
+                        );
+                    }
+                }
+            },
+            || self.shutting_down.load(Ordering::Acquire),
+        );
+        self.verifying.store(Arc::new(None));
+
+        if let Some(id) = progress_id
+            && let Err(e) = progress_sender.send(ProgressMessage::Remove { job_id: id })
+        {
+            log::warn!(
+                "Progress channel closed, cannot remove verify model job.\nError source: {:#?}",
+                e.source()
+            );
+        }
+
+        let record = result?;
+        let status = record.status();
+        let mut records = self.integrity_records.write();
+        records.insert(file_name.to_string(), record);
+        save_integrity_records(self.model_directory(), &records)?;
+        Ok(status)
+    }
+
     fn contains_model(&self, model_id: ModelId) -> bool {
         self.model_map.read().contains_key(&model_id)
     }
 
+    fn get_model_file(&self, model_id: ModelId) -> Option<ModelFile> {
+        self.model_map.read().get(&model_id).cloned()
+    }
+
     fn retrieve_model(&self, model_id: ModelId) -> Option<ModelLocation> {
         self.model_map
             .read()
@@ -221,9 +600,6 @@ impl RibbleModelBankState {
         Ok(self.fill_model_bank()?)
     }
 
-    // NOTE: this can probably stay, though I'm not sure that I want to deal with
-    // delete buttons in the UI.
-
     // Returns Ok(Some(ModelId)) if the model was in the bank and there were no file errors
     // Returns Ok(None) if the model was not in the bank and there were no file errors
     // OR: if it's called on an asset-packed model.
@@ -272,7 +648,9 @@ pub(crate) struct RibbleModelBank {
     work_sender: Sender<WorkRequest>,
     progress_message_sender: Sender<ProgressMessage>,
     download_sender: Sender<DownloadRequest>,
+    verify_sender: Sender<ModelVerifyRequest>,
     worker_thread: Option<JoinHandle<()>>,
+    verify_thread: Option<JoinHandle<()>>,
     debouncer: Option<Debouncer<RecommendedWatcher, RecommendedCache>>,
 }
 
@@ -343,12 +721,47 @@ impl RibbleModelBank {
             };
         }
 
+        // Models are verified one at a time, so that verifying several doesn't thrash the disk.
+        let (verify_sender, verify_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let verify_inner = Arc::clone(&inner);
+        let progress_sender = bus.progress_message_sender();
+        let console_sender = bus.console_message_sender();
+        let verify_thread = std::thread::spawn(move || {
+            while let Ok(ModelVerifyRequest::Verify(model_path)) = verify_receiver.recv() {
+                let message = match verify_inner.verify_model(&model_path, &progress_sender) {
+                    Ok(status) if status.is_flagged() => {
+                        let message = format!(
+                            "Model failed verification: {}. {}",
+                            model_path.display(),
+                            status.description()
+                        );
+                        log::warn!("{message}");
+                        ConsoleMessage::Error(RibbleError::Core(message))
+                    }
+                    Ok(status) => ConsoleMessage::Status(format!(
+                        "Model checked: {}. {}",
+                        model_path.display(),
+                        status.description()
+                    )),
+                    Err(e) => ConsoleMessage::Error(e),
+                };
+                if let Err(e) = console_sender.send(message) {
+                    log::warn!(
+                        "Console channel closed, cannot send verify result.\nError source: {:#?}",
+                        e.source()
+                    );
+                }
+            }
+        });
+
         Ok(Self {
             inner,
             work_sender: bus.work_request_sender(),
             progress_message_sender: bus.progress_message_sender(),
             download_sender: bus.download_request_sender(),
+            verify_sender,
             worker_thread: Some(work_thread),
+            verify_thread: Some(verify_thread),
             debouncer: Some(debouncer),
         })
     }
@@ -365,13 +778,18 @@ impl RibbleModelBank {
         self.inner.contains_model(model_id)
     }
 
+    pub(crate) fn get_model_file(&self, model_id: ModelId) -> Option<ModelFile> {
+        self.inner.get_model_file(model_id)
+    }
+
     // TODO: decide whether or not to allow users to set an alternative directory for models.
     // If that becomes a necessary feature, move to ArcSwap and set up logic for re-spawning the
     // watcher thread -> it's best to leave model management up to the user if they're already
     // swapping directories.
     pub(crate) fn download_new_model(&self, url: &str) {
         let model_directory = self.inner.model_directory();
-        let download_request = DownloadRequest::new_job(url, model_directory);
+        let download_request = DownloadRequest::new_job(url, model_directory)
+            .with_verify_sender(self.verify_sender.clone());
         if let Err(e) = self.download_sender.try_send(download_request) {
             log::warn!(
                 "Cannot make download request, channel either closed or too small.\n\
@@ -390,6 +808,7 @@ impl RibbleModelBank {
         // NOTE: this could be owned in the inner state struct (which is Send),
         // but it's easy to just make a short-lived copy to give to the thread.
         let progress_sender = self.progress_message_sender.clone();
+        let verify_sender = self.verify_sender.clone();
         let worker = std::thread::spawn(move || {
             if !model_file_path.is_file() {
                 let err =
@@ -471,6 +890,13 @@ impl RibbleModelBank {
 
             remove_progress_job(&mut copy_job_id);
 
+            if let Err(e) = verify_sender.send(ModelVerifyRequest::Verify(dest)) {
+                log::warn!(
+                    "Model bank closed, cannot verify copied model.\nError source: {:#?}",
+                    e.source()
+                );
+            }
+
             let console_message = ConsoleMessage::Status(format!(
                 "Saved model: {:#?} to models directory.",
                 file_name.display()
@@ -492,6 +918,203 @@ impl RibbleModelBank {
         }
     }
 
+    // Loads (or reloads) the offline catalog on a background thread; None clears it.
+    pub(crate) fn load_model_catalog(&self, location: Option<PathBuf>) {
+        let Some(location) = location else {
+            self.inner.catalog.store(Arc::new(None));
+            return;
+        };
+
+        let thread_inner = Arc::clone(&self.inner);
+        let worker = std::thread::spawn(move || {
+            let catalog = ModelCatalog::load(location.as_path())
+                .inspect_err(|_e| thread_inner.catalog.store(Arc::new(None)))?;
+            let num_models = catalog.models().len();
+            thread_inner.catalog.store(Arc::new(Some(Arc::new(catalog))));
+
+            let console_message = ConsoleMessage::Status(format!(
+                "Loaded model catalog: {} ({num_models} models).",
+                location.display()
+            ));
+            Ok(RibbleMessage::Console(console_message))
+        });
+
+        let work_request = WorkRequest::Short(worker);
+        if let Err(e) = self.work_sender.try_send(work_request) {
+            log::warn!(
+                "Failed to send work request. Channel may be closed or too small.\n\
+                Error: {e}\n\
+                Error source: {:#?}",
+                e.source()
+            );
+        }
+    }
+
+    pub(crate) fn model_catalog(&self) -> Option<Arc<ModelCatalog>> {
+        self.inner.catalog.load().as_ref().clone()
+    }
+
+    // Copies a model over from the catalog; this is the offline counterpart to downloading.
+    pub(crate) fn install_catalog_model(&self, model: CatalogModel) {
+        let thread_inner = Arc::clone(&self.inner);
+        let progress_sender = self.progress_message_sender.clone();
+        let verify_sender = self.verify_sender.clone();
+        let worker = std::thread::spawn(move || {
+            let (id_sender, id_receiver) = get_channel(1);
+            let progress_message = ProgressMessage::Request {
+                job: Progress::new_determinate("Installing model", model.size().unwrap_or(1))
+                    .maybe_indeterminate(model.size().is_none()),
+                id_return_sender: id_sender,
+            };
+            if let Err(e) = progress_sender.send(progress_message) {
+                log::warn!(
+                    "Progress channel closed, cannot send install model job.\n\
+                    Error source: {:#?}",
+                    e.source()
+                );
+            }
+            let progress_id = id_receiver.recv().ok();
+
+            let on_progress = |delta| {
+                if let Some(id) = progress_id {
+                    let increment = ProgressMessage::Increment { job_id: id, delta };
+                    if let Err(e) = progress_sender.send(increment) {
+                        log::warn!(
+                            "Progress channel closed, cannot send install progress.\n\
+                            Error source: {:#?}",
+                            e.source()
+                        );
+                    }
+                }
+            };
+            let result = install_catalog_model(
+                &model,
+                thread_inner.model_directory(),
+                on_progress,
+                |file_name| thread_inner.claim_unused_model(file_name),
+            );
+
+            if let Some(id) = progress_id
+                && let Err(e) = progress_sender.send(ProgressMessage::Remove { job_id: id })
+            {
+                log::warn!(
+                    "Progress channel closed, cannot remove install model job.\n\
+                    Error source: {:#?}",
+                    e.source()
+                );
+            }
+
+            let (model_path, sha256) = result?;
+            match sha256 {
+                // The checksum was checked on the way in; there's no need to hash it again.
+                Some(sha256) => {
+                    if let Err(e) = thread_inner.record_integrity(model_path.as_path(), sha256) {
+                        log::warn!("Failed to save model integrity results.\nError: {e}");
+                    }
+                }
+                None => {
+                    if let Err(e) = verify_sender.send(ModelVerifyRequest::Verify(model_path.clone()))
+                    {
+                        log::warn!(
+                            "Model bank closed, cannot verify installed model.\n\
+                            Error source: {:#?}",
+                            e.source()
+                        );
+                    }
+                }
+            }
+
+            let console_message = ConsoleMessage::Status(format!(
+                "Installed model: {} from the catalog.",
+                model_path.display()
+            ));
+            Ok(RibbleMessage::Console(console_message))
+        });
+
+        let work_request = WorkRequest::Long(worker);
+        if let Err(e) = self.work_sender.try_send(work_request) {
+            log::warn!(
+                "Failed to send work request. Channel may be closed or too small.\n\
+                Error: {e}\n\
+                Error source: {:#?}",
+                e.source()
+            );
+        }
+    }
+
+    // Queues every model (that's a file) to be verified again.
+    pub(crate) fn verify_all_models(&self) {
+        let model_paths = self
+            .inner
+            .model_map
+            .read()
+            .values()
+            .filter_map(|model| match model {
+                ModelFile::File(file_name) => Some(self.model_directory().join(file_name.as_ref())),
+                #[cfg(any(debug_assertions, feature = "pack-in-models"))]
+                ModelFile::Packed(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        for model_path in model_paths {
+            if let Err(e) = self
+                .verify_sender
+                .try_send(ModelVerifyRequest::Verify(model_path))
+            {
+                log::warn!(
+                    "Cannot queue model verification, channel either closed or too small.\n\
+                    Error: {e}\n\
+                    Error source: {:#?}",
+                    e.source()
+                );
+            }
+        }
+    }
+
+    pub(crate) fn model_integrity(&self, model_id: ModelId) -> ModelIntegrity {
+        self.inner.model_integrity(model_id)
+    }
+
+    pub(crate) fn model_header(&self, model_id: ModelId) -> Option<ModelHeader> {
+        self.inner.model_header(model_id)
+    }
+
+    pub(crate) fn model_size(&self, model_id: ModelId) -> Option<u64> {
+        self.inner.model_size(model_id)
+    }
+
+    pub(crate) fn disk_usage(&self) -> ModelDiskUsage {
+        self.inner.disk_usage()
+    }
+
+    // None for packed models; they don't live on disk.
+    pub(crate) fn model_path(&self, model_id: ModelId) -> Option<PathBuf> {
+        self.inner.model_path(model_id)
+    }
+
+    // Hand this to the transcriber in place of the bank; see: ModelLease.
+    pub(crate) fn lease_model(&self, model_id: Option<ModelId>) -> ModelLease {
+        ModelLease::new(Arc::clone(&self.inner), model_id)
+    }
+
+    // Returns the file name of the deleted model.
+    // Fails if the model is in use.
+    pub(crate) fn delete_model(&self, model_id: ModelId) -> Result<Arc<str>, RibbleError> {
+        self.inner.delete_model(model_id)
+    }
+
+    pub(crate) fn rename_model(
+        &self,
+        model_id: ModelId,
+        new_name: &str,
+    ) -> Result<ModelId, RibbleError> {
+        self.inner.rename_model(model_id, new_name)
+    }
+
+    pub(crate) fn verifying_model(&self) -> Option<Arc<str>> {
+        self.inner.verifying.load().as_ref().clone()
+    }
+
     pub(crate) fn try_read_model_list(&self, copy_buffer: &mut Vec<(ModelId, ModelFile)>) {
         if let Some(guard) = self.inner.model_map.try_read() {
             copy_buffer.clear();
@@ -514,6 +1137,21 @@ impl Drop for RibbleModelBank {
                 .expect("Debouncer thread is expected to work properly and should not panic.");
             log::info!("RibbleModelBank Debouncer thread joined.");
         }
+        // Cancel any verification in progress; there's no point hashing through to the end.
+        self.inner.shutting_down.store(true, Ordering::Release);
+        if let Err(e) = self.verify_sender.send(ModelVerifyRequest::Shutdown) {
+            log::warn!(
+                "Verify channel already closed.\nError source: {:#?}",
+                e.source()
+            );
+        }
+        if let Some(handle) = self.verify_thread.take() {
+            log::info!("Joining RibbleModelBank verify thread.");
+            handle
+                .join()
+                .expect("Verify thread is expected to work properly and should not panic.");
+            log::info!("RibbleModelBank verify thread joined.");
+        }
     }
 }
 
@@ -522,3 +1160,165 @@ impl ModelRetriever for RibbleModelBank {
         self.inner.retrieve_model(model_id)
     }
 }
+
+// A model retriever that keeps its models from being deleted or renamed: the model it was leased
+// for, and any model it retrieves. The models are released when the lease is dropped, i.e. when
+// the transcriber it was handed to finishes.
+pub(crate) struct ModelLease {
+    inner: Arc<RibbleModelBankState>,
+    leased: Mutex<Vec<ModelId>>,
+}
+
+impl ModelLease {
+    fn new(inner: Arc<RibbleModelBankState>, model_id: Option<ModelId>) -> Self {
+        let mut leased = vec![];
+        if let Some(model_id) = model_id {
+            let mut models_in_use = inner.models_in_use.lock();
+            if inner.contains_model(model_id) {
+                Self::acquire(&mut leased, &mut models_in_use, model_id);
+            }
+        }
+        Self {
+            inner,
+            leased: Mutex::new(leased),
+        }
+    }
+
+    fn acquire(
+        leased: &mut Vec<ModelId>,
+        models_in_use: &mut HashMap<ModelId, usize>,
+        model_id: ModelId,
+    ) {
+        if !leased.contains(&model_id) {
+            *models_in_use.entry(model_id).or_default() += 1;
+            leased.push(model_id);
+        }
+    }
+}
+
+impl ModelRetriever for ModelLease {
+    fn retrieve_model(&self, model_id: ModelId) -> Option<ModelLocation> {
+        // NOTE: the model is resolved with the in-use map locked, so it can't be deleted between
+        // being resolved and being leased.
+        let mut leased = self.leased.lock();
+        let mut models_in_use = self.inner.models_in_use.lock();
+        let location = self.inner.retrieve_model(model_id)?;
+        Self::acquire(&mut leased, &mut models_in_use, model_id);
+        Some(location)
+    }
+}
+
+impl Drop for ModelLease {
+    fn drop(&mut self) {
+        let mut models_in_use = self.inner.models_in_use.lock();
+        for model_id in self.leased.get_mut().drain(..) {
+            if let Some(num_leases) = models_in_use.get_mut(&model_id) {
+                *num_leases -= 1;
+                if *num_leases == 0 {
+                    models_in_use.remove(&model_id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::test_harness::CapturedBus;
+    use tempfile::TempDir;
+
+    const MODEL_NAME: &str = "ggml-test.bin";
+
+    fn new_bank(bus: &CapturedBus) -> (TempDir, RibbleModelBank, ModelId) {
+        let model_directory = tempfile::tempdir().unwrap();
+        fs::write(model_directory.path().join(MODEL_NAME), b"ggml").unwrap();
+        let bank = RibbleModelBank::new(model_directory.path(), bus.bus()).unwrap();
+
+        let mut models = vec![];
+        bank.try_read_model_list(&mut models);
+        let model_id = models
+            .iter()
+            .find(|(_, model)| model.file_name() == MODEL_NAME)
+            .map(|(model_id, _)| *model_id)
+            .expect("The model should be in the bank.");
+        (model_directory, bank, model_id)
+    }
+
+    #[test]
+    fn a_leased_model_cannot_be_deleted_or_renamed() {
+        let bus = CapturedBus::new();
+        let (model_directory, bank, model_id) = new_bank(&bus);
+
+        let lease = bank.lease_model(Some(model_id));
+        assert!(bank.delete_model(model_id).is_err());
+        assert!(bank.rename_model(model_id, "renamed").is_err());
+        assert!(model_directory.path().join(MODEL_NAME).is_file());
+
+        drop(lease);
+        let new_id = bank.rename_model(model_id, "renamed").unwrap();
+        assert!(model_directory.path().join("renamed.bin").is_file());
+        assert_eq!(bank.delete_model(new_id).unwrap().as_ref(), "renamed.bin");
+        assert!(!model_directory.path().join("renamed.bin").exists());
+    }
+
+    #[test]
+    fn a_leased_model_cannot_be_replaced_from_the_catalog() {
+        let bus = CapturedBus::new();
+        let (_model_directory, bank, model_id) = new_bank(&bus);
+
+        let lease = bank.lease_model(Some(model_id));
+        assert!(bank.inner.claim_unused_model(MODEL_NAME).is_err());
+        assert!(bank.inner.claim_unused_model("ggml-other.bin").is_ok());
+
+        drop(lease);
+        let claim = bank.inner.claim_unused_model(MODEL_NAME).unwrap();
+        // Nothing can lease the model until it's been replaced.
+        assert!(bank.inner.models_in_use.try_lock().is_none());
+        drop(claim);
+    }
+
+    #[test]
+    fn retrieving_a_model_leases_it() {
+        let bus = CapturedBus::new();
+        let (model_directory, bank, model_id) = new_bank(&bus);
+
+        // e.g. the selection changed between the lease and the transcriber loading its model.
+        let lease = bank.lease_model(None);
+        let Some(ModelLocation::DynamicFilePath(path)) = lease.retrieve_model(model_id) else {
+            panic!("Expected the model's file path.");
+        };
+        assert_eq!(path, model_directory.path().join(MODEL_NAME));
+        assert!(bank.delete_model(model_id).is_err());
+
+        drop(lease);
+        assert!(bank.delete_model(model_id).is_ok());
+    }
+
+    #[test]
+    fn leases_are_counted() {
+        let bus = CapturedBus::new();
+        let (_model_directory, bank, model_id) = new_bank(&bus);
+
+        let first = bank.lease_model(Some(model_id));
+        let second = bank.lease_model(Some(model_id));
+        // Retrieving a model that's already leased doesn't lease it twice.
+        assert!(first.retrieve_model(model_id).is_some());
+
+        drop(first);
+        assert!(bank.delete_model(model_id).is_err());
+        drop(second);
+        assert!(bank.delete_model(model_id).is_ok());
+    }
+
+    #[test]
+    fn a_deleted_model_cannot_be_leased() {
+        let bus = CapturedBus::new();
+        let (_model_directory, bank, model_id) = new_bank(&bus);
+
+        bank.delete_model(model_id).unwrap();
+        let lease = bank.lease_model(Some(model_id));
+        assert!(lease.retrieve_model(model_id).is_none());
+        assert!(lease.leased.lock().is_empty());
+    }
+}
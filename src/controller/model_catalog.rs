@@ -0,0 +1,498 @@
+// Offline model catalogs, for machines without network access.
+// A catalog is a local directory (or a file:// url pointing at one) that holds the models and a
+// catalog.json that lists them:
+//
+// { "models": [{ "file": "ggml-base.en.bin", "sha256": "<hex>", "size": 147964211,
+//                "description": "Base (English)" }] }
+//
+// Paths are relative to the catalog directory; only "file" is required.
+// A copy of a Ribble models directory also works: the cached model manifest stands in for the
+// catalog. Failing both, the .bin files in the directory are listed without checksums.
+use crate::controller::model_integrity::ModelManifest;
+use crate::utils::errors::RibbleError;
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) const CATALOG_FILE: &str = "catalog.json";
+const MODEL_FILE_EXTENSION: &str = "bin";
+// NOTE: not .part; a resumable download of the same model would pick it up (see:
+// resumable_download.rs).
+const PART_FILE_EXTENSION: &str = "catalog-part";
+const COPY_BUFFER_SIZE: usize = 1 << 20;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub(crate) struct CatalogModel {
+    // Resolved against the catalog directory once the catalog is loaded.
+    file: PathBuf,
+    #[serde(default)]
+    sha256: Option<String>,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+impl CatalogModel {
+    // The name the model is installed under.
+    pub(crate) fn file_name(&self) -> Option<&str> {
+        self.file.file_name().and_then(OsStr::to_str)
+    }
+    pub(crate) fn size(&self) -> Option<u64> {
+        self.size
+    }
+    pub(crate) fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    pub(crate) fn has_checksum(&self) -> bool {
+        self.sha256.is_some()
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CatalogFile {
+    models: Vec<CatalogModel>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ModelCatalog {
+    directory: PathBuf,
+    models: Vec<CatalogModel>,
+}
+
+impl ModelCatalog {
+    // Location is either the catalog directory, or a catalog file.
+    pub(super) fn load(location: &Path) -> Result<Self, RibbleError> {
+        let (directory, catalog_path) = if location.is_dir() {
+            (location.to_path_buf(), location.join(CATALOG_FILE))
+        } else if location.is_file() {
+            let directory = location.parent().unwrap_or(Path::new("")).to_path_buf();
+            (directory, location.to_path_buf())
+        } else {
+            return Err(RibbleError::IOError(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("Model catalog not found: {}", location.display()),
+            )));
+        };
+
+        let models = match std::fs::read_to_string(catalog_path.as_path()) {
+            Ok(serialized) => serde_json::from_str::<CatalogFile>(&serialized)?.models,
+            Err(e) if e.kind() == ErrorKind::NotFound => match ModelManifest::load(&directory) {
+                Some(manifest) => models_from_manifest(&directory, &manifest),
+                None => scan_models(&directory)?,
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        let models = models
+            .into_iter()
+            .filter(|model| {
+                let is_model = model.file.extension() == Some(OsStr::new(MODEL_FILE_EXTENSION))
+                    && model.file_name().is_some();
+                if !is_model {
+                    log::warn!("Skipping catalog entry: {}", model.file.display());
+                }
+                is_model
+            })
+            .map(|model| CatalogModel {
+                file: directory.join(model.file),
+                ..model
+            })
+            .collect();
+
+        Ok(Self { directory, models })
+    }
+
+    pub(crate) fn directory(&self) -> &Path {
+        self.directory.as_path()
+    }
+
+    pub(crate) fn models(&self) -> &[CatalogModel] {
+        &self.models
+    }
+}
+
+// Accepts a plain path or a file:// url.
+pub(crate) fn parse_catalog_location(location: &str) -> Result<PathBuf, RibbleError> {
+    let location = location.trim();
+    if !location.starts_with("file://") {
+        return Ok(PathBuf::from(location));
+    }
+    reqwest::Url::parse(location)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or(RibbleError::Core(format!(
+            "Invalid catalog location: {location}"
+        )))
+}
+
+fn models_from_manifest(directory: &Path, manifest: &ModelManifest) -> Vec<CatalogModel> {
+    manifest
+        .known_models()
+        // The manifest lists every known model; only the ones that are actually here count.
+        .filter(|(file_name, _, _)| directory.join(file_name).is_file())
+        .map(|(file_name, sha256, size)| CatalogModel {
+            file: PathBuf::from(file_name),
+            sha256: Some(sha256.to_string()),
+            size: Some(size),
+            description: None,
+        })
+        .collect()
+}
+
+fn scan_models(directory: &Path) -> Result<Vec<CatalogModel>, RibbleError> {
+    let mut models = std::fs::read_dir(directory)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| CatalogModel {
+            file: PathBuf::from(entry.file_name()),
+            sha256: None,
+            size: entry.metadata().ok().map(|metadata| metadata.len()),
+            description: None,
+        })
+        .collect::<Vec<_>>();
+    models.sort_by(|a, b| a.file.cmp(&b.file));
+    Ok(models)
+}
+
+// Copies the model into the model directory, checking its size and checksum (if the catalog has
+// them) on the way. The copy goes to a .catalog-part file first, so a failed copy never shows up as
+// a model.
+// claim_model is asked (with the file name) before anything is copied, and again before the model
+// is swapped in; the guard it returns is held until the new model is in place. Use this to refuse
+// replacing a model that's in use.
+// Returns the installed model's path, and its checksum if it was verified.
+pub(super) fn install_catalog_model<F, C, G>(
+    model: &CatalogModel,
+    model_directory: &Path,
+    mut on_progress: F,
+    claim_model: C,
+) -> Result<(PathBuf, Option<String>), RibbleError>
+where
+    F: FnMut(u64),
+    C: Fn(&str) -> Result<G, RibbleError>,
+{
+    let file_name = model.file_name().ok_or(RibbleError::Core(format!(
+        "Invalid catalog model: {}",
+        model.file.display()
+    )))?;
+    // Fail early, rather than after copying a few gigabytes.
+    drop(claim_model(file_name)?);
+    let size = std::fs::metadata(model.file.as_path())?.len();
+    if let Some(expected_size) = model.size
+        && size != expected_size
+    {
+        return Err(RibbleError::Core(format!(
+            "Catalog model {file_name} is {size} bytes; the catalog expects {expected_size} bytes."
+        )));
+    }
+
+    let part_path = model_directory.join(format!("{file_name}.{PART_FILE_EXTENSION}"));
+    let sha256 = match copy_and_hash(model.file.as_path(), part_path.as_path(), &mut on_progress) {
+        Ok(sha256) => sha256,
+        Err(e) => {
+            remove_part_file(part_path.as_path());
+            return Err(e);
+        }
+    };
+
+    let verified = match model.sha256.as_ref() {
+        Some(expected) if !expected.eq_ignore_ascii_case(&sha256) => {
+            remove_part_file(part_path.as_path());
+            return Err(RibbleError::Core(format!(
+                "Checksum mismatch for catalog model {file_name}. The catalog copy may be \
+                corrupted."
+            )));
+        }
+        Some(_) => Some(sha256),
+        None => None,
+    };
+
+    let model_path = model_directory.join(file_name);
+    let _claim = match claim_model(file_name) {
+        Ok(claim) => claim,
+        Err(e) => {
+            remove_part_file(part_path.as_path());
+            return Err(e);
+        }
+    };
+    // NOTE: rename doesn't replace an existing file on every platform.
+    if let Err(e) = std::fs::remove_file(model_path.as_path())
+        && e.kind() != ErrorKind::NotFound
+    {
+        remove_part_file(part_path.as_path());
+        return Err(e.into());
+    }
+    std::fs::rename(part_path.as_path(), model_path.as_path())?;
+    Ok((model_path, verified))
+}
+
+fn copy_and_hash<F>(source: &Path, dest: &Path, on_progress: &mut F) -> Result<String, RibbleError>
+where
+    F: FnMut(u64),
+{
+    let mut source = File::open(source)?;
+    let mut dest = File::create(dest)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+    loop {
+        let num_read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(num_read) => num_read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        dest.write_all(&buffer[..num_read])?;
+        hasher.update(&buffer[..num_read]);
+        on_progress(num_read as u64);
+    }
+    dest.sync_all()?;
+
+    let digest = hasher.finalize();
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+fn remove_part_file(part_path: &Path) {
+    if let Err(e) = std::fs::remove_file(part_path)
+        && e.kind() != ErrorKind::NotFound
+    {
+        log::warn!(
+            "Failed to remove partial copy: {}\nError: {e}",
+            part_path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::model_integrity::MANIFEST_FILE;
+    use tempfile::TempDir;
+
+    const MODEL_CONTENTS: &[u8] = b"ggml model weights";
+
+    fn sha256_of(contents: &[u8]) -> String {
+        Sha256::digest(contents)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn catalog_directory(files: &[&str]) -> TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        for file in files {
+            std::fs::write(directory.path().join(file), MODEL_CONTENTS).unwrap();
+        }
+        directory
+    }
+
+    fn catalog_model(
+        directory: &TempDir,
+        sha256: Option<String>,
+        size: Option<u64>,
+    ) -> CatalogModel {
+        CatalogModel {
+            file: directory.path().join("ggml-test.bin"),
+            sha256,
+            size,
+            description: None,
+        }
+    }
+
+    fn file_names(catalog: &ModelCatalog) -> Vec<&str> {
+        catalog
+            .models()
+            .iter()
+            .filter_map(CatalogModel::file_name)
+            .collect()
+    }
+
+    // Nothing may be left behind by a failed install.
+    fn assert_empty(directory: &TempDir) {
+        assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 0);
+    }
+
+    fn unclaimed(_: &str) -> Result<(), RibbleError> {
+        Ok(())
+    }
+
+    #[test]
+    fn the_catalog_file_lists_the_models() {
+        let directory = catalog_directory(&["ggml-base.bin", "ggml-tiny.bin"]);
+        let catalog_json = r#"{ "models": [
+            { "file": "ggml-base.bin", "sha256": "abc", "size": 18, "description": "Base" },
+            { "file": "notes.txt" },
+            { "file": "ggml-tiny.bin" }
+        ] }"#;
+        std::fs::write(directory.path().join(CATALOG_FILE), catalog_json).unwrap();
+
+        let catalog = ModelCatalog::load(directory.path()).unwrap();
+        assert_eq!(catalog.directory(), directory.path());
+        assert_eq!(file_names(&catalog), ["ggml-base.bin", "ggml-tiny.bin"]);
+        let base = &catalog.models()[0];
+        assert_eq!(base.file, directory.path().join("ggml-base.bin"));
+        assert_eq!(base.size(), Some(18));
+        assert_eq!(base.description(), Some("Base"));
+        assert!(base.has_checksum());
+        assert!(!catalog.models()[1].has_checksum());
+
+        // The catalog file itself works as the location too.
+        let catalog = ModelCatalog::load(directory.path().join(CATALOG_FILE).as_path()).unwrap();
+        assert_eq!(catalog.directory(), directory.path());
+        assert_eq!(catalog.models().len(), 2);
+    }
+
+    #[test]
+    fn a_models_directory_falls_back_to_its_manifest() {
+        let directory = catalog_directory(&["ggml-base.bin"]);
+        let manifest = format!(
+            r#"{{ "models": {{
+                "ggml-base.bin": {{ "sha256": "{}", "size": 18 }},
+                "ggml-large.bin": {{ "sha256": "def", "size": 3000000000 }}
+            }} }}"#,
+            sha256_of(MODEL_CONTENTS)
+        );
+        std::fs::write(directory.path().join(MANIFEST_FILE), manifest).unwrap();
+
+        // Only the models that are actually in the directory are listed.
+        let catalog = ModelCatalog::load(directory.path()).unwrap();
+        assert_eq!(file_names(&catalog), ["ggml-base.bin"]);
+        assert!(catalog.models()[0].has_checksum());
+        assert_eq!(catalog.models()[0].size(), Some(18));
+    }
+
+    #[test]
+    fn a_plain_directory_is_scanned_for_models() {
+        let directory = catalog_directory(&["ggml-tiny.bin", "ggml-base.bin", "README.md"]);
+        std::fs::create_dir(directory.path().join("more.bin")).unwrap();
+
+        let catalog = ModelCatalog::load(directory.path()).unwrap();
+        assert_eq!(file_names(&catalog), ["ggml-base.bin", "ggml-tiny.bin"]);
+        assert!(catalog.models().iter().all(|model| !model.has_checksum()));
+        assert_eq!(
+            catalog.models()[0].size(),
+            Some(MODEL_CONTENTS.len() as u64)
+        );
+
+        assert!(ModelCatalog::load(directory.path().join("missing").as_path()).is_err());
+    }
+
+    #[test]
+    fn a_checked_model_is_installed() {
+        let catalog = catalog_directory(&["ggml-test.bin"]);
+        let model_directory = tempfile::tempdir().unwrap();
+        let sha256 = sha256_of(MODEL_CONTENTS);
+        let model = catalog_model(
+            &catalog,
+            Some(sha256.to_uppercase()),
+            Some(MODEL_CONTENTS.len() as u64),
+        );
+
+        let mut num_copied = 0;
+        let (model_path, verified) = install_catalog_model(
+            &model,
+            model_directory.path(),
+            |delta| num_copied += delta,
+            unclaimed,
+        )
+        .unwrap();
+
+        assert_eq!(model_path, model_directory.path().join("ggml-test.bin"));
+        assert_eq!(std::fs::read(model_path).unwrap(), MODEL_CONTENTS);
+        assert_eq!(verified, Some(sha256));
+        assert_eq!(num_copied, MODEL_CONTENTS.len() as u64);
+        // Only the model is left.
+        assert_eq!(
+            std::fs::read_dir(model_directory.path()).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn an_unchecked_model_is_left_to_be_verified() {
+        let catalog = catalog_directory(&["ggml-test.bin"]);
+        let model_directory = tempfile::tempdir().unwrap();
+        let model = catalog_model(&catalog, None, None);
+
+        let (_, verified) =
+            install_catalog_model(&model, model_directory.path(), |_| {}, unclaimed).unwrap();
+        assert_eq!(verified, None);
+    }
+
+    #[test]
+    fn a_size_mismatch_is_refused() {
+        let catalog = catalog_directory(&["ggml-test.bin"]);
+        let model_directory = tempfile::tempdir().unwrap();
+        let model = catalog_model(&catalog, None, Some(1000));
+
+        let result = install_catalog_model(&model, model_directory.path(), |_| {}, unclaimed);
+        assert!(result.is_err());
+        assert_empty(&model_directory);
+    }
+
+    #[test]
+    fn a_checksum_mismatch_is_cleaned_up() {
+        let catalog = catalog_directory(&["ggml-test.bin"]);
+        let model_directory = tempfile::tempdir().unwrap();
+        // The model that's already installed is kept.
+        let installed = model_directory.path().join("ggml-test.bin");
+        std::fs::write(installed.as_path(), b"the old model").unwrap();
+        let model = catalog_model(&catalog, Some(sha256_of(b"something else")), None);
+
+        let result = install_catalog_model(&model, model_directory.path(), |_| {}, unclaimed);
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(installed).unwrap(), b"the old model");
+        assert_eq!(
+            std::fs::read_dir(model_directory.path()).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_model_in_use_is_not_replaced() {
+        let catalog = catalog_directory(&["ggml-test.bin"]);
+        let model_directory = tempfile::tempdir().unwrap();
+        let model = catalog_model(&catalog, None, None);
+        let in_use =
+            |file_name: &str| Err::<(), _>(RibbleError::Core(format!("{file_name} is in use.")));
+
+        let result = install_catalog_model(&model, model_directory.path(), |_| {}, in_use);
+        assert!(result.is_err());
+        assert_empty(&model_directory);
+
+        // It's checked again before the swap; e.g. a transcription started mid-copy.
+        let num_claims = std::cell::Cell::new(0);
+        let claimed_mid_copy = |file_name: &str| {
+            num_claims.set(num_claims.get() + 1);
+            if num_claims.get() > 1 {
+                return in_use(file_name);
+            }
+            Ok(())
+        };
+        let result =
+            install_catalog_model(&model, model_directory.path(), |_| {}, claimed_mid_copy);
+        assert!(result.is_err());
+        assert_eq!(num_claims.get(), 2);
+        assert_empty(&model_directory);
+    }
+
+    #[test]
+    fn catalog_locations_can_be_file_urls() {
+        assert_eq!(
+            parse_catalog_location(" /mnt/models ").unwrap(),
+            PathBuf::from("/mnt/models")
+        );
+        assert_eq!(
+            parse_catalog_location("file:///mnt/models/catalog.json").unwrap(),
+            PathBuf::from("/mnt/models/catalog.json")
+        );
+        assert_eq!(
+            parse_catalog_location("file:///mnt/my%20models").unwrap(),
+            PathBuf::from("/mnt/my models")
+        );
+        // Only local files.
+        assert!(parse_catalog_location("file://fileserver/models").is_err());
+    }
+}
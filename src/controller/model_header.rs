@@ -0,0 +1,577 @@
+// Model header inspection.
+// whisper.cpp models are (legacy) ggml files: the magic, followed by the model hyperparameters.
+// The hyperparameters are enough to tell the architecture size, the quantization and whether the
+// model is multilingual, without loading the model.
+//
+// NOTE: GGUF files are recognized (and their metadata read, where it's there), but whisper.cpp
+// can't load them; they're never valid whisper models.
+use crate::utils::errors::RibbleError;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+
+// "ggml" (little-endian)
+const GGML_MAGIC: u32 = 0x67676d6c;
+// "GGUF" (little-endian)
+const GGUF_MAGIC: u32 = 0x46554747;
+// ggml stores the quantization version in the ftype: qnt_version * FACTOR + ftype.
+const GGML_QNT_VERSION_FACTOR: i32 = 1000;
+// The English-only models drop the multilingual (language) tokens.
+const ENGLISH_ONLY_VOCAB_SIZE: u32 = 51864;
+const MULTILINGUAL_VOCAB_SIZE: u32 = 51865;
+// Upper bounds for GGUF metadata, so that a garbage file can't trigger huge allocations or loops.
+const MAX_GGUF_KEY_VALUES: u64 = 4096;
+const MAX_GGUF_STRING_LEN: u64 = 1 << 16;
+// GGUF metadata value types.
+const GGUF_TYPE_U32: u32 = 4;
+const GGUF_TYPE_STRING: u32 = 8;
+const GGUF_TYPE_ARRAY: u32 = 9;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ModelFormat {
+    Ggml,
+    Gguf,
+    Unrecognized,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ModelSize {
+    Tiny,
+    Base,
+    Small,
+    Medium,
+    Large,
+    // Large encoder, 4-layer decoder.
+    LargeTurbo,
+    Unknown,
+}
+
+impl ModelSize {
+    // Whisper is identified by its encoder depth.
+    fn from_layers(n_audio_layer: i32, n_text_layer: i32) -> Self {
+        match (n_audio_layer, n_text_layer) {
+            (4, _) => ModelSize::Tiny,
+            (6, _) => ModelSize::Base,
+            (12, _) => ModelSize::Small,
+            (24, _) => ModelSize::Medium,
+            (32, 4) => ModelSize::LargeTurbo,
+            (32, _) => ModelSize::Large,
+            _ => ModelSize::Unknown,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ModelSize::Tiny => "tiny",
+            ModelSize::Base => "base",
+            ModelSize::Small => "small",
+            ModelSize::Medium => "medium",
+            ModelSize::Large => "large",
+            ModelSize::LargeTurbo => "large-turbo",
+            ModelSize::Unknown => "unknown size",
+        }
+    }
+}
+
+// A subset of ggml_ftype; these are the ones whisper.cpp's quantize tool produces.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ModelQuantization {
+    F32,
+    F16,
+    Q4_0,
+    Q4_1,
+    Q5_0,
+    Q5_1,
+    Q8_0,
+    Q2K,
+    Q3K,
+    Q4K,
+    Q5K,
+    Q6K,
+}
+
+impl ModelQuantization {
+    fn from_ftype(ftype: i32) -> Option<Self> {
+        match ftype {
+            0 => Some(ModelQuantization::F32),
+            1 => Some(ModelQuantization::F16),
+            2 => Some(ModelQuantization::Q4_0),
+            3 => Some(ModelQuantization::Q4_1),
+            7 => Some(ModelQuantization::Q8_0),
+            8 => Some(ModelQuantization::Q5_0),
+            9 => Some(ModelQuantization::Q5_1),
+            10 => Some(ModelQuantization::Q2K),
+            11 => Some(ModelQuantization::Q3K),
+            12 => Some(ModelQuantization::Q4K),
+            13 => Some(ModelQuantization::Q5K),
+            14 => Some(ModelQuantization::Q6K),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ModelQuantization::F32 => "f32",
+            ModelQuantization::F16 => "f16",
+            ModelQuantization::Q4_0 => "q4_0",
+            ModelQuantization::Q4_1 => "q4_1",
+            ModelQuantization::Q5_0 => "q5_0",
+            ModelQuantization::Q5_1 => "q5_1",
+            ModelQuantization::Q8_0 => "q8_0",
+            ModelQuantization::Q2K => "q2_k",
+            ModelQuantization::Q3K => "q3_k",
+            ModelQuantization::Q4K => "q4_k",
+            ModelQuantization::Q5K => "q5_k",
+            ModelQuantization::Q6K => "q6_k",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ModelHeader {
+    format: ModelFormat,
+    size: ModelSize,
+    quantization: Option<ModelQuantization>,
+    vocab_size: Option<u32>,
+    // Whether whisper.cpp can load it.
+    valid_whisper: bool,
+}
+
+impl ModelHeader {
+    fn unrecognized() -> Self {
+        Self {
+            format: ModelFormat::Unrecognized,
+            size: ModelSize::Unknown,
+            quantization: None,
+            vocab_size: None,
+            valid_whisper: false,
+        }
+    }
+
+    pub(crate) fn format(&self) -> ModelFormat {
+        self.format
+    }
+
+    pub(crate) fn is_valid_whisper(&self) -> bool {
+        self.valid_whisper
+    }
+
+    // None if the vocabulary size is unknown.
+    pub(crate) fn is_multilingual(&self) -> Option<bool> {
+        self.vocab_size
+            .map(|vocab_size| vocab_size >= MULTILINGUAL_VOCAB_SIZE)
+    }
+
+    pub(crate) fn is_english_only(&self) -> bool {
+        self.valid_whisper && self.vocab_size == Some(ENGLISH_ONLY_VOCAB_SIZE)
+    }
+
+    // A short description for the model list, e.g. "base, q5_1, multilingual".
+    pub(crate) fn summary(&self) -> String {
+        if !self.valid_whisper {
+            return match self.format {
+                ModelFormat::Gguf => "GGUF, unsupported".to_string(),
+                _ => "not a whisper model".to_string(),
+            };
+        }
+        let mut summary = self.size.as_str().to_string();
+        if let Some(quantization) = self.quantization {
+            summary.push_str(&format!(", {}", quantization.as_str()));
+        }
+        match self.is_multilingual() {
+            Some(true) => summary.push_str(", multilingual"),
+            Some(false) => summary.push_str(", English-only"),
+            None => {}
+        }
+        summary
+    }
+}
+
+impl Display for ModelHeader {
+    // The full description, for hover text.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let format = match self.format {
+            ModelFormat::Ggml => "ggml",
+            ModelFormat::Gguf => "GGUF",
+            ModelFormat::Unrecognized => "unrecognized",
+        };
+        writeln!(f, "Format: {format}")?;
+        if !self.valid_whisper {
+            return match self.format {
+                ModelFormat::Gguf => write!(f, "whisper.cpp can only load ggml models."),
+                _ => write!(f, "Not a valid whisper model."),
+            };
+        }
+        writeln!(f, "Size: {}", self.size.as_str())?;
+        let quantization = self
+            .quantization
+            .map_or("unknown", |quantization| quantization.as_str());
+        writeln!(f, "Quantization: {quantization}")?;
+        if let Some(vocab_size) = self.vocab_size {
+            writeln!(f, "Vocabulary: {vocab_size} tokens")?;
+        }
+        let languages = match self.is_multilingual() {
+            Some(true) => "multilingual",
+            Some(false) => "English only",
+            None => "unknown",
+        };
+        write!(f, "Languages: {languages}")
+    }
+}
+
+pub(super) fn read_model_header(path: &Path) -> Result<ModelHeader, RibbleError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    parse_model_header(&mut reader)
+}
+
+// Truncated or malformed headers aren't errors; they're just not valid models.
+pub(super) fn parse_model_header<R: Read>(reader: &mut R) -> Result<ModelHeader, RibbleError> {
+    let result = match read_u32(reader) {
+        Ok(GGML_MAGIC) => parse_ggml_header(reader),
+        Ok(GGUF_MAGIC) => parse_gguf_header(reader),
+        Ok(_) => return Ok(ModelHeader::unrecognized()),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(header) => Ok(header),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(ModelHeader::unrecognized()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn parse_ggml_header<R: Read>(reader: &mut R) -> std::io::Result<ModelHeader> {
+    let n_vocab = read_i32(reader)?;
+    let _n_audio_ctx = read_i32(reader)?;
+    let n_audio_state = read_i32(reader)?;
+    let _n_audio_head = read_i32(reader)?;
+    let n_audio_layer = read_i32(reader)?;
+    let _n_text_ctx = read_i32(reader)?;
+    let _n_text_state = read_i32(reader)?;
+    let _n_text_head = read_i32(reader)?;
+    let n_text_layer = read_i32(reader)?;
+    let n_mels = read_i32(reader)?;
+    let ftype = read_i32(reader)?;
+
+    let quantization = ModelQuantization::from_ftype(ftype % GGML_QNT_VERSION_FACTOR);
+    // whisper.cpp only supports 80 (and since large-v3, 128) mel bins.
+    let valid_whisper = n_vocab > 0
+        && n_audio_state > 0
+        && n_audio_layer > 0
+        && n_text_layer > 0
+        && matches!(n_mels, 80 | 128)
+        && quantization.is_some();
+
+    Ok(ModelHeader {
+        format: ModelFormat::Ggml,
+        size: ModelSize::from_layers(n_audio_layer, n_text_layer),
+        quantization,
+        vocab_size: u32::try_from(n_vocab).ok(),
+        valid_whisper,
+    })
+}
+
+// GGUF: version, tensor count, key-value count, then the key-value pairs.
+// Only the metadata that maps onto the ggml header is kept.
+fn parse_gguf_header<R: Read>(reader: &mut R) -> std::io::Result<ModelHeader> {
+    let _version = read_u32(reader)?;
+    let _n_tensors = read_u64(reader)?;
+    let n_key_values = read_u64(reader)?.min(MAX_GGUF_KEY_VALUES);
+
+    let mut header = ModelHeader {
+        format: ModelFormat::Gguf,
+        ..ModelHeader::unrecognized()
+    };
+    let mut architecture = None;
+    let mut layers = (0, 0);
+
+    for _ in 0..n_key_values {
+        let key = read_gguf_string(reader)?;
+        let value_type = read_u32(reader)?;
+        match (key.as_str(), value_type) {
+            ("general.architecture", GGUF_TYPE_STRING) => {
+                architecture = Some(read_gguf_string(reader)?);
+            }
+            ("general.file_type", GGUF_TYPE_U32) => {
+                let file_type = read_u32(reader)?;
+                header.quantization = i32::try_from(file_type)
+                    .ok()
+                    .and_then(ModelQuantization::from_ftype);
+            }
+            ("tokenizer.ggml.tokens", GGUF_TYPE_ARRAY) => {
+                let element_type = read_u32(reader)?;
+                let len = read_u64(reader)?;
+                header.vocab_size = u32::try_from(len).ok();
+                for _ in 0..len {
+                    skip_gguf_value(reader, element_type)?;
+                }
+            }
+            (key, GGUF_TYPE_U32) if key.ends_with(".encoder.block_count") => {
+                layers.0 = read_u32(reader)? as i32;
+            }
+            (key, GGUF_TYPE_U32) if key.ends_with(".decoder.block_count") => {
+                layers.1 = read_u32(reader)? as i32;
+            }
+            _ => skip_gguf_value(reader, value_type)?,
+        }
+    }
+
+    if architecture.as_deref() == Some("whisper") {
+        header.size = ModelSize::from_layers(layers.0, layers.1);
+    }
+    Ok(header)
+}
+
+fn skip_gguf_value<R: Read>(reader: &mut R, value_type: u32) -> std::io::Result<()> {
+    let num_bytes = match value_type {
+        // u8, i8, bool
+        0 | 1 | 7 => 1,
+        // u16, i16
+        2 | 3 => 2,
+        // u32, i32, f32
+        4..=6 => 4,
+        // u64, i64, f64
+        10..=12 => 8,
+        GGUF_TYPE_STRING => read_u64(reader)?,
+        GGUF_TYPE_ARRAY => {
+            let element_type = read_u32(reader)?;
+            let len = read_u64(reader)?;
+            for _ in 0..len {
+                skip_gguf_value(reader, element_type)?;
+            }
+            return Ok(());
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown GGUF value type: {value_type}"),
+            ));
+        }
+    };
+    let skipped = std::io::copy(&mut reader.take(num_bytes), &mut std::io::sink())?;
+    if skipped < num_bytes {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_gguf_string<R: Read>(reader: &mut R) -> std::io::Result<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_GGUF_STRING_LEN {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("GGUF string too long: {len}"),
+        ));
+    }
+    let mut buffer = vec![0u8; len as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> std::io::Result<i32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // n_vocab, n_audio_ctx, n_audio_state, n_audio_head, n_audio_layer, n_text_ctx,
+    // n_text_state, n_text_head, n_text_layer, n_mels, ftype
+    const TINY: [i32; 11] = [51865, 1500, 384, 6, 4, 448, 384, 6, 4, 80, 1];
+    const BASE_EN_Q5_1: [i32; 11] = [51864, 1500, 512, 8, 6, 448, 512, 8, 6, 80, 1009];
+    const LARGE_V3: [i32; 11] = [51866, 1500, 1280, 20, 32, 448, 1280, 20, 32, 128, 1];
+    const LARGE_V3_TURBO: [i32; 11] = [51866, 1500, 1280, 20, 32, 448, 1280, 20, 4, 128, 1];
+
+    fn ggml(hyperparameters: &[i32]) -> Vec<u8> {
+        let mut bytes = GGML_MAGIC.to_le_bytes().to_vec();
+        for value in hyperparameters {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        // Stand-in for the vocabulary and weights.
+        bytes.extend_from_slice(&[0u8; 64]);
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> ModelHeader {
+        parse_model_header(&mut &bytes[..]).unwrap()
+    }
+
+    fn push_gguf_string(bytes: &mut Vec<u8>, string: &str) {
+        bytes.extend_from_slice(&(string.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(string.as_bytes());
+    }
+
+    // A GGUF (v3) header, with no tensors; the metadata is appended by the caller.
+    fn gguf(num_key_values: u64) -> Vec<u8> {
+        let mut bytes = GGUF_MAGIC.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&num_key_values.to_le_bytes());
+        bytes
+    }
+
+    fn push_gguf_u32(bytes: &mut Vec<u8>, key: &str, value: u32) {
+        push_gguf_string(bytes, key);
+        bytes.extend_from_slice(&GGUF_TYPE_U32.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_gguf_str(bytes: &mut Vec<u8>, key: &str, value: &str) {
+        push_gguf_string(bytes, key);
+        bytes.extend_from_slice(&GGUF_TYPE_STRING.to_le_bytes());
+        push_gguf_string(bytes, value);
+    }
+
+    #[test]
+    fn model_sizes_map_from_the_layers() {
+        let tiny = parse(&ggml(&TINY));
+        assert_eq!(tiny.format, ModelFormat::Ggml);
+        assert_eq!(tiny.size, ModelSize::Tiny);
+        assert_eq!(tiny.quantization, Some(ModelQuantization::F16));
+        assert!(tiny.is_valid_whisper());
+        assert_eq!(tiny.summary(), "tiny, f16, multilingual");
+
+        let base = parse(&ggml(&BASE_EN_Q5_1));
+        assert_eq!(base.size, ModelSize::Base);
+        // The quantization version is stripped from the ftype.
+        assert_eq!(base.quantization, Some(ModelQuantization::Q5_1));
+        assert_eq!(base.summary(), "base, q5_1, English-only");
+
+        assert_eq!(parse(&ggml(&LARGE_V3)).size, ModelSize::Large);
+        let turbo = parse(&ggml(&LARGE_V3_TURBO));
+        assert_eq!(turbo.size, ModelSize::LargeTurbo);
+        assert!(turbo.is_valid_whisper());
+        assert_eq!(turbo.summary(), "large-turbo, f16, multilingual");
+
+        let mut unknown = TINY;
+        unknown[4] = 5;
+        let unknown = parse(&ggml(&unknown));
+        assert_eq!(unknown.size, ModelSize::Unknown);
+        // Odd sizes are still loadable.
+        assert!(unknown.is_valid_whisper());
+    }
+
+    #[test]
+    fn english_only_and_multilingual_vocabularies() {
+        let english = parse(&ggml(&BASE_EN_Q5_1));
+        assert_eq!(english.vocab_size, Some(ENGLISH_ONLY_VOCAB_SIZE));
+        assert_eq!(english.is_multilingual(), Some(false));
+        assert!(english.is_english_only());
+
+        let multilingual = parse(&ggml(&TINY));
+        assert_eq!(multilingual.is_multilingual(), Some(true));
+        assert!(!multilingual.is_english_only());
+
+        // large-v3 added a language token.
+        let large = parse(&ggml(&LARGE_V3));
+        assert_eq!(large.vocab_size, Some(51866));
+        assert_eq!(large.is_multilingual(), Some(true));
+    }
+
+    #[test]
+    fn invalid_hyperparameters_arent_whisper_models() {
+        let mut bad_mels = TINY;
+        bad_mels[9] = 64;
+        let mut bad_ftype = TINY;
+        bad_ftype[10] = 42;
+        let mut no_layers = TINY;
+        no_layers[4] = 0;
+        for hyperparameters in [bad_mels, bad_ftype, no_layers] {
+            let header = parse(&ggml(&hyperparameters));
+            assert_eq!(header.format, ModelFormat::Ggml);
+            assert!(!header.is_valid_whisper());
+            assert!(!header.is_english_only());
+            assert_eq!(header.summary(), "not a whisper model");
+        }
+    }
+
+    #[test]
+    fn truncated_and_foreign_files_are_unrecognized() {
+        let model = ggml(&TINY);
+        // Cut off partway through the hyperparameters.
+        for len in [0, 2, 4, 24, 47] {
+            let header = parse(&model[..len]);
+            assert_eq!(header, ModelHeader::unrecognized(), "{len} bytes");
+        }
+        assert!(parse(&model[..48]).is_valid_whisper());
+
+        let header = parse(b"RIFF\x24\x00\x00\x00WAVEfmt ");
+        assert_eq!(header, ModelHeader::unrecognized());
+        assert_eq!(header.summary(), "not a whisper model");
+    }
+
+    #[test]
+    fn gguf_metadata_is_read() {
+        let mut bytes = gguf(6);
+        push_gguf_str(&mut bytes, "general.architecture", "whisper");
+        push_gguf_u32(&mut bytes, "general.file_type", 8);
+        push_gguf_u32(&mut bytes, "whisper.encoder.block_count", 4);
+        push_gguf_u32(&mut bytes, "whisper.decoder.block_count", 4);
+        // Unrelated metadata is skipped.
+        push_gguf_string(&mut bytes, "general.name");
+        bytes.extend_from_slice(&GGUF_TYPE_STRING.to_le_bytes());
+        push_gguf_string(&mut bytes, "Whisper Tiny");
+        push_gguf_string(&mut bytes, "tokenizer.ggml.tokens");
+        bytes.extend_from_slice(&GGUF_TYPE_ARRAY.to_le_bytes());
+        bytes.extend_from_slice(&GGUF_TYPE_STRING.to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        for token in ["a", "b", "c"] {
+            push_gguf_string(&mut bytes, token);
+        }
+
+        let header = parse(&bytes);
+        assert_eq!(header.format, ModelFormat::Gguf);
+        assert_eq!(header.size, ModelSize::Tiny);
+        assert_eq!(header.quantization, Some(ModelQuantization::Q5_0));
+        assert_eq!(header.vocab_size, Some(3));
+        // whisper.cpp can't load these.
+        assert!(!header.is_valid_whisper());
+        assert_eq!(header.summary(), "GGUF, unsupported");
+    }
+
+    #[test]
+    fn gguf_sizes_need_the_whisper_architecture() {
+        let mut bytes = gguf(2);
+        push_gguf_str(&mut bytes, "general.architecture", "llama");
+        push_gguf_u32(&mut bytes, "llama.encoder.block_count", 4);
+        assert_eq!(parse(&bytes).size, ModelSize::Unknown);
+    }
+
+    #[test]
+    fn truncated_gguf_is_unrecognized() {
+        let mut bytes = gguf(2);
+        push_gguf_str(&mut bytes, "general.architecture", "whisper");
+        bytes.truncate(bytes.len() - 3);
+        assert_eq!(parse(&bytes), ModelHeader::unrecognized());
+    }
+
+    #[test]
+    fn oversized_gguf_strings_are_an_error() {
+        let mut bytes = gguf(1);
+        bytes.extend_from_slice(&(MAX_GGUF_STRING_LEN + 1).to_le_bytes());
+        bytes.extend_from_slice(b"general.architecture");
+        assert!(parse_model_header(&mut &bytes[..]).is_err());
+
+        // Unknown value types can't be skipped either.
+        let mut bytes = gguf(1);
+        push_gguf_string(&mut bytes, "general.mystery");
+        bytes.extend_from_slice(&99u32.to_le_bytes());
+        assert!(parse_model_header(&mut &bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+// Integrity checks for the models in the model bank.
+// Models are hashed (SHA-256) and checked against a manifest of the known (whisper.cpp) models,
+// which is fetched from huggingface and cached in the models directory.
+//
+// NOTE: the results are cached too, keyed by file name; a result only holds while the file's
+// size and modification time are unchanged.
+use crate::controller::model_header::{read_model_header, ModelFormat};
+use crate::utils::errors::RibbleError;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// Lists every file in the repository, with the LFS (SHA-256) object id for the large ones.
+const MANIFEST_URL: &str = "https://huggingface.co/api/models/ggerganov/whisper.cpp/tree/main";
+pub(super) const MANIFEST_FILE: &str = "model_manifest.json";
+pub(super) const INTEGRITY_FILE: &str = "model_integrity.json";
+// The manifest is a nicety: if huggingface can't be reached quickly (e.g. the machine is offline),
+// verification goes ahead with the cached copy, if there is one.
+const MANIFEST_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const MANIFEST_TIMEOUT: Duration = Duration::from_secs(10);
+const HASH_BUFFER_SIZE: usize = 1 << 20;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) enum ModelIntegrity {
+    #[default]
+    Unchecked,
+    // The checksum matches a known model.
+    Verified,
+    // Looks like a ggml model, but there's no known checksum to check it against.
+    Unknown,
+    ChecksumMismatch,
+    // Smaller than the known model; most likely an interrupted download or copy.
+    Truncated,
+    NotGgml,
+    // A GGUF model; whisper.cpp can only load ggml models.
+    Gguf,
+}
+
+impl ModelIntegrity {
+    // Whisper is liable to crash on these.
+    pub(crate) fn is_flagged(&self) -> bool {
+        matches!(
+            self,
+            ModelIntegrity::ChecksumMismatch
+                | ModelIntegrity::Truncated
+                | ModelIntegrity::NotGgml
+                | ModelIntegrity::Gguf
+        )
+    }
+
+    pub(crate) fn description(&self) -> &'static str {
+        match self {
+            ModelIntegrity::Unchecked => "Not yet verified.",
+            ModelIntegrity::Verified => "Verified: the checksum matches a known model.",
+            ModelIntegrity::Unknown => "Not a known model; there is no checksum to verify against.",
+            ModelIntegrity::ChecksumMismatch => {
+                "The checksum does not match the known model. The file may be corrupted."
+            }
+            ModelIntegrity::Truncated => {
+                "The file is smaller than the known model. The download or copy may have been \
+                interrupted."
+            }
+            ModelIntegrity::NotGgml => "The file is not a whisper (ggml) model.",
+            ModelIntegrity::Gguf => {
+                "The file is a GGUF model. Whisper.cpp can only load ggml models."
+            }
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct IntegrityRecord {
+    size: u64,
+    modified: SystemTime,
+    #[serde(default)]
+    sha256: Option<String>,
+    status: ModelIntegrity,
+}
+
+impl IntegrityRecord {
+    // For a model whose checksum was already checked against a trusted source (i.e. a catalog).
+    pub(super) fn verified(path: &Path, sha256: String) -> Result<Self, RibbleError> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+            sha256: Some(sha256),
+            status: ModelIntegrity::Verified,
+        })
+    }
+
+    pub(super) fn status(&self) -> ModelIntegrity {
+        self.status
+    }
+
+    // Whether the file has changed since it was verified.
+    pub(super) fn is_stale(&self, metadata: &std::fs::Metadata) -> bool {
+        metadata.len() != self.size || metadata.modified().ok() != Some(self.modified)
+    }
+}
+
+pub(super) fn load_integrity_records(model_directory: &Path) -> HashMap<String, IntegrityRecord> {
+    read_json(model_directory.join(INTEGRITY_FILE).as_path()).unwrap_or_default()
+}
+
+pub(super) fn save_integrity_records(
+    model_directory: &Path,
+    records: &HashMap<String, IntegrityRecord>,
+) -> Result<(), RibbleError> {
+    let serialized = serde_json::to_string_pretty(records)?;
+    std::fs::write(model_directory.join(INTEGRITY_FILE), serialized)?;
+    Ok(())
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct KnownModel {
+    sha256: String,
+    size: u64,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub(super) struct ModelManifest {
+    // File name -> checksum
+    models: HashMap<String, KnownModel>,
+}
+
+impl ModelManifest {
+    pub(super) fn load(model_directory: &Path) -> Option<Self> {
+        read_json(model_directory.join(MANIFEST_FILE).as_path())
+    }
+
+    pub(super) fn save(&self, model_directory: &Path) -> Result<(), RibbleError> {
+        let serialized = serde_json::to_string_pretty(self)?;
+        std::fs::write(model_directory.join(MANIFEST_FILE), serialized)?;
+        Ok(())
+    }
+
+    pub(super) fn fetch() -> Result<Self, RibbleError> {
+        #[derive(serde::Deserialize)]
+        struct LfsObject {
+            oid: String,
+            size: u64,
+        }
+        #[derive(serde::Deserialize)]
+        struct TreeEntry {
+            path: String,
+            #[serde(default)]
+            lfs: Option<LfsObject>,
+        }
+
+        let client = reqwest::blocking::Client::builder()
+            .connect_timeout(MANIFEST_CONNECT_TIMEOUT)
+            .timeout(MANIFEST_TIMEOUT)
+            .build()?;
+        let entries: Vec<TreeEntry> = client
+            .get(MANIFEST_URL)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let models = entries
+            .into_iter()
+            .filter(|entry| entry.path.ends_with(".bin"))
+            .filter_map(|entry| {
+                let lfs = entry.lfs?;
+                let known = KnownModel {
+                    sha256: lfs.oid,
+                    size: lfs.size,
+                };
+                Some((entry.path, known))
+            })
+            .collect();
+        Ok(Self { models })
+    }
+
+    pub(super) fn contains(&self, file_name: &str) -> bool {
+        self.models.contains_key(file_name)
+    }
+
+    // (File name, checksum, size)
+    pub(super) fn known_models(&self) -> impl Iterator<Item = (&str, &str, u64)> {
+        self.models
+            .iter()
+            .map(|(file_name, known)| (file_name.as_str(), known.sha256.as_str(), known.size))
+    }
+}
+
+// Hashes the file and checks it against the manifest.
+// The file is matched by name first, then (in case it was renamed) by checksum.
+pub(super) fn verify_model_file<F, A>(
+    path: &Path,
+    manifest: Option<&ModelManifest>,
+    mut on_progress: F,
+    should_abort: A,
+) -> Result<IntegrityRecord, RibbleError>
+where
+    F: FnMut(u64),
+    A: Fn() -> bool,
+{
+    let metadata = std::fs::metadata(path)?;
+    let size = metadata.len();
+    let modified = metadata.modified()?;
+    let record = |sha256, status| IntegrityRecord {
+        size,
+        modified,
+        sha256,
+        status,
+    };
+
+    let known = manifest.and_then(|manifest| {
+        let file_name = path.file_name()?.to_str()?;
+        manifest.models.get(file_name)
+    });
+    if known.is_some_and(|known| size < known.size) {
+        return Ok(record(None, ModelIntegrity::Truncated));
+    }
+
+    // NOTE: this defers to the header parser, so that the model list and the integrity check
+    // never disagree on what is (or isn't) a whisper model.
+    let header = read_model_header(path)?;
+    if !header.is_valid_whisper() {
+        let status = match header.format() {
+            ModelFormat::Gguf => ModelIntegrity::Gguf,
+            ModelFormat::Ggml | ModelFormat::Unrecognized => ModelIntegrity::NotGgml,
+        };
+        return Ok(record(None, status));
+    }
+
+    let sha256 = hash_file(path, &mut on_progress, should_abort)?;
+    let status = match known {
+        Some(known) if known.sha256.eq_ignore_ascii_case(&sha256) => ModelIntegrity::Verified,
+        Some(_) => ModelIntegrity::ChecksumMismatch,
+        None if manifest.is_some_and(|manifest| {
+            manifest
+                .models
+                .values()
+                .any(|known| known.sha256.eq_ignore_ascii_case(&sha256))
+        }) =>
+        {
+            ModelIntegrity::Verified
+        }
+        None => ModelIntegrity::Unknown,
+    };
+    Ok(record(Some(sha256), status))
+}
+
+fn hash_file<F, A>(path: &Path, on_progress: &mut F, should_abort: A) -> Result<String, RibbleError>
+where
+    F: FnMut(u64),
+    A: Fn() -> bool,
+{
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        if should_abort() {
+            return Err(RibbleError::Core(
+                "Model verification cancelled.".to_string(),
+            ));
+        }
+        let num_read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(num_read) => num_read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buffer[..num_read]);
+        on_progress(num_read as u64);
+    }
+
+    let digest = hasher.finalize();
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let serialized = match std::fs::read_to_string(path) {
+        Ok(serialized) => serialized,
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                log::warn!("Failed to read: {}\nError: {e}", path.display());
+            }
+            return None;
+        }
+    };
+    serde_json::from_str(&serialized)
+        .map_err(|e| log::warn!("Failed to parse: {}\nError: {e}", path.display()))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // "ggml" (little-endian)
+    const GGML_MAGIC_BYTES: [u8; 4] = 0x67676d6cu32.to_le_bytes();
+
+    // A base.en (f16) header, followed by some stand-in weights.
+    fn ggml_model() -> Vec<u8> {
+        let hyperparameters: [i32; 11] = [51864, 1500, 512, 8, 6, 448, 512, 8, 6, 80, 1];
+        let mut bytes = GGML_MAGIC_BYTES.to_vec();
+        for value in hyperparameters {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend((0..4096u32).map(|i| (i % 251) as u8));
+        bytes
+    }
+
+    fn sha256(bytes: &[u8]) -> String {
+        Sha256::digest(bytes)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn manifest(file_name: &str, bytes: &[u8]) -> ModelManifest {
+        let known = KnownModel {
+            sha256: sha256(bytes),
+            size: bytes.len() as u64,
+        };
+        ModelManifest {
+            models: HashMap::from([(file_name.to_string(), known)]),
+        }
+    }
+
+    fn verify(
+        directory: &TempDir,
+        file_name: &str,
+        bytes: &[u8],
+        manifest: Option<&ModelManifest>,
+    ) -> ModelIntegrity {
+        let path = directory.path().join(file_name);
+        std::fs::write(path.as_path(), bytes).unwrap();
+        verify_model_file(path.as_path(), manifest, |_| {}, || false)
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn a_known_model_is_verified() {
+        let directory = tempfile::tempdir().unwrap();
+        let model = ggml_model();
+        let manifest = manifest("ggml-base.en.bin", &model);
+        let status = verify(&directory, "ggml-base.en.bin", &model, Some(&manifest));
+        assert_eq!(status, ModelIntegrity::Verified);
+        // Renamed models are matched by their checksum.
+        let status = verify(&directory, "my-model.bin", &model, Some(&manifest));
+        assert_eq!(status, ModelIntegrity::Verified);
+    }
+
+    #[test]
+    fn a_corrupted_model_is_a_checksum_mismatch() {
+        let directory = tempfile::tempdir().unwrap();
+        let model = ggml_model();
+        let manifest = manifest("ggml-base.en.bin", &model);
+        let mut corrupted = model.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        let status = verify(&directory, "ggml-base.en.bin", &corrupted, Some(&manifest));
+        assert_eq!(status, ModelIntegrity::ChecksumMismatch);
+        assert!(status.is_flagged());
+    }
+
+    #[test]
+    fn a_short_model_is_truncated() {
+        let directory = tempfile::tempdir().unwrap();
+        let model = ggml_model();
+        let manifest = manifest("ggml-base.en.bin", &model);
+        let status = verify(
+            &directory,
+            "ggml-base.en.bin",
+            &model[..1024],
+            Some(&manifest),
+        );
+        assert_eq!(status, ModelIntegrity::Truncated);
+        // Even if it's cut off before the end of the header.
+        let status = verify(&directory, "ggml-base.en.bin", &model[..2], Some(&manifest));
+        assert_eq!(status, ModelIntegrity::Truncated);
+        assert!(status.is_flagged());
+    }
+
+    #[test]
+    fn files_that_arent_whisper_models_are_flagged() {
+        let directory = tempfile::tempdir().unwrap();
+        let status = verify(&directory, "notes.bin", b"not a model at all", None);
+        assert_eq!(status, ModelIntegrity::NotGgml);
+        assert_eq!(
+            verify(&directory, "empty.bin", &[], None),
+            ModelIntegrity::NotGgml
+        );
+
+        // The ggml magic alone isn't enough; the header has to make sense too.
+        let mut bad_header = ggml_model();
+        bad_header[40..44].copy_from_slice(&7i32.to_le_bytes());
+        let status = verify(&directory, "bad-mels.bin", &bad_header, None);
+        assert_eq!(status, ModelIntegrity::NotGgml);
+
+        // GGUF: version 3, no tensors, no metadata.
+        let mut gguf = b"GGUF".to_vec();
+        gguf.extend_from_slice(&3u32.to_le_bytes());
+        gguf.extend_from_slice(&0u64.to_le_bytes());
+        gguf.extend_from_slice(&0u64.to_le_bytes());
+        let status = verify(&directory, "model.gguf", &gguf, None);
+        assert_eq!(status, ModelIntegrity::Gguf);
+        assert!(status.is_flagged());
+    }
+
+    #[test]
+    fn an_unlisted_model_is_unknown() {
+        let directory = tempfile::tempdir().unwrap();
+        let model = ggml_model();
+        assert_eq!(
+            verify(&directory, "custom.bin", &model, None),
+            ModelIntegrity::Unknown
+        );
+        let manifest = manifest("ggml-tiny.bin", b"some other model");
+        let status = verify(&directory, "custom.bin", &model, Some(&manifest));
+        assert_eq!(status, ModelIntegrity::Unknown);
+        assert!(!status.is_flagged());
+    }
+
+    #[test]
+    fn the_record_tracks_the_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("ggml-base.en.bin");
+        let model = ggml_model();
+        std::fs::write(path.as_path(), &model).unwrap();
+
+        let mut num_hashed = 0;
+        let record =
+            verify_model_file(path.as_path(), None, |delta| num_hashed += delta, || false).unwrap();
+        assert_eq!(num_hashed, model.len() as u64);
+        assert_eq!(record.sha256.as_deref(), Some(sha256(&model).as_str()));
+        assert!(!record.is_stale(&std::fs::metadata(path.as_path()).unwrap()));
+
+        std::fs::write(path.as_path(), &model[..1024]).unwrap();
+        assert!(record.is_stale(&std::fs::metadata(path.as_path()).unwrap()));
+    }
+
+    #[test]
+    fn verification_can_be_cancelled() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("ggml-base.en.bin");
+        std::fs::write(path.as_path(), ggml_model()).unwrap();
+        assert!(verify_model_file(path.as_path(), None, |_| {}, || true).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::controller::recording_segments::{load_normalized_segments, segments_num_frames};
+use crate::controller::transcript::{TranscriptSegment, whisper_samples_to_duration};
+use crate::controller::{
+    Bus, ConsoleMessage, Progress, ProgressMessage, RibbleMessage, WorkRequest,
+};
+use crate::utils::errors::RibbleError;
+use arc_swap::ArcSwap;
+use ribble_whisper::audio::WhisperAudioSample;
+use ribble_whisper::sdl2::audio::AudioCallback;
+use ribble_whisper::transcriber::WHISPER_SAMPLE_RATE;
+use ribble_whisper::utils::{Sender, get_channel};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+// NOTE: audio is loaded the same way it is for transcription (16kHz mono). This keeps the
+// playhead on the same clock as the transcript segments, at the cost of some playback quality.
+pub(crate) const PLAYBACK_SAMPLE_RATE: i32 = WHISPER_SAMPLE_RATE as i32;
+
+struct PlaybackSource {
+    path: Arc<Path>,
+    samples: Arc<[f32]>,
+}
+
+struct PlaybackState {
+    source: ArcSwap<Option<PlaybackSource>>,
+    // The playhead, in samples.
+    position: AtomicUsize,
+    playing: AtomicBool,
+    loading: AtomicBool,
+}
+
+impl PlaybackState {
+    fn new() -> Self {
+        Self {
+            source: ArcSwap::new(Arc::new(None)),
+            position: AtomicUsize::new(0),
+            playing: AtomicBool::new(false),
+            loading: AtomicBool::new(false),
+        }
+    }
+
+    fn num_samples(&self) -> usize {
+        self.source
+            .load()
+            .as_ref()
+            .as_ref()
+            .map(|source| source.samples.len())
+            .unwrap_or_default()
+    }
+}
+
+// The audio output device (which has to live on the main thread) pulls samples through this.
+#[derive(Clone)]
+pub(crate) struct PlaybackHandle {
+    inner: Arc<PlaybackState>,
+}
+
+impl PlaybackHandle {
+    pub(crate) fn playing(&self) -> bool {
+        self.inner.playing.load(Ordering::Acquire)
+    }
+}
+
+impl AudioCallback for PlaybackHandle {
+    type Channel = f32;
+
+    // NOTE: this runs on the audio device thread; it must not block.
+    fn callback(&mut self, out: &mut [f32]) {
+        let source = self.inner.source.load();
+        let samples = match source.as_ref() {
+            Some(source) if self.playing() => &source.samples,
+            _ => {
+                out.fill(0.0);
+                return;
+            }
+        };
+
+        let start = self.inner.position.load(Ordering::Acquire);
+        let end = copy_from_playhead(samples, start, out);
+        advance_playhead(&self.inner.position, start, end);
+
+        if end == samples.len() {
+            self.inner.playing.store(false, Ordering::Release);
+        }
+    }
+}
+
+// Plays back the current audio file or a cached recording so that the transcript can be checked
+// against it.
+pub(super) struct PlaybackEngine {
+    inner: Arc<PlaybackState>,
+    progress_message_sender: Sender<ProgressMessage>,
+    work_request_sender: Sender<WorkRequest>,
+}
+
+impl PlaybackEngine {
+    pub(super) fn new(bus: &Bus) -> Self {
+        Self {
+            inner: Arc::new(PlaybackState::new()),
+            progress_message_sender: bus.progress_message_sender(),
+            work_request_sender: bus.work_request_sender(),
+        }
+    }
+
+    pub(super) fn playback_handle(&self) -> PlaybackHandle {
+        PlaybackHandle {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    pub(super) fn playback_loading(&self) -> bool {
+        self.inner.loading.load(Ordering::Acquire)
+    }
+
+    pub(super) fn playback_playing(&self) -> bool {
+        self.inner.playing.load(Ordering::Acquire)
+    }
+
+    pub(super) fn playback_path(&self) -> Option<PathBuf> {
+        self.inner
+            .source
+            .load()
+            .as_ref()
+            .as_ref()
+            .map(|source| source.path.to_path_buf())
+    }
+
+    pub(super) fn playback_position(&self) -> Duration {
+        let position = self.inner.position.load(Ordering::Acquire) as u64;
+        whisper_samples_to_duration(position)
+    }
+
+    pub(super) fn playback_duration(&self) -> Duration {
+        whisper_samples_to_duration(self.inner.num_samples() as u64)
+    }
+
+    pub(super) fn play(&self) {
+        let num_samples = self.inner.num_samples();
+        if num_samples == 0 {
+            return;
+        }
+        // Start over if the last play ran to the end.
+        if self.inner.position.load(Ordering::Acquire) >= num_samples {
+            self.inner.position.store(0, Ordering::Release);
+        }
+        self.inner.playing.store(true, Ordering::Release);
+    }
+
+    pub(super) fn pause(&self) {
+        self.inner.playing.store(false, Ordering::Release);
+    }
+
+    pub(super) fn seek(&self, position: Duration) {
+        let sample = seek_sample(position, self.inner.num_samples());
+        self.inner.position.store(sample, Ordering::Release);
+    }
+
+    // NOTE: a segmented recording is played back as one file; the first segment names it.
+    pub(super) fn load_audio(&self, audio_files: Vec<PathBuf>) {
+        let Some(audio_file_path) = audio_files.first().cloned() else {
+            log::warn!("No audio to load for playback.");
+            return;
+        };
+        if self.inner.loading.swap(true, Ordering::AcqRel) {
+            log::warn!("Playback audio is already loading.");
+            return;
+        }
+        self.pause();
+
+        let thread_inner = Arc::clone(&self.inner);
+        let progress_message_sender = self.progress_message_sender.clone();
+        let worker = std::thread::spawn(move || {
+            let result = load_playback_audio(&audio_files, &progress_message_sender);
+            let message = match result {
+                Ok(samples) => {
+                    let source = PlaybackSource {
+                        path: Arc::from(audio_file_path.as_path()),
+                        samples,
+                    };
+                    thread_inner.source.store(Arc::new(Some(source)));
+                    thread_inner.position.store(0, Ordering::Release);
+                    Ok(RibbleMessage::Console(ConsoleMessage::Status(format!(
+                        "Loaded for playback: {}",
+                        audio_file_path.display()
+                    ))))
+                }
+                Err(e) => Err(e),
+            };
+            thread_inner.loading.store(false, Ordering::Release);
+            message
+        });
+
+        let request = WorkRequest::Short(worker);
+        if let Err(e) = self.work_request_sender.try_send(request) {
+            log::warn!(
+                "Error sending playback work request. Channel may be too small, or worker engine missing.\n\
+                Error: {e}\n\
+                Error source: {:#?}",
+                e.source()
+            );
+        }
+    }
+}
+
+// Fills out with the samples from the playhead on (and silence past the end).
+// Returns where the playhead ends up.
+fn copy_from_playhead(samples: &[f32], playhead: usize, out: &mut [f32]) -> usize {
+    let start = playhead.min(samples.len());
+    let end = (start + out.len()).min(samples.len());
+    let num_copied = end - start;
+    out[..num_copied].copy_from_slice(&samples[start..end]);
+    out[num_copied..].fill(0.0);
+    end
+}
+
+// NOTE: if the UI seeked while the callback was copying, the seek wins.
+fn advance_playhead(playhead: &AtomicUsize, from: usize, to: usize) {
+    let _ = playhead.compare_exchange(from, to, Ordering::AcqRel, Ordering::Acquire);
+}
+
+// The sample to seek to, clamped to the end of the audio.
+fn seek_sample(position: Duration, num_samples: usize) -> usize {
+    let sample = (position.as_secs_f64() * WHISPER_SAMPLE_RATE) as usize;
+    sample.min(num_samples)
+}
+
+// The segment under the playhead, if any.
+// NOTE: transcript segments are in order and don't overlap.
+pub(crate) fn segment_at(segments: &[TranscriptSegment], position: Duration) -> Option<usize> {
+    let index = segments
+        .partition_point(|segment| segment.start() <= position)
+        .checked_sub(1)?;
+    (position < segments[index].end()).then_some(index)
+}
+
+fn load_playback_audio(
+    audio_files: &[PathBuf],
+    progress_message_sender: &Sender<ProgressMessage>,
+) -> Result<Arc<[f32]>, RibbleError> {
+    let n_frames = segments_num_frames(audio_files)?;
+    let (id_sender, id_receiver) = get_channel(1);
+    let progress_message = ProgressMessage::Request {
+        job: Progress::new_determinate("Loading playback audio", n_frames),
+        id_return_sender: id_sender,
+    };
+    if let Err(e) = progress_message_sender.send(progress_message) {
+        log::warn!(
+            "Progress engine closed, cannot send playback load audio job.\nError source: {:#?}",
+            e.source()
+        );
+    }
+    let progress_id = id_receiver.recv().ok();
+
+    let load_audio_callback = |progress: usize| {
+        if let Some(id) = progress_id {
+            let update_progress_message = ProgressMessage::Increment {
+                job_id: id,
+                delta: progress as u64,
+            };
+            if let Err(e) = progress_message_sender.send(update_progress_message) {
+                log::warn!(
+                    "Progress channel closed, cannot send playback increment progress message.\n\
+                    Error source: {:#?}",
+                    e.source()
+                );
+            }
+        }
+    };
+
+    let loaded_audio = load_normalized_segments(audio_files, &load_audio_callback);
+
+    if let Some(id) = progress_id
+        && let Err(e) = progress_message_sender.send(ProgressMessage::Remove { job_id: id })
+    {
+        log::warn!(
+            "Progress channel closed, cannot remove playback progress job.\nError source: {:#?}",
+            e.source()
+        );
+    }
+
+    match loaded_audio? {
+        WhisperAudioSample::F32(audio) => Ok(audio),
+        WhisperAudioSample::I16(_) => {
+            unreachable!("Loading normalized for whisper should never return integer audio.")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::test_harness::CapturedBus;
+    use crate::controller::transcript::SegmentState;
+
+    fn load_samples(engine: &PlaybackEngine, samples: Vec<f32>) {
+        let source = PlaybackSource {
+            path: Arc::from(Path::new("audio.wav")),
+            samples: Arc::from(samples),
+        };
+        engine.inner.source.store(Arc::new(Some(source)));
+        engine.inner.position.store(0, Ordering::Release);
+    }
+
+    fn segment(start_ms: u64, end_ms: u64) -> TranscriptSegment {
+        TranscriptSegment::new(
+            Duration::from_millis(start_ms),
+            Duration::from_millis(end_ms),
+            "Words.",
+            SegmentState::Confirmed,
+        )
+    }
+
+    #[test]
+    fn the_playhead_copies_and_pads_with_silence() {
+        let samples = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let mut out = [-1.0; 3];
+
+        assert_eq!(copy_from_playhead(&samples, 1, &mut out), 4);
+        assert_eq!(out, [2.0, 3.0, 4.0]);
+
+        assert_eq!(copy_from_playhead(&samples, 4, &mut out), 5);
+        assert_eq!(out, [5.0, 0.0, 0.0]);
+
+        // e.g. the audio was swapped for something shorter.
+        assert_eq!(copy_from_playhead(&samples, 100, &mut out), 5);
+        assert_eq!(out, [0.0; 3]);
+    }
+
+    #[test]
+    fn a_seek_during_the_callback_is_kept() {
+        let playhead = AtomicUsize::new(10);
+        advance_playhead(&playhead, 10, 20);
+        assert_eq!(playhead.load(Ordering::Acquire), 20);
+
+        // The UI seeked to 5 while the callback was copying from 20.
+        playhead.store(5, Ordering::Release);
+        advance_playhead(&playhead, 20, 30);
+        assert_eq!(playhead.load(Ordering::Acquire), 5);
+    }
+
+    #[test]
+    fn seeks_are_clamped_to_the_audio() {
+        let num_samples = WHISPER_SAMPLE_RATE as usize * 2;
+        assert_eq!(seek_sample(Duration::ZERO, num_samples), 0);
+        assert_eq!(
+            seek_sample(Duration::from_millis(500), num_samples),
+            WHISPER_SAMPLE_RATE as usize / 2
+        );
+        assert_eq!(
+            seek_sample(Duration::from_secs(60), num_samples),
+            num_samples
+        );
+    }
+
+    #[test]
+    fn the_segment_under_the_playhead_is_found() {
+        // There's a gap between the second and third segments.
+        let segments = [segment(0, 1000), segment(1000, 2500), segment(3000, 4000)];
+        let at = |ms| segment_at(&segments, Duration::from_millis(ms));
+
+        assert_eq!(at(0), Some(0));
+        assert_eq!(at(999), Some(0));
+        assert_eq!(at(1000), Some(1));
+        assert_eq!(at(2700), None);
+        assert_eq!(at(3000), Some(2));
+        assert_eq!(at(4000), None);
+        assert_eq!(segment_at(&[], Duration::ZERO), None);
+    }
+
+    #[test]
+    fn playback_stops_at_the_end_and_restarts_from_the_top() {
+        let bus = CapturedBus::new();
+        let engine = PlaybackEngine::new(bus.bus());
+        let mut handle = engine.playback_handle();
+        load_samples(&engine, vec![0.5; 6]);
+        let mut out = [0.0; 4];
+
+        // Paused: silence, and the playhead doesn't move.
+        out.fill(1.0);
+        handle.callback(&mut out);
+        assert_eq!(out, [0.0; 4]);
+        assert_eq!(engine.inner.position.load(Ordering::Acquire), 0);
+
+        engine.play();
+        handle.callback(&mut out);
+        assert_eq!(out, [0.5; 4]);
+        assert!(engine.playback_playing());
+
+        handle.callback(&mut out);
+        assert_eq!(out, [0.5, 0.5, 0.0, 0.0]);
+        assert!(!engine.playback_playing());
+
+        engine.play();
+        assert_eq!(engine.inner.position.load(Ordering::Acquire), 0);
+        assert!(engine.playback_playing());
+    }
+}
@@ -1,14 +1,17 @@
 use crate::controller::VisualizerPacket;
 use crate::controller::WriteRequest;
 use crate::controller::{
-    Bus, ConsoleMessage, Progress, ProgressMessage, RibbleMessage, UTILITY_QUEUE_SIZE, WorkRequest,
+    AUDIO_FEED_TIMEOUT, Bus, ConsoleMessage, Progress, ProgressMessage, RibbleMessage,
+    UTILITY_QUEUE_SIZE, WorkRequest,
 };
 use crate::utils::errors::RibbleError;
+use crate::utils::noise_suppression::NoiseSuppressionConfigs;
 use crate::utils::recorder_configs::{
-    AtomicRibbleExportFormat, RibbleExportFormat, RibbleRecordingConfigs,
+    AtomicRibbleExportFormat, AtomicRibbleOpusBitrate, RibbleExportFormat, RibbleOpusBitrate,
+    RibbleRecordingConfigs,
 };
 use arc_swap::ArcSwap;
-use crossbeam::channel::TrySendError;
+use crossbeam::channel::{RecvTimeoutError, TrySendError};
 use ribble_whisper::audio::audio_backend::AudioBackend;
 use ribble_whisper::audio::microphone::MicCapture;
 use ribble_whisper::audio::recorder::ArcChannelSink;
@@ -21,6 +24,7 @@ struct RecorderEngineState {
     recorder_running: Arc<AtomicBool>,
     recorder_configs: ArcSwap<RibbleRecordingConfigs>,
     export_format: AtomicRibbleExportFormat,
+    opus_bitrate: AtomicRibbleOpusBitrate,
     progress_message_sender: Sender<ProgressMessage>,
     write_request_sender: Sender<WriteRequest>,
     visualizer_sample_sender: Sender<VisualizerPacket>,
@@ -30,6 +34,7 @@ impl RecorderEngineState {
     fn new(
         starting_configs: Option<RibbleRecordingConfigs>,
         starting_export_format: Option<RibbleExportFormat>,
+        starting_opus_bitrate: Option<RibbleOpusBitrate>,
         bus: &Bus,
     ) -> Self {
         Self {
@@ -38,6 +43,7 @@ impl RecorderEngineState {
             export_format: AtomicRibbleExportFormat::new(
                 starting_export_format.unwrap_or_default(),
             ),
+            opus_bitrate: AtomicRibbleOpusBitrate::new(starting_opus_bitrate.unwrap_or_default()),
             progress_message_sender: bus.progress_message_sender(),
             write_request_sender: bus.write_request_sender(),
             visualizer_sample_sender: bus.visualizer_sample_sender(),
@@ -56,7 +62,11 @@ impl RecorderEngineState {
         }
     }
 
-    fn run_recorder_loop<A>(&self, audio_backend: &A) -> Result<(), RibbleError>
+    fn run_recorder_loop<A>(
+        &self,
+        audio_backend: &A,
+        noise_suppression: NoiseSuppressionConfigs,
+    ) -> Result<(), RibbleError>
     where
         A: AudioBackend<ArcChannelSink<f32>> + Send + Sync,
     {
@@ -88,9 +98,10 @@ impl RecorderEngineState {
 
         let (audio_sender, audio_receiver) = get_channel::<Arc<[f32]>>(UTILITY_QUEUE_SIZE);
         let sink = ArcChannelSink::new(audio_sender);
-        let spec = (*self.recorder_configs.load_full()).into();
+        let spec = (*self.recorder_configs.load_full()).clone().into();
         let mic = audio_backend.open_capture(spec, sink).inspect_err(|_e| {
             self.cleanup_remove_progress_job(setup_id);
+            self.recorder_running.store(false, Ordering::Release);
         })?;
         let (write_sender, write_receiver) = get_channel::<Arc<[f32]>>(UTILITY_QUEUE_SIZE);
         let confirmed_specs = RibbleRecordingConfigs::from_mic_capture(&mic);
@@ -101,11 +112,16 @@ impl RecorderEngineState {
         if self.write_request_sender.send(request).is_err() {
             let ribble_error =
                 RibbleError::Core("Writing engine no longer receiving write requests.".to_string());
+            self.cleanup_remove_progress_job(setup_id);
             self.recorder_running.store(false, Ordering::Release);
+            audio_backend.close_capture(mic);
             return Err(ribble_error);
         }
 
         let sample_rate = mic.sample_rate();
+        let mut noise_suppressor = (noise_suppression.use_recording()
+            && !noise_suppression.no_suppression())
+        .then(|| noise_suppression.build_noise_suppressor(sample_rate, mic.channels() as usize));
 
         if let Some(id) = setup_id {
             let remove_message = ProgressMessage::Remove { job_id: id };
@@ -119,8 +135,17 @@ impl RecorderEngineState {
 
         mic.play();
         while self.recorder_running.load(Ordering::Acquire) {
-            match audio_receiver.recv() {
+            match audio_receiver.recv_timeout(AUDIO_FEED_TIMEOUT) {
                 Ok(audio) => {
+                    let audio = match noise_suppressor.as_mut() {
+                        Some(suppressor) => Arc::from(suppressor.process(&audio)),
+                        None => audio,
+                    };
+                    // The denoiser holds onto audio until it has a full frame.
+                    if audio.is_empty() {
+                        continue;
+                    }
+
                     if let Err(TrySendError::Disconnected(_)) =
                         write_sender.try_send(Arc::clone(&audio))
                     {
@@ -143,9 +168,10 @@ impl RecorderEngineState {
                         );
                     }
                 }
+                Err(RecvTimeoutError::Timeout) => continue,
                 // This only happens if the audio callback has been dropped and there's no more
                 // audio to process.
-                Err(_) => {
+                Err(RecvTimeoutError::Disconnected) => {
                     log::info!(
                         "Audio callback closed, causing sending channel drop. Recording should be complete."
                     );
@@ -156,6 +182,11 @@ impl RecorderEngineState {
         mic.pause();
         audio_backend.close_capture(mic);
 
+        // Write out whatever is left in the denoiser.
+        if let Some(mut suppressor) = noise_suppressor {
+            let _ = write_sender.try_send(Arc::from(suppressor.flush()));
+        }
+
         Ok(())
     }
 }
@@ -169,20 +200,25 @@ impl RecorderEngine {
     pub(super) fn new(
         starting_configs: Option<RibbleRecordingConfigs>,
         starting_export_format: Option<RibbleExportFormat>,
+        starting_opus_bitrate: Option<RibbleOpusBitrate>,
         bus: &Bus,
     ) -> Self {
         Self {
             inner: Arc::new(RecorderEngineState::new(
                 starting_configs,
                 starting_export_format,
+                starting_opus_bitrate,
                 bus,
             )),
             work_request_sender: bus.work_request_sender(),
         }
     }
 
-    pub(super) fn start_recording<A>(&self, audio_backend: Arc<A>)
-    where
+    pub(super) fn start_recording<A>(
+        &self,
+        audio_backend: Arc<A>,
+        noise_suppression: NoiseSuppressionConfigs,
+    ) where
         A: AudioBackend<ArcChannelSink<f32>> + Send + Sync + 'static,
     {
         // Set the state flag so that the UI can update.
@@ -191,7 +227,7 @@ impl RecorderEngine {
         let thread_inner = Arc::clone(&self.inner);
         // Spawn a (long job) thread and send it off to the worker to join it.
         let worker = std::thread::spawn(move || {
-            thread_inner.run_recorder_loop(audio_backend.as_ref())?;
+            thread_inner.run_recorder_loop(audio_backend.as_ref(), noise_suppression)?;
             let message = String::from("Recording finished!");
             let console_message = ConsoleMessage::Status(message);
             Ok(RibbleMessage::Console(console_message))
@@ -234,4 +270,129 @@ impl RecorderEngine {
             .export_format
             .store(export_format, Ordering::Release);
     }
+    pub(super) fn read_opus_bitrate(&self) -> RibbleOpusBitrate {
+        self.inner.opus_bitrate.load(Ordering::Acquire)
+    }
+    pub(super) fn write_opus_bitrate(&self, opus_bitrate: RibbleOpusBitrate) {
+        self.inner.opus_bitrate.store(opus_bitrate, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::test_harness::{
+        BusRecord, CapturedBus, ScriptedAudio, ScriptedBackend, TEST_TIMEOUT, drain_write_job,
+        scripted_sample,
+    };
+    use crate::utils::recorder_configs::{RibbleChannels, RibblePeriod, RibbleSampleRate};
+
+    const CHUNK_LEN: usize = 1024;
+
+    // Waits for the worker to report the recording, then shuts the bus down.
+    fn finish_recording(mut bus: CapturedBus) -> BusRecord {
+        let message = bus.next_console_message(TEST_TIMEOUT).map(ConsoleMessage::message);
+        assert!(
+            message.is_some(),
+            "Recording not reported within {TEST_TIMEOUT:?}; it's likely hung."
+        );
+        bus.finish()
+    }
+
+    #[test]
+    fn recording_ends_when_the_source_closes() {
+        let bus = CapturedBus::new();
+        let engine = RecorderEngine::new(None, None, None, bus.bus());
+        let backend = Arc::new(ScriptedBackend::new(ScriptedAudio::new(8, CHUNK_LEN)));
+        engine.start_recording(Arc::clone(&backend), NoiseSuppressionConfigs::default());
+
+        let record = finish_recording(bus);
+        assert!(!engine.recorder_running());
+        assert_eq!(backend.num_opened(), 1);
+        assert_eq!(backend.num_closed(), 1);
+        assert_eq!(record.console_messages.len(), 1);
+        assert_eq!(record.console_messages[0].message(), "Recording finished!");
+        assert!(record.unfinished_progress_jobs().is_empty());
+        assert_eq!(record.num_visualizer_packets, 8);
+        assert_eq!(record.write_jobs.len(), 1);
+
+        let (receiver, spec) = &record.write_jobs[0];
+        assert_eq!(spec.sample_rate(), RibbleSampleRate::Medium);
+        assert_eq!(spec.num_channels(), RibbleChannels::Mono);
+        assert_eq!(spec.period(), RibblePeriod::Medium);
+
+        let audio = drain_write_job(receiver, TEST_TIMEOUT);
+        assert_eq!(audio.len(), 8);
+        for (i, chunk) in audio.iter().enumerate() {
+            assert_eq!(chunk.len(), CHUNK_LEN);
+            assert!(chunk.iter().all(|sample| *sample == scripted_sample(i)));
+        }
+    }
+
+    #[test]
+    fn recording_stops_when_asked() {
+        let bus = CapturedBus::new();
+        let engine = RecorderEngine::new(None, None, None, bus.bus());
+        let script = ScriptedAudio::new(4, CHUNK_LEN).then_silence();
+        let backend = Arc::new(ScriptedBackend::new(script));
+        engine.start_recording(Arc::clone(&backend), NoiseSuppressionConfigs::default());
+
+        let (receiver, _) = bus
+            .next_write_job(TEST_TIMEOUT)
+            .expect("No write job was requested.");
+        let mut audio = vec![];
+        for _ in 0..6 {
+            audio.push(receiver.recv_timeout(TEST_TIMEOUT).expect("Recording stalled."));
+        }
+        assert!(engine.recorder_running());
+        engine.stop_recording();
+
+        let record = finish_recording(bus);
+        assert_eq!(backend.num_opened(), 1);
+        assert_eq!(backend.num_closed(), 1);
+        assert_eq!(record.num_console_errors(), 0);
+        assert!(record.unfinished_progress_jobs().is_empty());
+
+        audio.extend(drain_write_job(&receiver, TEST_TIMEOUT));
+        for (i, chunk) in audio.iter().enumerate() {
+            let expected = if i < 4 { scripted_sample(i) } else { 0.0 };
+            assert!(chunk.iter().all(|sample| *sample == expected));
+        }
+    }
+
+    #[test]
+    fn recording_stops_when_the_writer_goes_away() {
+        let bus = CapturedBus::new();
+        let engine = RecorderEngine::new(None, None, None, bus.bus());
+        let backend = Arc::new(ScriptedBackend::new(
+            ScriptedAudio::new(0, CHUNK_LEN).then_silence(),
+        ));
+        engine.start_recording(Arc::clone(&backend), NoiseSuppressionConfigs::default());
+
+        let write_job = bus.next_write_job(TEST_TIMEOUT);
+        assert!(write_job.is_some(), "No write job was requested.");
+        drop(write_job);
+
+        let record = finish_recording(bus);
+        assert!(!engine.recorder_running());
+        assert_eq!(backend.num_closed(), 1);
+        assert_eq!(record.num_console_errors(), 0);
+        assert!(record.unfinished_progress_jobs().is_empty());
+    }
+
+    #[test]
+    fn failing_to_open_the_device_resets_the_recorder() {
+        let bus = CapturedBus::new();
+        let engine = RecorderEngine::new(None, None, None, bus.bus());
+        let backend = Arc::new(ScriptedBackend::failing());
+        engine.start_recording(Arc::clone(&backend), NoiseSuppressionConfigs::default());
+
+        let record = finish_recording(bus);
+        assert!(!engine.recorder_running());
+        assert_eq!(backend.num_opened(), 0);
+        assert_eq!(backend.num_closed(), 0);
+        assert_eq!(record.num_console_errors(), 1);
+        assert!(record.write_jobs.is_empty());
+        assert!(record.unfinished_progress_jobs().is_empty());
+    }
 }
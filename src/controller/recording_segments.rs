@@ -0,0 +1,410 @@
+// Long recordings are written as a sequence of numbered segment files so that:
+//  - a single file never grows past what a wav header can describe (4 GiB), and
+//  - a crash only puts the segment being written at risk.
+//
+// Segment file names: "tmp_recording_<ticket_no>.wav", "tmp_recording_<ticket_no>_part<N>.wav", ...
+// The first segment's file name is the recording's key; the rest are only known to the writer.
+// Segments are never joined back together: everything that reads a recording back (export,
+// playback, re-transcription) takes the whole segment list and reads it end to end.
+//
+// NOTE: the wav header is also rewritten periodically while recording, so a crash loses (at most)
+// the last few seconds. If it's still inconsistent, repair_wav_header() recomputes it from the
+// file length.
+use crate::utils::errors::RibbleError;
+use hound::{WavSpec, WavWriter};
+use ribble_whisper::audio::loading::{audio_file_num_frames, load_normalized_audio_file};
+use ribble_whisper::audio::WhisperAudioSample;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub(super) const SEGMENT_FILE_PREFIX: &str = "tmp_recording";
+pub(super) const SEGMENT_FILE_EXTENSION: &str = "wav";
+const SEGMENT_PART_SEPARATOR: &str = "_part";
+
+// Whichever comes first.
+const MAX_SEGMENT_SECS: usize = 30 * 60;
+const MAX_SEGMENT_BYTES: usize = 1 << 30;
+// How often the header is brought up to date while recording.
+const HEADER_FLUSH_SECS: usize = 5;
+// The most a single wav file can describe.
+// (The RIFF chunk size is a u32; leave some room for the header.)
+const MAX_WAV_DATA_BYTES: u64 = u32::MAX as u64 - (1 << 20);
+
+pub(super) fn segment_file_name(ticket_no: usize, part_no: usize) -> String {
+    if part_no == 0 {
+        format!("{SEGMENT_FILE_PREFIX}_{ticket_no}.{SEGMENT_FILE_EXTENSION}")
+    } else {
+        format!(
+            "{SEGMENT_FILE_PREFIX}_{ticket_no}{SEGMENT_PART_SEPARATOR}{part_no}.{SEGMENT_FILE_EXTENSION}"
+        )
+    }
+}
+
+// "tmp_recording_<ticket_no>.wav" -> (ticket_no, 0)
+// "tmp_recording_<ticket_no>_part<N>.wav" -> (ticket_no, N)
+pub(super) fn parse_segment_file_name(file_name: &str) -> Option<(usize, usize)> {
+    let stem = file_name
+        .strip_prefix(SEGMENT_FILE_PREFIX)?
+        .strip_prefix('_')?
+        .strip_suffix(SEGMENT_FILE_EXTENSION)?
+        .strip_suffix('.')?;
+    match stem.split_once(SEGMENT_PART_SEPARATOR) {
+        Some((ticket_no, part_no)) => Some((ticket_no.parse().ok()?, part_no.parse().ok()?)),
+        None => Some((stem.parse().ok()?, 0)),
+    }
+}
+
+pub(super) struct SegmentedRecording {
+    // File names, in order.
+    pub(super) segments: Vec<String>,
+    pub(super) num_samples: usize,
+}
+
+// Writes (interleaved) f32 audio, rolling over to a new segment file at the size/time limits.
+pub(super) struct SegmentedWavWriter {
+    data_directory: PathBuf,
+    ticket_no: usize,
+    spec: WavSpec,
+    writer: WavWriter<BufWriter<File>>,
+    segments: Vec<String>,
+    max_segment_samples: usize,
+    flush_interval_samples: usize,
+    segment_samples: usize,
+    unflushed_samples: usize,
+    num_samples: usize,
+}
+
+impl SegmentedWavWriter {
+    pub(super) fn create(
+        data_directory: &Path,
+        ticket_no: usize,
+        spec: WavSpec,
+    ) -> Result<Self, RibbleError> {
+        let file_name = segment_file_name(ticket_no, 0);
+        let writer = WavWriter::create(data_directory.join(&file_name), spec)?;
+
+        let samples_per_sec = spec.sample_rate as usize * spec.channels as usize;
+        let bytes_per_sample = (spec.bits_per_sample / 8) as usize;
+        let max_segment_samples =
+            (samples_per_sec * MAX_SEGMENT_SECS).min(MAX_SEGMENT_BYTES / bytes_per_sample);
+
+        Ok(Self {
+            data_directory: data_directory.to_path_buf(),
+            ticket_no,
+            spec,
+            writer,
+            segments: vec![file_name],
+            max_segment_samples,
+            flush_interval_samples: samples_per_sec * HEADER_FLUSH_SECS,
+            segment_samples: 0,
+            unflushed_samples: 0,
+            num_samples: 0,
+        })
+    }
+
+    // NOTE: segments only roll over between chunks, so that a frame is never split across files.
+    pub(super) fn write_chunk(&mut self, samples: &[f32]) -> Result<(), RibbleError> {
+        for sample in samples.iter().copied() {
+            self.writer.write_sample(sample)?;
+        }
+        self.segment_samples += samples.len();
+        self.unflushed_samples += samples.len();
+        self.num_samples += samples.len();
+
+        if self.segment_samples >= self.max_segment_samples {
+            self.next_segment()?;
+        } else if self.unflushed_samples >= self.flush_interval_samples {
+            // Rewrites the header so the file is readable up to this point.
+            self.writer.flush()?;
+            self.unflushed_samples = 0;
+        }
+        Ok(())
+    }
+
+    fn next_segment(&mut self) -> Result<(), RibbleError> {
+        let file_name = segment_file_name(self.ticket_no, self.segments.len());
+        let writer = WavWriter::create(self.data_directory.join(&file_name), self.spec)?;
+        let finished = std::mem::replace(&mut self.writer, writer);
+        finished.finalize()?;
+        log::info!("Recording rolled over to a new segment: {file_name}");
+
+        self.segments.push(file_name);
+        self.segment_samples = 0;
+        self.unflushed_samples = 0;
+        Ok(())
+    }
+
+    pub(super) fn finish(self) -> Result<SegmentedRecording, RibbleError> {
+        self.writer.finalize()?;
+        Ok(SegmentedRecording {
+            segments: self.segments,
+            num_samples: self.num_samples,
+        })
+    }
+}
+
+// The number of frames in the segments together (i.e. the total for a load progress job).
+pub(super) fn segments_num_frames(segment_paths: &[PathBuf]) -> Result<u64, RibbleError> {
+    segment_paths.iter().try_fold(0u64, |total, path| {
+        Ok(total + audio_file_num_frames(path.as_path())?)
+    })
+}
+
+// Loads the segments back to back as one recording, normalized for whisper (16kHz mono).
+// on_progress receives frame deltas, the same as load_normalized_audio_file's callback.
+// NOTE: a single file is handed straight through, so plain audio files can go through here too.
+pub(super) fn load_normalized_segments<F>(
+    segment_paths: &[PathBuf],
+    on_progress: &F,
+) -> Result<WhisperAudioSample, RibbleError>
+where
+    F: Fn(usize) + Sync,
+{
+    if let [path] = segment_paths {
+        let callback = |progress: usize| on_progress(progress);
+        return Ok(load_normalized_audio_file(path.as_path(), Some(callback))?);
+    }
+
+    let mut audio = Vec::new();
+    for path in segment_paths {
+        let callback = |progress: usize| on_progress(progress);
+        match load_normalized_audio_file(path.as_path(), Some(callback))? {
+            WhisperAudioSample::F32(segment) => audio.extend_from_slice(&segment),
+            WhisperAudioSample::I16(_) => {
+                unreachable!("Loading normalized for whisper should never return integer audio.")
+            }
+        }
+    }
+    Ok(WhisperAudioSample::F32(Arc::from(audio)))
+}
+
+// Makes the RIFF and data chunk sizes match what's actually in the file, and trims off any
+// partially-written frame. Returns whether the header needed repairing.
+pub(super) fn repair_wav_header(path: &Path) -> Result<bool, RibbleError> {
+    let not_a_wav = || RibbleError::Core(format!("Not a wav file: {}", path.display()));
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut riff_header = [0u8; 12];
+    file.read_exact(&mut riff_header)?;
+    if &riff_header[0..4] != b"RIFF" || &riff_header[8..12] != b"WAVE" {
+        return Err(not_a_wav());
+    }
+
+    // Walk the chunks until the data chunk; the block alignment comes from the fmt chunk.
+    let mut block_align = None;
+    let mut chunk_start = riff_header.len() as u64;
+    let data_chunk_start = loop {
+        if chunk_start + 8 > file_len {
+            return Err(not_a_wav());
+        }
+        let mut chunk_header = [0u8; 8];
+        file.seek(SeekFrom::Start(chunk_start))?;
+        file.read_exact(&mut chunk_header)?;
+        let chunk_size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+
+        match &chunk_header[0..4] {
+            b"fmt " => {
+                let mut fmt = [0u8; 14];
+                file.read_exact(&mut fmt)?;
+                block_align = Some(u16::from_le_bytes([fmt[12], fmt[13]]) as u64);
+            }
+            b"data" => break chunk_start,
+            _ => {}
+        }
+        // Chunks are padded to an even size.
+        chunk_start += 8 + chunk_size + (chunk_size & 1);
+    };
+
+    let block_align = block_align
+        .filter(|align| *align > 0)
+        .ok_or_else(not_a_wav)?;
+    let data_start = data_chunk_start + 8;
+    let available = file_len.saturating_sub(data_start).min(MAX_WAV_DATA_BYTES);
+    let data_len = available - available % block_align;
+
+    let mut size_bytes = [0u8; 4];
+    file.seek(SeekFrom::Start(data_chunk_start + 4))?;
+    file.read_exact(&mut size_bytes)?;
+    let declared_len = u32::from_le_bytes(size_bytes) as u64;
+    let riff_len = data_start + data_len - 8;
+    let declared_riff_len = u32::from_le_bytes(riff_header[4..8].try_into().unwrap()) as u64;
+
+    if declared_len == data_len
+        && declared_riff_len == riff_len
+        && file_len == data_start + data_len
+    {
+        return Ok(false);
+    }
+
+    file.set_len(data_start + data_len)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&(riff_len as u32).to_le_bytes())?;
+    file.seek(SeekFrom::Start(data_chunk_start + 4))?;
+    file.write_all(&(data_len as u32).to_le_bytes())?;
+    file.sync_all()?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavReader};
+
+    // One sample a second keeps the time limit small enough to actually reach.
+    fn slow_spec() -> WavSpec {
+        WavSpec {
+            channels: 1,
+            sample_rate: 1,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        }
+    }
+
+    fn read_samples(path: &Path) -> Vec<f32> {
+        WavReader::open(path)
+            .unwrap()
+            .samples::<f32>()
+            .map(|sample| sample.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn segment_file_names_round_trip() {
+        assert_eq!(segment_file_name(3, 0), "tmp_recording_3.wav");
+        assert_eq!(segment_file_name(3, 2), "tmp_recording_3_part2.wav");
+        assert_eq!(parse_segment_file_name("tmp_recording_3.wav"), Some((3, 0)));
+        assert_eq!(
+            parse_segment_file_name("tmp_recording_3_part2.wav"),
+            Some((3, 2))
+        );
+        assert_eq!(parse_segment_file_name("tmp_recording_x.wav"), None);
+        assert_eq!(parse_segment_file_name("recording_3.wav"), None);
+    }
+
+    #[test]
+    fn rolls_over_at_the_segment_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SegmentedWavWriter::create(dir.path(), 7, slow_spec()).unwrap();
+
+        // One sample short of the limit stays in the first segment.
+        let samples = (0..MAX_SEGMENT_SECS).map(|i| i as f32).collect::<Vec<_>>();
+        writer
+            .write_chunk(&samples[..MAX_SEGMENT_SECS - 1])
+            .unwrap();
+        assert_eq!(writer.segments.len(), 1);
+
+        // Reaching it rolls over.
+        writer
+            .write_chunk(&samples[MAX_SEGMENT_SECS - 1..])
+            .unwrap();
+        assert_eq!(writer.segments.len(), 2);
+
+        writer.write_chunk(&[-1.0, -2.0]).unwrap();
+        let recording = writer.finish().unwrap();
+        assert_eq!(
+            recording.segments,
+            vec![segment_file_name(7, 0), segment_file_name(7, 1)]
+        );
+        assert_eq!(recording.num_samples, MAX_SEGMENT_SECS + 2);
+
+        // Read back to back, the segments are the recording.
+        let read_back = recording
+            .segments
+            .iter()
+            .flat_map(|segment| read_samples(&dir.path().join(segment)))
+            .collect::<Vec<_>>();
+        let expected = samples
+            .iter()
+            .copied()
+            .chain([-1.0, -2.0])
+            .collect::<Vec<_>>();
+        assert_eq!(read_back, expected);
+    }
+
+    #[test]
+    fn byte_limit_applies_before_the_time_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = WavSpec {
+            channels: 8,
+            sample_rate: 48000,
+            ..slow_spec()
+        };
+        let writer = SegmentedWavWriter::create(dir.path(), 0, spec).unwrap();
+        // 30 minutes of 48kHz 8-channel f32 is ~2.6 GiB, so the size limit wins.
+        assert_eq!(writer.max_segment_samples, MAX_SEGMENT_BYTES / 4);
+    }
+
+    #[test]
+    fn repair_truncated_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(segment_file_name(0, 0));
+        let spec = WavSpec {
+            channels: 2,
+            ..slow_spec()
+        };
+        let mut writer = WavWriter::create(path.as_path(), spec).unwrap();
+        for i in 0..100 {
+            writer.write_sample(i as f32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // Cut off the last frame and a half, as a crash mid-write would.
+        let file = OpenOptions::new().write(true).open(path.as_path()).unwrap();
+        let file_len = file.metadata().unwrap().len();
+        file.set_len(file_len - 12).unwrap();
+        drop(file);
+
+        assert!(repair_wav_header(path.as_path()).unwrap());
+        // The partial frame is trimmed off, and the header describes what's left.
+        let repaired = read_samples(path.as_path());
+        assert_eq!(repaired, (0..96).map(|i| i as f32).collect::<Vec<_>>());
+        let reader = WavReader::open(path.as_path()).unwrap();
+        assert_eq!(reader.len(), 96);
+
+        // Once consistent, there's nothing left to do.
+        assert!(!repair_wav_header(path.as_path()).unwrap());
+    }
+
+    #[test]
+    fn repair_stale_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(segment_file_name(0, 0));
+        let mut writer = WavWriter::create(path.as_path(), slow_spec()).unwrap();
+        for i in 0..10 {
+            writer.write_sample(i as f32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // A header that was never brought up to date claims there's no data.
+        let mut file = OpenOptions::new().write(true).open(path.as_path()).unwrap();
+        file.seek(SeekFrom::Start(4)).unwrap();
+        file.write_all(&0u32.to_le_bytes()).unwrap();
+        let data_size_offset = std::fs::read(path.as_path())
+            .unwrap()
+            .windows(4)
+            .position(|window| window == b"data")
+            .unwrap() as u64
+            + 4;
+        file.seek(SeekFrom::Start(data_size_offset)).unwrap();
+        file.write_all(&0u32.to_le_bytes()).unwrap();
+        drop(file);
+
+        assert!(repair_wav_header(path.as_path()).unwrap());
+        assert_eq!(
+            read_samples(path.as_path()),
+            (0..10).map(|i| i as f32).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn repair_rejects_non_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not_a_wav.wav");
+        std::fs::write(path.as_path(), b"definitely not a riff file").unwrap();
+        assert!(repair_wav_header(path.as_path()).is_err());
+    }
+}
@@ -0,0 +1,649 @@
+// Resumable (model) downloads.
+// The file is downloaded to <name>.part and only renamed once it's complete. If a download is
+// aborted or fails, the .part file is left behind, and the next download of the same file picks up
+// where it left off with a Range request.
+//
+// The server's validator for the file (its ETag, or failing that its Last-Modified date) is kept
+// next to the .part file, in <name>.part.validator, and sent back with the range as If-Range. If
+// the file has changed since, the server sends all of it instead, so a .part file is never
+// finished with the bytes of a different file. A .part file without a validator isn't resumed.
+//
+// NOTE: not every server supports Range requests; if the server ignores the range (i.e. it
+// responds with the whole file) or rejects it, the download starts over from scratch.
+use crate::utils::errors::RibbleError;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{
+    HeaderMap, HeaderName, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE,
+    LAST_MODIFIED, RANGE,
+};
+use reqwest::StatusCode;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const PART_FILE_EXTENSION: &str = "part";
+const VALIDATOR_FILE_EXTENSION: &str = "validator";
+const DOWNLOAD_BUFFER_SIZE: usize = 1 << 16;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub(super) enum DownloadOutcome {
+    Finished(PathBuf),
+    // The .part file is kept, so the download can be resumed.
+    Aborted,
+}
+
+pub(super) struct ResumableDownload {
+    client: Client,
+    url: String,
+    content_name: String,
+    // From the HEAD request; None if the server didn't say.
+    total_size: Option<u64>,
+    validator: Option<String>,
+}
+
+impl ResumableDownload {
+    // Asks the server for the file name and size up front, so that the .part file (if there is
+    // one) can be found before the download starts.
+    pub(super) fn new(url: &str) -> Result<Self, RibbleError> {
+        // NOTE: the blocking client times out after 30 seconds by default, which covers the body;
+        // that's nowhere near enough for the larger models.
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(None)
+            .build()?;
+        let head = client.head(url).send()?;
+
+        // Some servers don't allow HEAD requests; the name and size can still come from the url
+        // and the download itself.
+        let (content_name, total_size, validator) = match head.status() {
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => {
+                (file_name_from_url(head.url()), None, None)
+            }
+            _ => {
+                let head = head.error_for_status()?;
+                let content_name = file_name_from_headers(head.headers())
+                    .or_else(|| file_name_from_url(head.url()));
+                (
+                    content_name,
+                    header_u64(head.headers(), CONTENT_LENGTH.as_str()),
+                    validator_from_headers(head.headers()),
+                )
+            }
+        };
+
+        let content_name = content_name.ok_or(RibbleError::Core(format!(
+            "File not found, likely invalid url.\nURL:{url}"
+        )))?;
+
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            content_name,
+            total_size,
+            validator,
+        })
+    }
+
+    pub(super) fn content_name(&self) -> &str {
+        &self.content_name
+    }
+
+    pub(super) fn total_size(&self) -> Option<u64> {
+        self.total_size
+    }
+
+    pub(super) fn part_path(&self, directory: &Path) -> PathBuf {
+        directory.join(format!("{}.{PART_FILE_EXTENSION}", self.content_name))
+    }
+
+    fn validator_path(&self, directory: &Path) -> PathBuf {
+        directory.join(format!(
+            "{}.{PART_FILE_EXTENSION}.{VALIDATOR_FILE_EXTENSION}",
+            self.content_name
+        ))
+    }
+
+    // The validator the .part file was downloaded under.
+    fn stored_validator(&self, directory: &Path) -> Option<String> {
+        let validator = std::fs::read_to_string(self.validator_path(directory)).ok()?;
+        let validator = validator.trim();
+        (!validator.is_empty()).then(|| validator.to_string())
+    }
+
+    // How much of the file has already been downloaded (and can be resumed).
+    pub(super) fn resume_offset(&self, directory: &Path) -> u64 {
+        let Some(stored_validator) = self.stored_validator(directory) else {
+            return 0;
+        };
+        // The file has changed since the .part file was started; there's no point asking.
+        if self
+            .validator
+            .as_ref()
+            .is_some_and(|validator| *validator != stored_validator)
+        {
+            return 0;
+        }
+
+        let offset = std::fs::metadata(self.part_path(directory)).map_or(0, |m| m.len());
+        match self.total_size {
+            // A .part file that's bigger than the file is garbage.
+            Some(total_size) if offset > total_size => 0,
+            _ => offset,
+        }
+    }
+
+    // on_progress receives the absolute position (i.e. including the resumed bytes).
+    pub(super) fn download<P, A>(
+        &self,
+        directory: &Path,
+        mut on_progress: P,
+        should_abort: A,
+    ) -> Result<DownloadOutcome, RibbleError>
+    where
+        P: FnMut(u64),
+        A: Fn() -> bool,
+    {
+        let part_path = self.part_path(directory);
+        let mut offset = self.resume_offset(directory);
+        let mut total_size = self.total_size;
+
+        if offset > 0 && total_size == Some(offset) {
+            return self.finish(part_path.as_path(), directory, Some(offset));
+        }
+
+        let stored_validator = self.stored_validator(directory);
+        let mut response = self.request(offset, stored_validator.as_deref())?;
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                // Make sure the server is resuming from the right place.
+                let content_range = parse_content_range(response.headers());
+                match content_range {
+                    Some((start, total)) if start == offset => {
+                        total_size = total.or(total_size);
+                    }
+                    _ => {
+                        log::warn!(
+                            "Unexpected Content-Range for {}; restarting the download.",
+                            self.content_name
+                        );
+                        offset = 0;
+                        response = self.request(offset, None)?;
+                    }
+                }
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                log::warn!(
+                    "Server rejected the range for {}; restarting the download.",
+                    self.content_name
+                );
+                offset = 0;
+                response = self.request(offset, None)?;
+            }
+            // The whole file: either the server doesn't do ranges, or the file has changed
+            // (If-Range didn't match).
+            _ => {
+                if offset > 0 {
+                    log::info!(
+                        "Server cannot resume {}; restarting the download.",
+                        self.content_name
+                    );
+                }
+                offset = 0;
+            }
+        }
+
+        let mut response = response.error_for_status()?;
+        if offset == 0 {
+            total_size = header_u64(response.headers(), CONTENT_LENGTH.as_str()).or(total_size);
+        }
+
+        let mut file = if offset > 0 {
+            log::info!("Resuming {} at {offset} bytes.", self.content_name);
+            OpenOptions::new().append(true).open(part_path.as_path())?
+        } else {
+            let file = File::create(part_path.as_path())?;
+            // NOTE: this is written after the .part file is truncated; if it's missing, the
+            // download can't be resumed, which is the safe way to fail.
+            let validator =
+                validator_from_headers(response.headers()).or_else(|| self.validator.clone());
+            self.store_validator(directory, validator.as_deref())?;
+            file
+        };
+
+        let mut position = offset;
+        on_progress(position);
+        let mut buffer = vec![0u8; DOWNLOAD_BUFFER_SIZE];
+        loop {
+            if should_abort() {
+                file.flush()?;
+                return Ok(DownloadOutcome::Aborted);
+            }
+            let num_read = match response.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(num_read) => num_read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            file.write_all(&buffer[..num_read])?;
+            position += num_read as u64;
+            on_progress(position);
+        }
+
+        file.sync_all()?;
+        drop(file);
+        self.finish(part_path.as_path(), directory, total_size)
+    }
+
+    fn store_validator(
+        &self,
+        directory: &Path,
+        validator: Option<&str>,
+    ) -> Result<(), RibbleError> {
+        let path = self.validator_path(directory);
+        match validator {
+            Some(validator) => std::fs::write(path, validator)?,
+            None => remove_if_exists(path.as_path())?,
+        }
+        Ok(())
+    }
+
+    fn request(&self, offset: u64, validator: Option<&str>) -> Result<Response, RibbleError> {
+        let request = self.client.get(self.url.as_str());
+        let request = match validator {
+            Some(validator) if offset > 0 => request
+                .header(RANGE, format!("bytes={offset}-"))
+                .header(IF_RANGE, validator),
+            _ => request,
+        };
+        Ok(request.send()?)
+    }
+
+    // Checks the size (if it's known) and moves the .part file into place.
+    fn finish(
+        &self,
+        part_path: &Path,
+        directory: &Path,
+        total_size: Option<u64>,
+    ) -> Result<DownloadOutcome, RibbleError> {
+        let size = std::fs::metadata(part_path)?.len();
+        if let Some(total_size) = total_size
+            && size != total_size
+        {
+            // NOTE: this is (almost always) the connection dropping early, so it's reported as
+            // such; the download can be retried from where it left off.
+            return Err(RibbleError::IOError(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "Download incomplete: {} ({size} of {total_size} bytes).",
+                    self.content_name
+                ),
+            )));
+        }
+
+        let download_path = directory.join(self.content_name.as_str());
+        // NOTE: rename doesn't replace an existing file on every platform.
+        remove_if_exists(download_path.as_path())?;
+        std::fs::rename(part_path, download_path.as_path())?;
+        remove_if_exists(self.validator_path(directory).as_path())?;
+        Ok(DownloadOutcome::Finished(download_path))
+    }
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// A weak ETag can't be used with If-Range, so the date is the fallback.
+fn validator_from_headers(headers: &HeaderMap) -> Option<String> {
+    let header = |name: HeaderName| {
+        let value = headers.get(name)?.to_str().ok()?.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    };
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+// Content-Range: bytes <start>-<end>/<total or *>
+fn parse_content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+    let (start, _end) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+// Content-Disposition: attachment; filename="<name>"
+fn file_name_from_headers(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_DISPOSITION)?.to_str().ok()?;
+    value.split(';').find_map(|part| {
+        let name = part.trim().strip_prefix("filename=")?.trim_matches('"');
+        sanitize_file_name(name)
+    })
+}
+
+fn file_name_from_url(url: &reqwest::Url) -> Option<String> {
+    let name = url.path_segments()?.next_back()?;
+    sanitize_file_name(name)
+}
+
+// The name comes from the server; it mustn't be able to escape the download directory.
+fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = Path::new(name).file_name()?.to_str()?;
+    (!name.is_empty() && !name.starts_with('.')).then(|| name.to_string())
+}
+
+// NOTE: the test server is shared with the download queue's tests (see: downloader.rs).
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use ribble_whisper::utils::Receiver;
+    use std::io::BufRead;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    pub(in crate::controller) const FILE_NAME: &str = "ggml-test.bin";
+    // Bigger than the download buffer, so an abort can land mid-file.
+    pub(in crate::controller) const FILE_LEN: usize = 3 * DOWNLOAD_BUFFER_SIZE + 123;
+
+    #[derive(Copy, Clone)]
+    pub(in crate::controller) enum Ranges {
+        // Honours a range if If-Range matches the ETag.
+        Supported,
+        // Always sends the whole file.
+        Ignored,
+        // Answers a range with a Content-Range that starts in the wrong place.
+        Misreported,
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub(in crate::controller) struct SeenRequest {
+        pub(in crate::controller) method: String,
+        pub(in crate::controller) range: Option<String>,
+        pub(in crate::controller) if_range: Option<String>,
+    }
+
+    pub(in crate::controller) fn file_contents() -> Vec<u8> {
+        (0..FILE_LEN).map(|i| (i % 251) as u8).collect()
+    }
+
+    // Serves file_contents() at /FILE_NAME, one request per connection.
+    pub(in crate::controller) fn serve(
+        etag: &'static str,
+        ranges: Ranges,
+    ) -> (String, Arc<Mutex<Vec<SeenRequest>>>) {
+        serve_held(etag, ranges, None)
+    }
+
+    // Like serve, but each response body waits for the go-ahead from release (or for it to hang
+    // up); the headers are sent straight away. Each connection gets its own thread, so that a held
+    // download doesn't hold up the others.
+    pub(in crate::controller) fn serve_held(
+        etag: &'static str,
+        ranges: Ranges,
+        release: Option<Receiver<()>>,
+    ) -> (String, Arc<Mutex<Vec<SeenRequest>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/{FILE_NAME}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(vec![]));
+        let thread_seen = Arc::clone(&seen);
+        // NOTE: detached; it's left blocked on accept once the test is done.
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let seen = Arc::clone(&thread_seen);
+                let release = release.clone();
+                std::thread::spawn(move || {
+                    respond(stream, etag, ranges, &seen, release.as_ref());
+                });
+            }
+        });
+        (url, seen)
+    }
+
+    // The request is recorded before it's answered, so the client never sees a response before
+    // the test can see the request.
+    fn respond(
+        mut stream: TcpStream,
+        etag: &str,
+        ranges: Ranges,
+        seen: &Mutex<Vec<SeenRequest>>,
+        release: Option<&Receiver<()>>,
+    ) {
+        let mut request = SeenRequest::default();
+        let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        request.method = line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                break;
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "range" => request.range = Some(value.trim().to_string()),
+                "if-range" => request.if_range = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+
+        seen.lock().unwrap().push(request.clone());
+
+        let contents = file_contents();
+        let start = request
+            .range
+            .as_deref()
+            .and_then(|range| {
+                range
+                    .strip_prefix("bytes=")?
+                    .strip_suffix('-')?
+                    .parse()
+                    .ok()
+            })
+            .filter(|_| request.if_range.as_deref() == Some(etag));
+        let (status, body, content_range) = match (start, ranges) {
+            (Some(start), Ranges::Supported) => (
+                "206 Partial Content",
+                &contents[start..],
+                Some(format!("bytes {start}-{}/{FILE_LEN}", FILE_LEN - 1)),
+            ),
+            (Some(start), Ranges::Misreported) => (
+                "206 Partial Content",
+                &contents[start..],
+                Some(format!("bytes 0-{}/{FILE_LEN}", FILE_LEN - 1)),
+            ),
+            _ => ("200 OK", &contents[..], None),
+        };
+
+        let mut head = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nETag: {etag}\r\nConnection: close\r\n",
+            body.len()
+        );
+        if let Some(content_range) = content_range {
+            head.push_str(&format!("Content-Range: {content_range}\r\n"));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).unwrap();
+        if request.method != "HEAD" {
+            if let Some(release) = release {
+                let _ = release.recv();
+            }
+            // The client may hang up early (an aborted download).
+            let _ = stream.write_all(body);
+        }
+    }
+
+    // Leaves a .part file with the start of the file, as an aborted download would.
+    fn write_part_file(directory: &Path, len: usize, validator: Option<&str>) {
+        let part_path = directory.join(format!("{FILE_NAME}.{PART_FILE_EXTENSION}"));
+        std::fs::write(part_path, &file_contents()[..len]).unwrap();
+        if let Some(validator) = validator {
+            let validator_path = directory.join(format!(
+                "{FILE_NAME}.{PART_FILE_EXTENSION}.{VALIDATOR_FILE_EXTENSION}"
+            ));
+            std::fs::write(validator_path, validator).unwrap();
+        }
+    }
+
+    fn download(url: &str, directory: &Path) -> (PathBuf, u64) {
+        let download = ResumableDownload::new(url).unwrap();
+        let mut first_position = None;
+        let outcome = download
+            .download(
+                directory,
+                |position| {
+                    first_position.get_or_insert(position);
+                },
+                || false,
+            )
+            .unwrap();
+        let DownloadOutcome::Finished(path) = outcome else {
+            panic!("Download aborted without being asked to.");
+        };
+        (path, first_position.unwrap())
+    }
+
+    fn assert_only_the_file_is_left(directory: &Path) {
+        let names: Vec<_> = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names, [FILE_NAME]);
+        assert_eq!(
+            std::fs::read(directory.join(FILE_NAME)).unwrap(),
+            file_contents()
+        );
+    }
+
+    fn download_and_check(url: &str, directory: &Path) -> (PathBuf, u64) {
+        let downloaded = download(url, directory);
+        assert_only_the_file_is_left(directory);
+        downloaded
+    }
+
+    #[test]
+    fn an_aborted_download_resumes_where_it_left_off() {
+        let directory = tempfile::tempdir().unwrap();
+        let (url, seen) = serve("\"v1\"", Ranges::Supported);
+
+        let download = ResumableDownload::new(&url).unwrap();
+        let position = std::cell::Cell::new(0);
+        let outcome = download
+            .download(directory.path(), |p| position.set(p), || position.get() > 0)
+            .unwrap();
+        assert!(matches!(outcome, DownloadOutcome::Aborted));
+        let offset = download.resume_offset(directory.path());
+        assert!(offset > 0 && offset < FILE_LEN as u64);
+
+        let (path, resumed_at) = download_and_check(&url, directory.path());
+        assert_eq!(resumed_at, offset);
+        assert_eq!(path, directory.path().join(FILE_NAME));
+
+        let seen = seen.lock().unwrap();
+        let last = seen.last().unwrap();
+        assert_eq!(
+            last.range.as_deref(),
+            Some(format!("bytes={offset}-").as_str())
+        );
+        assert_eq!(last.if_range.as_deref(), Some("\"v1\""));
+    }
+
+    #[test]
+    fn a_whole_file_response_restarts_the_download() {
+        let directory = tempfile::tempdir().unwrap();
+        let (url, seen) = serve("\"v1\"", Ranges::Ignored);
+        write_part_file(directory.path(), 1000, Some("\"v1\""));
+
+        let (_, first_position) = download_and_check(&url, directory.path());
+        assert_eq!(first_position, 0);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.last().unwrap().range.as_deref(), Some("bytes=1000-"));
+    }
+
+    #[test]
+    fn a_changed_file_is_not_resumed() {
+        let directory = tempfile::tempdir().unwrap();
+        let (url, seen) = serve("\"v2\"", Ranges::Supported);
+        write_part_file(directory.path(), 1000, Some("\"v1\""));
+
+        let (_, first_position) = download_and_check(&url, directory.path());
+        assert_eq!(first_position, 0);
+        let seen = seen.lock().unwrap();
+        assert!(seen.iter().all(|request| request.range.is_none()));
+    }
+
+    #[test]
+    fn a_part_file_without_a_validator_is_not_resumed() {
+        let directory = tempfile::tempdir().unwrap();
+        let (url, seen) = serve("\"v1\"", Ranges::Supported);
+        write_part_file(directory.path(), 1000, None);
+
+        let (_, first_position) = download_and_check(&url, directory.path());
+        assert_eq!(first_position, 0);
+        let seen = seen.lock().unwrap();
+        assert!(seen.iter().all(|request| request.range.is_none()));
+    }
+
+    #[test]
+    fn a_misplaced_content_range_restarts_the_download() {
+        let directory = tempfile::tempdir().unwrap();
+        let (url, seen) = serve("\"v1\"", Ranges::Misreported);
+        write_part_file(directory.path(), 1000, Some("\"v1\""));
+
+        let (_, first_position) = download_and_check(&url, directory.path());
+        assert_eq!(first_position, 0);
+        let seen = seen.lock().unwrap();
+        let gets: Vec<_> = seen
+            .iter()
+            .filter(|request| request.method == "GET")
+            .map(|request| request.range.as_deref())
+            .collect();
+        assert_eq!(gets, [Some("bytes=1000-"), None]);
+    }
+
+    #[test]
+    fn content_range_parsing() {
+        let parse = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_RANGE, HeaderValue::from_static(value));
+            parse_content_range(&headers)
+        };
+        assert_eq!(parse("bytes 100-199/200"), Some((100, Some(200))));
+        assert_eq!(parse("bytes 100-199/*"), Some((100, None)));
+        assert_eq!(parse(" bytes 0-0/1 "), Some((0, Some(1))));
+        assert_eq!(parse("bytes */200"), None);
+        assert_eq!(parse("items 100-199/200"), None);
+        assert_eq!(parse("bytes 100/200"), None);
+        assert_eq!(parse_content_range(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn file_names_from_the_server_stay_in_the_directory() {
+        assert_eq!(
+            sanitize_file_name("ggml-base.bin").as_deref(),
+            Some("ggml-base.bin")
+        );
+        assert_eq!(
+            sanitize_file_name("../../ggml-base.bin").as_deref(),
+            Some("ggml-base.bin")
+        );
+        assert_eq!(sanitize_file_name("/etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize_file_name(".bashrc"), None);
+        assert_eq!(sanitize_file_name(".."), None);
+        assert_eq!(sanitize_file_name(""), None);
+    }
+}
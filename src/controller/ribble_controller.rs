@@ -1,15 +1,31 @@
-use crate::controller::audio_backend_proxy::AudioBackendProxy;
-use crate::controller::kernel::Kernel;
+use crate::controller::audio_backend_proxy::{AudioBackendKind, AudioBackendProxy};
+use crate::controller::batch_queue::BatchJob;
+use crate::controller::kernel::{HeadlessKernel, Kernel};
+use crate::controller::model_catalog::{CatalogModel, ModelCatalog};
+use crate::controller::model_header::ModelHeader;
+use crate::controller::model_integrity::ModelIntegrity;
+use crate::controller::playback::PlaybackHandle;
+use crate::controller::transcript::{Transcript, TranscriptEdit};
 use crate::controller::{
     AmortizedDownloadProgress, AmortizedProgress, AnalysisType, CompletedRecordingJobs,
-    ConsoleMessage, FileDownload, LatestError, ModelFile, NUM_VISUALIZER_BUCKETS,
-    OfflineTranscriberFeedback, Progress, RotationDirection,
+    ConsoleMessage, FileDownload, LatestError, ModelDiskUsage, ModelFile, NUM_VISUALIZER_BUCKETS,
+    OfflineTranscriberFeedback, Progress, RibbleMessage, RotationDirection,
 };
 use crate::utils::audio_gain::AudioGainConfigs;
+use crate::utils::batch_configs::BatchConfigs;
+use crate::utils::control_api_configs::ControlApiConfigs;
+use crate::utils::download_configs::DownloadConfigs;
 use crate::utils::errors::RibbleError;
+use crate::utils::live_sink_configs::LiveSinkConfigs;
+use crate::utils::noise_suppression::NoiseSuppressionConfigs;
 use crate::utils::preferences::UserPreferences;
-use crate::utils::recorder_configs::{RibbleExportFormat, RibbleRecordingConfigs};
+use crate::utils::recorder_configs::{
+    RibbleExportFormat, RibbleOpusBitrate, RibbleRecordingConfigs,
+};
+use crate::utils::recording_library_configs::RecordingLibraryConfigs;
+use crate::utils::transcript_formats::{SubtitleConfigs, TranscriptFormat};
 use crate::utils::vad_configs::VadConfigs;
+use crate::utils::watch_folder_configs::WatchFolderConfigs;
 use ribble_whisper::transcriber::{TranscriptionSnapshot, WhisperControlPhrase};
 use ribble_whisper::utils::Sender;
 use ribble_whisper::whisper::configs::WhisperRealtimeConfigs;
@@ -17,6 +33,7 @@ use ribble_whisper::whisper::model::ModelId;
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 fn set_base_directory() -> PathBuf {
     use directories::BaseDirs;
@@ -44,8 +61,9 @@ impl RibbleController {
         data_directory: &Path,
         audio_backend: AudioBackendProxy,
         toasts_sender: Sender<egui_notify::Toast>,
+        restore_session: bool,
     ) -> Result<Self, RibbleError> {
-        let kernel = Arc::new(Kernel::new(data_directory, audio_backend)?);
+        let kernel = Arc::new(Kernel::new(data_directory, audio_backend, restore_session)?);
         let available_threads = std::thread::available_parallelism()?.get();
         let max_whisper_threads = available_threads.min(Self::RECOMMENDED_MAX_WHISPER_THREADS);
 
@@ -102,6 +120,15 @@ impl RibbleController {
         self.kernel.write_user_preferences(new_prefs);
     }
 
+    // NOTE: the control server itself is owned by the app (it needs a controller to drive);
+    // the app restarts it when these change.
+    pub(crate) fn read_control_api_configs(&self) -> Arc<ControlApiConfigs> {
+        self.kernel.read_control_api_configs()
+    }
+    pub(crate) fn write_control_api_configs(&self, new_configs: ControlApiConfigs) {
+        self.kernel.write_control_api_configs(new_configs);
+    }
+
     pub(crate) fn read_app_theme(&self) -> Option<catppuccin_egui::Theme> {
         self.kernel.read_app_theme()
     }
@@ -138,10 +165,50 @@ impl RibbleController {
         self.kernel.get_model_key(file_name)
     }
 
+    pub(crate) fn verify_all_models(&self) {
+        self.kernel.verify_all_models();
+    }
+
+    pub(crate) fn read_model_integrity(&self, model_id: ModelId) -> ModelIntegrity {
+        self.kernel.read_model_integrity(model_id)
+    }
+
+    // None if the model's header couldn't be read.
+    pub(crate) fn read_model_header(&self, model_id: ModelId) -> Option<ModelHeader> {
+        self.kernel.read_model_header(model_id)
+    }
+
+    // The file name of the model being verified, if any.
+    pub(crate) fn verifying_model(&self) -> Option<Arc<str>> {
+        self.kernel.verifying_model()
+    }
+
     pub(crate) fn get_model_directory(&self) -> &Path {
         self.kernel.get_model_directory()
     }
 
+    pub(crate) fn read_model_size(&self, model_id: ModelId) -> Option<u64> {
+        self.kernel.read_model_size(model_id)
+    }
+
+    pub(crate) fn model_disk_usage(&self) -> ModelDiskUsage {
+        self.kernel.model_disk_usage()
+    }
+
+    // None for models that are packed into the binary.
+    pub(crate) fn get_model_path(&self, model_id: ModelId) -> Option<PathBuf> {
+        self.kernel.get_model_path(model_id)
+    }
+
+    // Fails if the model is loaded by a running transcriber.
+    pub(crate) fn delete_model(&self, model_id: ModelId) -> Result<(), RibbleError> {
+        self.kernel.delete_model(model_id)
+    }
+
+    pub(crate) fn rename_model(&self, model_id: ModelId, new_name: &str) -> Result<(), RibbleError> {
+        self.kernel.rename_model(model_id, new_name)
+    }
+
     // TRANSCRIBER
     pub(crate) fn read_transcription_configs(&self) -> Arc<WhisperRealtimeConfigs> {
         self.kernel.read_transcription_configs()
@@ -171,12 +238,39 @@ impl RibbleController {
     pub(crate) fn write_audio_gain_configs(&self, new_settings: AudioGainConfigs) {
         self.kernel.write_audio_gain_configs(new_settings);
     }
+    pub(crate) fn read_noise_suppression_configs(&self) -> Arc<NoiseSuppressionConfigs> {
+        self.kernel.read_noise_suppression_configs()
+    }
+    pub(crate) fn write_noise_suppression_configs(&self, new_configs: NoiseSuppressionConfigs) {
+        self.kernel.write_noise_suppression_configs(new_configs);
+    }
+    pub(crate) fn read_subtitle_configs(&self) -> Arc<SubtitleConfigs> {
+        self.kernel.read_subtitle_configs()
+    }
+    pub(crate) fn write_subtitle_configs(&self, new_configs: SubtitleConfigs) {
+        self.kernel.write_subtitle_configs(new_configs);
+    }
+    pub(crate) fn read_batch_configs(&self) -> Arc<BatchConfigs> {
+        self.kernel.read_batch_configs()
+    }
+    pub(crate) fn write_batch_configs(&self, new_configs: BatchConfigs) {
+        self.kernel.write_batch_configs(new_configs);
+    }
+    pub(crate) fn read_live_sink_configs(&self) -> Arc<LiveSinkConfigs> {
+        self.kernel.read_live_sink_configs()
+    }
+    pub(crate) fn write_live_sink_configs(&self, new_configs: LiveSinkConfigs) {
+        self.kernel.write_live_sink_configs(new_configs);
+    }
     pub(crate) fn realtime_running(&self) -> bool {
         self.kernel.realtime_running()
     }
     pub(crate) fn offline_running(&self) -> bool {
         self.kernel.offline_running()
     }
+    pub(crate) fn batch_running(&self) -> bool {
+        self.kernel.batch_running()
+    }
 
     pub(crate) fn slow_stopping(&self) -> bool {
         self.kernel.slow_stopping()
@@ -208,6 +302,22 @@ impl RibbleController {
     pub(crate) fn read_transcription_snapshot(&self) -> Arc<TranscriptionSnapshot> {
         self.kernel.read_transcription_snapshot()
     }
+    // The timed, segment-level transcript. This is what gets exported/edited; the snapshot above
+    // is just for drawing the live transcription.
+    pub(crate) fn read_transcript(&self) -> Arc<Transcript> {
+        self.kernel.read_transcript()
+    }
+    // NOTE: edits replace the current transcript (and snapshot), so saving/exporting picks them
+    // up. They're dropped when a new transcription starts.
+    pub(crate) fn edit_transcript(&self, edit: &TranscriptEdit) -> Result<(), RibbleError> {
+        self.kernel.edit_transcript(edit)
+    }
+    pub(crate) fn revert_transcript_edits(&self) {
+        self.kernel.revert_transcript_edits();
+    }
+    pub(crate) fn transcript_edited(&self) -> bool {
+        self.kernel.transcript_edited()
+    }
     pub(crate) fn read_latest_control_phrase(&self) -> Arc<WhisperControlPhrase> {
         self.kernel.read_latest_control_phrase()
     }
@@ -223,6 +333,13 @@ impl RibbleController {
     pub(crate) fn set_audio_file_path(&self, path: PathBuf) {
         self.kernel.set_audio_file_path(path);
     }
+    pub(crate) fn set_audio_file_segments(&self, segments: Vec<PathBuf>) {
+        self.kernel.set_audio_file_segments(segments);
+    }
+    // The current audio file, or every segment if it's a segmented recording.
+    pub(crate) fn read_current_audio_files(&self) -> Vec<PathBuf> {
+        self.kernel.read_current_audio_files()
+    }
     pub(crate) fn clear_audio_file_path(&self) {
         self.kernel.clear_audio_file_path();
     }
@@ -231,12 +348,88 @@ impl RibbleController {
         self.kernel.start_offline_transcription();
     }
 
+    // NOTE: this bypasses the worker queue and blocks the calling thread; it's for callers that
+    // need the result directly (the control API).
+    // The GUI should use save_transcription().
+    pub(crate) fn format_transcription(
+        &self,
+        format: TranscriptFormat,
+    ) -> Result<String, RibbleError> {
+        self.kernel.format_transcription(format)
+    }
+
     pub(crate) fn try_retranscribe_latest(&self) {
         self.kernel.try_retranscribe_latest();
     }
 
-    pub(crate) fn save_transcription(&self, out_path: PathBuf) {
-        self.kernel.save_transcription(out_path);
+    pub(crate) fn save_transcription(&self, out_path: PathBuf, format: TranscriptFormat) {
+        self.kernel.save_transcription(out_path, format);
+    }
+
+    // BATCH TRANSCRIPTION
+    // NOTE: stop_transcription() also stops the batch queue.
+    pub(crate) fn start_batch_transcription(&self) {
+        self.kernel.start_batch_transcription();
+    }
+    pub(crate) fn enqueue_batch_files(&self, audio_file_paths: Vec<PathBuf>) {
+        self.kernel.enqueue_batch_files(audio_file_paths);
+    }
+    pub(crate) fn remove_batch_job(&self, job_id: usize) {
+        self.kernel.remove_batch_job(job_id);
+    }
+    pub(crate) fn requeue_batch_job(&self, job_id: usize) {
+        self.kernel.requeue_batch_job(job_id);
+    }
+    pub(crate) fn clear_finished_batch_jobs(&self) {
+        self.kernel.clear_finished_batch_jobs();
+    }
+    pub(crate) fn try_read_batch_queue(&self, copy_buffer: &mut Vec<(usize, BatchJob)>) {
+        self.kernel.try_read_batch_queue(copy_buffer);
+    }
+
+    // WATCH FOLDER
+    // NOTE: writing new configs (re)starts or stops the watcher as needed.
+    pub(crate) fn read_watch_folder_configs(&self) -> Arc<WatchFolderConfigs> {
+        self.kernel.read_watch_folder_configs()
+    }
+    pub(crate) fn write_watch_folder_configs(&self, new_configs: WatchFolderConfigs) {
+        self.kernel.write_watch_folder_configs(new_configs);
+    }
+    pub(crate) fn watch_folder_active(&self) -> bool {
+        self.kernel.watch_folder_active()
+    }
+
+    // PLAYBACK
+    // NOTE: the handle is for the audio output device, which the app owns on the main thread.
+    pub(crate) fn playback_handle(&self) -> PlaybackHandle {
+        self.kernel.playback_handle()
+    }
+    pub(crate) fn load_playback_audio(&self, audio_files: Vec<PathBuf>) {
+        self.kernel.load_playback_audio(audio_files);
+    }
+    pub(crate) fn playback_loading(&self) -> bool {
+        self.kernel.playback_loading()
+    }
+    pub(crate) fn playback_playing(&self) -> bool {
+        self.kernel.playback_playing()
+    }
+    pub(crate) fn playback_path(&self) -> Option<PathBuf> {
+        self.kernel.playback_path()
+    }
+    pub(crate) fn playback_position(&self) -> Duration {
+        self.kernel.playback_position()
+    }
+    pub(crate) fn playback_duration(&self) -> Duration {
+        self.kernel.playback_duration()
+    }
+    pub(crate) fn play_playback(&self) {
+        self.kernel.play_playback();
+    }
+    pub(crate) fn pause_playback(&self) {
+        self.kernel.pause_playback();
+    }
+    pub(crate) fn seek_playback(&self, position: Duration) {
+        self.kernel.seek_playback(position);
     }
 
     // RECORDER
@@ -251,12 +444,33 @@ impl RibbleController {
         self.kernel.write_recorder_configs(new_configs);
     }
 
+    // INPUT DEVICES
+    pub(crate) fn audio_backend_kind(&self) -> &AudioBackendKind {
+        self.kernel.audio_backend_kind()
+    }
+    pub(crate) fn read_input_devices(&self) -> Arc<Vec<Arc<str>>> {
+        self.kernel.read_input_devices()
+    }
+    // NOTE: these are for the main thread, which owns the audio devices.
+    pub(crate) fn set_input_devices(&self, input_devices: Vec<Arc<str>>) {
+        self.kernel.set_input_devices(input_devices);
+    }
+    pub(crate) fn input_device_disconnected(&self, name: Arc<str>) {
+        self.kernel.input_device_disconnected(name);
+    }
+
     pub(crate) fn read_export_format(&self) -> RibbleExportFormat {
         self.kernel.read_export_format()
     }
     pub(crate) fn write_export_format(&self, export_format: RibbleExportFormat) {
         self.kernel.write_export_format(export_format);
     }
+    pub(crate) fn read_opus_bitrate(&self) -> RibbleOpusBitrate {
+        self.kernel.read_opus_bitrate()
+    }
+    pub(crate) fn write_opus_bitrate(&self, opus_bitrate: RibbleOpusBitrate) {
+        self.kernel.write_opus_bitrate(opus_bitrate);
+    }
 
     pub(crate) fn start_recording(&self) {
         self.kernel.start_recording();
@@ -273,7 +487,7 @@ impl RibbleController {
     pub(crate) fn clear_recording_cache(&self) {
         self.kernel.clear_recording_cache()
     }
-    // pub(crate) fn try_get_latest_recording(&self) -> Option<PathBuf> {
+    // pub(crate) fn try_get_latest_recording(&self) -> Option<Vec<PathBuf>> {
     //     self.kernel.try_get_latest_recording()
     // }
 
@@ -293,8 +507,19 @@ impl RibbleController {
         self.kernel.try_read_recording_metadata(copy_buffer)
     }
 
-    pub(crate) fn try_get_recording_path(&self, file_name: Arc<str>) -> Option<PathBuf> {
-        self.kernel.try_get_recording_path(file_name)
+    pub(crate) fn try_get_recording_segments(&self, file_name: Arc<str>) -> Option<Vec<PathBuf>> {
+        self.kernel.try_get_recording_segments(file_name)
+    }
+
+    pub(crate) fn read_recording_library_configs(&self) -> Arc<RecordingLibraryConfigs> {
+        self.kernel.read_recording_library_configs()
+    }
+    pub(crate) fn write_recording_library_configs(&self, new_configs: RecordingLibraryConfigs) {
+        self.kernel.write_recording_library_configs(new_configs);
+    }
+    // NOTE: an empty label resets the recording to its default name.
+    pub(crate) fn set_recording_label(&self, recording_file_name: &str, label: &str) {
+        self.kernel.set_recording_label(recording_file_name, label);
     }
 
     // NOTE: recording_file_name is internal -- It's the left-half of the (Arc<str>, CompletedRecordingJobs) tuple.
@@ -375,6 +600,31 @@ impl RibbleController {
     pub(crate) fn abort_download(&self, download_id: usize) {
         self.kernel.abort_download(download_id);
     }
+    pub(crate) fn pause_download(&self, download_id: usize) {
+        self.kernel.pause_download(download_id);
+    }
+    // Resumes a paused download, or retries a failed one.
+    pub(crate) fn resume_download(&self, download_id: usize) {
+        self.kernel.resume_download(download_id);
+    }
+
+    pub(crate) fn read_download_configs(&self) -> Arc<DownloadConfigs> {
+        self.kernel.read_download_configs()
+    }
+    pub(crate) fn write_download_configs(&self, new_configs: DownloadConfigs) {
+        self.kernel.write_download_configs(new_configs);
+    }
+
+    // MODEL CATALOG
+    pub(crate) fn refresh_model_catalog(&self) {
+        self.kernel.refresh_model_catalog();
+    }
+    pub(crate) fn read_model_catalog(&self) -> Option<Arc<ModelCatalog>> {
+        self.kernel.read_model_catalog()
+    }
+    pub(crate) fn install_catalog_model(&self, model: CatalogModel) {
+        self.kernel.install_catalog_model(model);
+    }
 
     // VISUALIZER
     pub(crate) fn set_visualizer_visibility(&self, is_visible: bool) {
@@ -399,3 +649,34 @@ impl RibbleController {
         self.kernel.rotate_visualizer_type(direction);
     }
 }
+
+// For running without a window (e.g. the CLI); see: HeadlessKernel.
+#[derive(Clone)]
+pub(crate) struct HeadlessController {
+    kernel: Arc<HeadlessKernel>,
+}
+
+impl HeadlessController {
+    pub(crate) fn new(data_directory: &Path) -> Result<Self, RibbleError> {
+        let kernel = Arc::new(HeadlessKernel::new(data_directory)?);
+        Ok(Self { kernel })
+    }
+
+    pub(crate) fn transcribe_file_blocking(
+        &self,
+        audio_file_path: PathBuf,
+    ) -> Result<RibbleMessage, RibbleError> {
+        self.kernel.transcribe_file_blocking(audio_file_path)
+    }
+
+    pub(crate) fn format_transcription(
+        &self,
+        format: TranscriptFormat,
+    ) -> Result<String, RibbleError> {
+        self.kernel.format_transcription(format)
+    }
+
+    pub(crate) fn try_read_progress_metadata(&self, copy_buffer: &mut Vec<Progress>) {
+        self.kernel.try_read_progress_metadata(copy_buffer);
+    }
+}
@@ -0,0 +1,485 @@
+// Crash recovery for the current session.
+// While the app is running, the transcript (and the recording in progress, if any) are
+// periodically checkpointed to the session directory. On a clean exit, the checkpoints are
+// removed; if they're still there on the next launch, the app didn't shut down properly and the
+// runner offers to restore them.
+//
+// NOTE: the recording itself is already on disk (see: recording_segments.rs); the recording
+// checkpoint only records which recording was in progress, so that it can be restored (or
+// discarded) as part of the session.
+use crate::controller::recording_segments::parse_segment_file_name;
+use crate::controller::transcript::{SegmentState, Transcript, TranscriptSegment};
+use crate::utils::errors::RibbleError;
+use crate::utils::transcript_formats::format_rfc3339;
+use parking_lot::Mutex;
+use ribble_whisper::transcriber::TranscriptionSnapshot;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+const SESSION_DIR_SLUG: &str = "session";
+const TRANSCRIPT_CHECKPOINT_FILE: &str = "transcript.json";
+const RECORDING_CHECKPOINT_FILE: &str = "recording.json";
+// Transcript updates arrive far more often than this; it's only worth losing a few seconds.
+const TRANSCRIPT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CheckpointSegment {
+    start: Duration,
+    end: Duration,
+    text: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct TranscriptCheckpoint {
+    saved_at: SystemTime,
+    // The snapshot text, including anything that was still pending.
+    text: String,
+    #[serde(default)]
+    segments: Vec<CheckpointSegment>,
+    #[serde(default)]
+    audio_file: Option<PathBuf>,
+    #[serde(default)]
+    whisper_timestamps: bool,
+}
+
+impl TranscriptCheckpoint {
+    pub(super) fn saved_at(&self) -> SystemTime {
+        self.saved_at
+    }
+
+    // Restored text can't be revised by the transcriber anymore, so it's all confirmed.
+    pub(super) fn into_parts(self) -> (TranscriptionSnapshot, Transcript, Option<PathBuf>) {
+        let snapshot = TranscriptionSnapshot::new(Arc::from(self.text), Default::default());
+        let segments = self
+            .segments
+            .into_iter()
+            .map(|segment| {
+                TranscriptSegment::new(
+                    segment.start,
+                    segment.end,
+                    &segment.text,
+                    SegmentState::Confirmed,
+                )
+            })
+            .collect();
+        let transcript = if self.whisper_timestamps {
+            Transcript::from_whisper_segments(segments)
+        } else {
+            Transcript::from_segments(segments)
+        };
+        (snapshot, transcript, self.audio_file)
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(super) struct RecordingCheckpoint {
+    recording_directory: PathBuf,
+    ticket_no: usize,
+    started_at: SystemTime,
+}
+
+impl RecordingCheckpoint {
+    pub(super) fn ticket_no(&self) -> usize {
+        self.ticket_no
+    }
+    pub(super) fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+}
+
+// What was left behind by a session that didn't exit cleanly.
+pub(crate) struct UnfinishedSession {
+    transcript: Option<TranscriptCheckpoint>,
+    recording: Option<RecordingCheckpoint>,
+}
+
+impl UnfinishedSession {
+    // For the restore prompt.
+    pub(crate) fn description(&self) -> String {
+        let mut description = "Ribble didn't shut down properly last time.".to_string();
+        if let Some(transcript) = self.transcript.as_ref() {
+            let num_words = transcript.text.split_whitespace().count();
+            description.push_str(&format!(
+                "\n\nUnsaved transcript: {num_words} words (last saved: {}).",
+                format_rfc3339(transcript.saved_at)
+            ));
+        }
+        if let Some(recording) = self.recording.as_ref() {
+            description.push_str(&format!(
+                "\n\nUnfinished recording (started: {}).",
+                format_rfc3339(recording.started_at)
+            ));
+        }
+        description.push_str("\n\nRestore them?");
+        description
+    }
+}
+
+pub(crate) fn find_unfinished_session(data_directory: &Path) -> Option<UnfinishedSession> {
+    let session_directory = data_directory.join(SESSION_DIR_SLUG);
+    let transcript = read_checkpoint::<TranscriptCheckpoint>(
+        session_directory.join(TRANSCRIPT_CHECKPOINT_FILE).as_path(),
+    )
+    .filter(|transcript| !transcript.text.trim().is_empty());
+    let recording = read_checkpoint::<RecordingCheckpoint>(
+        session_directory.join(RECORDING_CHECKPOINT_FILE).as_path(),
+    );
+
+    (transcript.is_some() || recording.is_some()).then_some(UnfinishedSession {
+        transcript,
+        recording,
+    })
+}
+
+// Removes the checkpoints, and the files of the recording that was in progress.
+pub(crate) fn discard_unfinished_session(data_directory: &Path) {
+    let session_directory = data_directory.join(SESSION_DIR_SLUG);
+    let recording = read_checkpoint::<RecordingCheckpoint>(
+        session_directory.join(RECORDING_CHECKPOINT_FILE).as_path(),
+    );
+
+    if let Some(recording) = recording
+        && let Ok(entries) = std::fs::read_dir(recording.recording_directory.as_path())
+    {
+        for entry in entries.flatten() {
+            let is_part_of_recording = entry
+                .file_name()
+                .to_str()
+                .and_then(parse_segment_file_name)
+                .is_some_and(|(ticket_no, _)| ticket_no == recording.ticket_no);
+            if is_part_of_recording && let Err(e) = std::fs::remove_file(entry.path()) {
+                log::warn!(
+                    "Failed to remove unfinished recording: {}\nError: {e}",
+                    entry.path().display()
+                );
+            }
+        }
+    }
+
+    remove_checkpoint(session_directory.join(TRANSCRIPT_CHECKPOINT_FILE).as_path());
+    remove_checkpoint(session_directory.join(RECORDING_CHECKPOINT_FILE).as_path());
+}
+
+// Shared between the engines that have something worth checkpointing.
+pub(super) struct SessionCheckpointer {
+    session_directory: PathBuf,
+    // Headless runs don't checkpoint, and must leave the app's checkpoints alone.
+    enabled: bool,
+    // Set on a clean exit, so that late writes (from threads that are still winding down) can't
+    // leave a checkpoint behind.
+    closed: AtomicBool,
+    last_transcript_checkpoint: Mutex<Option<Instant>>,
+}
+
+impl SessionCheckpointer {
+    pub(super) fn new(data_directory: &Path, enabled: bool) -> Self {
+        Self {
+            session_directory: data_directory.join(SESSION_DIR_SLUG),
+            enabled,
+            closed: AtomicBool::new(false),
+            last_transcript_checkpoint: Mutex::new(None),
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.enabled && !self.closed.load(Ordering::Acquire)
+    }
+
+    fn transcript_path(&self) -> PathBuf {
+        self.session_directory.join(TRANSCRIPT_CHECKPOINT_FILE)
+    }
+    fn recording_path(&self) -> PathBuf {
+        self.session_directory.join(RECORDING_CHECKPOINT_FILE)
+    }
+
+    // Unless forced, this only writes every TRANSCRIPT_CHECKPOINT_INTERVAL.
+    pub(super) fn checkpoint_transcript(
+        &self,
+        snapshot: &TranscriptionSnapshot,
+        transcript: &Transcript,
+        audio_file: Option<&Path>,
+        force: bool,
+    ) {
+        if !self.active() {
+            return;
+        }
+        {
+            let mut last_checkpoint = self.last_transcript_checkpoint.lock();
+            if !force
+                && last_checkpoint
+                    .is_some_and(|last| last.elapsed() < TRANSCRIPT_CHECKPOINT_INTERVAL)
+            {
+                return;
+            }
+            *last_checkpoint = Some(Instant::now());
+        }
+
+        let checkpoint = TranscriptCheckpoint {
+            saved_at: SystemTime::now(),
+            text: snapshot.clone().into_string(),
+            segments: transcript
+                .segments()
+                .iter()
+                .map(|segment| CheckpointSegment {
+                    start: segment.start(),
+                    end: segment.end(),
+                    text: segment.text().to_string(),
+                })
+                .collect(),
+            audio_file: audio_file.map(Path::to_path_buf),
+            whisper_timestamps: transcript.has_whisper_timestamps(),
+        };
+
+        if let Err(e) = self.write_checkpoint(self.transcript_path().as_path(), &checkpoint) {
+            log::warn!("Failed to checkpoint transcript.\nError: {e}");
+        }
+    }
+
+    pub(super) fn clear_transcript(&self) {
+        if self.active() {
+            remove_checkpoint(self.transcript_path().as_path());
+        }
+    }
+
+    pub(super) fn checkpoint_recording(
+        &self,
+        recording_directory: &Path,
+        ticket_no: usize,
+        started_at: SystemTime,
+    ) {
+        if !self.active() {
+            return;
+        }
+        let checkpoint = RecordingCheckpoint {
+            recording_directory: recording_directory.to_path_buf(),
+            ticket_no,
+            started_at,
+        };
+        if let Err(e) = self.write_checkpoint(self.recording_path().as_path(), &checkpoint) {
+            log::warn!("Failed to checkpoint recording.\nError: {e}");
+        }
+    }
+
+    pub(super) fn clear_recording(&self) {
+        if self.active() {
+            remove_checkpoint(self.recording_path().as_path());
+        }
+    }
+
+    pub(super) fn read_transcript_checkpoint(&self) -> Option<TranscriptCheckpoint> {
+        if !self.enabled {
+            return None;
+        }
+        read_checkpoint(self.transcript_path().as_path())
+    }
+
+    pub(super) fn read_recording_checkpoint(&self) -> Option<RecordingCheckpoint> {
+        if !self.enabled {
+            return None;
+        }
+        read_checkpoint(self.recording_path().as_path())
+    }
+
+    // Call on a clean exit.
+    pub(super) fn close(&self) {
+        if !self.enabled || self.closed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        remove_checkpoint(self.transcript_path().as_path());
+        remove_checkpoint(self.recording_path().as_path());
+    }
+
+    // Written to a temporary file first, so that a crash mid-write can't clobber the last good
+    // checkpoint.
+    fn write_checkpoint<T: serde::Serialize>(
+        &self,
+        path: &Path,
+        checkpoint: &T,
+    ) -> Result<(), RibbleError> {
+        std::fs::create_dir_all(self.session_directory.as_path())?;
+        let serialized = serde_json::to_string(checkpoint)?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(tmp_path.as_path(), serialized)?;
+        std::fs::rename(tmp_path.as_path(), path)?;
+        Ok(())
+    }
+}
+
+fn read_checkpoint<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let serialized = match std::fs::read_to_string(path) {
+        Ok(serialized) => serialized,
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                log::warn!("Failed to read checkpoint: {}\nError: {e}", path.display());
+            }
+            return None;
+        }
+    };
+    serde_json::from_str(&serialized)
+        .map_err(|e| log::warn!("Failed to parse checkpoint: {}\nError: {e}", path.display()))
+        .ok()
+}
+
+fn remove_checkpoint(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path)
+        && e.kind() != ErrorKind::NotFound
+    {
+        log::warn!(
+            "Failed to remove checkpoint: {}\nError: {e}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::recording_segments::segment_file_name;
+    use tempfile::TempDir;
+
+    fn checkpoint_text(checkpointer: &SessionCheckpointer, text: &str, force: bool) {
+        let snapshot = TranscriptionSnapshot::new(Arc::from(text), Default::default());
+        let transcript = Transcript::from_segments(vec![TranscriptSegment::new(
+            Duration::ZERO,
+            Duration::from_secs(1),
+            text,
+            SegmentState::Confirmed,
+        )]);
+        checkpointer.checkpoint_transcript(&snapshot, &transcript, None, force);
+    }
+
+    fn checkpointed_text(checkpointer: &SessionCheckpointer) -> Option<String> {
+        checkpointer
+            .read_transcript_checkpoint()
+            .map(|checkpoint| checkpoint.text)
+    }
+
+    fn recording_directory(directory: &TempDir) -> PathBuf {
+        let recording_directory = directory.path().join("recordings");
+        std::fs::create_dir_all(recording_directory.as_path()).unwrap();
+        recording_directory
+    }
+
+    #[test]
+    fn transcript_checkpoints_are_throttled_unless_forced() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+
+        checkpoint_text(&checkpointer, "First.", false);
+        assert_eq!(checkpointed_text(&checkpointer).as_deref(), Some("First."));
+
+        // Within the interval: skipped.
+        checkpoint_text(&checkpointer, "Second.", false);
+        assert_eq!(checkpointed_text(&checkpointer).as_deref(), Some("First."));
+
+        checkpoint_text(&checkpointer, "Third.", true);
+        assert_eq!(checkpointed_text(&checkpointer).as_deref(), Some("Third."));
+
+        // Once the interval has passed, it's written again.
+        *checkpointer.last_transcript_checkpoint.lock() =
+            Instant::now().checked_sub(TRANSCRIPT_CHECKPOINT_INTERVAL);
+        checkpoint_text(&checkpointer, "Fourth.", false);
+        assert_eq!(checkpointed_text(&checkpointer).as_deref(), Some("Fourth."));
+    }
+
+    #[test]
+    fn a_torn_write_leaves_the_last_checkpoint_intact() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+        checkpoint_text(&checkpointer, "Saved.", true);
+
+        let tmp_path = checkpointer.transcript_path().with_extension("json.tmp");
+        assert!(!tmp_path.exists());
+
+        // e.g. the app crashed halfway through writing the next checkpoint.
+        std::fs::write(tmp_path.as_path(), "{\"saved_at\":").unwrap();
+        assert_eq!(checkpointed_text(&checkpointer).as_deref(), Some("Saved."));
+
+        checkpoint_text(&checkpointer, "Saved again.", true);
+        assert_eq!(
+            checkpointed_text(&checkpointer).as_deref(),
+            Some("Saved again.")
+        );
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn closing_clears_the_checkpoints_for_good() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+        checkpoint_text(&checkpointer, "Unsaved words.", true);
+        checkpointer.checkpoint_recording(directory.path(), 0, SystemTime::now());
+        assert!(find_unfinished_session(directory.path()).is_some());
+
+        checkpointer.close();
+        assert!(find_unfinished_session(directory.path()).is_none());
+
+        // Late writes from engines that are still shutting down.
+        checkpoint_text(&checkpointer, "Late words.", true);
+        checkpointer.checkpoint_recording(directory.path(), 1, SystemTime::now());
+        assert!(find_unfinished_session(directory.path()).is_none());
+    }
+
+    #[test]
+    fn headless_runs_leave_the_checkpoints_alone() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+        checkpoint_text(&checkpointer, "Unsaved words.", true);
+
+        let headless = SessionCheckpointer::new(directory.path(), false);
+        assert!(headless.read_transcript_checkpoint().is_none());
+        checkpoint_text(&headless, "Headless words.", true);
+        headless.close();
+
+        assert_eq!(
+            checkpointed_text(&checkpointer).as_deref(),
+            Some("Unsaved words.")
+        );
+    }
+
+    #[test]
+    fn an_unfinished_session_needs_something_to_restore() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+        assert!(find_unfinished_session(directory.path()).is_none());
+
+        // Blank transcripts aren't worth restoring.
+        checkpoint_text(&checkpointer, "  ", true);
+        assert!(find_unfinished_session(directory.path()).is_none());
+
+        checkpointer.checkpoint_recording(directory.path(), 3, SystemTime::UNIX_EPOCH);
+        let session = find_unfinished_session(directory.path()).unwrap();
+        assert!(session.transcript.is_none());
+        assert_eq!(session.recording.as_ref().map(|r| r.ticket_no()), Some(3));
+        assert!(session.description().contains("Unfinished recording"));
+    }
+
+    #[test]
+    fn discarding_removes_the_checkpoints_and_the_unfinished_recording() {
+        let directory = tempfile::tempdir().unwrap();
+        let recording_directory = recording_directory(&directory);
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+        checkpoint_text(&checkpointer, "Unsaved words.", true);
+        checkpointer.checkpoint_recording(recording_directory.as_path(), 2, SystemTime::now());
+
+        let file_names = [
+            segment_file_name(2, 0),
+            segment_file_name(2, 1),
+            segment_file_name(3, 0),
+        ];
+        for file_name in file_names.iter() {
+            std::fs::write(recording_directory.join(file_name), b"RIFF").unwrap();
+        }
+
+        discard_unfinished_session(directory.path());
+
+        assert!(find_unfinished_session(directory.path()).is_none());
+        assert!(!recording_directory.join(&file_names[0]).exists());
+        assert!(!recording_directory.join(&file_names[1]).exists());
+        // Anything else is left to the writer (see: WriterEngine::discard_orphaned_recordings).
+        assert!(recording_directory.join(&file_names[2]).exists());
+    }
+}
@@ -0,0 +1,528 @@
+// Test doubles for driving the engines without the kernel, a sound card, or a model.
+//
+// - CapturedBus: a Bus whose receiving ends are kept by the test. Jobs handed off by the engines
+//   go to a real worker engine, which reports them on the console like it does in the app.
+//   Progress requests are answered (the engines block on the job id), and everything else that
+//   passes through is recorded.
+// - ScriptedBackend: an audio backend that plays a fixed script of audio, then either closes (like
+//   an unplugged device) or keeps streaming silence until the capture is closed.
+// - ScriptedWhisper: stands in for whisper in a real-time run; it sends a fixed script of
+//   snapshots, then finishes (or fails) the way the real transcriber would.
+// - StubModelRetriever: knows no models, so any attempt at transcription fails.
+//
+// NOTE: the engines shut down via flags, channel disconnects and timeouts; a regression there
+// shows up as a hang. Everything here waits with a timeout, so that a hang fails the test instead.
+use crate::controller::transcriber::{RealtimeReady, RealtimeRun, RealtimeWhisper};
+use crate::controller::worker::WorkerEngine;
+use crate::controller::{
+    Bus, ConsoleMessage, DownloadRequest, ProgressMessage, VisualizerPacket, WorkRequest,
+    WriteRequest, SMALL_UTILITY_QUEUE_SIZE, UTILITY_QUEUE_SIZE,
+};
+use crate::utils::recorder_configs::RibbleRecordingConfigs;
+use ribble_whisper::audio::audio_backend::{AudioBackend, CaptureSpec};
+use ribble_whisper::audio::audio_ring_buffer::AudioRingBuffer;
+use ribble_whisper::audio::microphone::{MicCapture, RibbleAudioFormat};
+use ribble_whisper::audio::recorder::{ArcChannelSink, SampleSink};
+use ribble_whisper::transcriber::vad::VAD;
+use ribble_whisper::transcriber::{TranscriptionSnapshot, WhisperOutput};
+use ribble_whisper::utils::errors::RibbleWhisperError;
+use ribble_whisper::utils::{get_channel, Receiver, Sender};
+use ribble_whisper::whisper::configs::WhisperRealtimeConfigs;
+use ribble_whisper::whisper::model::{ModelId, ModelLocation, ModelRetriever};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub(super) const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+pub(super) const SCRIPTED_SAMPLE_RATE: usize = 16000;
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+const SILENCE_INTERVAL: Duration = Duration::from_millis(10);
+
+// Runs the closure on its own thread and waits for it to return. None if it didn't return by the
+// timeout (or panicked); a hung thread is left behind, so the test fails instead of hanging.
+pub(super) fn finish_within<T, F>(timeout: Duration, f: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = get_channel(1);
+    std::thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+    receiver.recv_timeout(timeout).ok()
+}
+
+// Collects a write job's audio, failing the test if the job's channel isn't closed by the timeout
+// (i.e. the engine is still holding on to its end).
+pub(super) fn drain_write_job(
+    receiver: &Receiver<Arc<[f32]>>,
+    timeout: Duration,
+) -> Vec<Arc<[f32]>> {
+    let deadline = Instant::now() + timeout;
+    let mut audio = vec![];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(chunk) => audio.push(chunk),
+            Err(e) if e.is_disconnected() => return audio,
+            Err(_) => panic!("Write job still open after {timeout:?}."),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum ProgressEvent {
+    Requested {
+        job_id: usize,
+        job_name: &'static str,
+    },
+    Updated {
+        job_id: usize,
+    },
+    Removed {
+        job_id: usize,
+    },
+}
+
+// What the engines (and the worker) sent over the bus.
+pub(super) struct BusRecord {
+    pub(super) console_messages: Vec<ConsoleMessage>,
+    pub(super) progress_events: Vec<ProgressEvent>,
+    pub(super) write_jobs: Vec<(Receiver<Arc<[f32]>>, RibbleRecordingConfigs)>,
+    pub(super) num_visualizer_packets: usize,
+}
+
+impl BusRecord {
+    // The names of the progress jobs that were requested but never removed.
+    pub(super) fn unfinished_progress_jobs(&self) -> Vec<&'static str> {
+        self.progress_events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::Requested { job_id, job_name } => {
+                    let removed = self
+                        .progress_events
+                        .contains(&ProgressEvent::Removed { job_id: *job_id });
+                    (!removed).then_some(*job_name)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub(super) fn num_console_errors(&self) -> usize {
+        self.console_messages
+            .iter()
+            .filter(|message| matches!(message, ConsoleMessage::Error(_)))
+            .count()
+    }
+}
+
+pub(super) struct CapturedBus {
+    bus: Bus,
+    worker: Option<WorkerEngine>,
+    console_receiver: Receiver<ConsoleMessage>,
+    console_messages: Vec<ConsoleMessage>,
+    write_receiver: Receiver<WriteRequest>,
+    visualizer_receiver: Receiver<VisualizerPacket>,
+    // Kept so that the engines' sends don't fail.
+    _download_receiver: Receiver<DownloadRequest>,
+    progress_sender: Sender<ProgressMessage>,
+    progress_thread: Option<JoinHandle<Vec<ProgressEvent>>>,
+}
+
+impl CapturedBus {
+    pub(super) fn new() -> Self {
+        let (console_sender, console_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (progress_sender, progress_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (work_sender, work_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (write_sender, write_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (visualizer_sender, visualizer_receiver) = get_channel(UTILITY_QUEUE_SIZE);
+        let (download_sender, download_receiver) = get_channel(SMALL_UTILITY_QUEUE_SIZE);
+
+        let bus = Bus::new(
+            console_sender,
+            progress_sender.clone(),
+            work_sender,
+            write_sender,
+            visualizer_sender,
+            download_sender,
+        );
+
+        let worker = WorkerEngine::new(work_receiver, &bus).expect("Failed to start the worker.");
+        let progress_thread = std::thread::spawn(move || answer_progress(progress_receiver));
+
+        Self {
+            bus,
+            worker: Some(worker),
+            console_receiver,
+            console_messages: vec![],
+            write_receiver,
+            visualizer_receiver,
+            _download_receiver: download_receiver,
+            progress_sender,
+            progress_thread: Some(progress_thread),
+        }
+    }
+
+    pub(super) fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    // The worker reports each job on the console once it's been joined, so this doubles as
+    // waiting for an engine's job to finish. It stays in the record.
+    pub(super) fn next_console_message(&mut self, timeout: Duration) -> Option<&ConsoleMessage> {
+        let message = self.console_receiver.recv_timeout(timeout).ok()?;
+        self.console_messages.push(message);
+        self.console_messages.last()
+    }
+
+    // For tests that need to act on a write job while the engine is still running; jobs taken
+    // here don't show up in the record.
+    pub(super) fn next_write_job(
+        &self,
+        timeout: Duration,
+    ) -> Option<(Receiver<Arc<[f32]>>, RibbleRecordingConfigs)> {
+        self.write_receiver.recv_timeout(timeout).ok()?.unpack()
+    }
+
+    // Call this once the engines' jobs have been reported; everything they sent is in the record.
+    pub(super) fn finish(mut self) -> BusRecord {
+        // The worker only finishes once it has joined every job it was handed.
+        if let Some(worker) = self.worker.take() {
+            if let Err(e) = self.bus.work_request_sender().send(WorkRequest::Shutdown) {
+                panic!("Worker closed early: {e}");
+            }
+            assert!(
+                finish_within(TEST_TIMEOUT, move || drop(worker)).is_some(),
+                "Worker did not finish within {TEST_TIMEOUT:?}; a job is likely hung."
+            );
+        }
+
+        // The progress queue is FIFO, so the shutdown is only seen once everything before it has
+        // been recorded.
+        self.progress_sender
+            .send(ProgressMessage::Shutdown)
+            .expect("Progress recorder closed early.");
+        let progress_events = self
+            .progress_thread
+            .take()
+            .map(|thread| thread.join().expect("Progress recorder panicked."))
+            .unwrap_or_default();
+
+        let mut console_messages = std::mem::take(&mut self.console_messages);
+        console_messages.extend(self.console_receiver.try_iter());
+
+        BusRecord {
+            console_messages,
+            progress_events,
+            write_jobs: self
+                .write_receiver
+                .try_iter()
+                .filter_map(WriteRequest::unpack)
+                .collect(),
+            num_visualizer_packets: self.visualizer_receiver.try_iter().count(),
+        }
+    }
+}
+
+impl Drop for CapturedBus {
+    fn drop(&mut self) {
+        // Only reached with a worker after a failed test: it may be stuck joining a hung job, and
+        // dropping it would hang the test instead of failing it.
+        if let Some(worker) = self.worker.take() {
+            std::mem::forget(worker);
+        }
+    }
+}
+
+// Hands out job ids (like the progress engine), and records what happens to them.
+fn answer_progress(progress_receiver: Receiver<ProgressMessage>) -> Vec<ProgressEvent> {
+    let mut events = vec![];
+    let mut next_id = 0;
+    while let Ok(message) = progress_receiver.recv() {
+        let event = match message {
+            ProgressMessage::Request {
+                job,
+                id_return_sender,
+            } => {
+                let job_id = next_id;
+                next_id += 1;
+                // If the engine has stopped waiting, there's nothing to answer.
+                let _ = id_return_sender.send(job_id);
+                ProgressEvent::Requested {
+                    job_id,
+                    job_name: job.job_name(),
+                }
+            }
+            ProgressMessage::Increment { job_id, .. }
+            | ProgressMessage::Decrement { job_id, .. }
+            | ProgressMessage::Set { job_id, .. }
+            | ProgressMessage::Reset { job_id } => ProgressEvent::Updated { job_id },
+            ProgressMessage::Remove { job_id } => ProgressEvent::Removed { job_id },
+            ProgressMessage::Shutdown => break,
+        };
+        events.push(event);
+    }
+    events
+}
+
+// The sample value of every frame in the nth chunk of a script.
+pub(super) fn scripted_sample(chunk: usize) -> f32 {
+    (chunk % 100) as f32 / 100.0
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(super) struct ScriptedAudio {
+    num_chunks: usize,
+    chunk_len: usize,
+    then_silence: bool,
+}
+
+impl ScriptedAudio {
+    // Mono, at SCRIPTED_SAMPLE_RATE; the source closes once the chunks run out.
+    pub(super) fn new(num_chunks: usize, chunk_len: usize) -> Self {
+        Self {
+            num_chunks,
+            chunk_len,
+            then_silence: false,
+        }
+    }
+
+    // Keeps streaming silence after the script, until the capture is closed.
+    pub(super) fn then_silence(mut self) -> Self {
+        self.then_silence = true;
+        self
+    }
+}
+
+pub(super) struct ScriptedBackend {
+    // None: the device fails to open.
+    script: Option<ScriptedAudio>,
+    num_opened: AtomicUsize,
+    num_closed: AtomicUsize,
+}
+
+impl ScriptedBackend {
+    pub(super) fn new(script: ScriptedAudio) -> Self {
+        Self {
+            script: Some(script),
+            num_opened: AtomicUsize::new(0),
+            num_closed: AtomicUsize::new(0),
+        }
+    }
+
+    pub(super) fn failing() -> Self {
+        Self {
+            script: None,
+            ..Self::new(ScriptedAudio::new(0, 0))
+        }
+    }
+
+    pub(super) fn num_opened(&self) -> usize {
+        self.num_opened.load(Ordering::Acquire)
+    }
+    pub(super) fn num_closed(&self) -> usize {
+        self.num_closed.load(Ordering::Acquire)
+    }
+}
+
+impl AudioBackend<ArcChannelSink<f32>> for ScriptedBackend {
+    type Capture = ScriptedCapture;
+
+    fn open_capture(
+        &self,
+        _spec: CaptureSpec,
+        sink: ArcChannelSink<f32>,
+    ) -> Result<Self::Capture, RibbleWhisperError> {
+        let script = self.script.ok_or(RibbleWhisperError::DeviceError(
+            "Scripted device failed to open.".to_string(),
+        ))?;
+        self.num_opened.fetch_add(1, Ordering::AcqRel);
+        Ok(ScriptedCapture::new(script, sink))
+    }
+
+    fn close_capture(&self, capture: Self::Capture) {
+        self.num_closed.fetch_add(1, Ordering::AcqRel);
+        drop(capture);
+    }
+}
+
+pub(super) struct ScriptedCapture {
+    chunk_len: usize,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+    feeder: Option<JoinHandle<()>>,
+}
+
+impl ScriptedCapture {
+    fn new(script: ScriptedAudio, sink: ArcChannelSink<f32>) -> Self {
+        let playing = Arc::new(AtomicBool::new(false));
+        let closed = Arc::new(AtomicBool::new(false));
+        let feeder_playing = Arc::clone(&playing);
+        let feeder_closed = Arc::clone(&closed);
+        let feeder = std::thread::spawn(move || feed(script, sink, feeder_playing, feeder_closed));
+
+        Self {
+            chunk_len: script.chunk_len,
+            playing,
+            closed,
+            feeder: Some(feeder),
+        }
+    }
+}
+
+fn feed(
+    script: ScriptedAudio,
+    mut sink: ArcChannelSink<f32>,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+) {
+    let mut next_chunk = 0;
+    while !closed.load(Ordering::Acquire) {
+        if !playing.load(Ordering::Acquire) {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        if next_chunk < script.num_chunks {
+            sink.push(&vec![scripted_sample(next_chunk); script.chunk_len]);
+            next_chunk += 1;
+        } else if script.then_silence {
+            sink.push(&vec![0.0; script.chunk_len]);
+            std::thread::sleep(SILENCE_INTERVAL);
+        } else {
+            // Dropping the sink closes the audio channel, as an unplugged device would.
+            break;
+        }
+    }
+}
+
+impl Drop for ScriptedCapture {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+        if let Some(feeder) = self.feeder.take() {
+            feeder.join().expect("Scripted audio feeder panicked.");
+        }
+    }
+}
+
+impl MicCapture for ScriptedCapture {
+    fn play(&self) {
+        self.playing.store(true, Ordering::Release);
+    }
+
+    fn pause(&self) {
+        self.playing.store(false, Ordering::Release);
+    }
+
+    fn sample_rate(&self) -> usize {
+        SCRIPTED_SAMPLE_RATE
+    }
+
+    fn format(&self) -> RibbleAudioFormat {
+        RibbleAudioFormat::F32
+    }
+
+    fn channels(&self) -> u8 {
+        1
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.chunk_len
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ScriptedOutcome {
+    // Runs until stopped, then returns the final transcription.
+    Finish(&'static str),
+    FailToBuild,
+    // Fails once the snapshots have been sent.
+    FailWhileRunning,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct ScriptedWhisper {
+    // The confirmed text of each snapshot; they're all sent as soon as the stream starts.
+    snapshots: Vec<&'static str>,
+    outcome: ScriptedOutcome,
+}
+
+impl ScriptedWhisper {
+    pub(super) fn new(snapshots: &[&'static str], final_transcription: &'static str) -> Self {
+        Self {
+            snapshots: snapshots.to_vec(),
+            outcome: ScriptedOutcome::Finish(final_transcription),
+        }
+    }
+
+    // Like a missing or broken model.
+    pub(super) fn failing_to_build() -> Self {
+        Self {
+            snapshots: vec![],
+            outcome: ScriptedOutcome::FailToBuild,
+        }
+    }
+
+    pub(super) fn failing_while_running(snapshots: &[&'static str]) -> Self {
+        Self {
+            snapshots: snapshots.to_vec(),
+            outcome: ScriptedOutcome::FailWhileRunning,
+        }
+    }
+}
+
+impl RealtimeWhisper for ScriptedWhisper {
+    fn build<'a, M, V>(
+        &self,
+        _configs: WhisperRealtimeConfigs,
+        _audio_ring_buffer: &'a AudioRingBuffer<f32>,
+        output_sender: Sender<WhisperOutput>,
+        _vad: V,
+        _shared_model_retriever: Arc<M>,
+    ) -> Result<(RealtimeRun<'a>, RealtimeReady<'a>), RibbleWhisperError>
+    where
+        M: ModelRetriever + Send + Sync + 'a,
+        V: VAD<f32> + Send + Sync + 'a,
+    {
+        let final_transcription = match self.outcome {
+            ScriptedOutcome::FailToBuild => {
+                return Err(RibbleWhisperError::DeviceError(
+                    "Scripted transcriber failed to build.".to_string(),
+                ));
+            }
+            ScriptedOutcome::Finish(final_transcription) => Some(final_transcription),
+            ScriptedOutcome::FailWhileRunning => None,
+        };
+
+        let snapshots = self.snapshots.clone();
+        let run: RealtimeRun<'a> = Box::new(move |run_transcription, _slow_stop| {
+            for confirmed in snapshots {
+                let snapshot = TranscriptionSnapshot::new(Arc::from(confirmed), Default::default());
+                output_sender
+                    .send(WhisperOutput::TranscriptionSnapshot(Arc::new(snapshot)))
+                    .expect("Real-time print thread closed early.");
+            }
+
+            let Some(final_transcription) = final_transcription else {
+                return Err(RibbleWhisperError::DeviceError(
+                    "Scripted transcriber failed while running.".to_string(),
+                ));
+            };
+            while run_transcription.load(Ordering::Acquire) {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            Ok(final_transcription.to_string())
+        });
+        Ok((run, Box::new(|| true)))
+    }
+}
+
+pub(super) struct StubModelRetriever;
+
+impl ModelRetriever for StubModelRetriever {
+    fn retrieve_model(&self, _model_id: ModelId) -> Option<ModelLocation> {
+        None
+    }
+}
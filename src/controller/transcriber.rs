@@ -1,22 +1,38 @@
+use crate::controller::batch_queue::{BatchJob, BatchJobStatus};
+use crate::controller::live_sink::LiveTranscriptSink;
+use crate::controller::recording_segments::{load_normalized_segments, segments_num_frames};
+use crate::controller::session_checkpoint::SessionCheckpointer;
+use crate::controller::transcript::{
+    whisper_samples_to_duration, whisper_ticks_to_duration, OfflineSegment,
+    OfflineTranscriptTracker, RealtimeTranscriptTracker, Transcript, TranscriptEdit,
+    TranscriptionRunInfo, TranscriptionSource,
+};
 use crate::controller::VisualizerPacket;
 use crate::controller::WriteRequest;
 use crate::controller::{
-    AtomicOfflineTranscriberFeedback, Bus, ConsoleMessage, OfflineTranscriberFeedback, Progress,
-    ProgressMessage, RibbleMessage, WorkRequest, UTILITY_QUEUE_SIZE,
+    AtomicOfflineTranscriberFeedback, Bus, ConsoleMessage, ModelFile, OfflineTranscriberFeedback,
+    Progress, ProgressMessage, RibbleMessage, WorkRequest, AUDIO_FEED_TIMEOUT, UTILITY_QUEUE_SIZE,
 };
-use crate::utils::audio_gain::AudioGainConfigs;
+use crate::utils::audio_gain::{AudioGainConfigs, AudioGainMode};
+use crate::utils::batch_configs::BatchConfigs;
 use crate::utils::dc_block::DCBlock;
 use crate::utils::errors::RibbleError;
+use crate::utils::live_sink_configs::LiveSinkConfigs;
+use crate::utils::loudness::normalize_loudness;
+use crate::utils::noise_suppression::NoiseSuppressionConfigs;
+use crate::utils::transcript_formats::{
+    format_json, format_srt, format_webvtt, SubtitleConfigs, TranscriptFormat,
+};
 use crate::utils::recorder_configs::{
     RibbleChannels, RibblePeriod, RibbleRecordingConfigs, RibbleSampleRate,
 };
 use crate::utils::vad_configs::{NopVAD, VadConfigs, VadType};
 use arc_swap::ArcSwap;
-use crossbeam::channel::TrySendError;
+use crossbeam::channel::{RecvTimeoutError, TrySendError};
 use crossbeam::scope;
+use parking_lot::{Mutex, RwLock};
 use ribble_whisper::audio::audio_backend::{AudioBackend, CaptureSpec};
 use ribble_whisper::audio::audio_ring_buffer::AudioRingBuffer;
-use ribble_whisper::audio::loading::{audio_file_num_frames, load_normalized_audio_file};
 use ribble_whisper::audio::microphone::MicCapture;
 use ribble_whisper::audio::recorder::ArcChannelSink;
 use ribble_whisper::audio::{AudioChannelConfiguration, WhisperAudioSample};
@@ -27,7 +43,9 @@ use ribble_whisper::transcriber::{
     redirect_whisper_logging_to_hooks, TranscriptionSnapshot, WhisperCallbacks,
     WhisperControlPhrase, WhisperOutput, WHISPER_SAMPLE_RATE,
 };
-use ribble_whisper::utils::callback::{RibbleWhisperCallback, StaticRibbleWhisperCallback};
+use ribble_whisper::utils::callback::{
+    RibbleWhisperCallback, SegmentCallbackData, StaticRibbleWhisperCallback,
+};
 use ribble_whisper::utils::errors::RibbleWhisperError;
 use ribble_whisper::utils::{get_channel, Sender};
 use ribble_whisper::whisper::configs::WhisperRealtimeConfigs;
@@ -35,10 +53,64 @@ use ribble_whisper::whisper::model::ModelRetriever;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use slab::Slab;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
+// Runs the stream until it's stopped (run flag, slow-stop flag), returning the final transcription.
+pub(super) type RealtimeRun<'a> = Box<
+    dyn FnOnce(Arc<AtomicBool>, Arc<AtomicBool>) -> Result<String, RibbleWhisperError> + Send + 'a,
+>;
+// Whether the stream is ready for audio; anything pushed before then is dropped.
+pub(super) type RealtimeReady<'a> = Box<dyn Fn() -> bool + Send + 'a>;
+
+// The whisper end of a real-time run: it reads the ring buffer and sends its output back to the
+// engine. Everything around it (capture, fanout, recording, transcript tracking) stays the same,
+// so the tests swap in a scripted one to drive that without a model.
+pub(super) trait RealtimeWhisper: Send + Sync {
+    fn build<'a, M, V>(
+        &self,
+        configs: WhisperRealtimeConfigs,
+        audio_ring_buffer: &'a AudioRingBuffer<f32>,
+        output_sender: Sender<WhisperOutput>,
+        vad: V,
+        shared_model_retriever: Arc<M>,
+    ) -> Result<(RealtimeRun<'a>, RealtimeReady<'a>), RibbleWhisperError>
+    where
+        M: ModelRetriever + Send + Sync + 'a,
+        V: VAD<f32> + Send + Sync + 'a;
+}
+
+pub(super) struct WhisperRealtime;
+
+impl RealtimeWhisper for WhisperRealtime {
+    fn build<'a, M, V>(
+        &self,
+        configs: WhisperRealtimeConfigs,
+        audio_ring_buffer: &'a AudioRingBuffer<f32>,
+        output_sender: Sender<WhisperOutput>,
+        vad: V,
+        shared_model_retriever: Arc<M>,
+    ) -> Result<(RealtimeRun<'a>, RealtimeReady<'a>), RibbleWhisperError>
+    where
+        M: ModelRetriever + Send + Sync + 'a,
+        V: VAD<f32> + Send + Sync + 'a,
+    {
+        let (transcriber, transcriber_handle) = RealtimeTranscriberBuilder::<V, M>::new()
+            .with_configs(configs)
+            .with_audio_buffer(audio_ring_buffer)
+            .with_output_sender(output_sender)
+            .with_voice_activity_detector(vad)
+            .with_shared_model_retriever(shared_model_retriever)
+            .build()?;
+        Ok((
+            Box::new(move |run, slow_stop| transcriber.run_stream(run, slow_stop)),
+            Box::new(move || transcriber_handle.ready()),
+        ))
+    }
+}
+
 // TODO: double-check the real-time print-update loop: make sure it ends when the queue goes out of scope instead of just the flag.
 struct TranscriberEngineState {
     transcription_configs: ArcSwap<WhisperRealtimeConfigs>,
@@ -47,13 +119,31 @@ struct TranscriberEngineState {
     offline_running: Arc<AtomicBool>,
     slow_stop: Arc<AtomicBool>,
     current_audio_file_path: ArcSwap<Option<PathBuf>>,
+    // A recording split across segment files: all of its segments, in order (the first is also
+    // the current audio file path). Empty for anything else.
+    current_audio_segments: ArcSwap<Vec<PathBuf>>,
+    batch_queue: RwLock<Slab<BatchJob>>,
+    batch_running: Arc<AtomicBool>,
+    // Held while starting a run, so that checking for a running transcriber and claiming it
+    // happen in one step.
+    start_lock: Mutex<()>,
+    batch_configs: ArcSwap<BatchConfigs>,
     offline_transcriber_feedback: Arc<AtomicOfflineTranscriberFeedback>,
     audio_gain_settings: ArcSwap<AudioGainConfigs>,
+    noise_suppression_configs: ArcSwap<NoiseSuppressionConfigs>,
+    subtitle_configs: ArcSwap<SubtitleConfigs>,
+    live_sink_configs: ArcSwap<LiveSinkConfigs>,
     current_snapshot: ArcSwap<TranscriptionSnapshot>,
+    current_transcript: ArcSwap<Transcript>,
+    // The transcriber's own output, kept from the first edit so that the edits can be reverted.
+    unedited_transcript: ArcSwap<Option<(Arc<Transcript>, Arc<TranscriptionSnapshot>)>>,
+    last_run_info: ArcSwap<Option<TranscriptionRunInfo>>,
     current_control_phrase: ArcSwap<WhisperControlPhrase>,
+    console_message_sender: Sender<ConsoleMessage>,
     progress_message_sender: Sender<ProgressMessage>,
     visualizer_sample_sender: Sender<VisualizerPacket>,
     write_request_sender: Sender<WriteRequest>,
+    session_checkpointer: Arc<SessionCheckpointer>,
 }
 
 impl TranscriberEngineState {
@@ -62,6 +152,11 @@ impl TranscriberEngineState {
         start_v_configs: Option<VadConfigs>,
         start_feedback_type: Option<OfflineTranscriberFeedback>,
         start_audio_gain_settings: Option<AudioGainConfigs>,
+        start_noise_suppression_configs: Option<NoiseSuppressionConfigs>,
+        start_subtitle_configs: Option<SubtitleConfigs>,
+        start_batch_configs: Option<BatchConfigs>,
+        start_live_sink_configs: Option<LiveSinkConfigs>,
+        session_checkpointer: Arc<SessionCheckpointer>,
         bus: &Bus,
     ) -> Self {
         let transcription_configs = ArcSwap::new(Arc::new(start_configs.unwrap_or_default()));
@@ -70,12 +165,25 @@ impl TranscriberEngineState {
         let offline_running = Arc::new(AtomicBool::new(false));
         let slow_stop = Arc::new(AtomicBool::new(false));
         let current_audio_file_path = ArcSwap::new(Arc::new(None));
+        let current_audio_segments = ArcSwap::new(Arc::new(Vec::new()));
+        let batch_queue = RwLock::new(Slab::new());
+        let batch_running = Arc::new(AtomicBool::new(false));
+        let start_lock = Mutex::new(());
+        let batch_configs = ArcSwap::new(Arc::new(start_batch_configs.unwrap_or_default()));
         let transcriber_feedback =
             AtomicOfflineTranscriberFeedback::new(start_feedback_type.unwrap_or_default());
         let offline_transcriber_feedback = Arc::new(transcriber_feedback);
         let audio_gain_settings =
             ArcSwap::new(Arc::new(start_audio_gain_settings.unwrap_or_default()));
+        let noise_suppression_configs =
+            ArcSwap::new(Arc::new(start_noise_suppression_configs.unwrap_or_default()));
+        let subtitle_configs = ArcSwap::new(Arc::new(start_subtitle_configs.unwrap_or_default()));
+        let live_sink_configs =
+            ArcSwap::new(Arc::new(start_live_sink_configs.unwrap_or_default()));
         let current_snapshot = ArcSwap::new(Arc::new(TranscriptionSnapshot::default()));
+        let current_transcript = ArcSwap::new(Arc::new(Transcript::default()));
+        let unedited_transcript = ArcSwap::new(Arc::new(None));
+        let last_run_info = ArcSwap::new(Arc::new(None));
         let current_control_phrase = ArcSwap::new(Arc::new(WhisperControlPhrase::default()));
         Self {
             transcription_configs,
@@ -84,16 +192,41 @@ impl TranscriberEngineState {
             offline_running,
             slow_stop,
             current_audio_file_path,
+            current_audio_segments,
+            batch_queue,
+            batch_running,
+            start_lock,
+            batch_configs,
             offline_transcriber_feedback,
             audio_gain_settings,
+            noise_suppression_configs,
+            subtitle_configs,
+            live_sink_configs,
             current_snapshot,
+            current_transcript,
+            unedited_transcript,
+            last_run_info,
             current_control_phrase,
+            console_message_sender: bus.console_message_sender(),
             progress_message_sender: bus.progress_message_sender(),
             visualizer_sample_sender: bus.visualizer_sample_sender(),
             write_request_sender: bus.write_request_sender(),
+            session_checkpointer,
         }
     }
 
+    // NOTE: this is called as the transcript updates; unless forced, the checkpointer only writes
+    // every so often.
+    fn checkpoint_transcript(&self, force: bool) {
+        let audio_file = self.current_audio_file_path.load();
+        self.session_checkpointer.checkpoint_transcript(
+            &self.current_snapshot.load(),
+            &self.current_transcript.load(),
+            audio_file.as_ref().as_deref(),
+            force,
+        );
+    }
+
     fn cleanup_remove_progress_job(&self, maybe_id: Option<usize>) {
         if let Some(id) = maybe_id {
             let remove_setup = ProgressMessage::Remove { job_id: id };
@@ -107,14 +240,32 @@ impl TranscriberEngineState {
         }
     }
 
-    fn build_vad_run_realtime<M, A>(
+    // Snapshots the settings that are about to be used for a transcription run, for exporting.
+    fn run_info(&self, source: TranscriptionSource) -> TranscriptionRunInfo {
+        TranscriptionRunInfo::new(
+            source,
+            *self.transcription_configs.load_full(),
+            *self.vad_configs.load_full(),
+            *self.audio_gain_settings.load_full(),
+            *self.noise_suppression_configs.load_full(),
+        )
+    }
+
+    fn record_run_info(&self, source: TranscriptionSource) {
+        let run_info = self.run_info(source);
+        self.last_run_info.store(Arc::new(Some(run_info)));
+    }
+
+    fn build_vad_run_realtime<M, A, W>(
         &self,
         audio_backend: &A,
         shared_model_retriever: Arc<M>,
+        whisper: &W,
     ) -> Result<RibbleMessage, RibbleError>
     where
         M: ModelRetriever + Send + Sync,
         A: AudioBackend<ArcChannelSink<f32>> + Send + Sync,
+        W: RealtimeWhisper,
     {
         let configs = *self.vad_configs.load_full();
         match configs.vad_type() {
@@ -122,39 +273,42 @@ impl TranscriberEngineState {
                 let vad = configs.build_silero().inspect_err(|_| {
                     self.realtime_running.store(false, Ordering::Release);
                 })?;
-                self.run_realtime_transcription(audio_backend, shared_model_retriever, vad)
+                self.run_realtime_transcription(audio_backend, shared_model_retriever, vad, whisper)
             }
             VadType::WebRtc => {
                 let vad = configs.build_webrtc().inspect_err(|_| {
                     self.realtime_running.store(false, Ordering::Release);
                 })?;
-                self.run_realtime_transcription(audio_backend, shared_model_retriever, vad)
+                self.run_realtime_transcription(audio_backend, shared_model_retriever, vad, whisper)
             }
             // VadType::Earshot => {
             //     let vad = configs.build_earshot()?;
-            //     self.run_realtime_transcription(audio_backend, shared_model_retriever, vad)
+            //     self.run_realtime_transcription(audio_backend, shared_model_retriever, vad, whisper)
             // }
             VadType::Auto => {
                 let vad = configs.build_auto().inspect_err(|_| {
                     self.realtime_running.store(false, Ordering::Release);
                 })?;
-                self.run_realtime_transcription(audio_backend, shared_model_retriever, vad)
+                self.run_realtime_transcription(audio_backend, shared_model_retriever, vad, whisper)
             }
         }
     }
 
-    fn run_realtime_transcription<M, A, V>(
+    fn run_realtime_transcription<M, A, V, W>(
         &self,
         audio_backend: &A,
         shared_model_retriever: Arc<M>,
         vad: V,
+        whisper: &W,
     ) -> Result<RibbleMessage, RibbleError>
     where
         M: ModelRetriever + Send + Sync,
         A: AudioBackend<ArcChannelSink<f32>> + Send + Sync,
         V: VAD<f32> + Send + Sync,
+        W: RealtimeWhisper,
     {
         self.clear_transcription();
+        self.record_run_info(TranscriptionSource::Microphone);
 
         // Send a progress job so the UI can be updated.
         let setup_progress = Progress::new_indeterminate("Setting up real-time transcription.");
@@ -196,33 +350,59 @@ impl TranscriberEngineState {
 
         let mic = audio_backend.open_capture(spec, sink).inspect_err(|_e| {
             self.cleanup_remove_progress_job(setup_id);
+            self.realtime_running.store(false, Ordering::Release);
         })?;
 
         // Get a copy of the configs
         let configs = *self.transcription_configs.load_full();
 
-        // Extract the gain configs, build a gain struct and see if it has a multiplier.
-        let audio_gain = self.audio_gain_settings.load_full().build_audio_gain();
-        let audio_gain = if audio_gain.no_gain() {
+        // Extract the gain configs and build the gain stage, if there's any gain to apply.
+        let audio_gain_settings = self.audio_gain_settings.load_full();
+        let mut audio_gain = if audio_gain_settings.no_gain() {
             None
         } else {
-            Some(audio_gain)
+            Some(audio_gain_settings.build_realtime_gain(WHISPER_SAMPLE_RATE as f32))
         };
 
-        let (transcriber, transcriber_handle) = RealtimeTranscriberBuilder::<V, M>::new()
-            .with_configs(configs)
-            .with_audio_buffer(&audio_ring_buffer)
-            .with_output_sender(text_sender)
-            .with_voice_activity_detector(vad)
-            .with_shared_model_retriever(shared_model_retriever)
-            .build()
-            .inspect_err(|_e| {
+        // The mic is whisper-ready (mono, 16kHz).
+        let noise_suppression = *self.noise_suppression_configs.load_full();
+        let mut noise_suppressor = (!noise_suppression.no_suppression())
+            .then(|| noise_suppression.build_noise_suppressor(WHISPER_SAMPLE_RATE as usize, 1));
+        let denoise_recording = noise_suppressor.is_some() && noise_suppression.use_recording();
+
+        let built = whisper.build(
+            configs,
+            &audio_ring_buffer,
+            text_sender,
+            vad,
+            shared_model_retriever,
+        );
+        let (run_transcriber, transcriber_ready) = match built {
+            Ok(built) => built,
+            Err(e) => {
+                // The capture is already open; it still has to go back to be closed.
                 self.cleanup_remove_progress_job(setup_id);
-            })?;
+                self.realtime_running.store(false, Ordering::Release);
+                audio_backend.close_capture(mic);
+                return Err(e.into());
+            }
+        };
 
         let recording_expected_available = Arc::new(AtomicBool::new(true));
         let a_thread_recording_expected_available = Arc::clone(&recording_expected_available);
 
+        // The number of (whisper-ready) samples pushed into the ring buffer.
+        // This is used as the clock for timing the transcript segments.
+        let audio_clock = Arc::new(AtomicU64::new(0));
+        let a_thread_audio_clock = Arc::clone(&audio_clock);
+        let p_thread_audio_clock = Arc::clone(&audio_clock);
+
+        // Opt-in: stream the transcript out as it arrives.
+        let live_sink = LiveTranscriptSink::open(
+            &self.live_sink_configs.load(),
+            self.console_message_sender.clone(),
+        );
+
         let result = scope(|s| {
             // Audio Fanout
             let a_thread_run_transcription = Arc::clone(&self.realtime_running);
@@ -263,43 +443,44 @@ impl TranscriberEngineState {
 
             // Spawn the scoped worker threads
             let _audio_fanout_thread = s.spawn(move |_| {
-                let mut rolling_peak: f32 = 1.0;
                 while a_thread_run_transcription.load(Ordering::Acquire) {
-                    match audio_receiver.recv() {
+                    match audio_receiver.recv_timeout(AUDIO_FEED_TIMEOUT) {
                         Ok(audio) => {
-                            if !transcriber_handle.ready() {
+                            if !transcriber_ready() {
                                 continue;
                             }
 
                             // Run a cheap DCBlock filter before pushing to the ring buffer
                             let dc_block =
                                 DCBlock::new().with_sample_rate(WHISPER_SAMPLE_RATE as f32);
+                            let mut filtered: Vec<f32> = dc_block.process_signal_map(audio.iter()).collect();
 
-                            // TODO: it might be more efficient to pre-calculate the expected peak
-                            // using the audio gain multiplier.
-                            let filtered: Vec<f32> = if let Some(gain) = audio_gain {
-                                let block_filter = dc_block.process_signal_map(audio.iter());
-                                let mut out: Vec<f32> = gain.apply_gain_map(block_filter).collect();
-                                rolling_peak = out.iter().copied().fold(rolling_peak, |acc, f| {
-                                    f32::max(acc, f.abs())
-                                }).max(1.0);
-                                out.iter_mut().for_each(|f| *f /= rolling_peak);
-                                debug_assert!(out.iter().all(|f| f.is_finite() && *f >= -1.0 && *f <= 1.0));
-                                out
+                            // Denoise before the gain, so that the noise isn't boosted along with
+                            // the speech.
+                            if let Some(suppressor) = noise_suppressor.as_mut() {
+                                filtered = suppressor.process(&filtered);
+                            }
+                            let recorded = if denoise_recording {
+                                Arc::from(filtered.as_slice())
                             } else {
-                                let mapped: Vec<f32> = dc_block.process_signal_map(audio.iter()).collect();
-                                debug_assert!(mapped.iter().all(|f| f.is_finite() && *f >= -1.0 && *f <= 1.0));
-                                mapped
+                                Arc::clone(&audio)
                             };
 
+                            // The gain stage ends in a limiter, so this stays in range.
+                            if let Some(gain) = audio_gain.as_mut() {
+                                gain.process(&mut filtered);
+                            }
+                            debug_assert!(filtered.iter().all(|f| f.is_finite() && *f >= -1.0 && *f <= 1.0));
+
                             // Write into the ringbuffer
                             audio_ring_buffer.push_audio(&filtered);
+                            a_thread_audio_clock.fetch_add(filtered.len() as u64, Ordering::Release);
                             // Fan the data out.
 
                             // If the write thread panics, the receiver will be deallocated.
                             // Stop the transcription because the recording is gone.
                             if let Err(TrySendError::Disconnected(_)) =
-                                write_sender.try_send(Arc::clone(&audio))
+                                write_sender.try_send(recorded)
                             {
                                 a_thread_recording_expected_available
                                     .store(false, Ordering::Release);
@@ -325,13 +506,28 @@ impl TranscriberEngineState {
                                 Error source: {:#?}", &e, e.source());
                             }
                         }
-                        Err(_) => a_thread_run_transcription.store(false, Ordering::Release),
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => a_thread_run_transcription.store(false, Ordering::Release),
                     }
                 }
+
+                // The last few milliseconds are still in the denoiser.
+                // NOTE: this blocks (briefly) if the channel is full; the writer keeps draining
+                // until this sender is dropped, so the tail isn't lost.
+                if denoise_recording
+                    && let Some(suppressor) = noise_suppressor.as_mut()
+                    && let Err(e) = write_sender.send(Arc::from(suppressor.flush()))
+                {
+                    log::warn!(
+                        "Writer thread disconnected, the denoised tail of the recording is lost.\n\
+                        Error source: {:#?}",
+                        e.source()
+                    );
+                }
             });
 
             let transcription_thread =
-                s.spawn(move |_| transcriber.run_stream(t_thread_run_transcription, t_thread_slow_stop).inspect_err(|_| {
+                s.spawn(move |_| run_transcriber(t_thread_run_transcription, t_thread_slow_stop).inspect_err(|_| {
                     // If this has early-returned an error, the other threads may not get the
                     // message.
                     self.realtime_running.store(false, Ordering::Release);
@@ -340,7 +536,7 @@ impl TranscriberEngineState {
             // For updating the inner transcription
             // It's easiest to just duplicate the logic across transcription impls; otherwise it
             // becomes a huge lifetime headache.
-            let _print_thread = s.spawn(move |_| {
+            let print_thread = s.spawn(move |_| {
                 // NOTE: test this for accidental deadlocking -> the atomic boolean here is a
                 // little conservative. This should go out of scope when the transcriber gets
                 // dropped. 
@@ -375,10 +571,21 @@ impl TranscriberEngineState {
                 //     }
                 // }
 
+                let mut transcript_tracker = RealtimeTranscriptTracker::new();
+                let mut live_sink = live_sink;
                 while let Ok(message) = text_receiver.recv() {
                     match message {
                         WhisperOutput::TranscriptionSnapshot(snapshot) => {
+                            let audio_position = whisper_samples_to_duration(
+                                p_thread_audio_clock.load(Ordering::Acquire),
+                            );
+                            let transcript = transcript_tracker.update(&snapshot, audio_position);
+                            if let Some(sink) = live_sink.as_mut() {
+                                sink.update(&transcript);
+                            }
+                            self.current_transcript.store(Arc::new(transcript));
                             self.current_snapshot.store(Arc::clone(&snapshot));
+                            self.checkpoint_transcript(false);
                         }
 
                         WhisperOutput::ControlPhrase(control) => {
@@ -399,10 +606,11 @@ impl TranscriberEngineState {
                         }
                     }
                 }
+                (transcript_tracker, live_sink)
             });
 
             // This -should- properly coerce into RibbleAppError, but it might need to be explicit.
-            transcription_thread
+            let transcription_result = transcription_thread
                 .join()
                 .unwrap_or_else(|e| {
                     self.realtime_running.store(false, Ordering::Release);
@@ -420,7 +628,18 @@ impl TranscriberEngineState {
                     } else {
                         e.into()
                     }
-                })
+                });
+
+            // The print thread finishes once the transcriber drops its output sender.
+            // If it somehow panicked, the segment timing is lost, but the final transcription
+            // string is still returned.
+            let (transcript_tracker, live_sink) = print_thread.join().unwrap_or_else(|e| {
+                log::warn!("Real-time print thread panicked, segment timing lost.\nError: {e:#?}");
+                (RealtimeTranscriptTracker::new(), None)
+            });
+
+            transcription_result
+                .map(|transcription| (transcription, transcript_tracker, live_sink))
         })
             // Since the type is opaque here (scope return), it's not entirely known as to what the error is.
             // The easiest thing to do here is to wrap it in a "ThreadPanic", as even if the exit is
@@ -436,9 +655,18 @@ impl TranscriberEngineState {
         audio_backend.close_capture(mic);
 
         // Unwrap the result -after- closing the microphone capture.
-        let result = result??;
+        // If a thread panicked, the flag might not have been reset.
+        let (result, transcript_tracker, live_sink) =
+            result.and_then(|result| result).inspect_err(|_e| {
+                self.realtime_running.store(false, Ordering::Release);
+            })?;
 
-        self.finalize_transcription(result);
+        let audio_position = whisper_samples_to_duration(audio_clock.load(Ordering::Acquire));
+        let transcript = transcript_tracker.finish(&result, audio_position);
+        if let Some(sink) = live_sink {
+            sink.finish(&transcript);
+        }
+        self.finalize_transcription(result, transcript);
 
         // In case something weird has happened, just set the flag to false.
         // (This should -always- be false because realtime has to be stopped via UI)
@@ -467,7 +695,43 @@ impl TranscriberEngineState {
     fn build_vad_run_offline<M>(
         &self,
         shared_model_retriever: Arc<M>,
+        audio_file_path: PathBuf,
     ) -> Result<RibbleMessage, RibbleError>
+    where
+        M: ModelRetriever + Sync + Send,
+    {
+        // Clear the previous transcription
+        self.clear_transcription();
+
+        self.record_run_info(TranscriptionSource::AudioFile(audio_file_path.clone()));
+
+        let progressive = matches!(
+            self.offline_transcriber_feedback.load(Ordering::Acquire),
+            OfflineTranscriberFeedback::Progressive
+        );
+        let (result, transcript) = self.build_vad_transcribe_file(
+            shared_model_retriever,
+            audio_file_path.as_path(),
+            progressive,
+        )?;
+        self.finalize_transcription(result, transcript);
+        self.offline_running.store(false, Ordering::Release);
+
+        // Finalize by preparing a status message for the console.
+        let message = format!("Finished transcribing: {}!", audio_file_path.display());
+        let console_message = ConsoleMessage::Status(message);
+        Ok(RibbleMessage::Console(console_message))
+    }
+
+    // Runs the offline transcriber over an audio file and returns the transcription.
+    // NOTE: only a progressive run publishes the transcript (as it arrives); otherwise the shared
+    // transcript is left alone, so that e.g. a batch job doesn't replace the one that's open.
+    fn build_vad_transcribe_file<M>(
+        &self,
+        shared_model_retriever: Arc<M>,
+        audio_file_path: &Path,
+        progressive: bool,
+    ) -> Result<(String, Transcript), RibbleError>
     where
         M: ModelRetriever + Sync + Send,
     {
@@ -478,54 +742,61 @@ impl TranscriberEngineState {
                     let vad = configs.build_silero().inspect_err(|_| {
                         self.offline_running.store(false, Ordering::Release);
                     })?;
-                    self.run_offline_transcription(shared_model_retriever, Some(vad))
+                    self.transcribe_file(
+                        shared_model_retriever,
+                        audio_file_path,
+                        Some(vad),
+                        progressive,
+                    )
                 }
                 VadType::WebRtc => {
                     let vad = configs.build_webrtc().inspect_err(|_| {
                         self.offline_running.store(false, Ordering::Release);
                     })?;
-                    self.run_offline_transcription(shared_model_retriever, Some(vad))
+                    self.transcribe_file(
+                        shared_model_retriever,
+                        audio_file_path,
+                        Some(vad),
+                        progressive,
+                    )
                 }
                 // VadType::Earshot => {
                 //     let vad = configs.build_earshot()?;
-                //     self.run_offline_transcription(shared_model_retriever, Some(vad))
+                //     self.transcribe_file(shared_model_retriever, audio_file_path, Some(vad), progressive)
                 // }
                 VadType::Auto => {
                     let vad = configs.build_auto().inspect_err(|_| {
                         self.offline_running.store(false, Ordering::Release);
                     })?;
-                    self.run_offline_transcription(shared_model_retriever, Some(vad))
+                    self.transcribe_file(
+                        shared_model_retriever,
+                        audio_file_path,
+                        Some(vad),
+                        progressive,
+                    )
                 }
             }
         } else {
-            self.run_offline_transcription(shared_model_retriever, None::<NopVAD>)
+            self.transcribe_file(
+                shared_model_retriever,
+                audio_file_path,
+                None::<NopVAD>,
+                progressive,
+            )
         }
     }
 
-    fn run_offline_transcription<M, V>(
+    fn transcribe_file<M, V>(
         &self,
         shared_model_retriever: Arc<M>,
+        audio_file_path: &Path,
         vad: Option<V>,
-    ) -> Result<RibbleMessage, RibbleError>
+        progressive: bool,
+    ) -> Result<(String, Transcript), RibbleError>
     where
         M: ModelRetriever + Sync + Send,
         V: VAD<f32> + Send + Sync,
     {
-        // Clear the previous transcription
-        self.clear_transcription();
-
-        // Unpack the audio.
-        let audio_path = self.current_audio_file_path.load_full();
-
-        let audio_file_path = if let Some(path) = audio_path.as_ref() {
-            Ok(path.clone())
-        } else {
-            Err(RibbleError::Core("Audio file path not loaded.".to_string()))
-        }
-            .inspect_err(|_e| {
-                self.realtime_running.store(false, Ordering::Release);
-            })?;
-
         // Send a progress job so the UI can be updated.
         let setup_progress = Progress::new_indeterminate("Setting up offline transcription.");
 
@@ -559,7 +830,8 @@ impl TranscriberEngineState {
         // realtime parameters.
         let configs = (*self.transcription_configs.load_full()).into_whisper_configs();
 
-        let n_frames = audio_file_num_frames(audio_file_path.as_path()).inspect_err(|_e| {
+        let audio_files = self.audio_files_for(audio_file_path);
+        let n_frames = segments_num_frames(&audio_files).inspect_err(|_e| {
             self.cleanup_remove_progress_job(setup_id);
             self.offline_running.store(false, Ordering::Release);
         })?;
@@ -612,45 +884,68 @@ impl TranscriberEngineState {
         };
 
         // Load the audio file.
-        let loaded_audio =
-            load_normalized_audio_file(audio_file_path.as_path(), Some(load_audio_callback))
-                .inspect_err(|_e| {
-                    self.cleanup_remove_progress_job(setup_id);
-                    self.cleanup_remove_progress_job(load_audio_id);
-                    self.offline_running.store(false, Ordering::Release);
-                })?;
+        let loaded_audio = load_normalized_segments(&audio_files, &load_audio_callback)
+            .inspect_err(|_e| {
+                self.cleanup_remove_progress_job(setup_id);
+                self.cleanup_remove_progress_job(load_audio_id);
+                self.offline_running.store(false, Ordering::Release);
+            })?;
 
         // Check for gain.
         let audio_gain_settings = self.audio_gain_settings.load_full();
         let audio_gain = if audio_gain_settings.use_offline() && !audio_gain_settings.no_gain() {
-            Some(audio_gain_settings.build_audio_gain())
+            Some(*audio_gain_settings)
         } else {
             None
         };
 
-        let audio = match loaded_audio {
+        // Check for noise suppression.
+        let noise_suppression = self.noise_suppression_configs.load_full();
+        let noise_suppressor =
+            if noise_suppression.use_offline() && !noise_suppression.no_suppression() {
+                Some(noise_suppression.build_noise_suppressor(WHISPER_SAMPLE_RATE as usize, 1))
+            } else {
+                None
+            };
+
+        let (audio, audio_duration) = match loaded_audio {
             WhisperAudioSample::F32(audio) => {
                 // TODO: determine whether to -actually- run the dc_block or not.
                 // PERHAPS IT COULD BE A TOGGLE.
                 let dc_block = DCBlock::new().with_sample_rate(WHISPER_SAMPLE_RATE as f32);
 
-                let filtered: Vec<f32> = if let Some(gain) = audio_gain {
-                    // TODO: it might be more efficient to pre-calculate the expected peak
-                    // using the audio gain multiplier.
-                    let block_filter = dc_block.process_signal_map(audio.iter());
-                    let mut out: Vec<f32> = gain.apply_gain_map(block_filter).collect();
-                    let peak = out
-                        .iter()
-                        .copied()
-                        .fold(1.0, |acc, f| f32::max(acc, f.abs()))
-                        .max(1.0);
-                    out.iter_mut().for_each(|f| *f /= peak);
-                    out
-                } else {
-                    dc_block.process_signal_map(audio.iter()).collect()
-                };
+                let mut filtered: Vec<f32> = dc_block.process_signal_map(audio.iter()).collect();
+
+                // Denoise before the gain, so that the noise isn't boosted along with the speech.
+                if let Some(suppressor) = noise_suppressor {
+                    filtered = suppressor.process_all(&filtered);
+                }
+
+                if let Some(gain_settings) = audio_gain {
+                    match gain_settings.mode() {
+                        AudioGainMode::Fixed => {
+                            // TODO: it might be more efficient to pre-calculate the expected peak
+                            // using the audio gain multiplier.
+                            let gain = gain_settings.build_audio_gain();
+                            filtered = gain.apply_gain_map(filtered.iter()).collect();
+                            let peak = filtered
+                                .iter()
+                                .copied()
+                                .fold(1.0, |acc, f| f32::max(acc, f.abs()))
+                                .max(1.0);
+                            filtered.iter_mut().for_each(|f| *f /= peak);
+                        }
+                        // The whole file is available, so measure it first, then normalize.
+                        AudioGainMode::Automatic => normalize_loudness(
+                            &mut filtered,
+                            WHISPER_SAMPLE_RATE as f32,
+                            gain_settings.agc_configs(),
+                        ),
+                    }
+                }
 
-                WhisperAudioSample::F32(Arc::from(filtered))
+                let audio_duration = whisper_samples_to_duration(filtered.len() as u64);
+                (WhisperAudioSample::F32(Arc::from(filtered)), audio_duration)
             }
             WhisperAudioSample::I16(_) => {
                 unreachable!("Loading normalized for whisper should never return integer audio.")
@@ -735,20 +1030,25 @@ impl TranscriberEngineState {
             let transcription_callback =
                 Some(StaticRibbleWhisperCallback::new(transcription_closure));
 
-            let segment_closure = move |segment_string| {
-                if let Err(e) = sender.try_send(segment_string) {
-                    log::warn!("Cannot send segment string.\n\
+            // NOTE: this blocks (rather than try_send) so that segments are never dropped from
+            // the timed transcript; the print thread drains the queue very quickly.
+            // The segment data carries whisper's timestamps (t0/t1) along with the text.
+            let segment_closure = move |segment: SegmentCallbackData| {
+                let segment = OfflineSegment {
+                    text: segment.text,
+                    start: whisper_ticks_to_duration(segment.start_timestamp),
+                    end: whisper_ticks_to_duration(segment.end_timestamp),
+                };
+                if let Err(e) = sender.send(segment) {
+                    log::warn!("Cannot send transcript segment.\n\
                         Error: {}\n\
                         Error source: {:#?}", &e, e.source());
                 }
             };
 
-            let offline_feedback = self.offline_transcriber_feedback.load(Ordering::Acquire);
-
-            let segment_callback = match offline_feedback {
-                OfflineTranscriberFeedback::Minimal => None,
-                OfflineTranscriberFeedback::Progressive => Some(RibbleWhisperCallback::new(segment_closure))
-            };
+            // The segment callback is always required to time the transcript.
+            // The feedback type only decides whether the snapshot is updated as segments arrive.
+            let segment_callback = Some(RibbleWhisperCallback::new(segment_closure));
 
             // With how the new_segment callback works, it's not possible atm to have an
             // early escape mechanism to avoid the heavy computation
@@ -771,60 +1071,43 @@ impl TranscriberEngineState {
             });
 
 
-            if matches!(offline_feedback, OfflineTranscriberFeedback::Progressive) {
-                let _print_thread = s.spawn(move |_| {
-
-                    // NOTE: this is going to cause allocation churn-there's not a lot I can do at
-                    // the moment without losing ArcSwap which works very well for the application
-                    // thus far.
-                    //
-                    // The alternative would be to grow a mutable string (yes, more efficient), and
-                    // set a locking mechanism (possibly not so efficient).
-                    //
-                    // For small strings, this will not be so bad; for large strings, it will
-                    // definitely be a pain.
-                    //
-                    // TODO: test this out on long audio to see what the allocation churn is like.
-                    let current_transcription: Arc<str> = Default::default();
-                    // while p_thread_running.load(Ordering::Acquire) {
-                    //     match receiver.recv() {
-                    //         Ok(new_segment) => {
-                    //             let current_transcription = if current_transcription.is_empty() {
-                    //                 Arc::from(new_segment)
-                    //             } else {
-                    //                 Arc::from(format!("{current_transcription} {new_segment}").trim())
-                    //             };
-                    //             let new_snapshot = Arc::new(
-                    //                 TranscriptionSnapshot::new(
-                    //                     Arc::clone(&current_transcription),
-                    //                     Arc::default()));
-                    //             self.current_snapshot.store(new_snapshot)
-                    //         }
-                    //         Err(_) => p_thread_running.store(false, Ordering::Release)
-                    //     }
-                    // }
-
-                    while let Ok(new_segment) = receiver.recv() {
-                        let current_transcription = if current_transcription.is_empty() {
-                            Arc::from(new_segment)
-                        } else {
-                            Arc::from(format!("{current_transcription} {new_segment}").trim())
-                        };
-                        let new_snapshot = Arc::new(
-                            TranscriptionSnapshot::new(
-                                Arc::clone(&current_transcription),
-                                Arc::default()));
-                        self.current_snapshot.store(new_snapshot)
+            let print_thread = s.spawn(move |_| {
+                let mut transcript_tracker = OfflineTranscriptTracker::new(audio_duration);
+
+                // NOTE: this is going to cause allocation churn-there's not a lot I can do at
+                // the moment without losing ArcSwap which works very well for the application
+                // thus far.
+                //
+                // The alternative would be to grow a mutable string (yes, more efficient), and
+                // set a locking mechanism (possibly not so efficient).
+                //
+                // For small strings, this will not be so bad; for large strings, it will
+                // definitely be a pain.
+                //
+                // TODO: test this out on long audio to see what the allocation churn is like.
+                while let Ok(segment) = receiver.recv() {
+                    transcript_tracker.push_segment(segment);
+
+                    if progressive {
+                        let transcript = transcript_tracker.current_transcript();
+                        let new_snapshot = Arc::new(TranscriptionSnapshot::new(
+                            Arc::from(transcript.text()),
+                            Arc::default(),
+                        ));
+                        self.current_transcript.store(Arc::new(transcript));
+                        self.current_snapshot.store(new_snapshot);
+                        self.checkpoint_transcript(false);
                     }
-                });
-            }
+                }
+                transcript_tracker
+            });
 
             // If the transcription thread panicked, it's because of an uncaught whisper error
             // -- and thus the progress job most likely needs to be removed.
             // It is also most likely that if this job is still in the buffer, it's the only
             // one in the buffer, (or it did get removed and the buffer is empty).
             // Test this, but if either prove to be true, then it shouldn't matter wrt remove_progress_job.
-            transcription_thread
+            let transcription_result = transcription_thread
                 .join()
                 .unwrap_or_else(|e| {
                     self.cleanup_remove_progress_job(transcription_id);
@@ -840,51 +1123,268 @@ impl TranscriberEngineState {
                     } else {
                         e.into()
                     }
-                })
+                });
+
+            // The print thread finishes once the whisper callbacks (and their senders) are dropped.
+            let transcript_tracker = print_thread.join().unwrap_or_else(|e| {
+                log::warn!("Offline print thread panicked, segment timing lost.\nError: {e:#?}");
+                OfflineTranscriptTracker::new(audio_duration)
+            });
+
+            transcription_result.map(|transcription| (transcription, transcript_tracker))
         })
             // NOTE: the type of this is opaque due to the scope return.
             // It is most likely to be a ThreadPanic (ThreadPanic), due to locally scoped threads.
             // If this is particularly obtrusive, look at trying to deduplicate.
             .map_err(|e| RibbleError::ThreadPanic(format!("Possible cause: {e:#?}")))??;
 
-        self.finalize_transcription(result);
-        self.offline_running.store(false, Ordering::Release);
+        let (result, transcript_tracker) = result;
+        let transcript = transcript_tracker.finish(&result);
+        Ok((result, transcript))
+    }
 
-        // Finalize by preparing a status message for the console.
-        let message = format!("Finished transcribing: {}!", audio_file_path.display());
+    // Runs the queued batch jobs one after another through the offline transcriber.
+    // A failed job does not stop the batch; it gets marked as failed and the next job runs.
+    fn run_batch_transcription<M>(
+        &self,
+        shared_model_retriever: Arc<M>,
+        model_file: Option<ModelFile>,
+    ) -> Result<RibbleMessage, RibbleError>
+    where
+        M: ModelRetriever + Sync + Send,
+    {
+        let num_queued = self
+            .batch_queue
+            .read()
+            .iter()
+            .filter(|(_, job)| job.status() == BatchJobStatus::Queued)
+            .count();
+
+        let batch_progress = Progress::new_determinate("Batch transcription", num_queued as u64);
+        let (id_sender, id_receiver) = get_channel(1);
+        let batch_progress_message = ProgressMessage::Request {
+            job: batch_progress,
+            id_return_sender: id_sender,
+        };
+
+        if let Err(e) = self.progress_message_sender.send(batch_progress_message) {
+            log::warn!(
+                "Progress engine closed, cannot send batch transcription job.\n\
+            Error source: {:#?}",
+                e.source()
+            );
+        }
+
+        let batch_id = match id_receiver.recv() {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log::warn!(
+                    "Progress engine did not complete batch transcription rendezvous.\n\
+                Error source: {:#?}",
+                    e.source()
+                );
+                None
+            }
+        };
+
+        let mut num_finished = 0usize;
+        let mut num_failed = 0usize;
+
+        while self.batch_running.load(Ordering::Acquire) {
+            // Grab the next queued job and mark it as running.
+            let next_job = self
+                .batch_queue
+                .write()
+                .iter_mut()
+                .find(|(_, job)| job.status() == BatchJobStatus::Queued)
+                .map(|(job_id, job)| {
+                    job.set_running();
+                    (
+                        job_id,
+                        job.audio_path().to_path_buf(),
+                        job.output_directory().map(Path::to_path_buf),
+                    )
+                });
+
+            let Some((job_id, audio_file_path, output_directory)) = next_job else {
+                break;
+            };
+
+            // NOTE: these are re-read for each job so that any changes apply to the rest of the
+            // queue.
+            let batch_configs = self.batch_configs.load_full();
+            let export_format = batch_configs.export_format();
+
+            // Each job is transcribed into its own transcript; the open one is left alone.
+            let run_info = self.run_info(TranscriptionSource::AudioFile(audio_file_path.clone()));
+            self.offline_running.store(true, Ordering::Release);
+            let result = self
+                .build_vad_transcribe_file(
+                    Arc::clone(&shared_model_retriever),
+                    audio_file_path.as_path(),
+                    false,
+                )
+                .and_then(|(text, transcript)| {
+                    // A stopped transcription still returns whatever it finished;
+                    // don't write a partial transcript.
+                    if !self.batch_running.load(Ordering::Acquire) {
+                        return Ok(None);
+                    }
+                    let contents = self.format_transcript(
+                        export_format,
+                        &text,
+                        &transcript,
+                        Some(&run_info),
+                        model_file.as_ref(),
+                    )?;
+                    let out_path = batch_configs
+                        .output_path_for(audio_file_path.as_path(), output_directory.as_deref());
+                    write_transcript(out_path.as_path(), &contents)?;
+                    Ok(Some(out_path))
+                });
+            self.offline_running.store(false, Ordering::Release);
+
+            if let Some(job) = self.batch_queue.write().get_mut(job_id) {
+                match &result {
+                    Ok(Some(out_path)) => job.set_finished(out_path.clone()),
+                    Ok(None) => job.set_cancelled(),
+                    Err(e) => job.set_failed(&e.to_string()),
+                }
+            }
+
+            match result {
+                Ok(Some(_)) => num_finished += 1,
+                Ok(None) => {}
+                Err(e) => {
+                    num_failed += 1;
+                    log::warn!(
+                        "Batch transcription failed for: {}\nError: {e}\nError source: {:#?}",
+                        audio_file_path.display(),
+                        e.source()
+                    );
+                }
+            }
+
+            if let Some(id) = batch_id {
+                let increment = ProgressMessage::Increment {
+                    job_id: id,
+                    delta: 1,
+                };
+                if let Err(e) = self.progress_message_sender.send(increment) {
+                    log::warn!(
+                        "Progress channel closed, cannot send batch increment progress message.\n\
+                    Error source: {:#?}",
+                        e.source()
+                    );
+                }
+            }
+        }
+
+        self.cleanup_remove_progress_job(batch_id);
+        let stopped = !self.batch_running.swap(false, Ordering::AcqRel);
+
+        let message = if stopped {
+            format!(
+                "Batch transcription stopped. Transcribed: {num_finished}, failed: {num_failed}."
+            )
+        } else {
+            format!(
+                "Batch transcription finished. Transcribed: {num_finished}, failed: {num_failed}."
+            )
+        };
         let console_message = ConsoleMessage::Status(message);
         Ok(RibbleMessage::Console(console_message))
     }
 
-    fn finalize_transcription(&self, final_transcription: String) {
+    fn finalize_transcription(&self, final_transcription: String, transcript: Transcript) {
         let confirmed_transcription = Arc::from(final_transcription);
         let snapshot = TranscriptionSnapshot::new(confirmed_transcription, Default::default());
         self.current_snapshot.store(Arc::new(snapshot));
+        self.current_transcript.store(Arc::new(transcript));
+        self.unedited_transcript.store(Arc::new(None));
         self.current_control_phrase
             .store(Arc::new(WhisperControlPhrase::default()));
+        self.checkpoint_transcript(true);
+    }
+
+    fn restore_transcript(
+        &self,
+        snapshot: TranscriptionSnapshot,
+        transcript: Transcript,
+        audio_file: Option<PathBuf>,
+    ) {
+        self.current_snapshot.store(Arc::new(snapshot));
+        self.current_transcript.store(Arc::new(transcript));
+        self.unedited_transcript.store(Arc::new(None));
+        self.current_audio_file_path.store(Arc::new(audio_file));
+        self.current_audio_segments.store(Arc::new(Vec::new()));
+    }
+
+    // The files to read for an audio file path: every segment if it's the current (segmented)
+    // recording, otherwise just the file itself.
+    fn audio_files_for(&self, audio_file_path: &Path) -> Vec<PathBuf> {
+        let segments = self.current_audio_segments.load();
+        match segments.first() {
+            Some(first) if first.as_path() == audio_file_path => segments.as_ref().clone(),
+            _ => vec![audio_file_path.to_path_buf()],
+        }
     }
 
     fn clear_transcription(&self) {
         self.current_snapshot
             .store(Arc::new(TranscriptionSnapshot::default()));
+        self.current_transcript.store(Arc::new(Transcript::default()));
+        self.unedited_transcript.store(Arc::new(None));
         self.current_control_phrase
-            .store(Arc::new(WhisperControlPhrase::default()))
+            .store(Arc::new(WhisperControlPhrase::default()));
+        self.session_checkpointer.clear_transcript();
     }
 
-    fn save_transcription(&self, out_path: PathBuf) -> Result<RibbleMessage, RibbleError> {
-        // Create a file for writing.
-        let file = File::create(out_path.as_path())?;
-        let mut bufwriter = BufWriter::new(file);
-        // Join the transcription
-        let full_transcription = self
-            .current_snapshot
-            .load_full()
-            .as_ref()
-            .clone()
-            .into_string()
-            .into_bytes();
+    // NOTE: the snapshot is rebuilt from the edited segments so that everything which reads it
+    // (copy, save, export, the control API) sees the corrected text.
+    fn edit_transcript(&self, edit: &TranscriptEdit) -> Result<(), RibbleError> {
+        let transcript = self.current_transcript.load_full();
+        let snapshot = self.current_snapshot.load_full();
+        let edited = Arc::new(transcript.with_edit(edit)?);
+        let edited_snapshot =
+            TranscriptionSnapshot::new(Arc::from(edited.text()), Default::default());
+
+        // In case a run started (and replaced the transcript) since it was loaded.
+        let previous = self
+            .current_transcript
+            .compare_and_swap(&transcript, Arc::clone(&edited));
+        if !Arc::ptr_eq(&previous, &transcript) {
+            return Err(RibbleError::Core(
+                "The transcript changed while it was being edited.".to_string(),
+            ));
+        }
+
+        if self.unedited_transcript.load().is_none() {
+            self.unedited_transcript
+                .store(Arc::new(Some((transcript, snapshot))));
+        }
+        self.current_snapshot.store(Arc::new(edited_snapshot));
+        self.checkpoint_transcript(true);
+        Ok(())
+    }
+
+    fn revert_transcript_edits(&self) {
+        let unedited = self.unedited_transcript.swap(Arc::new(None));
+        if let Some((transcript, snapshot)) = unedited.as_ref() {
+            self.current_snapshot.store(Arc::clone(snapshot));
+            self.current_transcript.store(Arc::clone(transcript));
+            self.checkpoint_transcript(true);
+        }
+    }
 
-        bufwriter.write_all(&full_transcription)?;
+    fn save_transcription(
+        &self,
+        out_path: PathBuf,
+        format: TranscriptFormat,
+        model_file: Option<ModelFile>,
+    ) -> Result<RibbleMessage, RibbleError> {
+        let file_contents = self.format_transcription(format, model_file)?;
+        write_transcript(out_path.as_path(), &file_contents)?;
 
         let console_message =
             ConsoleMessage::Status(format!("Transcription saved to: {}!", out_path.display()));
@@ -892,8 +1392,70 @@ impl TranscriberEngineState {
         let ribble_message = RibbleMessage::Console(console_message);
         Ok(ribble_message)
     }
+
+    fn format_transcription(
+        &self,
+        format: TranscriptFormat,
+        model_file: Option<ModelFile>,
+    ) -> Result<String, RibbleError> {
+        // Join the transcription
+        let text = self
+            .current_snapshot
+            .load_full()
+            .as_ref()
+            .clone()
+            .into_string();
+        let transcript = self.current_transcript.load_full();
+        let run_info = self.last_run_info.load_full();
+        self.format_transcript(
+            format,
+            &text,
+            &transcript,
+            run_info.as_ref().as_ref(),
+            model_file.as_ref(),
+        )
+    }
+
+    fn format_transcript(
+        &self,
+        format: TranscriptFormat,
+        text: &str,
+        transcript: &Transcript,
+        run_info: Option<&TranscriptionRunInfo>,
+        model_file: Option<&ModelFile>,
+    ) -> Result<String, RibbleError> {
+        let contents = match format {
+            TranscriptFormat::PlainText => text.to_string(),
+            TranscriptFormat::Srt | TranscriptFormat::WebVtt => {
+                if transcript.is_empty() {
+                    return Err(RibbleError::Core(
+                        "No timed transcript segments to export as subtitles.".to_string(),
+                    ));
+                }
+                let subtitle_configs = self.subtitle_configs.load_full();
+                if matches!(format, TranscriptFormat::Srt) {
+                    format_srt(transcript, &subtitle_configs)
+                } else {
+                    format_webvtt(transcript, &subtitle_configs)
+                }
+            }
+            TranscriptFormat::Json => format_json(transcript, text, run_info, model_file)?,
+        };
+        Ok(contents)
+    }
+}
+
+fn write_transcript(out_path: &Path, contents: &str) -> Result<(), RibbleError> {
+    let file = File::create(out_path)?;
+    let mut bufwriter = BufWriter::new(file);
+    bufwriter.write_all(contents.as_bytes())?;
+    bufwriter.flush()?;
+    Ok(())
 }
 
+// NOTE: this is a cheap clone (shared state + a channel sender); the watch folder needs its own
+// handle to queue files and start batches.
+#[derive(Clone)]
 pub(super) struct TranscriberEngine {
     inner: Arc<TranscriberEngineState>,
     work_request_sender: Sender<WorkRequest>,
@@ -906,6 +1468,11 @@ impl TranscriberEngine {
         start_vad_configs: Option<VadConfigs>,
         start_feedback_type: Option<OfflineTranscriberFeedback>,
         start_audio_gain_settings: Option<AudioGainConfigs>,
+        start_noise_suppression_configs: Option<NoiseSuppressionConfigs>,
+        start_subtitle_configs: Option<SubtitleConfigs>,
+        start_batch_configs: Option<BatchConfigs>,
+        start_live_sink_configs: Option<LiveSinkConfigs>,
+        session_checkpointer: Arc<SessionCheckpointer>,
         bus: &Bus,
     ) -> Self {
         let inner = Arc::new(TranscriberEngineState::new(
@@ -913,6 +1480,11 @@ impl TranscriberEngine {
             start_vad_configs,
             start_feedback_type,
             start_audio_gain_settings,
+            start_noise_suppression_configs,
+            start_subtitle_configs,
+            start_batch_configs,
+            start_live_sink_configs,
+            session_checkpointer,
             bus,
         ));
         Self {
@@ -922,7 +1494,10 @@ impl TranscriberEngine {
     }
 
     pub(super) fn transcriber_running(&self) -> bool {
-        self.realtime_running() || self.offline_running() || self.slow_stopping()
+        self.realtime_running()
+            || self.offline_running()
+            || self.batch_running()
+            || self.slow_stopping()
     }
     pub(super) fn realtime_running(&self) -> bool {
         self.inner.realtime_running.load(Ordering::Acquire)
@@ -930,15 +1505,31 @@ impl TranscriberEngine {
     pub(super) fn offline_running(&self) -> bool {
         self.inner.offline_running.load(Ordering::Acquire)
     }
+    pub(super) fn batch_running(&self) -> bool {
+        self.inner.batch_running.load(Ordering::Acquire)
+    }
 
     pub(super) fn slow_stopping(&self) -> bool {
         self.inner.slow_stop.load(Ordering::Acquire)
     }
 
+    // Marks a run as started, unless the transcriber is already running.
+    // NOTE: the UI, the watch folder and the control API can all try to start runs at once.
+    fn try_claim_transcriber(&self, running: &AtomicBool) -> bool {
+        let _guard = self.inner.start_lock.lock();
+        if self.transcriber_running() {
+            return false;
+        }
+        running.store(true, Ordering::Release);
+        true
+    }
+
     pub(super) fn stop_realtime(&self) {
         self.inner.realtime_running.store(false, Ordering::Release);
     }
+    // NOTE: this also stops the batch queue; the remaining jobs stay queued.
     pub(super) fn stop_offline(&self) {
+        self.inner.batch_running.store(false, Ordering::Release);
         self.inner.offline_running.store(false, Ordering::Release);
     }
 
@@ -974,6 +1565,41 @@ impl TranscriberEngine {
         self.inner.audio_gain_settings.store(Arc::new(new_settings));
     }
 
+    pub(super) fn read_noise_suppression_configs(&self) -> Arc<NoiseSuppressionConfigs> {
+        self.inner.noise_suppression_configs.load_full()
+    }
+
+    pub(super) fn write_noise_suppression_configs(&self, new_configs: NoiseSuppressionConfigs) {
+        self.inner
+            .noise_suppression_configs
+            .store(Arc::new(new_configs));
+    }
+
+    pub(super) fn read_subtitle_configs(&self) -> Arc<SubtitleConfigs> {
+        self.inner.subtitle_configs.load_full()
+    }
+
+    pub(super) fn write_subtitle_configs(&self, new_configs: SubtitleConfigs) {
+        self.inner.subtitle_configs.store(Arc::new(new_configs));
+    }
+
+    pub(super) fn read_live_sink_configs(&self) -> Arc<LiveSinkConfigs> {
+        self.inner.live_sink_configs.load_full()
+    }
+
+    // NOTE: changes apply from the next real-time transcription.
+    pub(super) fn write_live_sink_configs(&self, new_configs: LiveSinkConfigs) {
+        self.inner.live_sink_configs.store(Arc::new(new_configs));
+    }
+
+    pub(super) fn read_batch_configs(&self) -> Arc<BatchConfigs> {
+        self.inner.batch_configs.load_full()
+    }
+
+    pub(super) fn write_batch_configs(&self, new_configs: BatchConfigs) {
+        self.inner.batch_configs.store(Arc::new(new_configs));
+    }
+
     pub(super) fn write_transcription_configs(&self, configs: WhisperRealtimeConfigs) {
         self.inner.transcription_configs.store(Arc::new(configs));
     }
@@ -984,13 +1610,67 @@ impl TranscriberEngine {
     pub(super) fn read_transcription_snapshot(&self) -> Arc<TranscriptionSnapshot> {
         self.inner.current_snapshot.load_full()
     }
+    pub(super) fn read_transcript(&self) -> Arc<Transcript> {
+        self.inner.current_transcript.load_full()
+    }
     pub(super) fn read_latest_control_phrase(&self) -> Arc<WhisperControlPhrase> {
         self.inner.current_control_phrase.load_full()
     }
 
+    // The transcript is replaced wholesale while transcribing, so edits are only accepted once
+    // the transcriber has stopped.
+    pub(super) fn edit_transcript(&self, edit: &TranscriptEdit) -> Result<(), RibbleError> {
+        if self.batch_running() {
+            return Err(RibbleError::Core(
+                "The transcript cannot be edited while a batch is running.".to_string(),
+            ));
+        }
+        if self.transcriber_running() {
+            return Err(RibbleError::Core(
+                "The transcript cannot be edited while transcribing.".to_string(),
+            ));
+        }
+        self.inner.edit_transcript(edit)
+    }
+    pub(super) fn revert_transcript_edits(&self) {
+        if self.transcriber_running() {
+            return;
+        }
+        self.inner.revert_transcript_edits();
+    }
+    pub(super) fn restore_transcript(
+        &self,
+        snapshot: TranscriptionSnapshot,
+        transcript: Transcript,
+        audio_file: Option<PathBuf>,
+    ) {
+        self.inner
+            .restore_transcript(snapshot, transcript, audio_file);
+    }
+    pub(super) fn transcript_edited(&self) -> bool {
+        self.inner.unedited_transcript.load().is_some()
+    }
+
     fn update_current_audio_file_path(&self, path: Option<PathBuf>) {
         let new_path = Arc::new(path);
         self.inner.current_audio_file_path.swap(new_path);
+        self.inner.current_audio_segments.store(Arc::new(Vec::new()));
+    }
+
+    // NOTE: the segments are set after the path, so that they're never matched to a stale path.
+    pub(super) fn set_current_audio_segments(&self, segments: Vec<PathBuf>) {
+        let Some(first) = segments.first() else {
+            return;
+        };
+        self.update_current_audio_file_path(Some(first.clone()));
+        self.inner.current_audio_segments.store(Arc::new(segments));
+    }
+
+    pub(super) fn read_current_audio_files(&self) -> Vec<PathBuf> {
+        match self.inner.current_audio_file_path.load().as_ref() {
+            Some(path) => self.inner.audio_files_for(path.as_path()),
+            None => Vec::new(),
+        }
     }
 
     pub(super) fn set_current_audio_file_path(&self, path: PathBuf) {
@@ -1000,6 +1680,10 @@ impl TranscriberEngine {
         self.update_current_audio_file_path(None);
     }
 
+    pub(super) fn read_last_run_info(&self) -> Arc<Option<TranscriptionRunInfo>> {
+        self.inner.last_run_info.load_full()
+    }
+
     pub(super) fn read_current_audio_file_path(&self) -> Arc<Option<PathBuf>> {
         self.inner.current_audio_file_path.load_full()
     }
@@ -1011,12 +1695,36 @@ impl TranscriberEngine {
     ) where
         M: ModelRetriever + Send + Sync + 'static,
         A: AudioBackend<ArcChannelSink<f32>> + Send + Sync + 'static,
+    {
+        self.start_realtime_transcription_with(
+            audio_backend,
+            shared_model_retriever,
+            WhisperRealtime,
+        )
+    }
+
+    pub(super) fn start_realtime_transcription_with<M, A, W>(
+        &self,
+        audio_backend: Arc<A>,
+        shared_model_retriever: Arc<M>,
+        whisper: W,
+    ) where
+        M: ModelRetriever + Send + Sync + 'static,
+        A: AudioBackend<ArcChannelSink<f32>> + Send + Sync + 'static,
+        W: RealtimeWhisper + 'static,
     {
         // Set the flag that the realtime runner is running so that the UI can update.
-        self.inner.realtime_running.store(true, Ordering::Release);
+        if !self.try_claim_transcriber(&self.inner.realtime_running) {
+            log::warn!("Cannot start real-time transcription, the transcriber is busy.");
+            return;
+        }
         let thread_inner = Arc::clone(&self.inner);
         let worker = std::thread::spawn(move || {
-            thread_inner.build_vad_run_realtime(audio_backend.as_ref(), shared_model_retriever)
+            thread_inner.build_vad_run_realtime(
+                audio_backend.as_ref(),
+                shared_model_retriever,
+                &whisper,
+            )
         });
 
         let work_request = WorkRequest::Long(worker);
@@ -1036,13 +1744,26 @@ impl TranscriberEngine {
         M: ModelRetriever + Send + Sync + 'static,
     {
         // Set the flag that the offline runner is running so that the UI can update.
-        self.inner.offline_running.store(true, Ordering::Release);
+        if !self.try_claim_transcriber(&self.inner.offline_running) {
+            log::warn!("Cannot start offline transcription, the transcriber is busy.");
+            return;
+        }
 
         let thread_inner = Arc::clone(&self.inner);
 
         // Set up the worker.
-        let worker =
-            std::thread::spawn(move || thread_inner.build_vad_run_offline(shared_model_retriever));
+        let worker = std::thread::spawn(move || {
+            let audio_file_path = thread_inner
+                .current_audio_file_path
+                .load_full()
+                .as_ref()
+                .clone()
+                .ok_or(RibbleError::Core("Audio file path not loaded.".to_string()))
+                .inspect_err(|_e| {
+                    thread_inner.offline_running.store(false, Ordering::Release);
+                })?;
+            thread_inner.build_vad_run_offline(shared_model_retriever, audio_file_path)
+        });
 
         // Send off the request
         let work_request = WorkRequest::Long(worker);
@@ -1057,9 +1778,141 @@ impl TranscriberEngine {
         }
     }
 
-    pub(super) fn save_transcription(&self, out_path: PathBuf) {
+    // Runs an offline transcription on the calling thread, bypassing the worker queue.
+    // This is for headless (CLI) runs, which need the result directly instead of via the console.
+    pub(super) fn transcribe_file_blocking<M>(
+        &self,
+        shared_model_retriever: Arc<M>,
+        audio_file_path: PathBuf,
+    ) -> Result<RibbleMessage, RibbleError>
+    where
+        M: ModelRetriever + Send + Sync,
+    {
+        if !self.try_claim_transcriber(&self.inner.offline_running) {
+            return Err(RibbleError::Core(
+                "Offline transcription already running.".to_string(),
+            ));
+        }
+        self.inner
+            .build_vad_run_offline(shared_model_retriever, audio_file_path)
+    }
+
+    // NOTE: the model file is only used for metadata in JSON exports.
+    // Returns false (and leaves the queue alone) if the transcriber is already running.
+    pub(super) fn start_batch_transcription<M>(
+        &self,
+        shared_model_retriever: Arc<M>,
+        model_file: Option<ModelFile>,
+    ) -> bool
+    where
+        M: ModelRetriever + Send + Sync + 'static,
+    {
+        // Set the flags so that the UI can update.
+        if !self.try_claim_transcriber(&self.inner.batch_running) {
+            log::warn!("Cannot start batch transcription, the transcriber is busy.");
+            return false;
+        }
+        self.inner.offline_running.store(true, Ordering::Release);
+
+        let thread_inner = Arc::clone(&self.inner);
+        let worker = std::thread::spawn(move || {
+            thread_inner.run_batch_transcription(shared_model_retriever, model_file)
+        });
+
+        let work_request = WorkRequest::Long(worker);
+        if let Err(e) = self.work_request_sender.try_send(work_request) {
+            log::warn!(
+                "Cannot send batch transcription request, channel is too small or closed.\n\
+            Error: {}\n\
+                Error source: {:#?}",
+                &e,
+                e.source()
+            );
+        }
+        true
+    }
+
+    // Files that are already waiting in (or running through) the queue are skipped.
+    // If output_directory is None, the batch configs decide where the transcripts go.
+    pub(super) fn enqueue_batch_files(
+        &self,
+        audio_file_paths: Vec<PathBuf>,
+        output_directory: Option<PathBuf>,
+    ) {
+        let mut guard = self.inner.batch_queue.write();
+        for audio_file_path in audio_file_paths {
+            let pending = guard.iter().any(|(_, job)| {
+                !job.status().is_done() && job.audio_path() == audio_file_path.as_path()
+            });
+            if !pending {
+                guard.insert(BatchJob::new(audio_file_path, output_directory.clone()));
+            }
+        }
+    }
+
+    pub(super) fn has_queued_batch_jobs(&self) -> bool {
+        self.inner
+            .batch_queue
+            .read()
+            .iter()
+            .any(|(_, job)| job.status() == BatchJobStatus::Queued)
+    }
+
+    // Running jobs cannot be removed; stop the batch first.
+    pub(super) fn remove_batch_job(&self, job_id: usize) {
+        let mut guard = self.inner.batch_queue.write();
+        if guard
+            .get(job_id)
+            .is_some_and(|job| job.status() != BatchJobStatus::Running)
+        {
+            guard.remove(job_id);
+        }
+    }
+
+    pub(super) fn requeue_batch_job(&self, job_id: usize) {
+        if let Some(job) = self.inner.batch_queue.write().get_mut(job_id)
+            && matches!(
+                job.status(),
+                BatchJobStatus::Failed | BatchJobStatus::Cancelled
+            )
+        {
+            job.requeue();
+        }
+    }
+
+    pub(super) fn clear_finished_batch_jobs(&self) {
+        self.inner
+            .batch_queue
+            .write()
+            .retain(|_, job| !job.status().is_done());
+    }
+
+    // BatchJob is a cheap clone; this should be harmless to call in the UI.
+    pub(super) fn try_read_batch_queue(&self, copy_buffer: &mut Vec<(usize, BatchJob)>) {
+        if let Some(guard) = self.inner.batch_queue.try_read() {
+            copy_buffer.clear();
+            copy_buffer.extend(guard.iter().map(|(job_id, job)| (job_id, job.clone())));
+        }
+    }
+
+    pub(super) fn format_transcription(
+        &self,
+        format: TranscriptFormat,
+        model_file: Option<ModelFile>,
+    ) -> Result<String, RibbleError> {
+        self.inner.format_transcription(format, model_file)
+    }
+
+    pub(super) fn save_transcription(
+        &self,
+        out_path: PathBuf,
+        format: TranscriptFormat,
+        model_file: Option<ModelFile>,
+    ) {
         let thread_inner = Arc::clone(&self.inner);
-        let worker = std::thread::spawn(move || thread_inner.save_transcription(out_path));
+        let worker = std::thread::spawn(move || {
+            thread_inner.save_transcription(out_path, format, model_file)
+        });
 
         let work_request = WorkRequest::Short(worker);
         if let Err(e) = self.work_request_sender.try_send(work_request) {
@@ -1073,3 +1926,358 @@ impl TranscriberEngine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::test_harness::{
+        drain_write_job, scripted_sample, CapturedBus, ProgressEvent, ScriptedAudio,
+        ScriptedBackend, ScriptedWhisper, StubModelRetriever, TEST_TIMEOUT,
+    };
+    use crate::controller::transcript::{SegmentState, TranscriptSegment};
+    use std::time::Duration;
+
+    const CHUNK_LEN: usize = 1024;
+    const SETUP_JOB: &str = "Setting up real-time transcription.";
+    const FINISHED: &str =
+        "Finished real-time transcription! Recording available for offline re-transcription.";
+
+    fn new_engine(bus: &CapturedBus) -> TranscriberEngine {
+        // The checkpointer is disabled, so nothing touches the disk.
+        let checkpointer = Arc::new(SessionCheckpointer::new(&std::env::temp_dir(), false));
+        // WebRtc doesn't need any model files.
+        let vad_configs = VadConfigs::new().with_vad_type(VadType::WebRtc);
+        TranscriberEngine::new(
+            None,
+            Some(vad_configs),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            checkpointer,
+            bus.bus(),
+        )
+    }
+
+    fn setup_job_events() -> Vec<ProgressEvent> {
+        vec![
+            ProgressEvent::Requested {
+                job_id: 0,
+                job_name: SETUP_JOB,
+            },
+            ProgressEvent::Removed { job_id: 0 },
+        ]
+    }
+
+    fn scripted_chunk(chunk: usize) -> Vec<f32> {
+        vec![scripted_sample(chunk); CHUNK_LEN]
+    }
+
+    #[test]
+    fn realtime_runs_to_the_end_of_the_audio() {
+        const NUM_CHUNKS: usize = 8;
+        let mut bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        // Left over from a previous run; these should be cleared as the run starts.
+        let stale = TranscriptSegment::new(
+            Duration::ZERO,
+            Duration::from_secs(1),
+            "Stale",
+            SegmentState::Confirmed,
+        );
+        engine
+            .inner
+            .current_transcript
+            .store(Arc::new(Transcript::from_segments(vec![stale])));
+
+        let backend = Arc::new(ScriptedBackend::new(ScriptedAudio::new(
+            NUM_CHUNKS, CHUNK_LEN,
+        )));
+        let whisper = ScriptedWhisper::new(
+            &["Hello there.", "Hello there. General Kenobi."],
+            "Hello there. General Kenobi. You are a bold one.",
+        );
+        engine.start_realtime_transcription_with(
+            Arc::clone(&backend),
+            Arc::new(StubModelRetriever),
+            whisper,
+        );
+
+        // The recording gets every sample the device delivered, untouched and in order.
+        let (write_receiver, _) = bus
+            .next_write_job(TEST_TIMEOUT)
+            .expect("No write job for the recording.");
+        let recorded: Vec<f32> = drain_write_job(&write_receiver, TEST_TIMEOUT)
+            .iter()
+            .flat_map(|chunk| chunk.iter().copied())
+            .collect();
+        let expected: Vec<f32> = (0..NUM_CHUNKS).flat_map(scripted_chunk).collect();
+        assert_eq!(recorded, expected);
+
+        // The device closing ends the run without a stop.
+        let message = bus.next_console_message(TEST_TIMEOUT);
+        assert!(
+            matches!(message, Some(ConsoleMessage::Status(status)) if status == FINISHED),
+            "Expected the run to finish within {TEST_TIMEOUT:?}, got: {message:?}"
+        );
+
+        let record = bus.finish();
+        assert_eq!(record.console_messages.len(), 1);
+        assert_eq!(record.progress_events, setup_job_events());
+        assert!(record.write_jobs.is_empty());
+        assert!(!engine.realtime_running());
+        assert!(!engine.slow_stopping());
+        assert_eq!(backend.num_opened(), 1);
+        assert_eq!(backend.num_closed(), 1);
+
+        let snapshot = engine.read_transcription_snapshot();
+        assert_eq!(
+            &*snapshot.confirmed(),
+            "Hello there. General Kenobi. You are a bold one."
+        );
+        assert!(snapshot.string_segments().is_empty());
+        let transcript = engine.read_transcript();
+        assert_eq!(
+            transcript.text(),
+            "Hello there. General Kenobi. You are a bold one."
+        );
+        assert!(transcript
+            .segments()
+            .iter()
+            .all(|segment| segment.is_confirmed()));
+    }
+
+    #[test]
+    fn stopping_realtime_closes_the_recording() {
+        const NUM_CHUNKS: usize = 4;
+        let mut bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        let backend = Arc::new(ScriptedBackend::new(
+            ScriptedAudio::new(NUM_CHUNKS, CHUNK_LEN).then_silence(),
+        ));
+        engine.start_realtime_transcription_with(
+            Arc::clone(&backend),
+            Arc::new(StubModelRetriever),
+            ScriptedWhisper::new(&["Testing."], "Testing. One two."),
+        );
+
+        let (write_receiver, _) = bus
+            .next_write_job(TEST_TIMEOUT)
+            .expect("No write job for the recording.");
+        for chunk in 0..NUM_CHUNKS {
+            let recorded = write_receiver
+                .recv_timeout(TEST_TIMEOUT)
+                .expect("The recording stopped receiving audio.");
+            assert_eq!(recorded.as_ref(), scripted_chunk(chunk).as_slice());
+        }
+
+        engine.stop_realtime();
+        // Whatever made it in before the stop is the device's silence.
+        let rest = drain_write_job(&write_receiver, TEST_TIMEOUT);
+        assert!(rest.iter().all(|chunk| chunk.iter().all(|f| *f == 0.0)));
+
+        let message = bus.next_console_message(TEST_TIMEOUT);
+        assert!(
+            matches!(message, Some(ConsoleMessage::Status(status)) if status == FINISHED),
+            "Expected the run to stop within {TEST_TIMEOUT:?}, got: {message:?}"
+        );
+
+        let record = bus.finish();
+        assert_eq!(record.console_messages.len(), 1);
+        assert_eq!(record.progress_events, setup_job_events());
+        assert!(!engine.realtime_running());
+        assert_eq!(backend.num_closed(), 1);
+        assert_eq!(
+            &*engine.read_transcription_snapshot().confirmed(),
+            "Testing. One two."
+        );
+        assert_eq!(engine.read_transcript().text(), "Testing. One two.");
+    }
+
+    #[test]
+    fn failing_to_build_the_transcriber_closes_the_device() {
+        let mut bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        let backend = Arc::new(ScriptedBackend::new(
+            ScriptedAudio::new(0, CHUNK_LEN).then_silence(),
+        ));
+        engine.start_realtime_transcription_with(
+            Arc::clone(&backend),
+            Arc::new(StubModelRetriever),
+            ScriptedWhisper::failing_to_build(),
+        );
+
+        let message = bus.next_console_message(TEST_TIMEOUT);
+        assert!(
+            matches!(message, Some(ConsoleMessage::Error(_))),
+            "Expected the build error on the console, got: {message:?}"
+        );
+
+        let record = bus.finish();
+        assert_eq!(record.console_messages.len(), 1);
+        assert_eq!(record.progress_events, setup_job_events());
+        assert!(record.write_jobs.is_empty());
+        assert!(!engine.realtime_running());
+        assert_eq!(backend.num_opened(), 1);
+        assert_eq!(backend.num_closed(), 1);
+        assert!(engine.read_transcript().is_empty());
+    }
+
+    #[test]
+    fn a_transcriber_error_stops_the_run() {
+        let mut bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        let backend = Arc::new(ScriptedBackend::new(
+            ScriptedAudio::new(4, CHUNK_LEN).then_silence(),
+        ));
+        engine.start_realtime_transcription_with(
+            Arc::clone(&backend),
+            Arc::new(StubModelRetriever),
+            ScriptedWhisper::failing_while_running(&["Partial."]),
+        );
+
+        // The fanout stops on its own, so the recording is closed without a stop.
+        let (write_receiver, _) = bus
+            .next_write_job(TEST_TIMEOUT)
+            .expect("No write job for the recording.");
+        drain_write_job(&write_receiver, TEST_TIMEOUT);
+
+        let message = bus.next_console_message(TEST_TIMEOUT);
+        assert!(
+            matches!(message, Some(ConsoleMessage::Error(_))),
+            "Expected the transcriber error on the console, got: {message:?}"
+        );
+
+        let record = bus.finish();
+        assert_eq!(record.console_messages.len(), 1);
+        assert_eq!(record.progress_events, setup_job_events());
+        assert!(!engine.realtime_running());
+        assert!(!engine.slow_stopping());
+        assert_eq!(backend.num_closed(), 1);
+        // The run never finished, so only what was sent before the error is kept.
+        assert_eq!(
+            &*engine.read_transcription_snapshot().confirmed(),
+            "Partial."
+        );
+    }
+
+    #[test]
+    fn failing_to_open_the_device_resets_realtime() {
+        let mut bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        let backend = Arc::new(ScriptedBackend::failing());
+        engine.start_realtime_transcription(Arc::clone(&backend), Arc::new(StubModelRetriever));
+
+        let message = bus.next_console_message(TEST_TIMEOUT);
+        assert!(
+            matches!(message, Some(ConsoleMessage::Error(_))),
+            "Expected the device error on the console, got: {message:?}"
+        );
+
+        let record = bus.finish();
+        assert!(!engine.realtime_running());
+        assert_eq!(backend.num_opened(), 0);
+        assert_eq!(backend.num_closed(), 0);
+        assert!(record.write_jobs.is_empty());
+        assert!(record.unfinished_progress_jobs().is_empty());
+    }
+
+    #[test]
+    fn edits_are_refused_while_a_batch_is_running() {
+        let bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        engine.inner.batch_running.store(true, Ordering::Release);
+
+        let edit = TranscriptEdit::SetText {
+            index: 0,
+            text: "Edited.".to_string(),
+        };
+        let Err(RibbleError::Core(message)) = engine.edit_transcript(&edit) else {
+            panic!("Expected the edit to be refused while a batch is running.");
+        };
+        assert!(message.contains("batch"), "Unexpected refusal: {message}");
+    }
+
+    fn batch_statuses(engine: &TranscriberEngine) -> Vec<(PathBuf, BatchJobStatus)> {
+        let mut jobs = vec![];
+        engine.try_read_batch_queue(&mut jobs);
+        jobs.into_iter()
+            .map(|(_, job)| (job.audio_path().to_path_buf(), job.status()))
+            .collect()
+    }
+
+    #[test]
+    fn queued_files_are_not_queued_twice() {
+        let bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        let (a, b) = (PathBuf::from("a.wav"), PathBuf::from("b.wav"));
+        engine.enqueue_batch_files(vec![a.clone(), b.clone(), a.clone()], None);
+        engine.enqueue_batch_files(vec![b.clone()], None);
+
+        assert!(engine.has_queued_batch_jobs());
+        assert_eq!(
+            batch_statuses(&engine),
+            vec![(a, BatchJobStatus::Queued), (b, BatchJobStatus::Queued)]
+        );
+    }
+
+    #[test]
+    fn batch_jobs_move_through_the_queue() {
+        let bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        let paths: Vec<PathBuf> = ["running.wav", "failed.wav", "finished.wav"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        engine.enqueue_batch_files(paths.clone(), None);
+        {
+            let mut queue = engine.inner.batch_queue.write();
+            queue[0].set_running();
+            queue[1].set_failed("No speech.");
+            queue[2].set_finished(PathBuf::from("finished.txt"));
+        }
+        assert!(!engine.has_queued_batch_jobs());
+
+        // Running jobs can't be removed.
+        engine.remove_batch_job(0);
+        // Only failed/cancelled jobs can be requeued.
+        engine.requeue_batch_job(1);
+        engine.requeue_batch_job(2);
+        assert_eq!(
+            batch_statuses(&engine),
+            vec![
+                (paths[0].clone(), BatchJobStatus::Running),
+                (paths[1].clone(), BatchJobStatus::Queued),
+                (paths[2].clone(), BatchJobStatus::Finished),
+            ]
+        );
+
+        engine.clear_finished_batch_jobs();
+        assert_eq!(batch_statuses(&engine).len(), 2);
+
+        // A done job doesn't block the same file from being queued again.
+        engine.inner.batch_queue.write()[1].set_cancelled();
+        engine.enqueue_batch_files(vec![paths[1].clone()], None);
+        assert_eq!(batch_statuses(&engine).len(), 3);
+    }
+
+    #[test]
+    fn a_batch_does_not_start_over_a_running_transcriber() {
+        let bus = CapturedBus::new();
+        let engine = new_engine(&bus);
+        engine.enqueue_batch_files(vec![PathBuf::from("a.wav")], None);
+
+        for running in [
+            &engine.inner.realtime_running,
+            &engine.inner.offline_running,
+        ] {
+            running.store(true, Ordering::Release);
+            assert!(!engine.start_batch_transcription(Arc::new(StubModelRetriever), None));
+            assert!(!engine.batch_running());
+            assert!(engine.has_queued_batch_jobs());
+            running.store(false, Ordering::Release);
+        }
+    }
+}
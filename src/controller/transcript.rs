@@ -0,0 +1,876 @@
+use crate::utils::audio_gain::AudioGainConfigs;
+use crate::utils::errors::RibbleError;
+use crate::utils::noise_suppression::NoiseSuppressionConfigs;
+use crate::utils::vad_configs::VadConfigs;
+use ribble_whisper::transcriber::{TranscriptionSnapshot, WHISPER_SAMPLE_RATE};
+use ribble_whisper::whisper::configs::WhisperRealtimeConfigs;
+use ribble_whisper::whisper::model::ModelId;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+// Segment timing:
+// - Offline: whisper's own segment timestamps (t0/t1), which the new-segment callback hands over
+//   with the text.
+// - Real-time: the real-time transcriber only surfaces snapshots, so timing is estimated from the
+//   audio clock (the number of samples pushed into the ring buffer). Segments that arrive within
+//   the same clock window have the window split proportionally by their character count.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) enum SegmentState {
+    // The transcriber will not revise this text any further.
+    Confirmed,
+    // The text is still in the transcriber's working buffer and may change.
+    Pending,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TranscriptSegment {
+    start: Duration,
+    end: Duration,
+    text: Arc<str>,
+    state: SegmentState,
+}
+
+impl TranscriptSegment {
+    pub(crate) fn new(start: Duration, end: Duration, text: &str, state: SegmentState) -> Self {
+        Self {
+            start,
+            // Guard against inverted segments; these are always a timing bug.
+            end: end.max(start),
+            text: Arc::from(text),
+            state,
+        }
+    }
+
+    pub(crate) fn start(&self) -> Duration {
+        self.start
+    }
+    pub(crate) fn end(&self) -> Duration {
+        self.end
+    }
+    pub(crate) fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+    pub(crate) fn text(&self) -> &str {
+        self.text.as_ref()
+    }
+    pub(crate) fn state(&self) -> SegmentState {
+        self.state
+    }
+
+    pub(crate) fn is_confirmed(&self) -> bool {
+        matches!(self.state, SegmentState::Confirmed)
+    }
+
+    fn confirm(mut self) -> Self {
+        self.state = SegmentState::Confirmed;
+        self
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Transcript {
+    segments: Vec<TranscriptSegment>,
+    // Whether the segments were timed by whisper, rather than estimated.
+    whisper_timestamps: bool,
+}
+
+impl Transcript {
+    // Segments with estimated timestamps.
+    pub(crate) fn from_segments(segments: Vec<TranscriptSegment>) -> Self {
+        Self {
+            segments,
+            whisper_timestamps: false,
+        }
+    }
+
+    // Segments timed by whisper.
+    pub(crate) fn from_whisper_segments(segments: Vec<TranscriptSegment>) -> Self {
+        Self {
+            segments,
+            whisper_timestamps: true,
+        }
+    }
+
+    pub(crate) fn segments(&self) -> &[TranscriptSegment] {
+        &self.segments
+    }
+
+    pub(crate) fn has_whisper_timestamps(&self) -> bool {
+        self.whisper_timestamps
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.segments.iter().all(|segment| segment.text().trim().is_empty())
+    }
+
+    // The end of the last segment; this is an estimate of the transcribed audio length.
+    pub(crate) fn duration(&self) -> Duration {
+        self.segments
+            .last()
+            .map(|segment| segment.end())
+            .unwrap_or_default()
+    }
+
+    // Joins the segments into a single (whitespace-normalized) string.
+    pub(crate) fn text(&self) -> String {
+        let mut out = String::new();
+        for segment in self.segments.iter() {
+            let text = segment.text().trim();
+            if text.is_empty() {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(text);
+        }
+        out
+    }
+
+    // Returns a copy of the transcript with the edit applied; edited segments are confirmed.
+    pub(crate) fn with_edit(&self, edit: &TranscriptEdit) -> Result<Self, RibbleError> {
+        let mut segments = self.segments.clone();
+        let index = edit.index();
+        let Some(segment) = segments.get(index).cloned() else {
+            return Err(RibbleError::Core(format!(
+                "Transcript segment: {index} does not exist."
+            )));
+        };
+
+        match edit {
+            TranscriptEdit::SetText { text, .. } => {
+                if text.trim().is_empty() {
+                    segments.remove(index);
+                } else {
+                    segments[index] = TranscriptSegment::new(
+                        segment.start(),
+                        segment.end(),
+                        text.trim(),
+                        SegmentState::Confirmed,
+                    );
+                }
+            }
+            TranscriptEdit::MergeWithNext { .. } => {
+                let Some(next) = segments.get(index + 1) else {
+                    return Err(RibbleError::Core(
+                        "The last transcript segment has nothing to merge with.".to_string(),
+                    ));
+                };
+                let text = format!("{} {}", segment.text().trim(), next.text().trim());
+                let merged = TranscriptSegment::new(
+                    segment.start(),
+                    segment.end().max(next.end()),
+                    text.trim(),
+                    SegmentState::Confirmed,
+                );
+                segments.splice(index..=index + 1, [merged]);
+            }
+            TranscriptEdit::Split { at_char, .. } => {
+                let text = segment.text();
+                let byte_offset = text
+                    .char_indices()
+                    .nth(*at_char)
+                    .map(|(offset, _)| offset)
+                    .unwrap_or(text.len());
+                let (left, right) = text.split_at(byte_offset);
+                let (left, right) = (left.trim(), right.trim());
+                if left.is_empty() || right.is_empty() {
+                    return Err(RibbleError::Core(
+                        "Cannot split a transcript segment at its start or end.".to_string(),
+                    ));
+                }
+
+                let mut windows =
+                    distribute_window(segment.start(), segment.end(), [left, right].into_iter())
+                        .into_iter();
+                let (Some((left_start, left_end)), Some((right_start, right_end))) =
+                    (windows.next(), windows.next())
+                else {
+                    unreachable!("Splitting a segment always yields two time windows.")
+                };
+                let split = [
+                    TranscriptSegment::new(left_start, left_end, left, SegmentState::Confirmed),
+                    TranscriptSegment::new(right_start, right_end, right, SegmentState::Confirmed),
+                ];
+                segments.splice(index..=index, split);
+            }
+            TranscriptEdit::SetBounds { start, end, .. } => {
+                // Keep the segments in order; a boundary can't be moved past its neighbours.
+                let lower = index
+                    .checked_sub(1)
+                    .map(|prev| segments[prev].end())
+                    .unwrap_or(Duration::ZERO);
+                let upper = segments.get(index + 1).map(|next| next.start());
+
+                let start = (*start).max(lower);
+                let end = match upper {
+                    Some(upper) => (*end).min(upper),
+                    None => *end,
+                };
+                segments[index] = TranscriptSegment::new(
+                    start.min(end),
+                    end,
+                    segment.text(),
+                    SegmentState::Confirmed,
+                );
+            }
+        }
+
+        Ok(Self {
+            segments,
+            whisper_timestamps: self.whisper_timestamps,
+        })
+    }
+}
+
+// Corrections made to a finished transcript.
+#[derive(Clone, Debug)]
+pub(crate) enum TranscriptEdit {
+    // Replaces a segment's text; an empty text removes the segment.
+    SetText { index: usize, text: String },
+    // Joins a segment with the one after it.
+    MergeWithNext { index: usize },
+    // Splits a segment in two at a character offset into its text.
+    // The segment's time is divided by the character count of each half.
+    Split { index: usize, at_char: usize },
+    // Moves a segment's start/end, clamped to its neighbouring segments.
+    SetBounds {
+        index: usize,
+        start: Duration,
+        end: Duration,
+    },
+}
+
+impl TranscriptEdit {
+    pub(crate) fn index(&self) -> usize {
+        match self {
+            TranscriptEdit::SetText { index, .. }
+            | TranscriptEdit::MergeWithNext { index }
+            | TranscriptEdit::Split { index, .. }
+            | TranscriptEdit::SetBounds { index, .. } => *index,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum TranscriptionSource {
+    Microphone,
+    AudioFile(PathBuf),
+}
+
+// A record of the settings that were in effect when the current transcription was produced.
+// This is captured when a transcription starts, so that changing the settings afterward does not
+// misrepresent the run on export.
+// NOTE: the config types do not implement Debug.
+#[derive(Clone)]
+pub(crate) struct TranscriptionRunInfo {
+    source: TranscriptionSource,
+    transcription_configs: WhisperRealtimeConfigs,
+    vad_configs: VadConfigs,
+    audio_gain_configs: AudioGainConfigs,
+    noise_suppression_configs: NoiseSuppressionConfigs,
+    started_at: SystemTime,
+}
+
+impl TranscriptionRunInfo {
+    pub(super) fn new(
+        source: TranscriptionSource,
+        transcription_configs: WhisperRealtimeConfigs,
+        vad_configs: VadConfigs,
+        audio_gain_configs: AudioGainConfigs,
+        noise_suppression_configs: NoiseSuppressionConfigs,
+    ) -> Self {
+        Self {
+            source,
+            transcription_configs,
+            vad_configs,
+            audio_gain_configs,
+            noise_suppression_configs,
+            started_at: SystemTime::now(),
+        }
+    }
+
+    pub(crate) fn source(&self) -> &TranscriptionSource {
+        &self.source
+    }
+    pub(crate) fn model_id(&self) -> Option<ModelId> {
+        *self.transcription_configs.model_id()
+    }
+    pub(crate) fn transcription_configs(&self) -> &WhisperRealtimeConfigs {
+        &self.transcription_configs
+    }
+    pub(crate) fn vad_configs(&self) -> &VadConfigs {
+        &self.vad_configs
+    }
+    pub(crate) fn audio_gain_configs(&self) -> &AudioGainConfigs {
+        &self.audio_gain_configs
+    }
+    pub(crate) fn noise_suppression_configs(&self) -> &NoiseSuppressionConfigs {
+        &self.noise_suppression_configs
+    }
+    pub(crate) fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+}
+
+// Converts a whisper-ready (mono, 16kHz) sample count into a duration.
+pub(super) fn whisper_samples_to_duration(num_samples: u64) -> Duration {
+    Duration::from_secs_f64(num_samples as f64 / WHISPER_SAMPLE_RATE)
+}
+
+// Whisper timestamps (t0/t1) count in 10ms ticks.
+pub(super) fn whisper_ticks_to_duration(ticks: i64) -> Duration {
+    Duration::from_millis(ticks.max(0) as u64 * 10)
+}
+
+// Splits [start, end] across the texts, proportional to their character counts.
+fn distribute_window<'a, I>(start: Duration, end: Duration, texts: I) -> Vec<(Duration, Duration)>
+where
+    I: Iterator<Item=&'a str> + Clone,
+{
+    let total_weight: usize = texts.clone().map(|text| text.chars().count().max(1)).sum();
+    let span = end.saturating_sub(start);
+    let mut out = Vec::new();
+    if total_weight == 0 {
+        return out;
+    }
+
+    let mut accumulated = 0usize;
+    let mut segment_start = start;
+    for text in texts {
+        accumulated += text.chars().count().max(1);
+        let segment_end = start + span.mul_f64(accumulated as f64 / total_weight as f64);
+        out.push((segment_start, segment_end));
+        segment_start = segment_end;
+    }
+    out
+}
+
+// Builds a timed transcript from the real-time transcriber's snapshots.
+// The confirmed string in a snapshot only ever grows; each time it does, the new text becomes a
+// confirmed segment.
+#[derive(Default)]
+pub(super) struct RealtimeTranscriptTracker {
+    confirmed: Vec<TranscriptSegment>,
+    last_confirmed: Arc<str>,
+    confirmed_end: Duration,
+    last_pending: Vec<TranscriptSegment>,
+}
+
+impl RealtimeTranscriptTracker {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn update(
+        &mut self,
+        snapshot: &TranscriptionSnapshot,
+        audio_position: Duration,
+    ) -> Transcript {
+        let confirmed = snapshot.confirmed();
+        let confirmed: &str = &confirmed[..];
+
+        let newly_confirmed = match confirmed.strip_prefix(self.last_confirmed.as_ref()) {
+            Some(suffix) => suffix.trim(),
+            // If this ever happens, the transcriber has rewritten its confirmed output.
+            // Re-time everything as a single segment rather than keeping stale text.
+            None => {
+                log::warn!("Real-time confirmed transcription diverged; resetting segment timing.");
+                self.confirmed.clear();
+                self.confirmed_end = Duration::ZERO;
+                confirmed.trim()
+            }
+        };
+
+        let pending: Vec<&str> = snapshot
+            .string_segments()
+            .iter()
+            .map(|segment| segment.trim())
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let texts = std::iter::once(newly_confirmed)
+            .filter(|text| !text.is_empty())
+            .chain(pending.iter().copied());
+
+        let mut windows = distribute_window(self.confirmed_end, audio_position, texts).into_iter();
+
+        if !newly_confirmed.is_empty()
+            && let Some((start, end)) = windows.next()
+        {
+            self.confirmed.push(TranscriptSegment::new(
+                start,
+                end,
+                newly_confirmed,
+                SegmentState::Confirmed,
+            ));
+            self.confirmed_end = end;
+        }
+        self.last_confirmed = Arc::from(confirmed);
+
+        self.last_pending = pending
+            .into_iter()
+            .zip(windows)
+            .map(|(text, (start, end))| {
+                TranscriptSegment::new(start, end, text, SegmentState::Pending)
+            })
+            .collect();
+
+        let mut segments = Vec::with_capacity(self.confirmed.len() + self.last_pending.len());
+        segments.extend(self.confirmed.iter().cloned());
+        segments.extend(self.last_pending.iter().cloned());
+        Transcript::from_segments(segments)
+    }
+
+    // Consumes the tracker once the transcriber has returned its final string.
+    // Anything beyond the last confirmed output is confirmed up to the end of the audio.
+    pub(super) fn finish(self, final_transcription: &str, audio_position: Duration) -> Transcript {
+        let Self {
+            mut confirmed,
+            last_confirmed,
+            confirmed_end,
+            last_pending,
+        } = self;
+
+        match final_transcription.strip_prefix(last_confirmed.as_ref()) {
+            Some(remainder) => {
+                let remainder = remainder.trim();
+                if !remainder.is_empty() {
+                    confirmed.push(TranscriptSegment::new(
+                        confirmed_end,
+                        audio_position,
+                        remainder,
+                        SegmentState::Confirmed,
+                    ));
+                }
+            }
+            None => {
+                confirmed.extend(last_pending.into_iter().map(TranscriptSegment::confirm));
+            }
+        }
+
+        let transcript = Transcript::from_segments(confirmed);
+        if transcript.is_empty() && !final_transcription.trim().is_empty() {
+            return single_segment_transcript(final_transcription, audio_position);
+        }
+        transcript
+    }
+}
+
+// A segment from the offline transcriber's new-segment callback, with whisper's timestamps.
+pub(super) struct OfflineSegment {
+    pub(super) text: String,
+    pub(super) start: Duration,
+    pub(super) end: Duration,
+}
+
+// Builds a timed transcript from the offline transcriber's new-segment callback.
+// The timestamps come from whisper; they're only tidied up so that the segments stay in order,
+// don't overlap, and don't run past the end of the audio.
+pub(super) struct OfflineTranscriptTracker {
+    confirmed: Vec<TranscriptSegment>,
+    audio_duration: Duration,
+}
+
+impl OfflineTranscriptTracker {
+    pub(super) fn new(audio_duration: Duration) -> Self {
+        Self {
+            confirmed: vec![],
+            audio_duration,
+        }
+    }
+
+    pub(super) fn push_segment(&mut self, segment: OfflineSegment) {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            return;
+        }
+
+        let previous_end = self
+            .confirmed
+            .last()
+            .map(|segment| segment.end())
+            .unwrap_or_default();
+        let start = segment
+            .start
+            .max(previous_end)
+            .min(self.audio_duration.max(previous_end));
+        let end = segment.end.min(self.audio_duration).max(start);
+        self.confirmed.push(TranscriptSegment::new(
+            start,
+            end,
+            text,
+            SegmentState::Confirmed,
+        ));
+    }
+
+    pub(super) fn current_transcript(&self) -> Transcript {
+        Transcript::from_whisper_segments(self.confirmed.clone())
+    }
+
+    pub(super) fn finish(self, final_transcription: &str) -> Transcript {
+        // If the segment callbacks never fired (e.g. a panic in the print thread), fall back to
+        // the transcriber's output so that the text is never lost.
+        if self.confirmed.is_empty() && !final_transcription.trim().is_empty() {
+            return single_segment_transcript(final_transcription, self.audio_duration);
+        }
+        Transcript::from_whisper_segments(self.confirmed)
+    }
+}
+
+fn single_segment_transcript(text: &str, audio_duration: Duration) -> Transcript {
+    Transcript::from_segments(vec![TranscriptSegment::new(
+        Duration::ZERO,
+        audio_duration,
+        text.trim(),
+        SegmentState::Confirmed,
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn snapshot(confirmed: &str, pending: &[&str]) -> TranscriptionSnapshot {
+        TranscriptionSnapshot::new(
+            Arc::from(confirmed),
+            pending.iter().map(|segment| (*segment).into()).collect(),
+        )
+    }
+
+    // In order, non-overlapping, and (if given) spanning the whole window.
+    fn assert_well_formed(segments: &[TranscriptSegment], window: Option<(Duration, Duration)>) {
+        let mut previous_end = Duration::ZERO;
+        for segment in segments {
+            assert!(segment.start() <= segment.end(), "{segment:?}");
+            assert!(segment.start() >= previous_end, "{segment:?} overlaps");
+            previous_end = segment.end();
+        }
+        if let Some((start, end)) = window {
+            assert_eq!(segments.first().unwrap().start(), start);
+            assert_eq!(segments.last().unwrap().end(), end);
+            // No gaps between the segments either.
+            for pair in segments.windows(2) {
+                assert_eq!(pair[0].end(), pair[1].start());
+            }
+        }
+    }
+
+    fn texts(transcript: &Transcript) -> Vec<&str> {
+        transcript.segments().iter().map(|segment| segment.text()).collect()
+    }
+
+    #[test]
+    fn distribute_window_covers_the_window() {
+        let texts = ["a", "bbb", "cccccc"];
+        let windows = distribute_window(secs(1.0), secs(11.0), texts.iter().copied());
+        assert_eq!(windows.len(), texts.len());
+        assert_eq!(windows[0].0, secs(1.0));
+        assert_eq!(windows.last().unwrap().1, secs(11.0));
+        for pair in windows.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        // Proportional to the character count: 1 + 3 + 6 = 10 characters over 10 seconds.
+        let lengths = windows
+            .iter()
+            .map(|(start, end)| (*end - *start).as_secs_f64())
+            .collect::<Vec<_>>();
+        for (length, expected) in lengths.iter().zip([1.0, 3.0, 6.0]) {
+            assert!((length - expected).abs() < 1e-6, "{lengths:?}");
+        }
+    }
+
+    #[test]
+    fn distribute_window_multibyte_and_empty() {
+        // Characters, not bytes: "é" counts once. Empty text still gets a (minimum) share.
+        let windows = distribute_window(secs(0.0), secs(3.0), ["é", "", "a"].into_iter());
+        assert_eq!(windows.len(), 3);
+        for (start, end) in windows.iter() {
+            assert!(((*end - *start).as_secs_f64() - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(windows.last().unwrap().1, secs(3.0));
+
+        // An empty window gives every text a zero-length slot, rather than going backwards.
+        let windows = distribute_window(secs(2.0), secs(1.0), ["a", "b"].into_iter());
+        assert!(windows.iter().all(|(start, end)| *start == secs(2.0) && *end == secs(2.0)));
+
+        assert!(distribute_window(secs(0.0), secs(1.0), std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn realtime_tracker_times_new_confirmed_text() {
+        let mut tracker = RealtimeTranscriptTracker::new();
+
+        let transcript = tracker.update(&snapshot("Hello", &[]), secs(1.0));
+        assert_eq!(texts(&transcript), ["Hello"]);
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(1.0))));
+        assert!(!transcript.has_whisper_timestamps());
+
+        let transcript = tracker.update(&snapshot("Hello world", &["still going"]), secs(3.0));
+        assert_eq!(texts(&transcript), ["Hello", "world", "still going"]);
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(3.0))));
+        let states = transcript
+            .segments()
+            .iter()
+            .map(|segment| segment.state())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            [
+                SegmentState::Confirmed,
+                SegmentState::Confirmed,
+                SegmentState::Pending
+            ]
+        );
+
+        // Pending text is re-timed from the end of the confirmed text each update.
+        let transcript = tracker.update(&snapshot("Hello world", &["still going on"]), secs(4.0));
+        assert_eq!(texts(&transcript), ["Hello", "world", "still going on"]);
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(4.0))));
+
+        let transcript = tracker.finish("Hello world still going on", secs(5.0));
+        assert_eq!(texts(&transcript), ["Hello", "world", "still going on"]);
+        assert!(transcript.segments().iter().all(TranscriptSegment::is_confirmed));
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(5.0))));
+    }
+
+    #[test]
+    fn realtime_tracker_resets_on_divergence() {
+        let mut tracker = RealtimeTranscriptTracker::new();
+        tracker.update(&snapshot("Hello", &[]), secs(1.0));
+        tracker.update(&snapshot("Hello there", &[]), secs(2.0));
+
+        // The transcriber rewrote its confirmed output; it's re-timed as one segment.
+        let transcript = tracker.update(&snapshot("Yellow there", &[]), secs(3.0));
+        assert_eq!(texts(&transcript), ["Yellow there"]);
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(3.0))));
+    }
+
+    #[test]
+    fn realtime_tracker_falls_back_to_the_final_text() {
+        let tracker = RealtimeTranscriptTracker::new();
+        let transcript = tracker.finish("Only the final text.", secs(2.0));
+        assert_eq!(texts(&transcript), ["Only the final text."]);
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(2.0))));
+    }
+
+    fn offline_segment(text: &str, start: f64, end: f64) -> OfflineSegment {
+        OfflineSegment {
+            text: text.to_string(),
+            start: secs(start),
+            end: secs(end),
+        }
+    }
+
+    #[test]
+    fn offline_tracker_keeps_whisper_timestamps() {
+        let mut tracker = OfflineTranscriptTracker::new(secs(10.0));
+        tracker.push_segment(offline_segment(" One.", 0.5, 2.0));
+        tracker.push_segment(offline_segment("   ", 2.0, 3.0));
+        tracker.push_segment(offline_segment("Two.", 3.0, 4.5));
+
+        let transcript = tracker.current_transcript();
+        assert!(transcript.has_whisper_timestamps());
+        assert_eq!(texts(&transcript), ["One.", "Two."]);
+        let bounds = transcript
+            .segments()
+            .iter()
+            .map(|segment| (segment.start(), segment.end()))
+            .collect::<Vec<_>>();
+        // Gaps (silence) are kept as they are.
+        assert_eq!(
+            bounds,
+            [(secs(0.5), secs(2.0)), (secs(3.0), secs(4.5))]
+        );
+
+        let transcript = tracker.finish("One. Two.");
+        assert!(transcript.has_whisper_timestamps());
+        assert_eq!(texts(&transcript), ["One.", "Two."]);
+    }
+
+    #[test]
+    fn offline_tracker_tidies_overlaps_and_overruns() {
+        let mut tracker = OfflineTranscriptTracker::new(secs(5.0));
+        tracker.push_segment(offline_segment("One.", 0.0, 2.0));
+        // Starts before the previous one ends.
+        tracker.push_segment(offline_segment("Two.", 1.5, 3.0));
+        // Runs past the end of the audio.
+        tracker.push_segment(offline_segment("Three.", 4.0, 6.0));
+        // Entirely past the end of the audio.
+        tracker.push_segment(offline_segment("Four.", 7.0, 8.0));
+
+        let transcript = tracker.finish("One. Two. Three. Four.");
+        assert_eq!(texts(&transcript), ["One.", "Two.", "Three.", "Four."]);
+        assert_well_formed(transcript.segments(), None);
+        let segments = transcript.segments();
+        assert_eq!(segments[1].start(), secs(2.0));
+        assert_eq!(segments[2].end(), secs(5.0));
+        assert_eq!((segments[3].start(), segments[3].end()), (secs(5.0), secs(5.0)));
+    }
+
+    #[test]
+    fn offline_tracker_falls_back_to_the_final_text() {
+        let tracker = OfflineTranscriptTracker::new(secs(4.0));
+        let transcript = tracker.finish("No segments arrived.");
+        assert_eq!(texts(&transcript), ["No segments arrived."]);
+        assert_well_formed(transcript.segments(), Some((secs(0.0), secs(4.0))));
+
+        let tracker = OfflineTranscriptTracker::new(secs(4.0));
+        assert!(tracker.finish("  ").segments().is_empty());
+    }
+
+    #[test]
+    fn whisper_ticks_are_centiseconds() {
+        assert_eq!(whisper_ticks_to_duration(0), Duration::ZERO);
+        assert_eq!(whisper_ticks_to_duration(150), secs(1.5));
+        assert_eq!(whisper_ticks_to_duration(-3), Duration::ZERO);
+    }
+
+    fn pending_transcript(segments: &[(f64, f64, &str)]) -> Transcript {
+        Transcript::from_whisper_segments(
+            segments
+                .iter()
+                .map(|&(start, end, text)| {
+                    TranscriptSegment::new(secs(start), secs(end), text, SegmentState::Pending)
+                })
+                .collect(),
+        )
+    }
+
+    fn bounds(transcript: &Transcript) -> Vec<(Duration, Duration)> {
+        transcript
+            .segments()
+            .iter()
+            .map(|segment| (segment.start(), segment.end()))
+            .collect()
+    }
+
+    #[test]
+    fn split_at_a_multibyte_boundary() {
+        let transcript = pending_transcript(&[(0.0, 2.0, "héllo wörld"), (2.0, 3.0, "Next.")]);
+
+        let edited = transcript
+            .with_edit(&TranscriptEdit::Split {
+                index: 0,
+                at_char: 6,
+            })
+            .unwrap();
+        assert_eq!(texts(&edited), ["héllo", "wörld", "Next."]);
+        // The time is divided by character count, not bytes.
+        assert_eq!(
+            bounds(&edited)[..2],
+            [(secs(0.0), secs(1.0)), (secs(1.0), secs(2.0))]
+        );
+        assert!(
+            edited.segments()[..2]
+                .iter()
+                .all(TranscriptSegment::is_confirmed)
+        );
+        assert!(!edited.segments()[2].is_confirmed());
+        assert!(edited.has_whisper_timestamps());
+
+        // Right before a multibyte character.
+        let edited = transcript
+            .with_edit(&TranscriptEdit::Split {
+                index: 0,
+                at_char: 7,
+            })
+            .unwrap();
+        assert_eq!(texts(&edited), ["héllo w", "örld", "Next."]);
+        assert_well_formed(edited.segments(), Some((secs(0.0), secs(3.0))));
+    }
+
+    #[test]
+    fn split_needs_text_on_both_sides() {
+        let transcript = pending_transcript(&[(0.0, 2.0, "héllo wörld")]);
+        for at_char in [0, 11, 100] {
+            let split = TranscriptEdit::Split { index: 0, at_char };
+            assert!(transcript.with_edit(&split).is_err(), "{at_char}");
+        }
+        // The transcript itself is left alone.
+        assert_eq!(texts(&transcript), ["héllo wörld"]);
+    }
+
+    #[test]
+    fn merge_with_next() {
+        let transcript = pending_transcript(&[
+            (0.0, 1.0, " One "),
+            (1.5, 2.0, "two."),
+            (2.0, 3.0, "Three."),
+        ]);
+
+        let edited = transcript
+            .with_edit(&TranscriptEdit::MergeWithNext { index: 0 })
+            .unwrap();
+        assert_eq!(texts(&edited), ["One two.", "Three."]);
+        assert_eq!(bounds(&edited)[0], (secs(0.0), secs(2.0)));
+        assert!(edited.segments()[0].is_confirmed());
+
+        // The last segment has nothing to merge with.
+        assert!(
+            transcript
+                .with_edit(&TranscriptEdit::MergeWithNext { index: 2 })
+                .is_err()
+        );
+        assert!(
+            transcript
+                .with_edit(&TranscriptEdit::MergeWithNext { index: 3 })
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn set_bounds_is_clamped_to_the_neighbours() {
+        let transcript =
+            pending_transcript(&[(1.0, 2.0, "One."), (3.0, 4.0, "Two."), (5.0, 6.0, "Three.")]);
+        let set_bounds = |index, start, end| {
+            let edit = TranscriptEdit::SetBounds {
+                index,
+                start: secs(start),
+                end: secs(end),
+            };
+            bounds(&transcript.with_edit(&edit).unwrap())[index]
+        };
+
+        assert_eq!(set_bounds(1, 2.5, 4.5), (secs(2.5), secs(4.5)));
+        assert_eq!(set_bounds(1, 0.5, 5.5), (secs(2.0), secs(5.0)));
+        // The first segment can go back to the start, and the last one can run on.
+        assert_eq!(set_bounds(0, 0.0, 2.5), (secs(0.0), secs(2.5)));
+        assert_eq!(set_bounds(2, 4.5, 10.0), (secs(4.5), secs(10.0)));
+        // Inverted bounds collapse onto the end.
+        assert_eq!(set_bounds(1, 3.8, 3.2), (secs(3.2), secs(3.2)));
+
+        let edit = TranscriptEdit::SetBounds {
+            index: 3,
+            start: secs(0.0),
+            end: secs(1.0),
+        };
+        assert!(transcript.with_edit(&edit).is_err());
+    }
+
+    #[test]
+    fn set_text_trims_and_removes() {
+        let transcript = pending_transcript(&[(0.0, 1.0, "One."), (1.0, 2.0, "Two.")]);
+
+        let edit = TranscriptEdit::SetText {
+            index: 1,
+            text: "  Too. ".to_string(),
+        };
+        let edited = transcript.with_edit(&edit).unwrap();
+        assert_eq!(texts(&edited), ["One.", "Too."]);
+        assert_eq!(bounds(&edited), bounds(&transcript));
+        assert!(edited.segments()[1].is_confirmed());
+
+        let edit = TranscriptEdit::SetText {
+            index: 0,
+            text: " ".to_string(),
+        };
+        assert_eq!(texts(&transcript.with_edit(&edit).unwrap()), ["Two."]);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::controller::model_bank::RibbleModelBank;
+use crate::controller::transcriber::TranscriberEngine;
+use crate::controller::{
+    Bus, ConsoleMessage, SMALL_UTILITY_QUEUE_SIZE, SUPPORTED_AUDIO_FILE_EXTENSIONS,
+};
+use crate::utils::errors::RibbleError;
+use crate::utils::watch_folder_configs::WatchFolderConfigs;
+use arc_swap::ArcSwap;
+use crossbeam::channel::RecvTimeoutError;
+use parking_lot::Mutex;
+use ribble_whisper::utils::{Receiver, Sender, get_channel};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use notify_debouncer_full::notify::event::ModifyKind;
+use notify_debouncer_full::notify::{
+    Config, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode,
+};
+use notify_debouncer_full::{
+    DebounceEventResult, DebouncedEvent, Debouncer, NoCache, RecommendedCache, new_debouncer,
+    new_debouncer_opt,
+};
+
+// NOTE: THESE ARE IN MILLISECONDS
+const MAX_DEBOUNCE_TIME: u64 = 2000;
+// How often to check whether files in the inbox have finished copying.
+const SETTLE_TICK: u64 = 1000;
+// Network shares are slow to list, so don't hammer them.
+const POLL_INTERVAL: u64 = 5000;
+
+// The recorder hardware (or a network copy) may still be writing a file when it shows up in the
+// inbox. A file is only queued once its size has stopped changing between two settle ticks.
+const UNSEEN_FILE_SIZE: u64 = u64::MAX;
+
+// A file that has already been queued, keyed by its path, size and modification time.
+// Touching/re-saving a file fires another event, but it's only transcribed again if its contents
+// (size or mtime) actually changed.
+type ProcessedFile = (PathBuf, u64, Option<SystemTime>);
+
+enum FolderDebouncer {
+    Native(Debouncer<RecommendedWatcher, RecommendedCache>),
+    Polling(Debouncer<PollWatcher, NoCache>),
+}
+
+impl FolderDebouncer {
+    fn stop(self) {
+        match self {
+            FolderDebouncer::Native(debouncer) => debouncer.stop(),
+            FolderDebouncer::Polling(debouncer) => debouncer.stop(),
+        }
+    }
+}
+
+struct FolderWatcher {
+    debouncer: Option<FolderDebouncer>,
+    worker_thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for FolderWatcher {
+    fn drop(&mut self) {
+        // NOTE: stopping the debouncer drops its event sender, which disconnects the worker
+        // thread's receiver and ends the loop.
+        if let Some(debouncer) = self.debouncer.take() {
+            log::info!("Stopping watch folder debouncer.");
+            debouncer.stop();
+        }
+        if let Some(handle) = self.worker_thread.take() {
+            log::info!("Joining watch folder thread.");
+            if handle.join().is_err() {
+                log::error!("Watch folder thread panicked.");
+            }
+            log::info!("Watch folder thread joined.");
+        }
+    }
+}
+
+// Watches a user-chosen inbox folder and feeds new audio files into the batch queue.
+// Transcripts go to the outbox folder (if set), otherwise next to the audio files.
+pub(super) struct WatchFolderEngine {
+    // NOTE: this is shared with the worker thread so that outbox changes apply without a restart.
+    configs: Arc<ArcSwap<WatchFolderConfigs>>,
+    transcriber_engine: TranscriberEngine,
+    model_bank: Arc<RibbleModelBank>,
+    console_message_sender: Sender<ConsoleMessage>,
+    watcher: Mutex<Option<FolderWatcher>>,
+}
+
+impl WatchFolderEngine {
+    pub(super) fn new(
+        start_configs: Option<WatchFolderConfigs>,
+        transcriber_engine: TranscriberEngine,
+        model_bank: Arc<RibbleModelBank>,
+        bus: &Bus,
+    ) -> Self {
+        let configs = Arc::new(ArcSwap::from(Arc::new(start_configs.unwrap_or_default())));
+        Self {
+            configs,
+            transcriber_engine,
+            model_bank,
+            console_message_sender: bus.console_message_sender(),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    // NOTE: this is a nop if the watch folder is disabled.
+    pub(super) fn start_watching(&self) {
+        // A missing inbox (e.g. an unmounted network share) shouldn't stop the app from starting.
+        if let Err(e) = self.restart_watcher() {
+            log::warn!(
+                "Failed to start watch folder.\nError: {e}\nError source: {:#?}",
+                e.source()
+            );
+        }
+    }
+
+    pub(super) fn read_watch_folder_configs(&self) -> Arc<WatchFolderConfigs> {
+        self.configs.load_full()
+    }
+
+    // If the watcher fails to restart (e.g. the inbox is unreachable), the configs are still
+    // kept so that the user can see/fix them; the error goes to the console.
+    pub(super) fn write_watch_folder_configs(&self, new_configs: WatchFolderConfigs) {
+        let old_configs = self.configs.swap(Arc::new(new_configs));
+        if !old_configs.needs_restart(&self.configs.load()) {
+            return;
+        }
+
+        if let Err(e) = self.restart_watcher() {
+            log::warn!(
+                "Failed to start watch folder.\nError: {e}\nError source: {:#?}",
+                e.source()
+            );
+            if let Err(e) = self.console_message_sender.try_send(ConsoleMessage::Error(e)) {
+                log::warn!(
+                    "Cannot send watch folder error, channel is too small or closed.\nError source: {:#?}",
+                    e.source()
+                );
+            }
+        }
+    }
+
+    pub(super) fn watch_folder_active(&self) -> bool {
+        self.watcher.lock().is_some()
+    }
+
+    pub(super) fn stop_watching(&self) {
+        // Dropping the watcher stops the debouncer and joins the thread.
+        self.watcher.lock().take();
+    }
+
+    fn restart_watcher(&self) -> Result<(), RibbleError> {
+        let mut guard = self.watcher.lock();
+        // Always tear down the old watcher first; there should only ever be one.
+        guard.take();
+
+        let configs = self.configs.load_full();
+        if !configs.enabled() {
+            return Ok(());
+        }
+
+        let Some(inbox) = configs.inbox_directory() else {
+            return Err(RibbleError::Core(
+                "Watch folder enabled without an inbox folder.".to_string(),
+            ));
+        };
+
+        if !inbox.is_dir() {
+            return Err(RibbleError::IOError(std::io::Error::new(
+                ErrorKind::NotADirectory,
+                format!("Watch folder inbox: {inbox:?} is not a directory"),
+            )));
+        }
+
+        *guard = Some(self.spawn_watcher(inbox, configs.use_polling())?);
+        Ok(())
+    }
+
+    fn spawn_watcher(
+        &self,
+        inbox: &Path,
+        use_polling: bool,
+    ) -> Result<FolderWatcher, RibbleError> {
+        let (event_sender, event_receiver) = get_channel(SMALL_UTILITY_QUEUE_SIZE);
+        let timeout = Duration::from_millis(MAX_DEBOUNCE_TIME);
+
+        let debouncer = if use_polling {
+            let config =
+                Config::default().with_poll_interval(Duration::from_millis(POLL_INTERVAL));
+            let mut debouncer = new_debouncer_opt::<_, PollWatcher, NoCache>(
+                timeout,
+                None,
+                event_sender,
+                NoCache,
+                config,
+            )?;
+            debouncer.watch(inbox, RecursiveMode::NonRecursive)?;
+            FolderDebouncer::Polling(debouncer)
+        } else {
+            let mut debouncer = new_debouncer(timeout, None, event_sender)?;
+            debouncer.watch(inbox, RecursiveMode::NonRecursive)?;
+            FolderDebouncer::Native(debouncer)
+        };
+
+        let worker = WatchFolderWorker {
+            events: event_receiver,
+            configs: Arc::clone(&self.configs),
+            transcriber_engine: self.transcriber_engine.clone(),
+            model_bank: Arc::clone(&self.model_bank),
+            console_message_sender: self.console_message_sender.clone(),
+        };
+
+        let worker_thread = std::thread::spawn(move || worker.run());
+
+        log::info!("Watching folder: {}", inbox.display());
+        let message = ConsoleMessage::Status(format!("Watching folder: {}", inbox.display()));
+        if let Err(e) = self.console_message_sender.try_send(message) {
+            log::warn!(
+                "Cannot send watch folder status, channel is too small or closed.\nError source: {:#?}",
+                e.source()
+            );
+        }
+
+        Ok(FolderWatcher {
+            debouncer: Some(debouncer),
+            worker_thread: Some(worker_thread),
+        })
+    }
+}
+
+struct WatchFolderWorker {
+    events: Receiver<DebounceEventResult>,
+    configs: Arc<ArcSwap<WatchFolderConfigs>>,
+    transcriber_engine: TranscriberEngine,
+    model_bank: Arc<RibbleModelBank>,
+    console_message_sender: Sender<ConsoleMessage>,
+}
+
+impl WatchFolderWorker {
+    fn run(self) {
+        // File path -> last seen size.
+        let mut pending: HashMap<PathBuf, u64> = HashMap::new();
+        let mut processed: HashSet<ProcessedFile> = HashSet::new();
+        let mut needs_start = false;
+        let tick = Duration::from_millis(SETTLE_TICK);
+
+        loop {
+            match self.events.recv_timeout(tick) {
+                Ok(Ok(events)) => self.collect_new_files(&events, &mut pending),
+                Ok(Err(mut errors)) => {
+                    while let Some(err) = errors.pop() {
+                        log::error!(
+                            "Debouncer Error in watch folder.\nError: {}\nError source: {:#?}",
+                            err,
+                            err.source()
+                        );
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let settled = Self::settle_pending(&mut pending, &mut processed);
+            if !settled.is_empty() {
+                self.enqueue(settled);
+                needs_start = true;
+            }
+
+            // If a batch is already running, it picks up the new jobs on its own.
+            // Otherwise, wait until the transcriber is free (e.g. a realtime session ends).
+            // NOTE: the engine refuses the start if something else claimed the transcriber in the
+            // meantime; the jobs stay queued and it's tried again on the next tick.
+            if needs_start && !self.transcriber_engine.transcriber_running() {
+                needs_start =
+                    self.transcriber_engine.has_queued_batch_jobs() && !self.start_batch();
+            }
+        }
+    }
+
+    fn collect_new_files(&self, events: &[DebouncedEvent], pending: &mut HashMap<PathBuf, u64>) {
+        let new_files = events
+            .iter()
+            .filter(|deb_event| match deb_event.event.kind {
+                // Permission changes, touch, etc. don't change the audio.
+                // NOTE: the poll watcher also reports writes as metadata (mtime) changes, but new
+                // files still come through as a Create, which is all the inbox needs.
+                EventKind::Modify(ModifyKind::Metadata(_)) => false,
+                EventKind::Create(_) | EventKind::Modify(_) => true,
+                _ => false,
+            })
+            .flat_map(|deb_event| deb_event.event.paths.iter())
+            .filter(|path| is_supported_audio_file(path));
+
+        for path in new_files {
+            pending.entry(path.to_path_buf()).or_insert(UNSEEN_FILE_SIZE);
+        }
+    }
+
+    fn settle_pending(
+        pending: &mut HashMap<PathBuf, u64>,
+        processed: &mut HashSet<ProcessedFile>,
+    ) -> Vec<PathBuf> {
+        let mut settled = vec![];
+        pending.retain(|path, last_size| {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                // The file was removed/moved before it could be queued.
+                return false;
+            };
+            let size = metadata.len();
+            if size > 0 && size == *last_size {
+                let modified = metadata.modified().ok();
+                if processed.insert((path.clone(), size, modified)) {
+                    settled.push(path.clone());
+                }
+                false
+            } else {
+                *last_size = size;
+                true
+            }
+        });
+        settled
+    }
+
+    fn enqueue(&self, audio_file_paths: Vec<PathBuf>) {
+        let message = match audio_file_paths.as_slice() {
+            [path] => format!("Watch folder queued: {}", path.display()),
+            paths => format!("Watch folder queued {} files.", paths.len()),
+        };
+
+        let outbox = self.configs.load().outbox_directory().map(Path::to_path_buf);
+        self.transcriber_engine
+            .enqueue_batch_files(audio_file_paths, outbox);
+
+        if let Err(e) = self
+            .console_message_sender
+            .try_send(ConsoleMessage::Status(message))
+        {
+            log::warn!(
+                "Cannot send watch folder status, channel is too small or closed.\nError source: {:#?}",
+                e.source()
+            );
+        }
+    }
+
+    fn start_batch(&self) -> bool {
+        // The batch runs with the current transcription configs.
+        let model_id = *self.transcriber_engine.read_transcription_configs().model_id();
+        let model_file = model_id.and_then(|model_id| self.model_bank.get_model_file(model_id));
+        // The model is leased for the length of the batch; see: ModelLease.
+        let lease = Arc::new(self.model_bank.lease_model(model_id));
+        self.transcriber_engine
+            .start_batch_transcription(lease, model_file)
+    }
+}
+
+fn is_supported_audio_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                SUPPORTED_AUDIO_FILE_EXTENSIONS
+                    .iter()
+                    .any(|supported| supported.eq_ignore_ascii_case(ext))
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // Runs settle ticks until the pending set is empty.
+    fn settle(
+        pending: &mut HashMap<PathBuf, u64>,
+        processed: &mut HashSet<ProcessedFile>,
+    ) -> Vec<PathBuf> {
+        let mut settled = vec![];
+        while !pending.is_empty() {
+            settled.extend(WatchFolderWorker::settle_pending(pending, processed));
+        }
+        settled
+    }
+
+    #[test]
+    fn a_file_is_only_queued_once_until_it_changes() {
+        let mut audio_file = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
+        audio_file.write_all(b"RIFF").unwrap();
+        let path = audio_file.path().to_path_buf();
+
+        let mut pending = HashMap::new();
+        let mut processed = HashSet::new();
+
+        pending.insert(path.clone(), UNSEEN_FILE_SIZE);
+        assert_eq!(settle(&mut pending, &mut processed), vec![path.clone()]);
+
+        // e.g. another Modify event for the same, unchanged file.
+        pending.insert(path.clone(), UNSEEN_FILE_SIZE);
+        assert!(settle(&mut pending, &mut processed).is_empty());
+
+        audio_file.write_all(b"WAVE").unwrap();
+        pending.insert(path.clone(), UNSEEN_FILE_SIZE);
+        assert_eq!(settle(&mut pending, &mut processed), vec![path]);
+    }
+
+    #[test]
+    fn empty_and_removed_files_are_not_queued() {
+        let audio_file = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
+        let path = audio_file.path().to_path_buf();
+
+        let mut pending = HashMap::from([(path.clone(), UNSEEN_FILE_SIZE)]);
+        let mut processed = HashSet::new();
+
+        // Still empty (i.e. hasn't started copying), so it stays pending.
+        assert!(WatchFolderWorker::settle_pending(&mut pending, &mut processed).is_empty());
+        assert!(WatchFolderWorker::settle_pending(&mut pending, &mut processed).is_empty());
+        assert!(pending.contains_key(&path));
+
+        drop(audio_file);
+        assert!(settle(&mut pending, &mut processed).is_empty());
+        assert!(processed.is_empty());
+    }
+}
@@ -1,25 +1,106 @@
+use crate::controller::export_encoders;
+use crate::controller::recording_segments::{
+    parse_segment_file_name, repair_wav_header, segment_file_name, SegmentedWavWriter,
+};
+use crate::controller::session_checkpoint::{RecordingCheckpoint, SessionCheckpointer};
 use crate::controller::{
-    Bus, CompletedRecordingJobs, ConsoleMessage, RibbleMessage, WorkRequest, WriteRequest,
+    Bus, CompletedRecordingJobs, ConsoleMessage, Progress, ProgressMessage, RibbleMessage,
+    WorkRequest, WriteRequest,
 };
 use crate::utils::errors::RibbleError;
-use crate::utils::recorder_configs::{RibbleExportFormat, RibbleRecordingConfigs};
-use hound::{WavReader, WavSpec, WavWriter};
+use crate::utils::recorder_configs::{
+    RibbleExportFormat, RibbleOpusBitrate, RibbleRecordingConfigs,
+};
+use crate::utils::recording_library_configs::RecordingLibraryConfigs;
+use arc_swap::ArcSwap;
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
 use indexmap::IndexMap;
 use parking_lot::RwLock;
 use ribble_whisper::audio::pcm::IntoPcmS16;
-use ribble_whisper::utils::{Receiver, Sender};
+use ribble_whisper::utils::{get_channel, Receiver, Sender};
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 // NOTE: This does not yet use a debouncer; it's probably unnecessary for what this engine does.
 // If a user gains access to the recordings cache and deletes files, then tries to access,
 // this will prune the entry from the IndexMap.
 // (This is also handled in the GUI).
 
+// NOTE: if the recording library is enabled, the recordings are kept on exit and indexed in a
+// sidecar file (in the recordings directory) so that they can be reloaded on the next launch.
+// The index is rewritten whenever the library changes; recordings without an entry are ignored
+// (and eventually clobbered, same as before).
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct RecordingLibraryIndex {
+    #[serde(default)]
+    recordings: Vec<RecordingIndexEntry>,
+}
+
+// Oldest to newest, same as the IndexMap.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RecordingIndexEntry {
+    file_name: String,
+    duration_secs: f64,
+    file_size: usize,
+    sample_rate: usize,
+    channels: usize,
+    created_at: SystemTime,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    transcript_path: Option<PathBuf>,
+    // Every file, in order; empty means the recording is just file_name.
+    #[serde(default)]
+    segments: Vec<String>,
+}
+
+impl RecordingIndexEntry {
+    fn from_job(file_name: &str, job: &CompletedRecordingJobs) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            duration_secs: job.total_duration.as_secs_f64(),
+            file_size: job.file_size_estimate,
+            sample_rate: job.sample_rate,
+            channels: job.channels,
+            created_at: job.created_at,
+            label: job.label.as_deref().map(str::to_string),
+            transcript_path: job.transcript_path.as_deref().map(Path::to_path_buf),
+            segments: job
+                .segments
+                .iter()
+                .map(|segment| segment.to_string())
+                .collect(),
+        }
+    }
+
+    fn into_job(self) -> (Arc<str>, CompletedRecordingJobs) {
+        let file_name: Arc<str> = Arc::from(self.file_name);
+        let segments = if self.segments.is_empty() {
+            Arc::from([Arc::clone(&file_name)])
+        } else {
+            self.segments.into_iter().map(Arc::from).collect()
+        };
+        let job = CompletedRecordingJobs {
+            file_size_estimate: self.file_size,
+            total_duration: Duration::from_secs_f64(self.duration_secs.max(0.0)),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            created_at: self.created_at,
+            label: self.label.map(Arc::from),
+            transcript_path: self.transcript_path.map(Arc::from),
+            segments,
+        };
+        (file_name, job)
+    }
+}
+
 // NOTE: this is only send when using mpmc (crossbeam)
 // If for whatever reason the std::mpsc is required,
 // this will need to have a lock on the Receiver
@@ -29,7 +110,10 @@ struct WriterEngineState {
     latest_exists: AtomicBool,
     data_directory: PathBuf,
     completed_jobs: RwLock<IndexMap<Arc<str>, CompletedRecordingJobs>>,
+    library_configs: ArcSwap<RecordingLibraryConfigs>,
     incoming_jobs: Receiver<WriteRequest>,
+    session_checkpointer: Arc<SessionCheckpointer>,
+    progress_message_sender: Sender<ProgressMessage>,
     // This is just for spawning a write loop - the outer WriterEngine has to handle sending clear
     // jobs.
     work_request_sender: Sender<WorkRequest>,
@@ -37,35 +121,384 @@ struct WriterEngineState {
 
 impl WriterEngineState {
     const DEFAULT_CACHE_SIZE: usize = 5;
-    const TMP_FILE: &'static str = "tmp_recording";
-    const FILE_EXTENSION: &'static str = "wav";
-    fn new(data_directory: PathBuf, incoming_jobs: Receiver<WriteRequest>, bus: &Bus) -> Self {
+    const LIBRARY_INDEX_FILE: &'static str = "library.json";
+    // Export progress is sent in chunks; per-sample updates would flood the progress channel.
+    const PROGRESS_CHUNK_SIZE: usize = 1 << 16;
+    fn new(
+        data_directory: PathBuf,
+        incoming_jobs: Receiver<WriteRequest>,
+        start_library_configs: Option<RecordingLibraryConfigs>,
+        session_checkpointer: Arc<SessionCheckpointer>,
+        bus: &Bus,
+    ) -> Self {
         let ticket = AtomicUsize::new(0);
         let clearing = AtomicBool::new(false);
         let latest_exists = AtomicBool::new(false);
         let completed_jobs = RwLock::new(IndexMap::with_capacity(Self::DEFAULT_CACHE_SIZE));
+        let library_configs = ArcSwap::from(Arc::new(start_library_configs.unwrap_or_default()));
 
-        Self {
+        let state = Self {
             ticket,
             clearing,
             latest_exists,
             data_directory,
             completed_jobs,
+            library_configs,
             incoming_jobs,
+            session_checkpointer,
+            progress_message_sender: bus.progress_message_sender(),
             work_request_sender: bus.work_request_sender(),
+        };
+
+        if state.library_enabled() {
+            state.load_library();
+        }
+        state
+    }
+
+    fn library_enabled(&self) -> bool {
+        self.library_configs.load().enabled()
+    }
+
+    fn library_index_path(&self) -> PathBuf {
+        self.data_directory.join(Self::LIBRARY_INDEX_FILE)
+    }
+
+    // Reloads the recordings kept from previous sessions.
+    fn load_library(&self) {
+        let index_path = self.library_index_path();
+        let index = match File::open(index_path.as_path()) {
+            Ok(index_file) => {
+                serde_json::from_reader::<_, RecordingLibraryIndex>(BufReader::new(index_file))
+                    .unwrap_or_else(|e| {
+                        log::warn!("Error reading recording library index: {e}");
+                        RecordingLibraryIndex::default()
+                    })
+            }
+            // No library yet (e.g. it was just enabled); not an error.
+            Err(e) if e.kind() == ErrorKind::NotFound => return,
+            Err(e) => {
+                log::warn!("Error opening recording library index: {e}");
+                return;
+            }
+        };
+
+        let mut completed_jobs = self.completed_jobs.write();
+        for entry in index.recordings {
+            let (file_name, mut job) = entry.into_job();
+            // Drop the entries for recordings that have been deleted outside of the app.
+            let Some(file_size) = self.segments_size(&job.segments) else {
+                log::info!("Recording missing from library: {file_name}");
+                continue;
+            };
+            job.file_size_estimate = file_size;
+            completed_jobs.insert(file_name, job);
+        }
+
+        self.update_next_ticket(&completed_jobs);
+
+        self.apply_retention(&mut completed_jobs);
+        self.latest_exists
+            .store(!completed_jobs.is_empty(), Ordering::Release);
+        self.save_library_index(&completed_jobs);
+        log::info!("Loaded {} recordings from library.", completed_jobs.len());
+    }
+
+    // Carry on numbering after the existing recordings so that none of them get clobbered.
+    fn update_next_ticket(&self, completed_jobs: &IndexMap<Arc<str>, CompletedRecordingJobs>) {
+        let next_ticket = completed_jobs
+            .keys()
+            .filter_map(|file_name| parse_segment_file_name(file_name))
+            .map(|(ticket_no, _)| ticket_no + 1)
+            .max()
+            .unwrap_or_default()
+            .max(self.ticket.load(Ordering::Acquire));
+        self.ticket.store(next_ticket, Ordering::Release);
+    }
+
+    // The total size of the segments on disk, or None if any of them are missing.
+    fn segments_size(&self, segments: &[Arc<str>]) -> Option<usize> {
+        segments.iter().try_fold(0, |total, segment| {
+            let metadata = std::fs::metadata(self.data_directory.join(segment.as_ref())).ok()?;
+            metadata.is_file().then(|| total + metadata.len() as usize)
+        })
+    }
+
+    fn remove_recording_files(&self, job: &CompletedRecordingJobs) {
+        for segment in job.segments.iter() {
+            let file_path = self.data_directory.join(segment.as_ref());
+            if let Err(e) = std::fs::remove_file(file_path.as_path())
+                && e.kind() != ErrorKind::NotFound
+            {
+                log::warn!(
+                    "Failed to remove recording: {}\nError: {e}",
+                    file_path.display()
+                );
+            }
+        }
+    }
+
+    // Recordings left behind by a crash (i.e. not cleared on exit, and not in the library), by
+    // ticket: (part, file name), in order.
+    fn orphaned_recordings(
+        &self,
+        completed_jobs: &IndexMap<Arc<str>, CompletedRecordingJobs>,
+    ) -> BTreeMap<usize, Vec<(usize, String)>> {
+        let mut orphans: BTreeMap<usize, Vec<(usize, String)>> = Default::default();
+        let entries = match std::fs::read_dir(self.data_directory.as_path()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Cannot read recordings directory.\nError: {e}");
+                return orphans;
+            }
+        };
+
+        let known_segments = completed_jobs
+            .values()
+            .flat_map(|job| job.segments.iter().map(|segment| segment.to_string()))
+            .collect::<HashSet<_>>();
+
+        for entry in entries.flatten() {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if known_segments.contains(&file_name) {
+                continue;
+            }
+            if let Some((ticket_no, part_no)) = parse_segment_file_name(&file_name) {
+                orphans
+                    .entry(ticket_no)
+                    .or_default()
+                    .push((part_no, file_name));
+            }
+        }
+        for parts in orphans.values_mut() {
+            parts.sort();
+        }
+        orphans
+    }
+
+    // The orphaned recordings are repaired and added back as recovered recordings.
+    // Returns the number of recordings recovered.
+    fn recover_orphaned_recordings(&self, in_progress: Option<&RecordingCheckpoint>) -> usize {
+        let mut completed_jobs = self.completed_jobs.write();
+        let mut num_recovered = 0;
+        for (ticket_no, parts) in self.orphaned_recordings(&completed_jobs) {
+            // The checkpoint knows when the recording actually started.
+            let started_at = in_progress
+                .filter(|checkpoint| checkpoint.ticket_no() == ticket_no)
+                .map(|checkpoint| checkpoint.started_at());
+            match self.recover_recording(&parts, started_at) {
+                Ok((file_name, job)) => {
+                    completed_jobs.insert(file_name, job);
+                    num_recovered += 1;
+                }
+                Err(e) => {
+                    log::warn!("Failed to recover recording: {}\nError: {e}", parts[0].1);
+                }
+            }
+        }
+
+        if num_recovered > 0 {
+            self.update_next_ticket(&completed_jobs);
+            self.latest_exists.store(true, Ordering::Release);
+            self.save_library_index(&completed_jobs);
         }
+        num_recovered
+    }
+
+    // For when the user doesn't want them back.
+    // Returns the number of recordings removed.
+    fn discard_orphaned_recordings(&self) -> usize {
+        let completed_jobs = self.completed_jobs.read();
+        let orphans = self.orphaned_recordings(&completed_jobs);
+        for (_, file_name) in orphans.values().flatten() {
+            let file_path = self.data_directory.join(file_name);
+            if let Err(e) = std::fs::remove_file(file_path.as_path())
+                && e.kind() != ErrorKind::NotFound
+            {
+                log::warn!(
+                    "Failed to remove unfinished recording: {}\nError: {e}",
+                    file_path.display()
+                );
+            }
+        }
+        orphans.len()
+    }
+
+    fn recover_recording(
+        &self,
+        parts: &[(usize, String)],
+        started_at: Option<SystemTime>,
+    ) -> Result<(Arc<str>, CompletedRecordingJobs), RibbleError> {
+        let mut spec = None;
+        let mut num_frames = 0u64;
+        let mut file_size = 0usize;
+        let mut created_at = SystemTime::now();
+        let mut segments = Vec::with_capacity(parts.len());
+
+        for (part_no, file_name) in parts {
+            let file_path = self.data_directory.join(file_name);
+            if repair_wav_header(file_path.as_path())? {
+                log::info!("Repaired recording header: {file_name}");
+            }
+
+            let reader = WavReader::open(file_path.as_path())?;
+            if *spec.get_or_insert(reader.spec()) != reader.spec() {
+                return Err(RibbleError::Core(format!(
+                    "Recording segment {part_no} doesn't match the rest of the recording."
+                )));
+            }
+            num_frames += reader.duration() as u64;
+
+            let metadata = std::fs::metadata(file_path.as_path())?;
+            file_size += metadata.len() as usize;
+            if let Ok(modified) = metadata.modified() {
+                created_at = created_at.min(modified);
+            }
+            segments.push(Arc::<str>::from(file_name.as_str()));
+        }
+
+        let spec = spec.ok_or(RibbleError::Core("Empty recording.".to_string()))?;
+        let job = CompletedRecordingJobs {
+            file_size_estimate: file_size,
+            total_duration: Duration::from_secs_f64(num_frames as f64 / spec.sample_rate as f64),
+            channels: spec.channels as usize,
+            sample_rate: spec.sample_rate as usize,
+            created_at: started_at.unwrap_or(created_at),
+            label: Some(Arc::from("Recovered recording")),
+            transcript_path: None,
+            segments: Arc::from(segments),
+        };
+        Ok((Arc::clone(&job.segments[0]), job))
+    }
+
+    // Removes the oldest recordings until the library fits within the retention rules.
+    // The newest recording is always kept, even if it's over the size limit on its own.
+    fn apply_retention(&self, completed_jobs: &mut IndexMap<Arc<str>, CompletedRecordingJobs>) {
+        let configs = self.library_configs.load();
+        if !configs.enabled() {
+            return;
+        }
+        let mut total_size = completed_jobs
+            .values()
+            .map(|job| job.file_size_estimate as u64)
+            .sum::<u64>();
+        let over_limit = |len: usize, total_size: u64| {
+            len > 1
+                && (configs.keep_last().is_some_and(|keep_last| len > keep_last)
+                    || configs
+                        .max_total_size_bytes()
+                        .is_some_and(|max_size| total_size > max_size))
+        };
+
+        while over_limit(completed_jobs.len(), total_size) {
+            let Some((file_name, job)) = completed_jobs.shift_remove_index(0) else {
+                break;
+            };
+            total_size = total_size.saturating_sub(job.file_size_estimate as u64);
+            self.remove_recording_files(&job);
+            log::info!("Removed old recording from library: {file_name}");
+        }
+    }
+
+    // NOTE: this is written to a temporary file first so that a crash mid-write can't take the
+    // whole library down with it.
+    fn save_library_index(&self, completed_jobs: &IndexMap<Arc<str>, CompletedRecordingJobs>) {
+        if !self.library_enabled() {
+            return;
+        }
+        let index = RecordingLibraryIndex {
+            recordings: completed_jobs
+                .iter()
+                .map(|(file_name, job)| RecordingIndexEntry::from_job(file_name, job))
+                .collect(),
+        };
+
+        if let Err(e) = self.write_library_index(&index) {
+            log::warn!("Failed to save recording library index.\nError: {e}");
+        }
+    }
+
+    fn write_library_index(&self, index: &RecordingLibraryIndex) -> Result<(), RibbleError> {
+        let index_path = self.library_index_path();
+        let tmp_path = index_path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(tmp_path.as_path())?);
+        serde_json::to_writer_pretty(&mut writer, index)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(tmp_path.as_path(), index_path.as_path())?;
+        Ok(())
+    }
+
+    fn remove_library_index(&self) {
+        if let Err(e) = std::fs::remove_file(self.library_index_path())
+            && e.kind() != ErrorKind::NotFound
+        {
+            log::warn!("Failed to remove recording library index.\nError: {e}");
+        }
+    }
+
+    fn write_library_configs(&self, new_configs: RecordingLibraryConfigs) {
+        let old_configs = self.library_configs.swap(Arc::new(new_configs));
+        // NOTE: the retention rules are only applied on startup and when a recording is added, so
+        // that adjusting them can't delete recordings out from under the user.
+        match (old_configs.enabled(), new_configs.enabled()) {
+            (false, true) => self.save_library_index(&self.completed_jobs.read()),
+            (true, false) => self.remove_library_index(),
+            _ => {}
+        }
+    }
+
+    fn set_recording_label(&self, file_name: &str, label: &str) {
+        let mut completed_jobs = self.completed_jobs.write();
+        let Some(job) = completed_jobs.get_mut(file_name) else {
+            return;
+        };
+        let label = label.trim();
+        job.label = (!label.is_empty()).then(|| Arc::from(label));
+        self.save_library_index(&completed_jobs);
+    }
+
+    fn link_transcript(&self, file_name: &str, transcript_path: &Path) {
+        let mut completed_jobs = self.completed_jobs.write();
+        let Some(job) = completed_jobs.get_mut(file_name) else {
+            return;
+        };
+        job.transcript_path = Some(Arc::from(transcript_path));
+        self.save_library_index(&completed_jobs);
+    }
+
+    // Returns the key if the path points to one of the recordings.
+    fn recording_file_name(&self, path: &Path) -> Option<Arc<str>> {
+        if path.parent() != Some(self.data_directory.as_path()) {
+            return None;
+        }
+        let file_name = path.file_name()?.to_str()?;
+        self.completed_jobs
+            .read()
+            .get_key_value(file_name)
+            .map(|(file_name, _)| Arc::clone(file_name))
+    }
+
+    fn first_recording_since(&self, time: SystemTime) -> Option<Arc<str>> {
+        self.completed_jobs
+            .read()
+            .iter()
+            .find(|(_, job)| job.created_at >= time)
+            .map(|(file_name, _)| Arc::clone(file_name))
     }
     fn is_clearing(&self) -> bool {
         self.clearing.load(Ordering::Acquire)
     }
 
-    fn try_get_latest(&self) -> Option<PathBuf> {
+    fn try_get_latest(&self) -> Option<Vec<PathBuf>> {
         // Try and get the last inserted key
         let latest = self
             .completed_jobs
             .read()
             .last()
-            .map(|(file_name, _)| self.data_directory.join(file_name.as_ref()));
+            .map(|(_, job)| self.segment_paths(&job.segments));
 
         // If it doesn't exist, internally update the status and return the Option.
         if latest.is_none() {
@@ -75,6 +508,13 @@ impl WriterEngineState {
         latest
     }
 
+    fn segment_paths(&self, segments: &[Arc<str>]) -> Vec<PathBuf> {
+        segments
+            .iter()
+            .map(|segment| self.data_directory.join(segment.as_ref()))
+            .collect()
+    }
+
     // NOTE: if this is called and causing issues with the UI loop, return an option instead and use heuristics.
     // If the offline/recording has been run at least once, there must exist a recording that can
     // be loaded.
@@ -82,22 +522,20 @@ impl WriterEngineState {
         self.completed_jobs.read().len()
     }
 
-    fn get_recording_path(&self, file_name: Arc<str>) -> Option<PathBuf> {
+    fn get_recording_segments(&self, file_name: Arc<str>) -> Option<Vec<PathBuf>> {
         let mut map = self.completed_jobs.write();
-        if !map.contains_key(&file_name) {
-            return None;
-        }
+        let segment_paths = self.segment_paths(&map.get(&file_name)?.segments);
 
-        let expected_path = self.data_directory.join(file_name.as_ref());
-        if !expected_path.is_file() {
+        if !segment_paths.iter().all(|path| path.is_file()) {
             // Remove the broken link from the map if it no longer exists.
             map.shift_remove(&file_name);
             if map.is_empty() {
                 self.latest_exists.store(false, Ordering::Release);
             }
+            self.save_library_index(&map);
             None
         } else {
-            Some(expected_path)
+            Some(segment_paths)
         }
     }
 
@@ -107,27 +545,24 @@ impl WriterEngineState {
         self.clearing.store(true, Ordering::Release);
         let mut completed_jobs = self.completed_jobs.write();
 
-        for file in completed_jobs.keys() {
-            let file_path = self.data_directory.join(file.as_ref());
-            if let Ok(exists) = std::fs::exists(file_path.as_path()) {
-                // Don't care if the path is a directory (it should never, ever be one)
-                // Don't care if the file is already gone (the entry will get deleted from the map)
-                //
-                // If a user lacks permission to remove the file, then they're going to have a lot
-                // of trouble running this application - but it's not an error.
-                // When the app re-launches, this will just clobber any existing temporary files
-                // for recordings - i.e. let whomever can clear the cache files, clear the cache
-                // files.
-                if exists {
-                    let _ = std::fs::remove_file(file_path.as_path());
-                }
-            }
+        for job in completed_jobs.values() {
+            // Don't care if the path is a directory (it should never, ever be one)
+            // Don't care if the file is already gone (the entry will get deleted from the map)
+            //
+            // If a user lacks permission to remove the file, then they're going to have a lot
+            // of trouble running this application - but it's not an error.
+            // When the app re-launches, this will just clobber any existing temporary files
+            // for recordings - i.e. let whomever can clear the cache files, clear the cache
+            // files.
+            self.remove_recording_files(job);
         }
 
         self.latest_exists.store(false, Ordering::Release);
         // Then empty the hashmap and reset the accumulator.
         completed_jobs.clear();
         self.ticket.store(0, Ordering::Release);
+        // The library is empty now too.
+        self.remove_library_index();
 
         let console_message = ConsoleMessage::Status("Recording cache cleared.".to_string());
         let ribble_message = RibbleMessage::Console(console_message);
@@ -143,56 +578,194 @@ impl WriterEngineState {
         // ibid -> take a clone of the shared string.
         key: Arc<str>,
         format: RibbleExportFormat,
+        // Only used for Opus.
+        opus_bitrate: RibbleOpusBitrate,
     ) -> Result<RibbleMessage, RibbleError> {
-        let tmp_file_path = self.data_directory.join(key.as_ref());
-        let check_tmp_file = std::fs::exists(tmp_file_path.as_path());
-        if check_tmp_file.is_err() || !check_tmp_file? {
+        // Copy out the metadata; the lock shouldn't be held for the whole encode.
+        let (sample_rate, channels, segments) = {
+            let read_guard = self.completed_jobs.read();
+            let job = read_guard.get(key.as_ref()).ok_or(RibbleError::Core(
+                "Temp recording metadata not found.".to_string(),
+            ))?;
+            (job.sample_rate(), job.channels(), Arc::clone(&job.segments))
+        };
+
+        let segment_paths = self.segment_paths(&segments);
+        if !segment_paths.iter().all(|path| path.is_file()) {
             let error = std::io::Error::from(std::io::ErrorKind::NotFound);
             return Err(RibbleError::IOError(error));
         }
-        // If it's already in floating point, then this can be a direct copy.
-        if matches!(format, RibbleExportFormat::F32) {
-            std::fs::copy(tmp_file_path.as_path(), outfile_path.as_path())?;
+
+        // If it's already in floating point (and in one piece), then this can be a direct copy.
+        if matches!(format, RibbleExportFormat::F32) && segment_paths.len() == 1 {
+            std::fs::copy(segment_paths[0].as_path(), outfile_path.as_path())?;
 
             let console_message =
                 ConsoleMessage::Status(format!("Saved recording to {}!", outfile_path.display()));
             let ribble_message = RibbleMessage::Console(console_message);
-            Ok(ribble_message)
-        } else {
-            let read_guard = self.completed_jobs.read();
+            return Ok(ribble_message);
+        }
 
-            let job = read_guard.get(key.as_ref()).ok_or(RibbleError::Core(
-                "Temp recording metadata not found.".to_string(),
-            ))?;
+        // Open a reader per segment; they're read back to back as one recording.
+        let mut readers = segment_paths
+            .iter()
+            .map(|path| WavReader::open(path.as_path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let num_samples = readers
+            .iter()
+            .map(|reader| reader.len() as u64)
+            .sum::<u64>();
 
-            let sample_rate = job.sample_rate();
-            let channels = job.channels();
-            let spec = WavSpec {
-                channels: channels as u16,
-                sample_rate: sample_rate as u32,
-                bits_per_sample: 16,
-                sample_format: format.into(),
-            };
+        let result = match format {
+            RibbleExportFormat::Flac | RibbleExportFormat::Opus => {
+                // The encoders report progress as they pull each block through.
+                let progress_id = self.start_progress_job("Exporting recording", num_samples);
+                let increment = |delta: u64| self.increment_progress_job(progress_id, delta);
+                let samples = Self::recording_samples(&mut readers);
+                let result = match format {
+                    RibbleExportFormat::Flac => export_encoders::encode_flac(
+                        outfile_path.as_path(),
+                        samples,
+                        channels,
+                        sample_rate,
+                        increment,
+                    ),
+                    _ => export_encoders::encode_opus(
+                        outfile_path.as_path(),
+                        samples,
+                        num_samples,
+                        channels,
+                        sample_rate,
+                        opus_bitrate,
+                        increment,
+                    ),
+                };
+                self.cleanup_remove_progress_job(progress_id);
+                result
+            }
+            _ => {
+                // Wav is streamed straight through, so this is a single pass.
+                let progress_id = self.start_progress_job("Exporting recording", num_samples);
+                let increment = |delta: u64| self.increment_progress_job(progress_id, delta);
+                let spec = WavSpec {
+                    channels: channels as u16,
+                    sample_rate: sample_rate as u32,
+                    bits_per_sample: format.bits_per_sample(),
+                    sample_format: format.into(),
+                };
+                let result = Self::write_wav(outfile_path.as_path(), spec, &mut readers, increment);
+                self.cleanup_remove_progress_job(progress_id);
+                result
+            }
+        };
+        result?;
 
-            // Open a reader to read in the file to a buffer
-            let mut reader = WavReader::open(tmp_file_path.as_path())?;
+        let console_message =
+            ConsoleMessage::Status(format!("Saved recording to {}!", outfile_path.display()));
+        let ribble_message = RibbleMessage::Console(console_message);
+        Ok(ribble_message)
+    }
 
-            let int_audio = reader
-                .samples::<f32>()
-                .map(|sample| sample.map(|f| f.into_pcm_s16()))
-                .collect::<Result<Vec<i16>, _>>()?;
+    // The segments' samples, back to back, as one recording.
+    fn recording_samples(
+        readers: &mut [WavReader<BufReader<File>>],
+    ) -> impl Iterator<Item = Result<f32, RibbleError>> + '_ {
+        readers
+            .iter_mut()
+            .flat_map(|reader| reader.samples::<f32>())
+            .map(|sample| sample.map_err(RibbleError::from))
+    }
 
-            // Open a writer to read the new file out.
-            let mut writer = WavWriter::create(outfile_path.as_path(), spec)?;
-            for int_sample in int_audio {
-                writer.write_sample(int_sample)?
+    // Writes out the segments as one wav file (in the export sample format).
+    fn write_wav<F>(
+        outfile_path: &Path,
+        spec: WavSpec,
+        readers: &mut [WavReader<BufReader<File>>],
+        mut on_progress: F,
+    ) -> Result<(), RibbleError>
+    where
+        F: FnMut(u64),
+    {
+        // Open a writer to read the new file out.
+        let mut writer = WavWriter::create(outfile_path, spec)?;
+        let mut num_written = 0usize;
+        for reader in readers.iter_mut() {
+            for sample in reader.samples::<f32>() {
+                let sample = sample?;
+                match spec.sample_format {
+                    SampleFormat::Float => writer.write_sample(sample)?,
+                    SampleFormat::Int => writer.write_sample(sample.into_pcm_s16())?,
+                }
+                num_written += 1;
+                if num_written % Self::PROGRESS_CHUNK_SIZE == 0 {
+                    on_progress(Self::PROGRESS_CHUNK_SIZE as u64);
+                }
             }
+        }
+        on_progress((num_written % Self::PROGRESS_CHUNK_SIZE) as u64);
 
-            writer.finalize()?;
-            let console_message =
-                ConsoleMessage::Status(format!("Saved recording to {}!", outfile_path.display()));
-            let ribble_message = RibbleMessage::Console(console_message);
-            Ok(ribble_message)
+        writer.finalize()?;
+        Ok(())
+    }
+
+    fn start_progress_job(&self, job_name: &'static str, total_size: u64) -> Option<usize> {
+        let (id_sender, id_receiver) = get_channel(1);
+        let progress_message = ProgressMessage::Request {
+            job: Progress::new_determinate(job_name, total_size),
+            id_return_sender: id_sender,
+        };
+        if let Err(e) = self.progress_message_sender.send(progress_message) {
+            log::warn!(
+                "Progress channel closed, cannot send export job.\nError source: {:#?}",
+                e.source()
+            );
+            return None;
+        }
+        id_receiver.recv().ok()
+    }
+
+    fn increment_progress_job(&self, maybe_id: Option<usize>, delta: u64) {
+        if let Some(id) = maybe_id {
+            let increment = ProgressMessage::Increment { job_id: id, delta };
+            if let Err(e) = self.progress_message_sender.send(increment) {
+                log::warn!(
+                    "Progress channel closed, cannot send export progress.\nError source: {:#?}",
+                    e.source()
+                );
+            }
+        }
+    }
+
+    fn cleanup_remove_progress_job(&self, maybe_id: Option<usize>) {
+        if let Some(id) = maybe_id {
+            let remove_job = ProgressMessage::Remove { job_id: id };
+            if let Err(e) = self.progress_message_sender.send(remove_job) {
+                log::warn!(
+                    "Progress channel closed, cannot remove export job.\nError source: {:#?}",
+                    e.source()
+                );
+            }
+        }
+    }
+
+    // The files should look like "tmp_recording_<ticket_no>.wav"
+    // NOTE: a ticket is skipped if anything is still using it: a recording in the map (e.g. a
+    // recovered recording, or one that's being exported), or leftover files on disk.
+    fn next_ticket(&self) -> usize {
+        loop {
+            let ticket_no = self.ticket.fetch_add(1, Ordering::AcqRel);
+            let path = self.data_directory.join(segment_file_name(ticket_no, 0));
+            let in_use = path.exists()
+                || self
+                    .completed_jobs
+                    .read()
+                    .values()
+                    .flat_map(|job| job.segments.iter())
+                    .filter_map(|segment| parse_segment_file_name(segment))
+                    .any(|(segment_ticket, _)| segment_ticket == ticket_no);
+            if !in_use {
+                return ticket_no;
+            }
         }
     }
 
@@ -201,44 +774,51 @@ impl WriterEngineState {
         receiver: Receiver<Arc<[f32]>>,
         spec: RibbleRecordingConfigs,
     ) -> Result<RibbleMessage, RibbleError> {
-        // The files should look like "tmp_recording_<ticket_no>.wav"
-        let ticket_no = self.ticket.fetch_add(1, Ordering::AcqRel);
-        let tmp_name = Self::TMP_FILE;
-        let ext = Self::FILE_EXTENSION;
-        let file_name = format!("{tmp_name}_{ticket_no}.{ext}");
-        let path = self.data_directory.join(&file_name);
+        let created_at = SystemTime::now();
+        let ticket_no = self.next_ticket();
+        self.session_checkpointer
+            .checkpoint_recording(&self.data_directory, ticket_no, created_at);
 
         // Make a new WavWriter
         let wav_spec = spec.into_wav_spec(RibbleExportFormat::F32)?;
-        let mut writer = WavWriter::create(path, wav_spec)?;
+        let mut writer = SegmentedWavWriter::create(&self.data_directory, ticket_no, wav_spec)?;
         // NOTE: SDL (current backend sends interleaved data)
         // Wav is also interleaved, so this can just automatically write samples
-
-        let mut num_floats = 0usize;
         while let Ok(samples) = receiver.recv() {
-            for sample in samples.iter().copied() {
-                writer.write_sample(sample)?;
-            }
-
-            num_floats += samples.len();
+            writer.write_chunk(&samples)?;
         }
 
-        let total_duration_in_seconds = writer.duration() / wav_spec.sample_rate;
-        let total_duration = Duration::from_secs(total_duration_in_seconds as u64);
-        writer.finalize()?;
-        let file_size_estimate = num_floats * size_of::<f32>();
+        let recording = writer.finish()?;
+        let num_frames = recording.num_samples / wav_spec.channels as usize;
+        let total_duration =
+            Duration::from_secs_f64(num_frames as f64 / wav_spec.sample_rate as f64);
+        let file_size_estimate = recording.num_samples * size_of::<f32>();
+
+        let segments = recording
+            .segments
+            .into_iter()
+            .map(Arc::<str>::from)
+            .collect::<Vec<_>>();
 
         // Make a new entry in the completed jobs queue.
         let mut job_bank = self.completed_jobs.write();
         job_bank.insert(
-            Arc::from(file_name),
+            Arc::clone(&segments[0]),
             CompletedRecordingJobs {
                 total_duration,
                 file_size_estimate,
                 sample_rate: wav_spec.sample_rate as usize,
                 channels: wav_spec.channels as usize,
+                created_at,
+                label: None,
+                transcript_path: None,
+                segments: Arc::from(segments),
             },
         );
+        self.apply_retention(&mut job_bank);
+        self.save_library_index(&job_bank);
+        // The recording is accounted for; it's no longer at risk.
+        self.session_checkpointer.clear_recording();
 
         // Format HH:MM:SS
         let secs = total_duration.as_secs();
@@ -270,9 +850,17 @@ impl WriterEngine {
     pub(super) fn new(
         data_directory: PathBuf,
         incoming_jobs: Receiver<WriteRequest>,
+        start_library_configs: Option<RecordingLibraryConfigs>,
+        session_checkpointer: Arc<SessionCheckpointer>,
         bus: &Bus,
     ) -> Self {
-        let inner = Arc::new(WriterEngineState::new(data_directory, incoming_jobs, bus));
+        let inner = Arc::new(WriterEngineState::new(
+            data_directory,
+            incoming_jobs,
+            start_library_configs,
+            session_checkpointer,
+            bus,
+        ));
         let thread_inner = Arc::clone(&inner);
         // NOTE: It would probably be an optimization to just pre-clone the pointer; if it's
         // genuinely an issue, that's low-hanging fruit.
@@ -312,10 +900,11 @@ impl WriterEngine {
         // This is the key -> The caller needs to clone it higher up and pass ownership of the new pointer
         job_file_name: Arc<str>,
         output_format: RibbleExportFormat,
+        opus_bitrate: RibbleOpusBitrate,
     ) {
         let thread_inner = Arc::clone(&self.inner);
         let worker = std::thread::spawn(move || {
-            thread_inner.export_recording(out_path, job_file_name, output_format)
+            thread_inner.export_recording(out_path, job_file_name, output_format, opus_bitrate)
         });
 
         let work_request = WorkRequest::Short(worker);
@@ -339,7 +928,7 @@ impl WriterEngine {
             copy_buffer.clear();
             copy_buffer.extend(
                 jobs.iter()
-                    .map(|(file_name, metadata)| (Arc::clone(file_name), *metadata)),
+                    .map(|(file_name, metadata)| (Arc::clone(file_name), metadata.clone())),
             );
             copy_buffer.reverse();
         }
@@ -353,7 +942,9 @@ impl WriterEngine {
     // In the UI, label this button: Re-Transcribe latest (recording)
     // If this is none, that means either writing hasn't finished, or there are no recordings.
     // This is not necessarily an error.
-    pub(super) fn try_get_latest(&self) -> Option<PathBuf> {
+    // NOTE: a long recording is split across several segment files; these are its segments, in
+    // order.
+    pub(super) fn try_get_latest(&self) -> Option<Vec<PathBuf>> {
         self.inner.try_get_latest()
     }
 
@@ -363,8 +954,43 @@ impl WriterEngine {
         self.inner.get_num_completed()
     }
 
-    pub(super) fn get_recording_path(&self, file_name: Arc<str>) -> Option<PathBuf> {
-        self.inner.get_recording_path(file_name)
+    pub(super) fn get_recording_segments(&self, file_name: Arc<str>) -> Option<Vec<PathBuf>> {
+        self.inner.get_recording_segments(file_name)
+    }
+
+    pub(super) fn read_library_configs(&self) -> Arc<RecordingLibraryConfigs> {
+        self.inner.library_configs.load_full()
+    }
+    pub(super) fn write_library_configs(&self, new_configs: RecordingLibraryConfigs) {
+        self.inner.write_library_configs(new_configs);
+    }
+
+    // An empty label resets the recording to its default name.
+    pub(super) fn set_recording_label(&self, file_name: &str, label: &str) {
+        self.inner.set_recording_label(file_name, label);
+    }
+    pub(super) fn link_transcript(&self, file_name: &str, transcript_path: &Path) {
+        self.inner.link_transcript(file_name, transcript_path);
+    }
+    pub(super) fn recording_file_name(&self, path: &Path) -> Option<Arc<str>> {
+        self.inner.recording_file_name(path)
+    }
+    // NOTE: real-time transcription records as it goes; use this with the run's start time to
+    // find its recording.
+    pub(super) fn first_recording_since(&self, time: SystemTime) -> Option<Arc<str>> {
+        self.inner.first_recording_since(time)
+    }
+
+    // Call once on startup (and not for headless runs, which leave the app's recordings alone).
+    pub(super) fn recover_orphaned_recordings(
+        &self,
+        in_progress: Option<&RecordingCheckpoint>,
+    ) -> usize {
+        self.inner.recover_orphaned_recordings(in_progress)
+    }
+    // Call once on startup, instead of recovering them.
+    pub(super) fn discard_orphaned_recordings(&self) -> usize {
+        self.inner.discard_orphaned_recordings()
     }
 
     // Use this to disable a clear cache button in the UI thread.
@@ -408,6 +1034,14 @@ impl Drop for WriterEngine {
             );
             log::info!("WriterEngine work thread joined.");
         }
+        // Keep the library for the next session.
+        if self.inner.library_enabled() {
+            log::info!("Saving recording library.");
+            self.inner
+                .save_library_index(&self.inner.completed_jobs.read());
+            log::info!("Recording library saved.");
+            return;
+        }
         log::info!("Clearing WriterEngine cache.");
         // Also, clear the cache - it's easier to just nuke it and let the next session start fresh.
         if let Err(e) = self.inner.clear_cache() {
@@ -416,3 +1050,288 @@ impl Drop for WriterEngine {
         log::info!("WriterEngine cache cleared.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::test_harness::CapturedBus;
+    use tempfile::TempDir;
+
+    const MB: usize = 1024 * 1024;
+
+    fn new_state(
+        directory: &TempDir,
+        library_configs: RecordingLibraryConfigs,
+        bus: &CapturedBus,
+    ) -> WriterEngineState {
+        let (_, incoming_jobs) = get_channel(1);
+        let checkpointer = Arc::new(SessionCheckpointer::new(directory.path(), false));
+        WriterEngineState::new(
+            directory.path().to_path_buf(),
+            incoming_jobs,
+            Some(library_configs),
+            checkpointer,
+            bus.bus(),
+        )
+    }
+
+    fn library(
+        keep_last: Option<usize>,
+        max_total_size_mb: Option<u64>,
+    ) -> RecordingLibraryConfigs {
+        RecordingLibraryConfigs::new()
+            .with_enabled(true)
+            .with_keep_last(keep_last)
+            .with_max_total_size_mb(max_total_size_mb)
+    }
+
+    // Writes out a (fake) recording file, so that there's something to remove.
+    fn add_recording(
+        state: &WriterEngineState,
+        completed_jobs: &mut IndexMap<Arc<str>, CompletedRecordingJobs>,
+        ticket_no: usize,
+        file_size: usize,
+    ) -> Arc<str> {
+        let file_name: Arc<str> = Arc::from(segment_file_name(ticket_no, 0));
+        std::fs::write(state.data_directory.join(file_name.as_ref()), b"RIFF").unwrap();
+        let job = CompletedRecordingJobs::new(
+            file_size,
+            Duration::from_millis(1500),
+            1,
+            16000,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(ticket_no as u64),
+            Arc::from([Arc::clone(&file_name)]),
+        );
+        completed_jobs.insert(Arc::clone(&file_name), job);
+        file_name
+    }
+
+    fn file_exists(state: &WriterEngineState, file_name: &str) -> bool {
+        state.data_directory.join(file_name).exists()
+    }
+
+    #[test]
+    fn retention_keeps_the_last_n_recordings() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let state = new_state(&directory, library(Some(2), None), &bus);
+        let mut completed_jobs = IndexMap::new();
+        let file_names = (0..4)
+            .map(|ticket_no| add_recording(&state, &mut completed_jobs, ticket_no, 1))
+            .collect::<Vec<_>>();
+
+        state.apply_retention(&mut completed_jobs);
+
+        assert_eq!(
+            completed_jobs.keys().cloned().collect::<Vec<_>>(),
+            file_names[2..]
+        );
+        assert!(!file_exists(&state, &file_names[0]));
+        assert!(!file_exists(&state, &file_names[1]));
+        assert!(file_exists(&state, &file_names[3]));
+    }
+
+    #[test]
+    fn retention_removes_the_oldest_until_under_the_size_limit() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let state = new_state(&directory, library(None, Some(1)), &bus);
+        let mut completed_jobs = IndexMap::new();
+        let file_names = (0..4)
+            .map(|ticket_no| add_recording(&state, &mut completed_jobs, ticket_no, MB / 3))
+            .collect::<Vec<_>>();
+
+        state.apply_retention(&mut completed_jobs);
+
+        // 4 thirds of a MB is over the limit; 3 are not.
+        assert_eq!(
+            completed_jobs.keys().cloned().collect::<Vec<_>>(),
+            file_names[1..]
+        );
+        assert!(!file_exists(&state, &file_names[0]));
+    }
+
+    #[test]
+    fn retention_always_keeps_the_newest_recording() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let state = new_state(&directory, library(None, Some(1)), &bus);
+        let mut completed_jobs = IndexMap::new();
+        add_recording(&state, &mut completed_jobs, 0, 1);
+        let newest = add_recording(&state, &mut completed_jobs, 1, 10 * MB);
+
+        state.apply_retention(&mut completed_jobs);
+
+        assert_eq!(completed_jobs.keys().cloned().collect::<Vec<_>>(), [newest]);
+    }
+
+    #[test]
+    fn retention_is_off_without_the_library() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let configs = library(Some(1), Some(1)).with_enabled(false);
+        let state = new_state(&directory, configs, &bus);
+        let mut completed_jobs = IndexMap::new();
+        for ticket_no in 0..3 {
+            add_recording(&state, &mut completed_jobs, ticket_no, 10 * MB);
+        }
+
+        state.apply_retention(&mut completed_jobs);
+        assert_eq!(completed_jobs.len(), 3);
+    }
+
+    #[test]
+    fn the_library_is_reloaded_on_the_next_launch() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let configs = library(None, None);
+        let transcript_path: Arc<Path> = Arc::from(Path::new("/transcripts/meeting.txt"));
+        {
+            let state = new_state(&directory, configs, &bus);
+            let mut completed_jobs = state.completed_jobs.write();
+            add_recording(&state, &mut completed_jobs, 0, 1);
+            let file_name = add_recording(&state, &mut completed_jobs, 3, 1);
+            // This one is deleted outside of the app before the next launch.
+            let deleted = add_recording(&state, &mut completed_jobs, 4, 1);
+            let job = completed_jobs.get_mut(&file_name).unwrap();
+            job.label = Some(Arc::from("Meeting"));
+            job.transcript_path = Some(Arc::clone(&transcript_path));
+            state.save_library_index(&completed_jobs);
+            std::fs::remove_file(directory.path().join(deleted.as_ref())).unwrap();
+        }
+
+        let state = new_state(&directory, configs, &bus);
+        let completed_jobs = state.completed_jobs.read();
+        assert_eq!(
+            completed_jobs
+                .keys()
+                .map(|key| key.as_ref())
+                .collect::<Vec<_>>(),
+            ["tmp_recording_0.wav", "tmp_recording_3.wav"]
+        );
+        let job = &completed_jobs["tmp_recording_3.wav"];
+        assert_eq!(job.label.as_deref(), Some("Meeting"));
+        assert_eq!(job.transcript_path, Some(transcript_path));
+        assert_eq!(job.total_duration, Duration::from_millis(1500));
+        assert_eq!(
+            job.created_at,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(3)
+        );
+        assert_eq!(job.sample_rate, 16000);
+        // The size is taken from the disk, not the index.
+        assert_eq!(job.file_size_estimate, 4);
+        assert!(state.latest_exists.load(Ordering::Acquire));
+        drop(completed_jobs);
+
+        // New recordings are numbered after the library's.
+        assert_eq!(state.next_ticket(), 4);
+    }
+
+    fn write_orphan(directory: &TempDir, file_name: &str, num_frames: usize) {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(directory.path().join(file_name), spec).unwrap();
+        for _ in 0..num_frames {
+            writer.write_sample(0.25f32).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn orphaned_recordings_are_recovered() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let state = new_state(&directory, library(None, None), &bus);
+        {
+            let mut completed_jobs = state.completed_jobs.write();
+            add_recording(&state, &mut completed_jobs, 0, 1);
+        }
+        write_orphan(&directory, &segment_file_name(2, 1), 8000);
+        write_orphan(&directory, &segment_file_name(2, 0), 16000);
+        write_orphan(&directory, &segment_file_name(5, 0), 4000);
+
+        // Recording 2 was in progress when the app crashed.
+        let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        let checkpointer = SessionCheckpointer::new(directory.path(), true);
+        checkpointer.checkpoint_recording(directory.path(), 2, started_at);
+        let in_progress = checkpointer.read_recording_checkpoint();
+
+        assert_eq!(state.recover_orphaned_recordings(in_progress.as_ref()), 2);
+
+        let completed_jobs = state.completed_jobs.read();
+        assert_eq!(
+            completed_jobs
+                .keys()
+                .map(|key| key.as_ref())
+                .collect::<Vec<_>>(),
+            [
+                "tmp_recording_0.wav",
+                "tmp_recording_2.wav",
+                "tmp_recording_5.wav"
+            ]
+        );
+        let job = &completed_jobs["tmp_recording_2.wav"];
+        assert_eq!(
+            job.segments.iter().map(|s| s.as_ref()).collect::<Vec<_>>(),
+            ["tmp_recording_2.wav", "tmp_recording_2_part1.wav"]
+        );
+        assert_eq!(job.total_duration, Duration::from_millis(1500));
+        assert_eq!(job.created_at, started_at);
+        assert_eq!(job.label.as_deref(), Some("Recovered recording"));
+        assert_eq!(
+            completed_jobs["tmp_recording_5.wav"].total_duration,
+            Duration::from_millis(250)
+        );
+        drop(completed_jobs);
+
+        // Recovered recordings are kept in the library; the next launch has nothing to recover.
+        assert!(state.library_index_path().exists());
+        assert_eq!(state.recover_orphaned_recordings(None), 0);
+        assert_eq!(state.next_ticket(), 6);
+    }
+
+    #[test]
+    fn discarded_orphans_are_removed_from_disk() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let state = new_state(&directory, library(None, None), &bus);
+        let kept = {
+            let mut completed_jobs = state.completed_jobs.write();
+            add_recording(&state, &mut completed_jobs, 0, 1)
+        };
+        write_orphan(&directory, &segment_file_name(2, 0), 16000);
+        write_orphan(&directory, &segment_file_name(2, 1), 16000);
+        write_orphan(&directory, &segment_file_name(5, 0), 16000);
+
+        assert_eq!(state.discard_orphaned_recordings(), 2);
+
+        assert!(file_exists(&state, &kept));
+        assert!(!file_exists(&state, &segment_file_name(2, 0)));
+        assert!(!file_exists(&state, &segment_file_name(2, 1)));
+        assert!(!file_exists(&state, &segment_file_name(5, 0)));
+        assert_eq!(state.recover_orphaned_recordings(None), 0);
+        assert_eq!(state.get_num_completed(), 1);
+    }
+
+    #[test]
+    fn tickets_in_use_are_skipped() {
+        let directory = tempfile::tempdir().unwrap();
+        let bus = CapturedBus::new();
+        let state = new_state(&directory, RecordingLibraryConfigs::new(), &bus);
+        {
+            let mut completed_jobs = state.completed_jobs.write();
+            // e.g. a recovered recording, whose files are being exported.
+            add_recording(&state, &mut completed_jobs, 0, 1);
+            std::fs::remove_file(directory.path().join(segment_file_name(0, 0))).unwrap();
+        }
+        // e.g. left behind by another session.
+        std::fs::write(directory.path().join(segment_file_name(1, 0)), b"RIFF").unwrap();
+
+        assert_eq!(state.next_ticket(), 2);
+        assert_eq!(state.next_ticket(), 3);
+    }
+}
@@ -5,8 +5,10 @@
 // There is a lot of unused application code--mostly constructions/artifacts of the design process
 // They may end up useful in the future/somewhere else, so for now they'll remain in the project.
 #![windows_subsystem = "windows"] // Hide console window in release mode on windows.
+// NOTE: this also detaches stdout/stderr in release mode on windows, so the CLI is silent there.
 use mimalloc::MiMalloc;
 
+use crate::cli::{CliCommand, USAGE};
 use crate::runner::RibbleRunner;
 use crate::utils::errors::RibbleError;
 
@@ -14,13 +16,46 @@ use crate::utils::errors::RibbleError;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
+mod cli;
+mod control_api;
 mod controller;
 mod runner;
 mod ui;
 mod utils;
 
 fn main() -> Result<(), RibbleError> {
-    let ribble = RibbleRunner::new()?;
-    ribble.run()
+    // Parse before setting up the runner so that bad arguments don't touch the data directory.
+    let command = match CliCommand::from_args(std::env::args_os().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        None => {
+            let ribble = RibbleRunner::new()?;
+            ribble.run()
+        }
+        Some(CliCommand::Launch(args)) => {
+            let ribble = RibbleRunner::new()?.with_audio_backend(args.audio_backend());
+            ribble.run()
+        }
+        Some(CliCommand::Help) => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => {
+            let ribble = RibbleRunner::new_headless()?;
+            // NOTE: the runner (and kernel) need to be dropped before exiting so that the logs
+            // are flushed.
+            if let Err(e) = ribble.run_headless(command) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
 }
 
@@ -1,3 +1,9 @@
+use crate::cli::{run_transcribe, CliCommand};
+use crate::controller::audio_backend_proxy::AudioBackendKind;
+use crate::controller::ribble_controller::HeadlessController;
+use crate::controller::session_checkpoint::{
+    discard_unfinished_session, find_unfinished_session, UnfinishedSession,
+};
 use crate::ui::app::Ribble;
 use crate::utils::crash_handler::set_up_desktop_crash_handler;
 use crate::utils::errors::RibbleError;
@@ -40,25 +46,36 @@ static MIGRATION_VERSION: OnceLock<RibbleVersion> = OnceLock::new();
 // old paths don't need to be maintained, or move to a different Versioning mechanism.
 const OLD_MODEL_STUB: &str = "models";
 
-pub(crate) struct RibbleRunner<'a> {
+pub(crate) struct RibbleRunner {
     version: RibbleVersion,
     // NOTE: this -could- have just a path reference, perhaps that might be better.
     data_directory: PathBuf,
-    app: Option<AppCreator<'a>>,
+    // NOTE: the app itself is created on run, once it's known whether to restore the last session.
+    audio_backend: Option<AudioBackendKind>,
     window_options: Option<NativeOptions>,
     _logger: Option<LoggerHandle>,
     _crash_handler: CrashHandler,
 }
 
-impl RibbleRunner<'_> {
+impl RibbleRunner {
     const VERSION_FILE_NAME: &'static str = "version.ron";
     // This is a "week's worth" of log files, or 7 launches of the application.
     const MAX_LOG_FILES: usize = 7;
     const LOG_FILE_NAME: &'static str = "ribble_log";
+    const HEADLESS_LOG_FILE_NAME: &'static str = "ribble_cli_log";
     const EGUI_MEMORY_FILE_NAME: &'static str = "egui.ron";
     const LOGS_SLUG: &'static str = "logs";
 
     pub(crate) fn new() -> Result<Self, RibbleError> {
+        Self::build(false)
+    }
+
+    // For running a CLI command without a window: see run_headless().
+    pub(crate) fn new_headless() -> Result<Self, RibbleError> {
+        Self::build(true)
+    }
+
+    fn build(headless: bool) -> Result<Self, RibbleError> {
         // Set up the project directory
         let proj_dirs = ProjectDirs::from(QUALIFIER, ORGANIZATION, APP_ID).ok_or(
             RibbleError::Core("Failed to form project directory.".to_string()),
@@ -143,11 +160,44 @@ impl RibbleRunner<'_> {
             std::fs::create_dir_all(logs_directory.as_path())?;
         }
 
-        #[cfg(debug_assertions)]
-        let logger = Logger::try_with_str("info")?
+        let logger = if headless {
+            Self::headless_logger(logs_directory.as_path())?
+        } else {
+            Self::app_logger(logs_directory.as_path())?
+        };
+
+        // This needs to be kept alive until the app goes out of scope (consume on run).
+        let logger_handle = logger.start()?;
+
+        // Set up the crash handler
+        let crash_handler = set_up_desktop_crash_handler()?;
+
+        // Set up the window (there's no window to build when headless).
+        // Give a copy of the data dir to the eframe window (for other persistence)
+        let (audio_backend, window_options) = if headless {
+            (None, None)
+        } else {
+            let window_options = build_window(data_directory.join(Self::EGUI_MEMORY_FILE_NAME));
+            (Some(AudioBackendKind::default()), Some(window_options))
+        };
+
+        // Return the runner
+        Ok(RibbleRunner {
+            version,
+            data_directory,
+            audio_backend,
+            window_options,
+            _logger: Some(logger_handle),
+            _crash_handler: crash_handler,
+        })
+    }
+
+    #[cfg(debug_assertions)]
+    fn app_logger(logs_directory: &Path) -> Result<Logger, RibbleError> {
+        Ok(Logger::try_with_str("info")?
             .log_to_file(
                 FileSpec::default()
-                    .directory(logs_directory.as_path())
+                    .directory(logs_directory)
                     .basename(Self::LOG_FILE_NAME),
             )
             .write_mode(WriteMode::BufferAndFlush)
@@ -156,13 +206,15 @@ impl RibbleRunner<'_> {
                 Criterion::Age(Age::Day),
                 Naming::Timestamps,
                 Cleanup::KeepLogFiles(Self::MAX_LOG_FILES),
-            );
+            ))
+    }
 
-        #[cfg(not(debug_assertions))]
-        let logger = Logger::try_with_str("warn")?
+    #[cfg(not(debug_assertions))]
+    fn app_logger(logs_directory: &Path) -> Result<Logger, RibbleError> {
+        Ok(Logger::try_with_str("warn")?
             .log_to_file(
                 FileSpec::default()
-                    .directory(logs_directory.as_path())
+                    .directory(logs_directory)
                     .basename(Self::LOG_FILE_NAME),
             )
             .write_mode(WriteMode::BufferAndFlush)
@@ -170,33 +222,48 @@ impl RibbleRunner<'_> {
                 Criterion::Age(Age::Day),
                 Naming::Timestamps,
                 Cleanup::KeepLogFiles(Self::MAX_LOG_FILES),
-            );
-
-
-        // This needs to be kept alive until the app goes out of scope (consume on run).
-        let logger_handle = logger.start()?;
+            ))
+    }
 
-        // Set up the crash handler
-        let crash_handler = set_up_desktop_crash_handler()?;
+    // NOTE: stderr belongs to the CLI's own output (progress and errors), so nothing is
+    // duplicated there, even in debug builds. The logs go to their own files, so that CLI runs
+    // don't rotate out the app's logs.
+    fn headless_logger(logs_directory: &Path) -> Result<Logger, RibbleError> {
+        Ok(Logger::try_with_str("warn")?
+            .log_to_file(
+                FileSpec::default()
+                    .directory(logs_directory)
+                    .basename(Self::HEADLESS_LOG_FILE_NAME),
+            )
+            .write_mode(WriteMode::BufferAndFlush)
+            .rotate(
+                Criterion::Age(Age::Day),
+                Naming::Timestamps,
+                Cleanup::KeepLogFiles(Self::MAX_LOG_FILES),
+            ))
+    }
 
-        // Construct the app
-        // Give a copy of the data dir to the eframe window (for other persistence)
-        let window_options = build_window(data_directory.join(Self::EGUI_MEMORY_FILE_NAME));
+    // Swaps the audio capture backend (SDL2 by default) before the app is launched.
+    pub(crate) fn with_audio_backend(mut self, audio_backend: AudioBackendKind) -> Self {
+        self.audio_backend = Some(audio_backend);
+        self
+    }
 
-        let app_path = data_directory.clone();
-        let app: AppCreator<'_> = Box::new(move |cc| {
-            let ribble_app = Ribble::new(version, app_path.as_path(), cc)?;
+    fn app_creator(
+        version: RibbleVersion,
+        app_path: PathBuf,
+        audio_backend: AudioBackendKind,
+        restore_session: bool,
+    ) -> AppCreator<'static> {
+        Box::new(move |cc| {
+            let ribble_app = Ribble::new(
+                version,
+                app_path.as_path(),
+                audio_backend,
+                restore_session,
+                cc,
+            )?;
             Ok(Box::new(ribble_app))
-        });
-
-        // Return the runner
-        Ok(RibbleRunner {
-            version,
-            data_directory,
-            app: Some(app),
-            window_options: Some(window_options),
-            _logger: Some(logger_handle),
-            _crash_handler: crash_handler,
         })
     }
 
@@ -208,11 +275,34 @@ impl RibbleRunner<'_> {
             .take()
             .ok_or(RibbleError::Core("Window not initialized.".to_string()))?;
 
-        let app = self
-            .app
+        let audio_backend = self
+            .audio_backend
             .take()
             .ok_or(RibbleError::Core("App not initialized.".to_string()))?;
 
+        // If the last session crashed, this has to be decided before the app (and its kernel)
+        // loads: the kernel only restores the checkpoints (and recovers leftover recordings) if
+        // the user wants them back; otherwise, the leftover recordings are removed.
+        let restore_session = match find_unfinished_session(self.data_directory.as_path()) {
+            Some(session) if offer_session_restore(&session) => {
+                log::info!("Restoring unfinished session.");
+                true
+            }
+            Some(_) => {
+                log::info!("Discarding unfinished session.");
+                discard_unfinished_session(self.data_directory.as_path());
+                false
+            }
+            // Nothing was checkpointed, but anything left behind is still worth recovering.
+            None => true,
+        };
+        let app = Self::app_creator(
+            self.version,
+            self.data_directory.clone(),
+            audio_backend,
+            restore_session,
+        );
+
         run_native(APP_ID, window_options, app)
             .map_err(|err| RibbleError::Eframe(err.to_string()))?;
         log::info!("Ribble window terminated.");
@@ -220,6 +310,20 @@ impl RibbleRunner<'_> {
         // -- Expect the app to be dropped here; if not, things might get a bit crusty with logging.
     }
 
+    // Runs a CLI command without creating a window.
+    // NOTE: this also consumes the runner -> the version will get serialized on drop.
+    pub(crate) fn run_headless(self, command: CliCommand) -> Result<(), RibbleError> {
+        log::info!("Starting Ribble (headless).");
+        match command {
+            CliCommand::Help | CliCommand::Launch(_) => Ok(()),
+            CliCommand::Transcribe(args) => {
+                let controller = HeadlessController::new(self.data_directory.as_path())?;
+                run_transcribe(controller, args)
+            }
+        }
+        // -- Expect the controller (and kernel) to be dropped before the runner.
+    }
+
     fn serialize_version(&self) {
         let canonicalized = self.data_directory.join(Self::VERSION_FILE_NAME);
         match File::create(canonicalized.as_path()) {
@@ -266,7 +370,7 @@ impl RibbleRunner<'_> {
     }
 }
 
-impl Drop for RibbleRunner<'_> {
+impl Drop for RibbleRunner {
     fn drop(&mut self) {
         log::info!("Dropping ribble runner, serializing version.");
         self.serialize_version();
@@ -274,6 +378,17 @@ impl Drop for RibbleRunner<'_> {
     }
 }
 
+// NOTE: this runs before the window exists, so it uses a native dialog (same as the crash handler).
+fn offer_session_restore(session: &UnfinishedSession) -> bool {
+    let result = rfd::MessageDialog::new()
+        .set_title("Restore Session?")
+        .set_description(session.description())
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .set_level(rfd::MessageLevel::Warning)
+        .show();
+    matches!(result, rfd::MessageDialogResult::Yes)
+}
+
 const DEFAULT_WINDOW_SIZE: egui::Vec2 = egui::Vec2::new(1024.0, 768.0);
 
 #[inline]
@@ -1,20 +1,29 @@
+use std::collections::HashSet;
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use slab::Slab;
 
+use crate::control_api::ControlServer;
 use crate::controller::audio_backend_proxy::{
-    AudioBackendProxy, AudioCaptureRequest, SharedSdl2Capture,
+    list_input_devices, AudioBackendKind, AudioBackendProxy, AudioCaptureRequest, SdlCapture,
+    SharedSdl2Capture,
 };
+#[cfg(feature = "cpal-backend")]
+use crate::controller::cpal_capture;
+use crate::controller::playback::{PlaybackHandle, PLAYBACK_SAMPLE_RATE};
 use crate::controller::ribble_controller::RibbleController;
 use crate::controller::{
     AmortizedDownloadProgress, AmortizedProgress, LatestError, UI_UPDATE_QUEUE_SIZE,
 };
 use crate::ui::panes::ribble_pane::{ClosableRibbleViewPane, RibblePaneId};
 use crate::ui::panes::RibbleTree;
+use crate::ui::DEFAULT_TOAST_DURATION;
 use crate::ui::widgets::pie_progress::pie_progress;
 use crate::ui::widgets::recording_icon::recording_icon;
+use crate::utils::control_api_configs::ControlApiConfigs;
 use crate::utils::errors::RibbleError;
 #[cfg(debug_assertions)]
 use crate::utils::errors::RibbleErrorCategory;
@@ -28,9 +37,10 @@ use irox_egui_extras::progressbar::ProgressBar;
 use ribble_whisper::audio::audio_backend::{
     default_backend, AudioBackend, CaptureSpec, Sdl2Backend,
 };
-use ribble_whisper::audio::microphone::{MicCapture, Sdl2Capture};
+use ribble_whisper::audio::microphone::MicCapture;
 use ribble_whisper::audio::recorder::ArcChannelSink;
 use ribble_whisper::sdl2;
+use ribble_whisper::sdl2::audio::{AudioDevice, AudioSpecDesired, AudioStatus};
 use ribble_whisper::utils::errors::RibbleWhisperError;
 use ribble_whisper::utils::{get_channel, Receiver};
 use std::sync::Arc;
@@ -43,6 +53,9 @@ const ERROR_ICON: &str = "❎";
 const TOOLTIP_GRACE_TIME: f32 = 0.0;
 const TOOLTIP_DELAY: f32 = 0.5;
 
+// SDL re-enumerates the devices on every call, so the list is only refreshed this often.
+const INPUT_DEVICE_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
 // This is in seconds
 const THEME_TRANSITION_TIME: f32 = 0.3;
 const RECORDING_ICON_FLICKER_SPEED: f32 = 1.0;
@@ -59,14 +72,28 @@ const TOP_BAR_BUTTON_SIZE: f32 = 20.0;
 pub struct Ribble {
     version: RibbleVersion,
     tree: RibbleTree,
-    _sdl: sdl2::Sdl,
+    sdl: sdl2::Sdl,
     backend: Sdl2Backend,
     // This needs to be polled in the UI loop to handle
     capture_requests: Receiver<AudioCaptureRequest>,
     toasts_handle: Toasts,
     toasts_receiver: Receiver<Toast>,
-    current_devices: Slab<Arc<Sdl2Capture<ArcChannelSink<f32>>>>,
+    audio_backend: AudioBackendKind,
+    // Only SDL2 captures are kept here; the other backends own their streams.
+    current_devices: Slab<Arc<SdlCapture>>,
+    // Open devices that have been unplugged (and already reported).
+    disconnected_devices: HashSet<usize>,
+    input_devices_refreshed: Option<Instant>,
+    // NOTE: like the capture devices, this must only be dropped on the main thread.
+    // None if the output device could not be opened; playback is unavailable.
+    playback_device: Option<AudioDevice<PlaybackHandle>>,
     controller: RibbleController,
+    // The control API writes its token here.
+    data_directory: PathBuf,
+    // NOTE: this holds a controller, so it needs to be dropped before the app exits.
+    control_server: Option<ControlServer>,
+    // The last configs the server was (re)started with; None until the first frame.
+    applied_control_api_configs: Option<ControlApiConfigs>,
 
     theme_animator: ThemeAnimator,
 
@@ -83,6 +110,9 @@ impl Ribble {
     pub(crate) fn new(
         version: RibbleVersion,
         data_directory: &Path,
+        audio_backend: AudioBackendKind,
+        // Whether the user chose to restore the last session, if it didn't exit cleanly.
+        restore_session: bool,
         cc: &eframe::CreationContext<'_>,
     ) -> Result<Self, RibbleError> {
         // Pack these in the app struct so they live on the main thread.
@@ -99,9 +129,17 @@ impl Ribble {
         let toasts_handle = Toasts::default();
 
         // Send this to the kernel
-        let backend_proxy = AudioBackendProxy::new(request_sender);
+        if audio_backend != AudioBackendKind::Sdl2 {
+            log::info!("Capturing audio with: {audio_backend:?}");
+        }
+        let backend_proxy = AudioBackendProxy::new(audio_backend.clone(), request_sender);
         // Deserialize/default construct the controller.
-        let controller = RibbleController::new(data_directory, backend_proxy, toasts_sender)?;
+        let controller = RibbleController::new(
+            data_directory,
+            backend_proxy,
+            toasts_sender,
+            restore_session,
+        )?;
 
         // Deserialize/default construct the app tree -> this has its own default layout.
         let tree = RibbleTree::new(data_directory, controller.clone());
@@ -122,17 +160,25 @@ impl Ribble {
         cc.egui_ctx.set_visuals(system_visuals);
 
         let current_devices = Slab::new();
+        let playback_device = Self::open_playback_device(&sdl_ctx, &controller);
 
         Ok(Self {
             version,
             tree,
-            _sdl: sdl_ctx,
+            sdl: sdl_ctx,
             backend,
             capture_requests: request_receiver,
             toasts_handle,
             toasts_receiver,
+            audio_backend,
             current_devices,
+            disconnected_devices: HashSet::new(),
+            input_devices_refreshed: None,
+            playback_device,
             controller,
+            data_directory: data_directory.to_path_buf(),
+            control_server: None,
+            applied_control_api_configs: None,
             theme_animator,
             periodic_serialize: None,
             // Since the data is guarded by sync locks, these need to be cached in the UI,
@@ -147,13 +193,24 @@ impl Ribble {
     fn open_audio_device(
         &mut self,
         spec: CaptureSpec,
+        input_device: Option<Arc<str>>,
         sink: ArcChannelSink<f32>,
-    ) -> Result<SharedSdl2Capture<ArcChannelSink<f32>>, RibbleWhisperError> {
+    ) -> Result<SharedSdl2Capture, RibbleWhisperError> {
         // Try to open capture
         // Give ownership to the Arc temporarily
         // -This is technically a major "warn", but there are mechanisms in place to ensure that
         // the device is only dropped on the main thread.
-        let device = Arc::new(self.backend.open_capture(spec, sink)?);
+        let device = match input_device {
+            Some(name) => {
+                let audio_subsystem = self
+                    .sdl
+                    .audio()
+                    .map_err(RibbleWhisperError::DeviceError)?;
+                SdlCapture::open_device(&audio_subsystem, name, spec, sink)?
+            }
+            None => SdlCapture::Default(self.backend.open_capture(spec, sink)?),
+        };
+        let device = Arc::new(device);
 
         // Clone a reference to consume for the shared capture
         let shared_device = Arc::clone(&device);
@@ -202,6 +259,7 @@ impl Ribble {
         // This will panic if the device is not in the slab.
         // Use try_remove in case the device has already been removed.
         // This shouldn't ever happen--but this needs to be tested first.
+        self.disconnected_devices.remove(&device_id);
         if let Some(shared_device) = self.current_devices.try_remove(device_id) {
             Self::consume_audio_device(shared_device)
         } else {
@@ -209,7 +267,7 @@ impl Ribble {
         }
     }
 
-    fn consume_audio_device(device: Arc<Sdl2Capture<ArcChannelSink<f32>>>) {
+    fn consume_audio_device(device: Arc<SdlCapture>) {
         let _strong_count = Arc::strong_count(&device);
 
         // This will consume the inner from the Arc and leave the pointer empty.
@@ -231,6 +289,128 @@ impl Ribble {
             .default_visuals()
     }
 
+    // Keeps the controller's device list fresh, and reports devices that have been unplugged while
+    // in use; the engines can't tell the difference between a silent and a missing device.
+    fn sync_input_devices(&mut self) {
+        for (device_id, device) in self.current_devices.iter() {
+            if let Some(name) = device.disconnected_device()
+                && self.disconnected_devices.insert(device_id)
+            {
+                log::warn!("Input device disconnected: {name}");
+                self.controller.input_device_disconnected(name);
+            }
+        }
+
+        if self
+            .input_devices_refreshed
+            .is_some_and(|refreshed| refreshed.elapsed() < INPUT_DEVICE_REFRESH_INTERVAL)
+        {
+            return;
+        }
+        self.input_devices_refreshed = Some(Instant::now());
+        match &self.audio_backend {
+            AudioBackendKind::Sdl2 => match self.sdl.audio() {
+                Ok(audio_subsystem) => {
+                    self.controller
+                        .set_input_devices(list_input_devices(&audio_subsystem));
+                }
+                Err(e) => log::warn!("Failed to list input devices: {e}"),
+            },
+            #[cfg(feature = "cpal-backend")]
+            AudioBackendKind::Cpal => {
+                self.controller
+                    .set_input_devices(cpal_capture::list_input_devices());
+            }
+            AudioBackendKind::File(_) => {}
+        }
+    }
+
+    fn open_playback_device(
+        sdl_ctx: &sdl2::Sdl,
+        controller: &RibbleController,
+    ) -> Option<AudioDevice<PlaybackHandle>> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(PLAYBACK_SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let playback_handle = controller.playback_handle();
+        // The device is left paused until something is played.
+        let device = sdl_ctx
+            .audio()
+            .and_then(|audio| audio.open_playback(None, &desired_spec, |_| playback_handle));
+
+        match device {
+            Ok(device) => Some(device),
+            Err(e) => {
+                log::warn!("Failed to open audio playback device: {e}");
+                let mut toast = Toast::warning("Audio playback unavailable.");
+                toast.duration(Some(DEFAULT_TOAST_DURATION));
+                controller.send_toast(toast);
+                None
+            }
+        }
+    }
+
+    // The controller owns the playhead; the device just needs to run whenever it's playing.
+    fn sync_playback_device(&mut self, ctx: &egui::Context) {
+        let Some(device) = self.playback_device.as_ref() else {
+            if self.controller.playback_playing() {
+                self.controller.pause_playback();
+            }
+            return;
+        };
+
+        let playing = self.controller.playback_playing();
+        match device.status() {
+            AudioStatus::Playing if !playing => device.pause(),
+            AudioStatus::Playing => {}
+            _ if playing => device.resume(),
+            _ => {}
+        }
+
+        // Keep the playhead moving in the UI.
+        if playing {
+            ctx.request_repaint();
+        }
+    }
+
+    // The control API server drives the app through its own controller, so it lives here rather
+    // than in the kernel. This (re)starts/stops it whenever the configs change.
+    fn sync_control_server(&mut self) {
+        let configs = *self.controller.read_control_api_configs();
+        if self.applied_control_api_configs == Some(configs) {
+            return;
+        }
+        self.applied_control_api_configs = Some(configs);
+
+        // Drop the old server first to free up the port.
+        self.control_server = None;
+        if !configs.enabled() {
+            return;
+        }
+
+        match ControlServer::start(self.controller.clone(), configs, &self.data_directory) {
+            Ok(server) => {
+                log::info!("Control API started on: {}", server.local_addr());
+                self.control_server = Some(server);
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to start control API.\nError: {}\nError source: {:#?}",
+                    &e,
+                    e.source()
+                );
+                let mut toast = Toast::error(format!(
+                    "Failed to start control API on port {}",
+                    configs.port()
+                ));
+                toast.duration(Some(DEFAULT_TOAST_DURATION));
+                self.toasts_handle.add(toast);
+            }
+        }
+    }
+
     fn check_join_last_save(&mut self) {
         if let Some(handle) = self.periodic_serialize.take()
             && let Err(e) = handle.join()
@@ -276,8 +456,8 @@ impl eframe::App for Ribble {
         // Check requests for an audio handle and produce an AudioDevice for capture.
         while let Ok(request) = self.capture_requests.try_recv() {
             match request {
-                AudioCaptureRequest::Open(spec, sink, sender) => {
-                    let shared_capture = self.open_audio_device(spec, sink);
+                AudioCaptureRequest::Open(spec, input_device, sink, sender) => {
+                    let shared_capture = self.open_audio_device(spec, input_device, sink);
 
                     // If there's a problem with communicating to send a handle to the requesting thread,
                     // treat this as an error and close the app after logging.
@@ -296,6 +476,10 @@ impl eframe::App for Ribble {
             }
         }
 
+        self.sync_control_server();
+        self.sync_playback_device(ctx);
+        self.sync_input_devices();
+
         // Grab any new toasts that haven't been drawn.
         while let Ok(toast) = self.toasts_receiver.try_recv() {
             self.toasts_handle.add(toast);
@@ -811,7 +995,11 @@ impl eframe::App for Ribble {
     // the program will deadlock.
     fn on_exit(&mut self, _gl: Option<&Context>) {
         log::info!("Starting runtime cleanup.");
+        // Stop taking outside requests before stopping the background work.
+        self.control_server = None;
         self.controller.stop_work();
+        // The playback device has to be dropped on the main thread.
+        self.playback_device = None;
         // WAIT until the last SDL device gets dropped on the main thread before dropping everything.
         // Check requests for an audio handle and produce an AudioDevice for capture.
 
@@ -0,0 +1,412 @@
+use crate::controller::batch_queue::{BatchJob, BatchJobStatus};
+use crate::controller::ribble_controller::RibbleController;
+use crate::controller::SUPPORTED_AUDIO_FILE_EXTENSIONS;
+use crate::ui::panes::ribble_pane::{PaneView, RibblePaneId};
+use crate::ui::{DEFAULT_TOAST_DURATION, GRID_ROW_SPACING_COEFF, PANE_INNER_MARGIN};
+use crate::utils::transcript_formats::TranscriptFormat;
+use std::error::Error;
+use strum::IntoEnumIterator;
+
+// https://unicodeplus.com/U+1F5D9 -> "X" (Cancellation glyph)
+const REMOVE_X: &str = "🗙";
+// https://unicodeplus.com/U+21BB -> Clockwise open circle arrow
+const RETRY_ICON: &str = "↻";
+// https://unicodeplus.com/U+1F4C2 -> Open file folder
+const FOLDER_ICON: &str = "📂";
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub(in crate::ui) struct BatchPane {
+    #[serde(skip)]
+    #[serde(default)]
+    batch_jobs: Vec<(usize, BatchJob)>,
+}
+
+impl PaneView for BatchPane {
+    fn pane_id(&self) -> RibblePaneId {
+        RibblePaneId::Batch
+    }
+
+    fn pane_title(&self) -> egui::WidgetText {
+        "Batch Transcription".into()
+    }
+
+    fn pane_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        should_close: &mut bool,
+        controller: RibbleController,
+    ) -> egui::Response {
+        controller.try_read_batch_queue(&mut self.batch_jobs);
+
+        let batch_running = controller.batch_running();
+        let transcriber_running = controller.transcriber_running();
+        if batch_running {
+            ui.ctx().request_repaint();
+        }
+
+        let batch_configs = (*controller.read_batch_configs()).clone();
+        let watch_folder_configs = (*controller.read_watch_folder_configs()).clone();
+        let watch_folder_active = controller.watch_folder_active();
+        let mut export_format = batch_configs.export_format();
+        let any_queued = self
+            .batch_jobs
+            .iter()
+            .any(|(_, job)| job.status() == BatchJobStatus::Queued);
+        let any_done = self
+            .batch_jobs
+            .iter()
+            .any(|(_, job)| job.status().is_done());
+
+        let panel_col = ui.visuals().panel_fill;
+
+        let pane_id = egui::Id::new("batch_pane");
+        let resp = ui
+            .interact(ui.max_rect(), pane_id, egui::Sense::click_and_drag())
+            .on_hover_cursor(egui::CursorIcon::Grab);
+
+        egui::Frame::default()
+            .fill(panel_col)
+            .inner_margin(PANE_INNER_MARGIN)
+            .show(ui, |ui| {
+                ui.heading("Batch Transcription:");
+                let button_spacing = ui.spacing().button_padding.y;
+
+                ui.vertical_centered_justified(|ui| {
+                    if ui
+                        .add_enabled(
+                            !transcriber_running && any_queued,
+                            egui::Button::new("Start batch"),
+                        )
+                        .on_hover_cursor(egui::CursorIcon::Default)
+                        .clicked()
+                    {
+                        controller.start_batch_transcription();
+                    }
+                    ui.add_space(button_spacing);
+
+                    if ui
+                        .add_enabled(batch_running, egui::Button::new("Stop"))
+                        .on_hover_text("Stops the current file; the rest stay queued.")
+                        .on_hover_cursor(egui::CursorIcon::Default)
+                        .clicked()
+                    {
+                        controller.stop_transcription();
+                    }
+                });
+
+                ui.add_space(button_spacing);
+                ui.separator();
+
+                egui::Grid::new("batch_configs_grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+                    .show(ui, |ui| {
+                        ui.label("Export format:");
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_salt("batch_export_format_combobox")
+                                .selected_text(export_format.as_ref())
+                                .show_ui(ui, |ui| {
+                                    for format in TranscriptFormat::iter() {
+                                        if ui
+                                            .selectable_value(
+                                                &mut export_format,
+                                                format,
+                                                format.as_ref(),
+                                            )
+                                            .on_hover_text(format.tooltip())
+                                            .clicked()
+                                        {
+                                            let new_configs = batch_configs
+                                                .clone()
+                                                .with_export_format(export_format);
+                                            controller.write_batch_configs(new_configs);
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_cursor(egui::CursorIcon::Default);
+                            ui.add_space(ui.available_width());
+                        });
+                        ui.end_row();
+
+                        ui.label("Output folder:").on_hover_text(
+                            "Where to write the transcripts. By default, they are written next to the audio files.",
+                        );
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Choose")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                                && let Some(directory) = rfd::FileDialog::new()
+                                .set_directory(controller.base_dir())
+                                .pick_folder()
+                            {
+                                let new_configs = batch_configs
+                                    .clone()
+                                    .with_output_directory(Some(directory));
+                                controller.write_batch_configs(new_configs);
+                            }
+
+                            if ui
+                                .add_enabled(
+                                    batch_configs.output_directory().is_some(),
+                                    egui::Button::new("Reset"),
+                                )
+                                .on_hover_text("Write transcripts next to the audio files.")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                let new_configs =
+                                    batch_configs.clone().with_output_directory(None);
+                                controller.write_batch_configs(new_configs);
+                            }
+
+                            match batch_configs.output_directory() {
+                                Some(directory) => {
+                                    ui.label(directory.display().to_string());
+                                }
+                                None => {
+                                    ui.label("Next to audio files");
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                ui.add_space(button_spacing);
+                ui.separator();
+                ui.heading("Watch Folder:");
+
+                egui::Grid::new("watch_folder_grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+                    .show(ui, |ui| {
+                        ui.label("Watch inbox:").on_hover_text(
+                            "Automatically transcribe new audio files that land in the inbox folder.",
+                        );
+                        ui.horizontal(|ui| {
+                            let mut enabled = watch_folder_configs.enabled();
+                            if ui
+                                .add_enabled(
+                                    watch_folder_configs.inbox_directory().is_some(),
+                                    egui::Checkbox::without_text(&mut enabled),
+                                )
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                let new_configs =
+                                    watch_folder_configs.clone().with_enabled(enabled);
+                                controller.write_watch_folder_configs(new_configs);
+                            }
+
+                            if watch_folder_active {
+                                ui.label("Watching");
+                            } else if watch_folder_configs.enabled() {
+                                ui.label("Not watching").on_hover_text(
+                                    "The inbox folder could not be watched; check the console.",
+                                );
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Inbox folder:");
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Choose")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                                && let Some(directory) = rfd::FileDialog::new()
+                                .set_directory(controller.base_dir())
+                                .pick_folder()
+                            {
+                                let new_configs = watch_folder_configs
+                                    .clone()
+                                    .with_inbox_directory(Some(directory));
+                                controller.write_watch_folder_configs(new_configs);
+                            }
+
+                            match watch_folder_configs.inbox_directory() {
+                                Some(directory) => {
+                                    ui.label(directory.display().to_string());
+                                }
+                                None => {
+                                    ui.label("None");
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Outbox folder:").on_hover_text(
+                            "Where to write the transcripts. By default, they are written next to the audio files.",
+                        );
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Choose")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                                && let Some(directory) = rfd::FileDialog::new()
+                                .set_directory(controller.base_dir())
+                                .pick_folder()
+                            {
+                                let new_configs = watch_folder_configs
+                                    .clone()
+                                    .with_outbox_directory(Some(directory));
+                                controller.write_watch_folder_configs(new_configs);
+                            }
+
+                            if ui
+                                .add_enabled(
+                                    watch_folder_configs.outbox_directory().is_some(),
+                                    egui::Button::new("Reset"),
+                                )
+                                .on_hover_text("Write transcripts next to the audio files.")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                let new_configs =
+                                    watch_folder_configs.clone().with_outbox_directory(None);
+                                controller.write_watch_folder_configs(new_configs);
+                            }
+
+                            match watch_folder_configs.outbox_directory() {
+                                Some(directory) => {
+                                    ui.label(directory.display().to_string());
+                                }
+                                None => {
+                                    ui.label("Next to audio files");
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Poll for changes:").on_hover_text(
+                            "Use for network shares, which do not report new files on their own.",
+                        );
+                        ui.horizontal(|ui| {
+                            let mut use_polling = watch_folder_configs.use_polling();
+                            if ui
+                                .add(egui::Checkbox::without_text(&mut use_polling))
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                let new_configs =
+                                    watch_folder_configs.clone().with_use_polling(use_polling);
+                                controller.write_watch_folder_configs(new_configs);
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                ui.add_space(button_spacing);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Add files")
+                        .on_hover_cursor(egui::CursorIcon::Default)
+                        .clicked()
+                        && let Some(paths) = rfd::FileDialog::new()
+                        .add_filter("all supported", &SUPPORTED_AUDIO_FILE_EXTENSIONS)
+                        .set_directory(controller.base_dir())
+                        .pick_files()
+                    {
+                        controller.enqueue_batch_files(paths);
+                    }
+
+                    if ui
+                        .add_enabled(any_done, egui::Button::new("Clear finished"))
+                        .on_hover_cursor(egui::CursorIcon::Default)
+                        .clicked()
+                    {
+                        controller.clear_finished_batch_jobs();
+                    }
+                });
+
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        let min_row_height = ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF;
+                        egui::Grid::new("batch_queue_grid")
+                            .num_columns(3)
+                            .striped(true)
+                            .min_row_height(min_row_height)
+                            .show(ui, |ui| {
+                                for (job_id, job) in self.batch_jobs.iter() {
+                                    ui.label(job.file_name())
+                                        .on_hover_text(job.audio_path().display().to_string());
+
+                                    let status = ui.label(job.status().as_ref());
+                                    if let Some(error) = job.error() {
+                                        status.on_hover_text(error);
+                                    } else if let Some(output_path) = job.output_path() {
+                                        status.on_hover_text(output_path.display().to_string());
+                                    }
+
+                                    ui.horizontal(|ui| {
+                                        if let Some(output_path) = job.output_path()
+                                            && ui
+                                            .button(FOLDER_ICON)
+                                            .on_hover_text("Show transcript in folder.")
+                                            .on_hover_cursor(egui::CursorIcon::Default)
+                                            .clicked()
+                                            && let Err(e) = opener::reveal(output_path)
+                                        {
+                                            log::warn!(
+                                                "Failed to reveal transcript. Error: {}\n\
+                                                Error source: {:#?}",
+                                                &e,
+                                                e.source()
+                                            );
+                                            let mut toast = egui_notify::Toast::error(
+                                                "Failed to open transcript folder",
+                                            );
+                                            toast.duration(Some(DEFAULT_TOAST_DURATION));
+                                            controller.send_toast(toast);
+                                        }
+
+                                        if matches!(
+                                            job.status(),
+                                            BatchJobStatus::Failed | BatchJobStatus::Cancelled
+                                        ) && ui
+                                            .button(RETRY_ICON)
+                                            .on_hover_text("Queue again.")
+                                            .on_hover_cursor(egui::CursorIcon::Default)
+                                            .clicked()
+                                        {
+                                            controller.requeue_batch_job(*job_id);
+                                        }
+
+                                        if ui
+                                            .add_enabled(
+                                                job.status() != BatchJobStatus::Running,
+                                                egui::Button::new(REMOVE_X),
+                                            )
+                                            .on_hover_text("Remove from queue.")
+                                            .on_hover_cursor(egui::CursorIcon::Default)
+                                            .clicked()
+                                        {
+                                            controller.remove_batch_job(*job_id);
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        // Add a context menu to make this closable -> NOTE: if the pane should not be closed, this
+        // will just nop.
+        resp.context_menu(|ui| {
+            ui.selectable_value(should_close, self.is_pane_closable(), "Close pane");
+        });
+
+        resp
+    }
+
+    fn is_pane_closable(&self) -> bool {
+        self.pane_id().is_closable()
+    }
+}
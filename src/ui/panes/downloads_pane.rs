@@ -1,8 +1,9 @@
 use crate::controller::ribble_controller::RibbleController;
-use crate::controller::FileDownload;
+use crate::controller::{DownloadState, FileDownload};
 use crate::ui::panes::ribble_pane::{PaneView, RibblePaneId};
 use crate::ui::GRID_ROW_SPACING_COEFF;
 use crate::ui::PANE_INNER_MARGIN;
+use crate::utils::download_configs::{MAX_CONCURRENT_DOWNLOADS, MAX_DOWNLOAD_RETRIES};
 use irox_egui_extras::progressbar::ProgressBar;
 use unit_prefix::NumberPrefix;
 
@@ -45,6 +46,21 @@ impl PaneView for DownloadsPane {
 
         egui::Frame::default().inner_margin(PANE_INNER_MARGIN).fill(panel_col).show(ui, |ui| {
             ui.heading("Downloads:");
+            let configs = controller.read_download_configs();
+            ui.horizontal_wrapped(|ui| {
+                let mut max_concurrent_downloads = configs.max_concurrent_downloads();
+                ui.label("At once:").on_hover_text("The number of downloads to run at the same time. The rest are queued.");
+                if ui.add(egui::DragValue::new(&mut max_concurrent_downloads).range(1..=MAX_CONCURRENT_DOWNLOADS))
+                    .changed() {
+                    controller.write_download_configs((*configs).clone().with_max_concurrent_downloads(max_concurrent_downloads));
+                }
+                let mut max_retries = configs.max_retries();
+                ui.label("Retries:").on_hover_text("How many times to retry a download after a connection error.");
+                if ui.add(egui::DragValue::new(&mut max_retries).range(0..=MAX_DOWNLOAD_RETRIES))
+                    .changed() {
+                    controller.write_download_configs((*configs).clone().with_max_retries(max_retries));
+                }
+            });
             egui::ScrollArea::vertical()
                 .auto_shrink([false; 2])
                 .stick_to_bottom(true)
@@ -71,23 +87,59 @@ impl PaneView for DownloadsPane {
                                     (NumberPrefix::Standalone(cur), NumberPrefix::Standalone(tot)) => format!("{cur:.2}/{tot:.2} B"),
                                     (NumberPrefix::Standalone(cur), NumberPrefix::Prefixed(prefix, tot)) => format!("{cur:02} B/{tot:.2} {prefix}B"),
                                     (NumberPrefix::Prefixed(c_pref, cur), NumberPrefix::Prefixed(t_pref, tot)) => format!("{cur:.2} {c_pref}B/{tot:.2} {t_pref}B"),
-                                    _ => unreachable!("Total size should never be less than current bytes. Cur: {current_bytes}, Tot: {total_size}"),
+                                    // NOTE: a resumed download without a known size can be ahead of its
+                                    // (placeholder) total.
+                                    (NumberPrefix::Prefixed(c_pref, cur), NumberPrefix::Standalone(_)) => format!("{cur:.2} {c_pref}B"),
+                                };
+
+                                let state = download.state();
+                                let status_text = match state {
+                                    DownloadState::Queued => Some("Queued".to_string()),
+                                    DownloadState::Downloading => None,
+                                    DownloadState::Retrying { attempt } => Some(format!("Retrying ({attempt})")),
+                                    DownloadState::Paused => Some("Paused".to_string()),
+                                    DownloadState::Failed(_) => Some("Failed".to_string()),
+                                };
+                                let right_text = match status_text {
+                                    Some(status_text) => format!("{status_text} | {bytes_format}"),
+                                    None => bytes_format,
                                 };
 
                                 ui.allocate_ui_with_layout(desired_size, layout, |ui| {
                                     if ui.button(CANCELLATION_X)
                                         .on_hover_text("Cancel download.")
                                         .clicked() {
-                                        // NOTE: at the moment, this is a read-blocking method.
+                                        // NOTE: at the moment, this is a write-blocking method.
                                         // The contention should be minimal, but if there's any jank,
                                         // run the action on a short-lived background thread instead.
                                         controller.abort_download(*download_id);
                                     }
+                                    match state {
+                                        DownloadState::Paused => {
+                                            if ui.button("Resume").clicked() {
+                                                controller.resume_download(*download_id);
+                                            }
+                                        }
+                                        DownloadState::Failed(error) => {
+                                            if ui.button("Retry")
+                                                .on_hover_text(format!("The download failed:\n{error}"))
+                                                .clicked() {
+                                                controller.resume_download(*download_id);
+                                            }
+                                        }
+                                        _ => {
+                                            if ui.button("Pause")
+                                                .on_hover_text("Pause the download. The downloaded part is kept.")
+                                                .clicked() {
+                                                controller.pause_download(*download_id);
+                                            }
+                                        }
+                                    }
                                     let mut pb = ProgressBar::new(download_progress.current_progress())
                                         .desired_width(ui.available_width())
                                         .text_left(download.name().to_string())
-                                        .text_right(bytes_format);
-                                    pb.animate = true;
+                                        .text_right(right_text);
+                                    pb.animate = state.is_active();
                                     ui.add(pb);
                                 });
                                 ui.end_row();
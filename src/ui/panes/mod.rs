@@ -2,8 +2,10 @@ mod recording_pane;
 pub(in crate::ui) mod ribble_pane;
 mod transcriber_pane;
 
+mod batch_pane;
 mod console_pane;
 mod downloads_pane;
+mod playback_pane;
 pub(in crate::ui) mod pane_list;
 mod progress_pane;
 mod transcription_pane;
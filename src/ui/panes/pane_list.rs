@@ -1,5 +1,7 @@
+pub(in crate::ui) use super::batch_pane::BatchPane;
 pub(in crate::ui) use super::console_pane::ConsolePane;
 pub(in crate::ui) use super::downloads_pane::DownloadsPane;
+pub(in crate::ui) use super::playback_pane::PlaybackPane;
 pub(in crate::ui) use super::progress_pane::ProgressPane;
 pub(in crate::ui) use super::recording_pane::RecordingPane;
 pub(in crate::ui) use super::transcriber_pane::TranscriberPane;
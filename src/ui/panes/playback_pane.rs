@@ -0,0 +1,295 @@
+use crate::controller::CompletedRecordingJobs;
+use crate::controller::playback::segment_at;
+use crate::controller::ribble_controller::RibbleController;
+use crate::ui::panes::ribble_pane::{PaneView, RibblePaneId};
+use crate::ui::widgets::recording_modal::build_recording_modal;
+use crate::ui::{DEFAULT_TOAST_DURATION, GRID_ROW_SPACING_COEFF, PANE_INNER_MARGIN};
+use std::sync::Arc;
+use std::time::Duration;
+
+// https://unicodeplus.com/U+25B6 -> Play
+const PLAY_ICON: &str = "▶";
+// https://unicodeplus.com/U+23F8 -> Pause
+const PAUSE_ICON: &str = "⏸";
+// https://unicodeplus.com/U+23F9 -> Stop
+const STOP_ICON: &str = "⏹";
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub(in crate::ui) struct PlaybackPane {
+    #[serde(skip)]
+    #[serde(default)]
+    recordings_buffer: Vec<(Arc<str>, CompletedRecordingJobs)>,
+    #[serde(skip)]
+    #[serde(default)]
+    recording_modal: bool,
+    // The last segment that was scrolled to, so that the list only follows the playhead when it
+    // moves onto a new segment.
+    #[serde(skip)]
+    #[serde(default)]
+    last_followed_segment: Option<usize>,
+}
+
+impl PaneView for PlaybackPane {
+    fn pane_id(&self) -> RibblePaneId {
+        RibblePaneId::Playback
+    }
+
+    fn pane_title(&self) -> egui::WidgetText {
+        "Playback".into()
+    }
+
+    fn pane_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        should_close: &mut bool,
+        controller: RibbleController,
+    ) -> egui::Response {
+        let playback_path = controller.playback_path();
+        let playback_loading = controller.playback_loading();
+        let playing = controller.playback_playing();
+        let position = controller.playback_position();
+        let duration = controller.playback_duration();
+        let current_audio_file = controller.read_current_audio_file_path();
+        let transcript = controller.read_transcript();
+
+        // The segment under the playhead.
+        let current_segment = playback_path
+            .as_ref()
+            .and_then(|_| segment_at(transcript.segments(), position));
+
+        let panel_col = ui.visuals().panel_fill;
+        let pane_id = egui::Id::new("playback_pane");
+        let resp = ui
+            .interact(ui.max_rect(), pane_id, egui::Sense::click_and_drag())
+            .on_hover_cursor(egui::CursorIcon::Grab);
+
+        egui::Frame::default()
+            .fill(panel_col)
+            .inner_margin(PANE_INNER_MARGIN)
+            .show(ui, |ui| {
+                ui.heading("Playback:");
+                let button_spacing = ui.spacing().button_padding.y;
+
+                egui::Grid::new("playback_source_grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+                    .show(ui, |ui| {
+                        ui.label("Source:");
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(
+                                    !playback_loading && current_audio_file.is_some(),
+                                    egui::Button::new("Audio file"),
+                                )
+                                .on_hover_text("Load the current audio file.")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                let audio_files = controller.read_current_audio_files();
+                                controller.load_playback_audio(audio_files);
+                            }
+
+                            if ui
+                                .add_enabled(
+                                    !playback_loading && controller.latest_recording_exists(),
+                                    egui::Button::new("Recording"),
+                                )
+                                .on_hover_text("Load a previous recording.")
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                self.recording_modal = true;
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Loaded:");
+                        if playback_loading {
+                            ui.label("Loading...");
+                        } else {
+                            match playback_path.as_ref() {
+                                Some(path) => {
+                                    let file_name = path
+                                        .file_name()
+                                        .map(|name| name.to_string_lossy().to_string())
+                                        .unwrap_or_else(|| path.display().to_string());
+                                    ui.label(file_name)
+                                        .on_hover_text(path.display().to_string());
+                                }
+                                None => {
+                                    ui.label("None");
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    });
+
+                ui.add_space(button_spacing);
+                ui.separator();
+
+                let can_play = playback_path.is_some() && !playback_loading;
+                ui.horizontal(|ui| {
+                    let (icon, hover_text) = if playing {
+                        (PAUSE_ICON, "Pause")
+                    } else {
+                        (PLAY_ICON, "Play")
+                    };
+                    if ui
+                        .add_enabled(can_play, egui::Button::new(icon))
+                        .on_hover_text(hover_text)
+                        .on_hover_cursor(egui::CursorIcon::Default)
+                        .clicked()
+                    {
+                        if playing {
+                            controller.pause_playback();
+                        } else {
+                            controller.play_playback();
+                        }
+                    }
+
+                    if ui
+                        .add_enabled(can_play, egui::Button::new(STOP_ICON))
+                        .on_hover_text("Stop")
+                        .on_hover_cursor(egui::CursorIcon::Default)
+                        .clicked()
+                    {
+                        controller.pause_playback();
+                        controller.seek_playback(Duration::ZERO);
+                    }
+
+                    ui.label(format!(
+                        "{} / {}",
+                        format_playhead(position),
+                        format_playhead(duration)
+                    ));
+                });
+
+                ui.add_enabled_ui(can_play, |ui| {
+                    let mut seconds = position.as_secs_f64();
+                    let slider_width = ui.available_width();
+                    ui.spacing_mut().slider_width = slider_width;
+                    let slider = ui.add(
+                        egui::Slider::new(&mut seconds, 0.0..=duration.as_secs_f64())
+                            .show_value(false),
+                    );
+                    if slider.changed() {
+                        controller.seek_playback(Duration::from_secs_f64(seconds));
+                    }
+                });
+
+                ui.add_space(button_spacing);
+                ui.separator();
+
+                if transcript.is_empty() {
+                    ui.label("No transcript.");
+                } else {
+                    ui.label("Click a segment to play from there.").on_hover_text(
+                        "Segment times are estimates, so the highlight may run slightly early or late.",
+                    );
+                }
+
+                let follow_segment = playing && current_segment != self.last_followed_segment;
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Grid::new("playback_segments_grid")
+                            .num_columns(2)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (index, segment) in transcript.segments().iter().enumerate() {
+                                    let text = segment.text().trim();
+                                    if text.is_empty() {
+                                        continue;
+                                    }
+                                    let is_current = current_segment == Some(index);
+
+                                    ui.label(format_playhead(segment.start()));
+                                    let label = ui
+                                        .add_enabled(
+                                            can_play,
+                                            egui::Button::selectable(is_current, text).wrap(),
+                                        )
+                                        .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                    if label.clicked() {
+                                        controller.seek_playback(segment.start());
+                                        controller.play_playback();
+                                    }
+                                    if is_current && follow_segment {
+                                        label.scroll_to_me(Some(egui::Align::Center));
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                if follow_segment {
+                    self.last_followed_segment = current_segment;
+                }
+            });
+
+        if self.recording_modal {
+            controller.try_read_recording_metadata(&mut self.recordings_buffer);
+            let err_ctx = ui.ctx().clone();
+            let handle_recordings = |file_name| match controller
+                .try_get_recording_segments(Arc::clone(&file_name))
+            {
+                Some(segments) => {
+                    controller.load_playback_audio(segments);
+                    self.recording_modal = false;
+                }
+                None => {
+                    log::warn!("Temporary recording file missing: {file_name}");
+                    let mut toast = egui_notify::Toast::warning("Failed to find saved recording.");
+                    toast.duration(Some(DEFAULT_TOAST_DURATION));
+                    controller.send_toast(toast);
+                    err_ctx.request_repaint();
+                }
+            };
+
+            let modal = build_recording_modal(
+                ui,
+                "playback_recording_modal",
+                "playback_recording_grid",
+                &controller,
+                &self.recordings_buffer,
+                handle_recordings,
+            );
+
+            if modal.should_close() {
+                self.recording_modal = false;
+            }
+        }
+
+        // Add a context menu to make this closable -> NOTE: if the pane should not be closed, this
+        // will just nop.
+        resp.context_menu(|ui| {
+            ui.selectable_value(should_close, self.is_pane_closable(), "Close pane");
+        });
+
+        resp
+    }
+
+    fn is_pane_closable(&self) -> bool {
+        self.pane_id().is_closable()
+    }
+
+    fn on_pane_close(&mut self, controller: RibbleController) -> bool {
+        // Don't keep playing audio that can't be seen or stopped.
+        controller.pause_playback();
+        self.is_pane_closable()
+    }
+}
+
+// mm:ss, or h:mm:ss for long audio.
+fn format_playhead(timestamp: Duration) -> String {
+    let total_seconds = timestamp.as_secs();
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes:02}:{seconds:02}")
+    }
+}
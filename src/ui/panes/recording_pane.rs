@@ -2,14 +2,25 @@ use crate::controller::ribble_controller::RibbleController;
 use crate::controller::CompletedRecordingJobs;
 use crate::ui::panes::ribble_pane::RibblePaneId;
 use crate::ui::panes::PaneView;
+use crate::ui::widgets::input_device_combobox::input_device_combobox;
 use crate::ui::widgets::recording_modal::build_recording_modal;
+use crate::ui::widgets::toggle_switch::toggle;
 use crate::ui::{DEFAULT_TOAST_DURATION, GRID_ROW_SPACING_COEFF, PANE_INNER_MARGIN};
 use crate::utils::recorder_configs::{
-    RibbleChannels, RibbleExportFormat, RibblePeriod, RibbleSampleRate,
+    RibbleChannels, RibbleExportFormat, RibbleOpusBitrate, RibblePeriod, RibbleSampleRate,
 };
+use crate::utils::recording_library_configs::{
+    DEFAULT_LIBRARY_KEEP_LAST, DEFAULT_LIBRARY_MAX_SIZE_MB, MIN_LIBRARY_MAX_SIZE_MB,
+};
+use std::error::Error;
 use std::sync::Arc;
 use strum::IntoEnumIterator;
 
+// Pencil: https://unicodeplus.com/U+270F
+const EDIT_ICON: &str = "✏";
+// Open file folder: https://unicodeplus.com/U+1F4C2
+const FOLDER_ICON: &str = "📂";
+
 #[derive(Default, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct RecordingPane {
     #[serde(skip)]
@@ -18,6 +29,10 @@ pub(crate) struct RecordingPane {
     #[serde(skip)]
     #[serde(default)]
     recording_modal: bool,
+    // The recording being renamed, and the label being typed.
+    #[serde(skip)]
+    #[serde(default)]
+    editing_label: Option<(Arc<str>, String)>,
 }
 
 impl PaneView for RecordingPane {
@@ -40,7 +55,7 @@ impl PaneView for RecordingPane {
         let audio_worker_running = recorder_running || controller.transcriber_running();
 
         // Configs/Export
-        let configs = *controller.read_recorder_configs();
+        let configs = (*controller.read_recorder_configs()).clone();
         let mut export_format = controller.read_export_format();
 
         // Pane UI
@@ -115,6 +130,14 @@ impl PaneView for RecordingPane {
                                             ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF,
                                         )
                                         .show(ui, |ui| {
+                                            ui.label("Input device:");
+                                            input_device_combobox(
+                                                ui,
+                                                "recording_input_device_combobox",
+                                                &controller,
+                                            );
+                                            ui.end_row();
+
                                             ui.label("Sample Rate:");
                                             let mut sample_rate = configs.sample_rate();
                                             ui.horizontal(|ui| {
@@ -131,6 +154,7 @@ impl PaneView for RecordingPane {
                                                                 .clicked()
                                                             {
                                                                 let new_configs = configs
+                                                                    .clone()
                                                                     .with_sample_rate(sample_rate);
                                                                 controller.write_recorder_configs(
                                                                     new_configs,
@@ -160,8 +184,9 @@ impl PaneView for RecordingPane {
                                                             )
                                                             .clicked()
                                                         {
-                                                            let new_configs =
-                                                                configs.with_num_channels(channels);
+                                                            let new_configs = configs
+                                                                .clone()
+                                                                .with_num_channels(channels);
                                                             controller
                                                                 .write_recorder_configs(new_configs);
                                                         }
@@ -186,7 +211,7 @@ impl PaneView for RecordingPane {
                                                             .clicked()
                                                         {
                                                             let new_configs =
-                                                                configs.with_period(period);
+                                                                configs.clone().with_period(period);
                                                             controller
                                                                 .write_recorder_configs(new_configs);
                                                         }
@@ -200,10 +225,13 @@ impl PaneView for RecordingPane {
                                             ui.label("Reset settings:");
                                             if ui
                                                 .button("Reset")
+                                                .on_hover_text("Resets the settings for this device.")
                                                 .on_hover_cursor(egui::CursorIcon::Default)
                                                 .clicked()
                                             {
-                                                controller.write_recorder_configs(Default::default());
+                                                controller.write_recorder_configs(
+                                                    configs.clone().with_default_capture_settings(),
+                                                );
                                             }
                                         });
                                 });
@@ -220,7 +248,7 @@ impl PaneView for RecordingPane {
                                     )
                                     .show(ui, |ui| {
                                         ui.label("Export format").on_hover_text(
-                                            "Set the (wav) audio file export format. Supported: I16, F32.",
+                                            "Set the audio file export format. Supported: I16 & F32 (wav), FLAC, Opus.",
                                         );
                                         // Recording Format Combobox.
                                         egui::ComboBox::from_id_salt("export_format_combobox")
@@ -246,12 +274,46 @@ impl PaneView for RecordingPane {
                                             .response
                                             .on_hover_cursor(egui::CursorIcon::Default);
                                         ui.end_row();
+
+                                        if matches!(export_format, RibbleExportFormat::Opus) {
+                                            ui.label("Bitrate");
+                                            let mut opus_bitrate = controller.read_opus_bitrate();
+                                            egui::ComboBox::from_id_salt("opus_bitrate_combobox")
+                                                .selected_text(opus_bitrate.as_ref())
+                                                .show_ui(ui, |ui| {
+                                                    for bitrate in RibbleOpusBitrate::iter() {
+                                                        if ui
+                                                            .selectable_value(
+                                                                &mut opus_bitrate,
+                                                                bitrate,
+                                                                bitrate.as_ref(),
+                                                            )
+                                                            .on_hover_text(bitrate.tooltip())
+                                                            .clicked()
+                                                        {
+                                                            controller
+                                                                .write_opus_bitrate(opus_bitrate);
+                                                        }
+                                                    }
+                                                })
+                                                .response
+                                                .on_hover_cursor(egui::CursorIcon::Default);
+                                            ui.end_row();
+                                        }
                                     });
                             });
 
                             export_dropdown
                                 .header_response
                                 .on_hover_cursor(egui::CursorIcon::Default);
+
+                            let library_dropdown = ui.collapsing("Library", |ui| {
+                                self.recording_library(ui, &controller);
+                            });
+
+                            library_dropdown
+                                .header_response
+                                .on_hover_cursor(egui::CursorIcon::Default);
                         });
                     });
             });
@@ -262,11 +324,12 @@ impl PaneView for RecordingPane {
             let err_ctx = ui.ctx().clone();
             let handle_recordings = |file_name| {
                 if controller
-                    .try_get_recording_path(Arc::clone(&file_name))
+                    .try_get_recording_segments(Arc::clone(&file_name))
                     .is_some()
                 {
+                    let extension = export_format.file_extension();
                     if let Some(out_path) = rfd::FileDialog::new()
-                        .add_filter("wav", &["wav"])
+                        .add_filter(extension, &[extension])
                         .set_directory(controller.base_dir())
                         .save_file()
                     {
@@ -277,11 +340,12 @@ impl PaneView for RecordingPane {
                         // MacOs and Windows will both append the proper extension.
                         #[cfg(target_os = "linux")]
                         {
-                            let out_path = if out_path.extension().is_some_and(|ext| ext == "wav") {
-                                out_path
-                            } else {
-                                out_path.with_extension("wav")
-                            };
+                            let out_path =
+                                if out_path.extension().is_some_and(|ext| ext == extension) {
+                                    out_path
+                                } else {
+                                    out_path.with_extension(extension)
+                                };
                             controller.export_recording(out_path, file_name, export_format);
                         }
 
@@ -291,7 +355,7 @@ impl PaneView for RecordingPane {
                         }
                     }
                 } else if controller
-                    .try_get_recording_path(Arc::clone(&file_name))
+                    .try_get_recording_segments(Arc::clone(&file_name))
                     .is_none()
                 {
                     log::warn!("Temporary recording file missing: {file_name}");
@@ -329,3 +393,171 @@ impl PaneView for RecordingPane {
         self.pane_id().is_closable()
     }
 }
+
+impl RecordingPane {
+    fn recording_library(&mut self, ui: &mut egui::Ui, controller: &RibbleController) {
+        let library_configs = *controller.read_recording_library_configs();
+        egui::Grid::new("recording_library_configs_grid")
+            .num_columns(2)
+            .striped(true)
+            .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+            .show(ui, |ui| {
+                ui.label("Keep recordings:").on_hover_text(
+                    "Keep recordings between sessions instead of clearing them on exit.\n\
+                    Turning this off clears the recordings on exit.",
+                );
+                let mut enabled = library_configs.enabled();
+                ui.horizontal(|ui| {
+                    if ui.add(toggle(&mut enabled)).clicked() {
+                        controller
+                            .write_recording_library_configs(library_configs.with_enabled(enabled));
+                    }
+                    // Tiny hack to paint the grid color to the edge of the pane.
+                    ui.add_space(ui.available_width());
+                });
+                ui.end_row();
+
+                ui.add_enabled_ui(library_configs.enabled(), |ui| {
+                    ui.label("Keep last:").on_hover_text(
+                        "The oldest recordings are removed when a new recording is added \
+                        (and on startup).",
+                    );
+                });
+                ui.add_enabled_ui(library_configs.enabled(), |ui| {
+                    ui.horizontal(|ui| {
+                        let mut limited = library_configs.keep_last().is_some();
+                        if ui.add(egui::Checkbox::without_text(&mut limited)).clicked() {
+                            let keep_last = limited.then_some(DEFAULT_LIBRARY_KEEP_LAST);
+                            controller.write_recording_library_configs(
+                                library_configs.with_keep_last(keep_last),
+                            );
+                        }
+                        if let Some(mut keep_last) = library_configs.keep_last()
+                            && ui
+                                .add(
+                                    egui::DragValue::new(&mut keep_last)
+                                        .range(1..=usize::MAX)
+                                        .suffix(" recordings"),
+                                )
+                                .changed()
+                        {
+                            controller.write_recording_library_configs(
+                                library_configs.with_keep_last(Some(keep_last)),
+                            );
+                        }
+                    });
+                });
+                ui.end_row();
+
+                ui.add_enabled_ui(library_configs.enabled(), |ui| {
+                    ui.label("Max size:").on_hover_text(
+                        "The oldest recordings are removed when the library grows past this \
+                        size.\nThe newest recording is always kept.",
+                    );
+                });
+                ui.add_enabled_ui(library_configs.enabled(), |ui| {
+                    ui.horizontal(|ui| {
+                        let mut limited = library_configs.max_total_size_mb().is_some();
+                        if ui.add(egui::Checkbox::without_text(&mut limited)).clicked() {
+                            let max_size = limited.then_some(DEFAULT_LIBRARY_MAX_SIZE_MB);
+                            controller.write_recording_library_configs(
+                                library_configs.with_max_total_size_mb(max_size),
+                            );
+                        }
+                        if let Some(mut max_size) = library_configs.max_total_size_mb()
+                            && ui
+                                .add(
+                                    egui::DragValue::new(&mut max_size)
+                                        .range(MIN_LIBRARY_MAX_SIZE_MB..=u64::MAX)
+                                        .suffix(" MiB"),
+                                )
+                                .changed()
+                        {
+                            controller.write_recording_library_configs(
+                                library_configs.with_max_total_size_mb(Some(max_size)),
+                            );
+                        }
+                    });
+                });
+                ui.end_row();
+            });
+
+        ui.add_space(ui.spacing().button_padding.y);
+        controller.try_read_recording_metadata(&mut self.recordings_buffer);
+        if self.recordings_buffer.is_empty() {
+            ui.label("No recordings.");
+            return;
+        }
+
+        egui::Grid::new("recording_library_grid")
+            .num_columns(2)
+            .striped(true)
+            .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+            .show(ui, |ui| {
+                let len = self.recordings_buffer.len();
+                for (i, (file_name, recording)) in self.recordings_buffer.iter().enumerate() {
+                    let default_label = format!("Recording {}", len - i);
+                    let label = recording.label().unwrap_or(default_label.as_str());
+
+                    match self.editing_label.as_mut() {
+                        Some((editing_file_name, label_buffer))
+                            if editing_file_name == file_name =>
+                        {
+                            let text_edit = ui.add(
+                                egui::TextEdit::singleline(label_buffer)
+                                    .hint_text(default_label.as_str()),
+                            );
+                            text_edit.request_focus();
+                            if text_edit.lost_focus() {
+                                // Escape cancels the rename.
+                                if !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                    controller.set_recording_label(file_name, label_buffer);
+                                }
+                                self.editing_label = None;
+                            }
+                        }
+                        _ => {
+                            ui.label(label);
+                        }
+                    }
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(EDIT_ICON)
+                            .on_hover_text("Rename")
+                            .on_hover_cursor(egui::CursorIcon::Default)
+                            .clicked()
+                        {
+                            let label_buffer = recording.label().unwrap_or_default().to_string();
+                            self.editing_label = Some((Arc::clone(file_name), label_buffer));
+                        }
+
+                        if let Some(transcript_path) = recording.transcript_path()
+                            && transcript_path.is_file()
+                            && ui
+                                .button(FOLDER_ICON)
+                                .on_hover_text(format!(
+                                    "Show transcript in folder.\n{}",
+                                    transcript_path.display()
+                                ))
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            && let Err(e) = opener::reveal(transcript_path)
+                        {
+                            log::warn!(
+                                "Failed to reveal transcript. Error: {}\n\
+                                Error source: {:#?}",
+                                &e,
+                                e.source()
+                            );
+                            let mut toast =
+                                egui_notify::Toast::error("Failed to open transcript folder");
+                            toast.duration(Some(DEFAULT_TOAST_DURATION));
+                            controller.send_toast(toast);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -45,6 +45,8 @@ pub(in crate::ui) enum RibblePane {
     Console(ConsolePane),
     Downloads(DownloadsPane),
     UserPreferences(UserPreferencesPane),
+    Batch(BatchPane),
+    Playback(PlaybackPane),
 }
 
 // Since data is just caching, define equality based on the discriminant.
@@ -66,6 +68,8 @@ pub(in crate::ui) enum RibblePaneId {
     Progress,
     Downloads,
     UserPreferences,
+    Batch,
+    Playback,
 }
 
 impl RibblePaneId {
@@ -79,7 +83,7 @@ impl From<RibblePaneId> for RibblePane {
         match value {
             RibblePaneId::Transcriber => RibblePane::Transcriber(TranscriberPane::default()),
             RibblePaneId::Recording => RibblePane::Recording(RecordingPane::default()),
-            RibblePaneId::Transcription => RibblePane::Transcription(TranscriptionPane {}),
+            RibblePaneId::Transcription => RibblePane::Transcription(TranscriptionPane::default()),
             RibblePaneId::Visualizer => RibblePane::Visualizer(VisualizerPane::default()),
             RibblePaneId::Console => RibblePane::Console(ConsolePane::default()),
             RibblePaneId::Downloads => RibblePane::Downloads(DownloadsPane::default()),
@@ -87,6 +91,8 @@ impl From<RibblePaneId> for RibblePane {
             RibblePaneId::UserPreferences => {
                 RibblePane::UserPreferences(UserPreferencesPane::default())
             }
+            RibblePaneId::Batch => RibblePane::Batch(BatchPane::default()),
+            RibblePaneId::Playback => RibblePane::Playback(PlaybackPane::default()),
         }
     }
 }
@@ -99,6 +105,8 @@ pub(in crate::ui) enum ClosableRibbleViewPane {
     Progress,
     Visualizer,
     UserPreferences,
+    Batch,
+    Playback,
 }
 
 impl From<ClosableRibbleViewPane> for RibblePane {
@@ -112,6 +120,8 @@ impl From<ClosableRibbleViewPane> for RibblePane {
             ClosableRibbleViewPane::UserPreferences => {
                 RibblePane::UserPreferences(UserPreferencesPane::default())
             }
+            ClosableRibbleViewPane::Batch => RibblePane::Batch(BatchPane::default()),
+            ClosableRibbleViewPane::Playback => RibblePane::Playback(PlaybackPane::default()),
         }
     }
 }
@@ -125,6 +135,8 @@ impl From<ClosableRibbleViewPane> for RibblePaneId {
             ClosableRibbleViewPane::Progress => RibblePaneId::Progress,
             ClosableRibbleViewPane::Visualizer => RibblePaneId::Visualizer,
             ClosableRibbleViewPane::UserPreferences => RibblePaneId::UserPreferences,
+            ClosableRibbleViewPane::Batch => RibblePaneId::Batch,
+            ClosableRibbleViewPane::Playback => RibblePaneId::Playback,
         }
     }
 }
@@ -146,6 +158,8 @@ impl TryFrom<RibblePaneId> for ClosableRibbleViewPane {
             RibblePaneId::Console => Ok(ClosableRibbleViewPane::Console),
             RibblePaneId::Downloads => Ok(ClosableRibbleViewPane::Downloads),
             RibblePaneId::UserPreferences => Ok(ClosableRibbleViewPane::UserPreferences),
+            RibblePaneId::Batch => Ok(ClosableRibbleViewPane::Batch),
+            RibblePaneId::Playback => Ok(ClosableRibbleViewPane::Playback),
         }
     }
 }
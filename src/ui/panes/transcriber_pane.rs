@@ -1,14 +1,26 @@
+use crate::controller::model_catalog::{parse_catalog_location, CATALOG_FILE};
+use crate::controller::model_header::ModelHeader;
+use crate::controller::model_integrity::ModelIntegrity;
 use crate::controller::ribble_controller::RibbleController;
-use crate::controller::{CompletedRecordingJobs, ModelFile, OfflineTranscriberFeedback};
+use crate::controller::{
+    CompletedRecordingJobs, ModelFile, OfflineTranscriberFeedback, SUPPORTED_AUDIO_FILE_EXTENSIONS,
+};
 use crate::ui::panes::ribble_pane::RibblePaneId;
 use crate::ui::panes::PaneView;
+use crate::ui::widgets::input_device_combobox::input_device_combobox;
 use crate::ui::widgets::recording_modal::build_recording_modal;
 use crate::ui::widgets::toggle_switch::toggle;
 use crate::ui::{
     DEFAULT_TOAST_DURATION, GRID_ROW_SPACING_COEFF, MODAL_HEIGHT_PROPORTION, PANE_INNER_MARGIN,
 };
-use crate::utils::audio_gain::MAX_AUDIO_GAIN_DB;
+use crate::utils::audio_gain::{AudioGainMode, MAX_AUDIO_GAIN_DB};
 use crate::utils::buffering_strategy::RibbleBufferingStrategy;
+use crate::utils::live_sink_configs::{LiveSinkTarget, MIN_LIVE_SINK_PORT};
+use crate::utils::loudness::{
+    MAX_AGC_GAIN_DB, MAX_ATTACK_MS, MAX_GATE_LUFS, MAX_RELEASE_MS, MAX_TARGET_LUFS, MIN_ATTACK_MS,
+    MIN_GATE_LUFS, MIN_RELEASE_MS, MIN_TARGET_LUFS,
+};
+use crate::utils::noise_suppression::NoiseSuppressionStrength;
 use crate::utils::realtime_settings::{AudioSampleLen, RealtimeTimeout, VadSampleLen};
 use crate::utils::vad_configs::{VadFrameSize, VadStrictness, VadType};
 use egui::Ui;
@@ -23,6 +35,8 @@ use strum::IntoEnumIterator;
 // include information in the README.
 const LINK_ICON: &str = "🌐";
 const LINK_BUTTON_SIZE: f32 = 18.0;
+// https://unicodeplus.com/U+26A0 -> Warning sign
+const WARNING_ICON: &str = "⚠";
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub(in crate::ui) struct TranscriberPane {
@@ -43,6 +57,21 @@ pub(in crate::ui) struct TranscriberPane {
     #[serde(skip)]
     #[serde(default)]
     model_url: String,
+    // The catalog location being typed in (in the download modal).
+    #[serde(skip)]
+    #[serde(default)]
+    catalog_location: String,
+    #[serde(skip)]
+    #[serde(default)]
+    manage_models_modal: bool,
+    // The model being renamed, and the new name.
+    #[serde(skip)]
+    #[serde(default)]
+    renaming_model: Option<(ModelId, String)>,
+    // Deleting a model asks for confirmation first.
+    #[serde(skip)]
+    #[serde(default)]
+    deleting_model: Option<ModelId>,
 }
 
 // This is for serde until it supports literals.
@@ -59,6 +88,10 @@ impl Default for TranscriberPane {
             recording_modal: false,
             download_modal: false,
             model_url: Default::default(),
+            catalog_location: Default::default(),
+            manage_models_modal: false,
+            renaming_model: None,
+            deleting_model: None,
         }
     }
 }
@@ -104,8 +137,16 @@ impl PaneView for TranscriberPane {
             .and_then(|id| self.model_list.iter().find(|(k, _)| *k == id))
             .cloned();
 
+        // Models that can't handle the current configs (e.g. English-only, with a French file) are
+        // caught here, before whisper gets a chance to produce garbage.
+        let model_problem = model_problem_text(
+            model_id.and_then(|id| controller.read_model_header(id)),
+            configs.language(),
+        );
+
         // RUN TRANSCRIPTION
-        let can_run_transcription = current_model.is_some() && !audio_worker_running;
+        let can_run_transcription =
+            current_model.is_some() && model_problem.is_none() && !audio_worker_running;
 
         // HEADING
         let header_text = if self.realtime {
@@ -171,11 +212,14 @@ impl PaneView for TranscriberPane {
             if self.realtime {
                 // RUNNER BUTTONS: START + STOP + Re-Transcribe
                 ui.vertical_centered_justified(|ui| {
-                    if ui
+                    let start = ui
                         .add_enabled(can_run_transcription, egui::Button::new("Start"))
-                        .on_hover_cursor(egui::CursorIcon::Default)
-                        .clicked()
-                    {
+                        .on_hover_cursor(egui::CursorIcon::Default);
+                    let start = match model_problem {
+                        Some(problem) => start.on_disabled_hover_text(problem),
+                        None => start,
+                    };
+                    if start.clicked() {
                         controller.start_realtime_transcription();
                     }
                     ui.add_space(button_spacing);
@@ -210,9 +254,13 @@ impl PaneView for TranscriberPane {
                 ui.vertical_centered_justified(|ui| {
                     let has_file = current_file.is_some();
 
-                    if ui.add_enabled(can_run_transcription && has_file, egui::Button::new("Start"))
-                        .on_hover_cursor(egui::CursorIcon::Default)
-                        .clicked() {
+                    let start = ui.add_enabled(can_run_transcription && has_file, egui::Button::new("Start"))
+                        .on_hover_cursor(egui::CursorIcon::Default);
+                    let start = match model_problem {
+                        Some(problem) => start.on_disabled_hover_text(problem),
+                        None => start,
+                    };
+                    if start.clicked() {
                         controller.start_offline_transcription();
                     }
 
@@ -292,8 +340,7 @@ impl PaneView for TranscriberPane {
                             .on_hover_cursor(egui::CursorIcon::Default)
                             .clicked() {
                             let file_dialog = rfd::FileDialog::new()
-                                .add_filter("all supported",
-                                            &["wav", "mpa", "mp2", "mp3", "mp4", "m4v", "ogg", "mkv", "aif", "aiff", "aifc", "caf", "alac", "flac"])
+                                .add_filter("all supported", &SUPPORTED_AUDIO_FILE_EXTENSIONS)
                                 .add_filter("wav", &["wav"])
                                 .add_filter("mpeg", &["mpa", "mp2", "mp3", "mp4", "m4v"])
                                 .add_filter("aiff", &["aif", "aiff", "aifc"])
@@ -370,55 +417,47 @@ impl PaneView for TranscriberPane {
                                     // Get a clone of the model_id to modify
                                     let mut model_id = *configs.model_id();
 
-                                    let model_id_combobox = match current_model {
-                                        Some((_, file)) => {
-                                            let salt = "model_id_combobox";
-                                            match file {
-                                                #[cfg(any(
-                                                    debug_assertions,
-                                                    feature = "pack-in-models"
-                                                ))]
-                                                ModelFile::Packed(idx) => {
-                                                    egui::ComboBox::from_id_salt(salt)
-                                                        .selected_text(ModelFile::PACKED_NAMES[idx])
-                                                }
-                                                ModelFile::File(name) => {
-                                                    egui::ComboBox::from_id_salt(salt)
-                                                        .selected_text(name.as_ref())
-                                                }
-                                            }
-                                        }
-                                        None => {
-                                            egui::ComboBox::from_id_salt("model_id_combobox")
-                                                .selected_text("Select a model.")
-                                        }
+                                    let selected_text = match current_model.as_ref() {
+                                        Some((m_id, file)) => model_list_text(file.file_name(), controller.read_model_integrity(*m_id), controller.read_model_header(*m_id)),
+                                        None => "Select a model.".to_string(),
                                     };
 
-                                    model_id_combobox.show_ui(ui, |ui| {
+                                    // NOTE: English-only models are disabled outright when a
+                                    // (non-English) language is set; Auto is left alone, it's
+                                    // the user's call.
+                                    let needs_multilingual = needs_multilingual_model(configs.language());
+                                    egui::ComboBox::from_id_salt("model_id_combobox").selected_text(selected_text).show_ui(ui, |ui| {
                                         for (m_id, model_file) in self.model_list.iter() {
-                                            match model_file {
-                                                #[cfg(any(
-                                                    debug_assertions,
-                                                    feature = "pack-in-models"
-                                                ))]
-                                                ModelFile::Packed(idx) => {
-                                                    if ui.selectable_value(&mut model_id, Some(*m_id), ModelFile::PACKED_NAMES[*idx])
-                                                        .clicked() {
-                                                        let new_configs = configs.with_model_id(model_id);
-                                                        controller.write_transcription_configs(new_configs);
-                                                    }
-                                                }
-                                                ModelFile::File(file_name) => {
-                                                    if ui.selectable_value(&mut model_id, Some(*m_id), file_name.as_ref())
-                                                        .clicked() {
-                                                        let new_configs = configs.with_model_id(model_id);
-                                                        controller.write_transcription_configs(new_configs);
-                                                    }
-                                                }
-                                            };
+                                            let integrity = controller.read_model_integrity(*m_id);
+                                            let header = controller.read_model_header(*m_id);
+                                            let english_only = header.is_some_and(|header| header.is_english_only());
+                                            let text = model_list_text(model_file.file_name(), integrity, header);
+
+                                            let selectable = ui.add_enabled(
+                                                !(english_only && needs_multilingual),
+                                                egui::Button::selectable(model_id == Some(*m_id), text),
+                                            );
+                                            let mut hover_text = header.map_or("Unable to read the model header.".to_string(), |header| header.to_string());
+                                            if integrity.is_flagged() {
+                                                hover_text.push_str(&format!("\n\n{}", integrity.description()));
+                                            }
+                                            let selectable = selectable.on_hover_text(hover_text)
+                                                .on_disabled_hover_text("This model is English-only.\n\
+                                                Set the language to English (or Auto) to use it.");
+
+                                            if selectable.clicked() {
+                                                model_id = Some(*m_id);
+                                                let new_configs = configs.with_model_id(model_id);
+                                                controller.write_transcription_configs(new_configs);
+                                            }
                                         }
                                     }).response
                                         .on_hover_cursor(egui::CursorIcon::Default);
+
+                                    if let Some(problem) = model_problem {
+                                        ui.colored_label(ui.visuals().warn_fg_color, WARNING_ICON)
+                                            .on_hover_text(problem);
+                                    }
                                 });
                                 ui.end_row();
 
@@ -435,8 +474,7 @@ impl PaneView for TranscriberPane {
                                             .set_directory(controller.base_dir());
 
                                         // If there is path, it is a ".bin".
-                                        // At the moment, there's no integrity checking
-                                        // mechanisms
+                                        // The model bank verifies it once it's been copied.
                                         if let Some(path) = file_dialog.pick_file() {
                                             // Try and set the Model ID if it's valid - the hash is expected to be stable.
 
@@ -462,6 +500,40 @@ impl PaneView for TranscriberPane {
                                 });
                                 ui.end_row();
 
+                                ui.label("Verify Models:").on_hover_text("Check the models against the known model checksums.");
+                                ui.horizontal(|ui| {
+                                    let verifying = controller.verifying_model();
+                                    if ui.add_enabled(verifying.is_none(), egui::Button::new("Verify all"))
+                                        .on_hover_text("Models are also verified after they're downloaded or loaded.")
+                                        .on_hover_cursor(egui::CursorIcon::Default)
+                                        .clicked() {
+                                        controller.verify_all_models();
+                                    }
+
+                                    if let Some(file_name) = verifying {
+                                        ui.label(format!("Verifying: {file_name}"));
+                                    } else if let Some(m_id) = *configs.model_id() {
+                                        let integrity = controller.read_model_integrity(m_id);
+                                        if integrity.is_flagged() {
+                                            ui.colored_label(ui.visuals().warn_fg_color, format!("{WARNING_ICON} Selected model"))
+                                                .on_hover_text(integrity.description());
+                                        }
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Manage Models:").on_hover_text("Rename, delete or locate the downloaded models.");
+                                ui.horizontal(|ui| {
+                                    if ui.button("Open manager")
+                                        .on_hover_cursor(egui::CursorIcon::Default)
+                                        .clicked() {
+                                        self.manage_models_modal = true;
+                                    }
+                                    let disk_usage = controller.model_disk_usage();
+                                    ui.label(format!("{} on disk", format_bytes(disk_usage.total_bytes())));
+                                });
+                                ui.end_row();
+
                                 ui.label("Download Model:").on_hover_text("Open the the downloads menu.");
                                 if ui.button("Open menu").clicked() {
                                     self.download_modal = true;
@@ -587,6 +659,12 @@ impl PaneView for TranscriberPane {
 
                                 // -- REALTIME specific configs.
                                 if self.realtime {
+                                    // ROW: INPUT DEVICE (shared with the recorder)
+                                    ui.label("Input device:").on_hover_text("Set the microphone to transcribe from.\n\
+                                    This is shared with the recorder.");
+                                    input_device_combobox(ui, "realtime_input_device_combobox", &controller);
+                                    ui.end_row();
+
                                     // ROW: REALTIME TIMEOUT -> PREDEFINE (NONE, 15 MIN, 30 MIN, 1HR, 2HR)
                                     let mut realtime_timeout: RealtimeTimeout = configs.realtime_timeout().into();
 
@@ -828,24 +906,105 @@ impl PaneView for TranscriberPane {
                     ui.add_enabled_ui(!transcription_running, |ui| {
                         egui::Grid::new("audio_gain_configs_grid").striped(true).num_columns(2)
                             .show(ui, |ui| {
-                                let mut db = audio_gain_configs.db();
-                                let db_range = 0.0..=MAX_AUDIO_GAIN_DB;
-                                ui.label("Audio gain:").on_hover_text("Apply audio gain to boost recording volume.\n\
-                                Recommended: ~6dB all-purpose, 20dB if using Silero VAD.");
+                                ui.label("Mode:").on_hover_text("Fixed: boost by a set amount.\n\
+                                Automatic: level the audio to a target loudness.");
+                                let mut mode = audio_gain_configs.mode();
                                 ui.horizontal(|ui| {
-                                    let slider = ui.add(egui::Slider::new(&mut db, db_range));
-                                    let keyboard_input = check_keyboard(ui);
-                                    if slider.drag_stopped() || (slider.changed() && keyboard_input) {
-                                        let new_configs = audio_gain_configs.with_decibels(db);
-                                        controller.write_audio_gain_configs(new_configs);
-                                    }
+                                    egui::ComboBox::from_id_salt("audio_gain_mode_combobox")
+                                        .selected_text(mode.as_ref())
+                                        .show_ui(ui, |ui| {
+                                            for next_mode in AudioGainMode::iter() {
+                                                if ui.selectable_value(&mut mode, next_mode, next_mode.as_ref())
+                                                    .on_hover_text(next_mode.tooltip())
+                                                    .clicked() {
+                                                    let new_configs = audio_gain_configs.with_mode(mode);
+                                                    controller.write_audio_gain_configs(new_configs);
+                                                }
+                                            }
+                                        }).response.on_hover_cursor(egui::CursorIcon::Default);
                                     // Tiny hack to paint the grid color to the edge of the pane.
                                     ui.add_space(ui.available_width());
                                 });
                                 ui.end_row();
 
+                                match audio_gain_configs.mode() {
+                                    AudioGainMode::Fixed => {
+                                        let mut db = audio_gain_configs.db();
+                                        let db_range = 0.0..=MAX_AUDIO_GAIN_DB;
+                                        ui.label("Audio gain:").on_hover_text("Apply audio gain to boost recording volume.\n\
+                                        Recommended: ~6dB all-purpose, 20dB if using Silero VAD.");
+                                        let slider = ui.add(egui::Slider::new(&mut db, db_range));
+                                        let keyboard_input = check_keyboard(ui);
+                                        if slider.drag_stopped() || (slider.changed() && keyboard_input) {
+                                            let new_configs = audio_gain_configs.with_decibels(db);
+                                            controller.write_audio_gain_configs(new_configs);
+                                        }
+                                        ui.end_row();
+                                    }
+                                    AudioGainMode::Automatic => {
+                                        let agc_configs = *audio_gain_configs.agc_configs();
+
+                                        let mut target_lufs = agc_configs.target_lufs();
+                                        ui.label("Target level:").on_hover_text("The loudness (LUFS) to bring speech to.\n\
+                                        Recommended: -20 LUFS.");
+                                        let slider = ui.add(egui::Slider::new(&mut target_lufs, MIN_TARGET_LUFS..=MAX_TARGET_LUFS)
+                                            .suffix(" LUFS"));
+                                        let keyboard_input = check_keyboard(ui);
+                                        if slider.drag_stopped() || (slider.changed() && keyboard_input) {
+                                            let new_configs = audio_gain_configs.with_agc_configs(agc_configs.with_target_lufs(target_lufs));
+                                            controller.write_audio_gain_configs(new_configs);
+                                        }
+                                        ui.end_row();
+
+                                        let mut max_gain_db = agc_configs.max_gain_db();
+                                        ui.label("Max gain:").on_hover_text("The most that quiet audio will be boosted.");
+                                        let slider = ui.add(egui::Slider::new(&mut max_gain_db, 0.0..=MAX_AGC_GAIN_DB)
+                                            .suffix(" dB"));
+                                        let keyboard_input = check_keyboard(ui);
+                                        if slider.drag_stopped() || (slider.changed() && keyboard_input) {
+                                            let new_configs = audio_gain_configs.with_agc_configs(agc_configs.with_max_gain_db(max_gain_db));
+                                            controller.write_audio_gain_configs(new_configs);
+                                        }
+                                        ui.end_row();
+
+                                        let mut attack_ms = agc_configs.attack_ms();
+                                        ui.label("Attack:").on_hover_text("How quickly loud audio is turned down.");
+                                        let slider = ui.add(egui::Slider::new(&mut attack_ms, MIN_ATTACK_MS..=MAX_ATTACK_MS)
+                                            .logarithmic(true).suffix(" ms"));
+                                        let keyboard_input = check_keyboard(ui);
+                                        if slider.drag_stopped() || (slider.changed() && keyboard_input) {
+                                            let new_configs = audio_gain_configs.with_agc_configs(agc_configs.with_attack_ms(attack_ms));
+                                            controller.write_audio_gain_configs(new_configs);
+                                        }
+                                        ui.end_row();
+
+                                        let mut release_ms = agc_configs.release_ms();
+                                        ui.label("Release:").on_hover_text("How quickly quiet audio is brought back up.");
+                                        let slider = ui.add(egui::Slider::new(&mut release_ms, MIN_RELEASE_MS..=MAX_RELEASE_MS)
+                                            .logarithmic(true).suffix(" ms"));
+                                        let keyboard_input = check_keyboard(ui);
+                                        if slider.drag_stopped() || (slider.changed() && keyboard_input) {
+                                            let new_configs = audio_gain_configs.with_agc_configs(agc_configs.with_release_ms(release_ms));
+                                            controller.write_audio_gain_configs(new_configs);
+                                        }
+                                        ui.end_row();
+
+                                        let mut gate_lufs = agc_configs.gate_lufs();
+                                        ui.label("Gate:").on_hover_text("Audio quieter than this (silence, background noise) is not boosted.");
+                                        let slider = ui.add(egui::Slider::new(&mut gate_lufs, MIN_GATE_LUFS..=MAX_GATE_LUFS)
+                                            .suffix(" LUFS"));
+                                        let keyboard_input = check_keyboard(ui);
+                                        if slider.drag_stopped() || (slider.changed() && keyboard_input) {
+                                            let new_configs = audio_gain_configs.with_agc_configs(agc_configs.with_gate_lufs(gate_lufs));
+                                            controller.write_audio_gain_configs(new_configs);
+                                        }
+                                        ui.end_row();
+                                    }
+                                }
+
                                 ui.label("File gain:").on_hover_text("Apply gain to files before transcribing?\n\
-                                Audio will be normalized to the highest peak.");
+                                Fixed: audio will be normalized to the highest peak.\n\
+                                Automatic: the file's loudness is measured, then normalized to the target level.");
 
                                 let mut use_offline = audio_gain_configs.use_offline();
                                 if ui.add(egui::Checkbox::without_text(&mut use_offline)).clicked() {
@@ -857,6 +1016,145 @@ impl PaneView for TranscriberPane {
                     });
                 });
                 audio_gain_configs.header_response.on_hover_cursor(egui::CursorIcon::Default);
+                ui.separator();
+                // NOISE SUPPRESSION SETTINGS
+                let noise_suppression = ui.collapsing("Noise suppression", |ui| {
+                    let noise_suppression_configs = *controller.read_noise_suppression_configs();
+
+                    ui.add_enabled_ui(!transcription_running, |ui| {
+                        egui::Grid::new("noise_suppression_configs_grid").striped(true).num_columns(2)
+                            .show(ui, |ui| {
+                                ui.label("Strength:").on_hover_text("Reduce steady background noise (fans, hum, hiss) before transcribing.\n\
+                                Stronger settings remove more noise, but can make speech sound thin.");
+                                let mut strength = noise_suppression_configs.strength();
+                                ui.horizontal(|ui| {
+                                    egui::ComboBox::from_id_salt("noise_suppression_strength_combobox")
+                                        .selected_text(strength.as_ref())
+                                        .show_ui(ui, |ui| {
+                                            for next_strength in NoiseSuppressionStrength::iter() {
+                                                if ui.selectable_value(&mut strength, next_strength, next_strength.as_ref())
+                                                    .on_hover_text(next_strength.tooltip())
+                                                    .clicked() {
+                                                    let new_configs = noise_suppression_configs.with_strength(strength);
+                                                    controller.write_noise_suppression_configs(new_configs);
+                                                }
+                                            }
+                                        }).response.on_hover_cursor(egui::CursorIcon::Default);
+                                    // Tiny hack to paint the grid color to the edge of the pane.
+                                    ui.add_space(ui.available_width());
+                                });
+                                ui.end_row();
+
+                                ui.label("File suppression:").on_hover_text("Suppress noise in files before transcribing?");
+                                let mut use_offline = noise_suppression_configs.use_offline();
+                                if ui.add(egui::Checkbox::without_text(&mut use_offline)).clicked() {
+                                    let new_configs = noise_suppression_configs.with_use_offline(use_offline);
+                                    controller.write_noise_suppression_configs(new_configs);
+                                }
+                                ui.end_row();
+
+                                ui.label("Recording suppression:").on_hover_text("Save the denoised audio in recordings?\n\
+                                Otherwise, recordings keep the original audio.");
+                                let mut use_recording = noise_suppression_configs.use_recording();
+                                if ui.add(egui::Checkbox::without_text(&mut use_recording)).clicked() {
+                                    let new_configs = noise_suppression_configs.with_use_recording(use_recording);
+                                    controller.write_noise_suppression_configs(new_configs);
+                                }
+                                ui.end_row();
+                            });
+                    });
+                });
+                noise_suppression.header_response.on_hover_cursor(egui::CursorIcon::Default);
+
+                // LIVE OUTPUT (real-time only)
+                if self.realtime {
+                    ui.separator();
+                    let live_output = ui.collapsing("Live output", |ui| {
+                        let live_sink_configs = (*controller.read_live_sink_configs()).clone();
+
+                        ui.add_enabled_ui(!transcription_running, |ui| {
+                            egui::Grid::new("live_sink_configs_grid").striped(true).num_columns(2)
+                                .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+                                .show(ui, |ui| {
+                                    ui.label("Stream transcript:").on_hover_text("Stream the transcript as newline-delimited JSON while transcribing.\n\
+                                    Confirmed segments are sent as soon as they arrive.");
+                                    let mut enabled = live_sink_configs.enabled();
+                                    ui.horizontal(|ui| {
+                                        if ui.add(toggle(&mut enabled)).clicked() {
+                                            let new_configs = live_sink_configs.clone().with_enabled(enabled);
+                                            controller.write_live_sink_configs(new_configs);
+                                        }
+                                        // Tiny hack to paint the grid color to the edge of the pane.
+                                        ui.add_space(ui.available_width());
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("Output:");
+                                    let mut target = live_sink_configs.target();
+                                    egui::ComboBox::from_id_salt("live_sink_target_combobox")
+                                        .selected_text(target.as_ref()).show_ui(ui, |ui| {
+                                        for sink_target in LiveSinkTarget::iter() {
+                                            if ui.selectable_value(&mut target, sink_target, sink_target.as_ref()).clicked() {
+                                                let new_configs = live_sink_configs.clone().with_target(target);
+                                                controller.write_live_sink_configs(new_configs);
+                                            }
+                                        }
+                                    }).response.on_hover_cursor(egui::CursorIcon::Default);
+                                    ui.end_row();
+
+                                    match live_sink_configs.target() {
+                                        LiveSinkTarget::Stdout => {}
+                                        LiveSinkTarget::File => {
+                                            ui.label("Output file:").on_hover_text("A file to append to, or a named pipe (FIFO).");
+                                            ui.horizontal(|ui| {
+                                                if ui.button("Choose")
+                                                    .on_hover_cursor(egui::CursorIcon::Default)
+                                                    .clicked()
+                                                    && let Some(path) = rfd::FileDialog::new()
+                                                    .set_directory(controller.base_dir())
+                                                    .add_filter("json lines", &["jsonl", "ndjson"])
+                                                    .save_file()
+                                                {
+                                                    let new_configs = live_sink_configs.clone().with_file_path(Some(path));
+                                                    controller.write_live_sink_configs(new_configs);
+                                                }
+
+                                                match live_sink_configs.file_path() {
+                                                    Some(path) => {
+                                                        ui.label(path.display().to_string());
+                                                    }
+                                                    None => {
+                                                        ui.label("None");
+                                                    }
+                                                }
+                                            });
+                                            ui.end_row();
+                                        }
+                                        LiveSinkTarget::Socket => {
+                                            ui.label("Port:").on_hover_text("Connects to a program listening on this port (on this computer).");
+                                            let mut port = live_sink_configs.port();
+                                            if ui.add(egui::DragValue::new(&mut port).range(MIN_LIVE_SINK_PORT..=u16::MAX))
+                                                .changed() {
+                                                let new_configs = live_sink_configs.clone().with_port(port);
+                                                controller.write_live_sink_configs(new_configs);
+                                            }
+                                            ui.end_row();
+                                        }
+                                    }
+
+                                    ui.label("In-progress text:").on_hover_text("Also stream the text that hasn't been confirmed yet.\n\
+                                    This may still change.");
+                                    let mut include_partial = live_sink_configs.include_partial();
+                                    if ui.add(egui::Checkbox::without_text(&mut include_partial)).clicked() {
+                                        let new_configs = live_sink_configs.clone().with_include_partial(include_partial);
+                                        controller.write_live_sink_configs(new_configs);
+                                    }
+                                    ui.end_row();
+                                });
+                        });
+                    });
+                    live_output.header_response.on_hover_cursor(egui::CursorIcon::Default);
+                }
             });
         });
 
@@ -866,10 +1164,10 @@ impl PaneView for TranscriberPane {
             // NOTE: this is a very cheap clone, so it should be fine to just cache and pass into the closure.
             let err_ctx = ui.ctx().clone();
             let handle_recordings = |file_name| match controller
-                .try_get_recording_path(Arc::clone(&file_name))
+                .try_get_recording_segments(Arc::clone(&file_name))
             {
-                Some(path) => {
-                    controller.set_audio_file_path(path);
+                Some(segments) => {
+                    controller.set_audio_file_segments(segments);
                     self.realtime = false;
                     self.recording_modal = false;
                 }
@@ -982,6 +1280,101 @@ impl PaneView for TranscriberPane {
                                 })
                                     .header_response
                                     .on_hover_text("A selection of downloadable models sourced from huggingface.");
+
+                                // Offline catalog: install models from a local directory/mirror.
+                                ui.collapsing("Offline catalog:", |ui| {
+                                    let download_configs = controller.read_download_configs();
+                                    ui.horizontal(|ui| {
+                                        let hint_text = download_configs.model_catalog()
+                                            .map_or("Directory or file:// url".to_string(), |path| path.display().to_string());
+                                        ui.add(egui::TextEdit::singleline(&mut self.catalog_location).hint_text(hint_text));
+
+                                        if ui.add_enabled(!self.catalog_location.trim().is_empty(), egui::Button::new("Set"))
+                                            .clicked() {
+                                            match parse_catalog_location(&self.catalog_location) {
+                                                Ok(path) => {
+                                                    let new_configs = (*download_configs).clone().with_model_catalog(Some(path));
+                                                    controller.write_download_configs(new_configs);
+                                                    self.catalog_location.clear();
+                                                }
+                                                Err(e) => {
+                                                    let mut toast = egui_notify::Toast::error(e.to_string());
+                                                    toast.duration(Some(DEFAULT_TOAST_DURATION));
+                                                    controller.send_toast(toast);
+                                                }
+                                            }
+                                        }
+
+                                        if ui.button("Browse")
+                                            .on_hover_cursor(egui::CursorIcon::Default)
+                                            .clicked()
+                                            && let Some(path) = rfd::FileDialog::new()
+                                            .set_directory(controller.base_dir())
+                                            .pick_folder()
+                                        {
+                                            let new_configs = (*download_configs).clone().with_model_catalog(Some(path));
+                                            controller.write_download_configs(new_configs);
+                                        }
+
+                                        if download_configs.model_catalog().is_some() {
+                                            if ui.button("Reload").on_hover_text("Read the catalog again.").clicked() {
+                                                controller.refresh_model_catalog();
+                                            }
+                                            if ui.button("Clear").clicked() {
+                                                let new_configs = (*download_configs).clone().with_model_catalog(None);
+                                                controller.write_download_configs(new_configs);
+                                            }
+                                        }
+                                    });
+
+                                    let Some(catalog) = controller.read_model_catalog() else {
+                                        ui.label("No catalog loaded.");
+                                        return;
+                                    };
+
+                                    if catalog.models().is_empty() {
+                                        ui.label(format!("No models found in: {}", catalog.directory().display()));
+                                        return;
+                                    }
+
+                                    egui::Grid::new("catalog_models_grid")
+                                        .num_columns(3)
+                                        .striped(true)
+                                        .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+                                        .show(ui, |ui| {
+                                            for model in catalog.models() {
+                                                let Some(file_name) = model.file_name() else {
+                                                    continue;
+                                                };
+                                                let label = ui.label(file_name);
+                                                if let Some(description) = model.description() {
+                                                    label.on_hover_text(description);
+                                                }
+
+                                                let mut details = model.size().map_or("unknown size".to_string(), format_bytes);
+                                                if !model.has_checksum() {
+                                                    details.push_str(", no checksum");
+                                                }
+                                                ui.label(details);
+
+                                                ui.horizontal(|ui| {
+                                                    if ui.button("Install")
+                                                        .on_hover_text("Copy the model into the models directory.")
+                                                        .clicked() {
+                                                        self.download_modal = false;
+                                                        controller.install_catalog_model(model.clone());
+                                                    }
+                                                    ui.add_space(ui.available_width());
+                                                });
+                                                ui.end_row();
+                                            }
+                                        });
+                                })
+                                    .header_response
+                                    .on_hover_text(format!(
+                                        "Install models from a local directory or mirror, without a network connection.\n\
+                                        The directory should have a {CATALOG_FILE} that lists the models and their checksums."
+                                    ));
                             });
                     });
                 });
@@ -992,6 +1385,118 @@ impl PaneView for TranscriberPane {
             }
         }
 
+        if self.manage_models_modal {
+            controller.try_read_model_list(&mut self.model_list);
+            let model_list = self.model_list.clone();
+            let modal = egui::Modal::new(egui::Id::new("manage_models_modal"))
+                .show(ui.ctx(), |ui| {
+                    let height = ui.ctx().screen_rect().height() * MODAL_HEIGHT_PROPORTION;
+                    ui.set_max_height(height);
+                    egui::Frame::default().inner_margin(PANE_INNER_MARGIN).show(ui, |ui| {
+                        ui.heading("Manage Models:");
+                        let disk_usage = controller.model_disk_usage();
+                        ui.label(format!("{} downloaded models, {} on disk.", disk_usage.num_models(), format_bytes(disk_usage.total_bytes())));
+
+                        let gap_space = ui.spacing().interact_size.y;
+                        ui.add_space(gap_space);
+
+                        egui::ScrollArea::vertical()
+                            .show(ui, |ui| {
+                                egui::Grid::new("manage_models_grid")
+                                    .num_columns(2)
+                                    .striped(true)
+                                    .min_row_height(ui.spacing().interact_size.y * GRID_ROW_SPACING_COEFF)
+                                    .show(ui, |ui| {
+                                        for (m_id, model_file) in model_list.iter() {
+                                            let m_id = *m_id;
+                                            let header = controller.read_model_header(m_id);
+                                            let mut details = header.map_or("unreadable header".to_string(), |header| header.summary());
+                                            if let Some(size) = controller.read_model_size(m_id) && !model_file.is_packed() {
+                                                details.push_str(&format!(", {}", format_bytes(size)));
+                                            }
+                                            ui.vertical(|ui| {
+                                                ui.strong(model_file.file_name());
+                                                ui.label(details);
+                                            });
+
+                                            ui.horizontal(|ui| {
+                                                // NOTE: the model bank also refuses these; the
+                                                // checks here are just to explain why.
+                                                let in_use = transcription_running && model_id == Some(m_id);
+                                                let editable = !model_file.is_packed() && !in_use;
+                                                let disabled_text = if in_use {
+                                                    "The model is in use. Stop transcribing first."
+                                                } else {
+                                                    "Built-in models cannot be changed."
+                                                };
+
+                                                if let Some((_, new_name)) = self.renaming_model.as_mut().filter(|(id, _)| *id == m_id) {
+                                                    ui.add(egui::TextEdit::singleline(new_name).hint_text("New name"));
+                                                    if ui.add_enabled(!new_name.trim().is_empty(), egui::Button::new("Save")).clicked() {
+                                                        if let Err(e) = controller.rename_model(m_id, new_name) {
+                                                            let mut toast = egui_notify::Toast::error(e.to_string());
+                                                            toast.duration(Some(DEFAULT_TOAST_DURATION));
+                                                            controller.send_toast(toast);
+                                                        }
+                                                        self.renaming_model = None;
+                                                    } else if ui.button("Cancel").clicked() {
+                                                        self.renaming_model = None;
+                                                    }
+                                                } else if self.deleting_model == Some(m_id) {
+                                                    ui.colored_label(ui.visuals().warn_fg_color, "Delete the model?");
+                                                    if ui.add_enabled(editable, egui::Button::new("Delete")).clicked() {
+                                                        if let Err(e) = controller.delete_model(m_id) {
+                                                            let mut toast = egui_notify::Toast::error(e.to_string());
+                                                            toast.duration(Some(DEFAULT_TOAST_DURATION));
+                                                            controller.send_toast(toast);
+                                                        }
+                                                        self.deleting_model = None;
+                                                    } else if ui.button("Cancel").clicked() {
+                                                        self.deleting_model = None;
+                                                    }
+                                                } else {
+                                                    if ui.add_enabled(editable, egui::Button::new("Rename"))
+                                                        .on_disabled_hover_text(disabled_text)
+                                                        .clicked() {
+                                                        self.deleting_model = None;
+                                                        self.renaming_model = Some((m_id, model_file.file_name().to_string()));
+                                                    }
+                                                    if ui.add_enabled(!model_file.is_packed(), egui::Button::new("Reveal"))
+                                                        .on_hover_text("Show the model in the file manager.")
+                                                        .on_disabled_hover_text("Built-in models are part of Ribble.")
+                                                        .clicked()
+                                                        && let Some(path) = controller.get_model_path(m_id)
+                                                        && let Err(e) = opener::reveal(path) {
+                                                        log::warn!("Failed to reveal model. Error: {}\n\
+                                                        Error source: {:#?}", &e, e.source());
+                                                        let mut toast = egui_notify::Toast::error("Failed to reveal model");
+                                                        toast.duration(Some(DEFAULT_TOAST_DURATION));
+                                                        controller.send_toast(toast);
+                                                    }
+                                                    if ui.add_enabled(editable, egui::Button::new("Delete"))
+                                                        .on_disabled_hover_text(disabled_text)
+                                                        .clicked() {
+                                                        self.renaming_model = None;
+                                                        self.deleting_model = Some(m_id);
+                                                    }
+                                                }
+                                                // GRID HACK.
+                                                ui.add_space(ui.available_width());
+                                            });
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                    });
+                });
+
+            if modal.should_close() {
+                self.manage_models_modal = false;
+                self.renaming_model = None;
+                self.deleting_model = None;
+            }
+        }
+
         resp
     }
 
@@ -1023,3 +1528,43 @@ fn check_keyboard(ui: &mut Ui) -> bool {
         })
     })
 }
+
+// Flags models that failed verification (or aren't whisper models), so they stand out before
+// they get loaded.
+fn model_list_text(file_name: &str, integrity: ModelIntegrity, header: Option<ModelHeader>) -> String {
+    let invalid = header.is_some_and(|header| !header.is_valid_whisper());
+    let mut text = if integrity.is_flagged() || invalid {
+        format!("{WARNING_ICON} {file_name}")
+    } else {
+        file_name.to_string()
+    };
+    if let Some(header) = header {
+        text.push_str(&format!(" ({})", header.summary()));
+    }
+    text
+}
+
+fn format_bytes(num_bytes: u64) -> String {
+    match unit_prefix::NumberPrefix::binary(num_bytes as f32) {
+        unit_prefix::NumberPrefix::Standalone(number) => format!("{number:.0} B"),
+        unit_prefix::NumberPrefix::Prefixed(prefix, number) => format!("{number:.2} {prefix}B"),
+    }
+}
+
+// Auto-detection is left to the model; an English-only model just assumes English.
+fn needs_multilingual_model(language: Option<Language>) -> bool {
+    language.is_some_and(|language| !matches!(language, Language::Auto) && language.as_ref() != "en")
+}
+
+// Why the selected model can't be used with the current configs, if it can't.
+fn model_problem_text(header: Option<ModelHeader>, language: Option<Language>) -> Option<&'static str> {
+    let header = header?;
+    if !header.is_valid_whisper() {
+        Some("The selected model is not a valid whisper model.")
+    } else if header.is_english_only() && needs_multilingual_model(language) {
+        Some("The selected model is English-only.\n\
+        Set the language to English (or Auto), or select a multilingual model.")
+    } else {
+        None
+    }
+}
@@ -1,16 +1,42 @@
 use crate::controller::ribble_controller::RibbleController;
+use crate::controller::transcript::{Transcript, TranscriptEdit};
 use crate::ui::panes::ribble_pane::RibblePaneId;
 use crate::ui::panes::PaneView;
 use crate::ui::{DEFAULT_TOAST_DURATION, LONG_TOAST_DURATION, PANE_HEADING_BUTTON_SIZE, PANE_INNER_MARGIN};
+use crate::utils::transcript_formats::TranscriptFormat;
 use egui_notify::Toast;
+use std::sync::Arc;
+use std::time::Duration;
+use strum::IntoEnumIterator;
 
-#[derive(Copy, Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
-pub(crate) struct TranscriptionPane {}
+#[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct TranscriptionPane {
+    #[serde(skip)]
+    #[serde(default)]
+    editing: bool,
+    // One text buffer per segment, rebuilt whenever the transcript changes.
+    #[serde(skip)]
+    #[serde(default)]
+    text_buffers: Vec<String>,
+    #[serde(skip)]
+    #[serde(default)]
+    buffered_transcript: Option<Arc<Transcript>>,
+    // (segment index, character index) of the text cursor; this is where a split happens.
+    #[serde(skip)]
+    #[serde(default)]
+    split_cursor: Option<(usize, usize)>,
+}
 
 // Clipboard: https://unicodeplus.com/U+1F4CB
 const COPY_ICON: &str = "📋";
 // Floppy Disk: https://unicodeplus.com/U+1F4BE
 const SAVE_ICON: &str = "💾";
+// Pencil: https://unicodeplus.com/U+270F
+const EDIT_ICON: &str = "✏";
+// Anticlockwise open circle arrow: https://unicodeplus.com/U+21BA
+const REVERT_ICON: &str = "↺";
+// How many seconds a boundary moves per pixel dragged.
+const BOUNDARY_DRAG_SPEED: f64 = 0.01;
 
 // TODO: ADD SOME MORE PADDING TO THE HEADER BAR ->
 // THE TEXT CENTERING IS CAUSING THE TEXT TO LAYOUT WEIRDLY DURING THE TRANSCRIPTION LOOP.
@@ -45,6 +71,15 @@ impl PaneView for TranscriptionPane {
         let transcriber_running = controller.transcriber_running();
 
         let control_phrase = controller.read_latest_control_phrase();
+        let transcript = controller.read_transcript();
+        let transcript_edited = controller.transcript_edited();
+        // The watch folder can start a batch at any time.
+        let watch_folder_active = controller.watch_folder_active();
+
+        // Edits can't be made while transcribing; the transcript is being replaced.
+        if transcriber_running || watch_folder_active || transcript.is_empty() {
+            self.editing = false;
+        }
 
         // NOTE: It might be wise to implement a "transcription_is_empty()" or similar on TranscriptionSnapshot
         let transcription_empty = transcription_snapshot.confirmed().is_empty()
@@ -99,6 +134,40 @@ impl PaneView for TranscriptionPane {
                                     ui.add_enabled_ui(
                                         !(transcriber_running || transcription_empty),
                                         |ui| {
+                                            let edit_button = egui::RichText::new(EDIT_ICON)
+                                                .size(PANE_HEADING_BUTTON_SIZE);
+                                            let edit_text = "Edit transcript.";
+                                            if ui
+                                                .add_enabled(
+                                                    !(transcript.is_empty() || watch_folder_active),
+                                                    egui::Button::selectable(self.editing, edit_button),
+                                                )
+                                                .on_hover_cursor(egui::CursorIcon::Default)
+                                                .on_hover_text(edit_text)
+                                                .on_disabled_hover_text(if watch_folder_active {
+                                                    "Stop the watch folder to edit the transcript."
+                                                } else {
+                                                    "There are no timed segments to edit."
+                                                })
+                                                .clicked()
+                                            {
+                                                self.editing = !self.editing;
+                                                self.split_cursor = None;
+                                            }
+
+                                            if transcript_edited && !watch_folder_active {
+                                                let revert_button = egui::RichText::new(REVERT_ICON)
+                                                    .size(PANE_HEADING_BUTTON_SIZE);
+                                                if ui
+                                                    .button(revert_button)
+                                                    .on_hover_cursor(egui::CursorIcon::Default)
+                                                    .on_hover_text("Undo all edits.")
+                                                    .clicked()
+                                                {
+                                                    controller.revert_transcript_edits();
+                                                }
+                                            }
+
                                             // NOTE: This might cause lag with long transcriptions
                                             // If that's the case, spawn a short-lived thread to perform the string join.
                                             let copy_button = egui::RichText::new(COPY_ICON)
@@ -133,30 +202,25 @@ impl PaneView for TranscriptionPane {
                                                 .clicked()
                                             {
                                                 // TODO: support for other file formats (markdown, etc.)
-                                                // At the moment, the transcription -only- outputs non Diarized text
-                                                // And no timestamps (for offline transcription).
-                                                // If/when timestamps/other metadata exists and it becomes relevant to support
-                                                // other filetimes, do so.
-                                                let file_dialog = rfd::FileDialog::new()
-                                                    .add_filter("txt", &["txt"])
+                                                // At the moment, the transcription -only- outputs non Diarized text.
+                                                // The file format is decided by the extension of the chosen path.
+                                                let mut file_dialog = rfd::FileDialog::new()
                                                     .set_directory(controller.base_dir());
+                                                for format in TranscriptFormat::iter() {
+                                                    file_dialog = file_dialog.add_filter(format.as_ref(), &[format.extension()]);
+                                                }
 
                                                 if let Some(out_path) = file_dialog.save_file() {
-
-                                                    // (linux) if the file extension hasn't been appended, append
-                                                    // it to the end of the file name
-                                                    #[cfg(target_os = "linux")] {
-                                                        let out_path = if out_path.extension().is_some_and(|ext| ext == "txt") {
-                                                            out_path
-                                                        } else {
-                                                            out_path.with_extension("txt")
-                                                        };
-                                                        controller.save_transcription(out_path);
-                                                    }
-
-                                                    #[cfg(not(target_os = "linux"))] {
-                                                        controller.save_transcription(out_path);
-                                                    }
+                                                    // If the file extension hasn't been appended (or is unknown),
+                                                    // fall back to plain text and append it to the end of the file name
+                                                    let (out_path, format) = match TranscriptFormat::from_path(out_path.as_path()) {
+                                                        Some(format) => (out_path, format),
+                                                        None => {
+                                                            let format = TranscriptFormat::PlainText;
+                                                            (out_path.with_extension(format.extension()), format)
+                                                        }
+                                                    };
+                                                    controller.save_transcription(out_path, format);
 
                                                     let mut toast = Toast::info("Saving file");
                                                     toast.duration(Some(LONG_TOAST_DURATION));
@@ -183,6 +247,13 @@ impl PaneView for TranscriptionPane {
                                 .auto_shrink([false; 2])
                                 .stick_to_bottom(true)
                                 .show(ui, |ui| {
+                                    if self.editing {
+                                        egui::Frame::default().inner_margin(PANE_INNER_MARGIN)
+                                            .show(ui, |ui| {
+                                                self.transcript_editor(ui, &transcript, &controller);
+                                            });
+                                        return;
+                                    }
                                     // Stick a second frame in the scroll area to prevent the
                                     // scrollbar from clobbering the text.
                                     egui::Frame::default().inner_margin(PANE_INNER_MARGIN)
@@ -229,3 +300,111 @@ impl PaneView for TranscriptionPane {
         self.pane_id().is_closable()
     }
 }
+
+impl TranscriptionPane {
+    fn transcript_editor(
+        &mut self,
+        ui: &mut egui::Ui,
+        transcript: &Arc<Transcript>,
+        controller: &RibbleController,
+    ) {
+        let transcript_changed = self
+            .buffered_transcript
+            .as_ref()
+            .is_none_or(|buffered| !Arc::ptr_eq(buffered, transcript));
+        if transcript_changed {
+            self.text_buffers = transcript
+                .segments()
+                .iter()
+                .map(|segment| segment.text().to_string())
+                .collect();
+            self.buffered_transcript = Some(Arc::clone(transcript));
+            self.split_cursor = None;
+        }
+
+        let segments = transcript.segments();
+        let mut pending_edit = None;
+
+        for (index, (segment, buffer)) in segments.iter().zip(self.text_buffers.iter_mut()).enumerate() {
+            ui.horizontal(|ui| {
+                let mut start = segment.start().as_secs_f64();
+                let mut end = segment.end().as_secs_f64();
+                let start_drag = ui.add(
+                    egui::DragValue::new(&mut start)
+                        .speed(BOUNDARY_DRAG_SPEED)
+                        .range(0.0..=f64::MAX)
+                        .suffix("s")
+                        .max_decimals(2),
+                );
+                ui.label("–");
+                let end_drag = ui.add(
+                    egui::DragValue::new(&mut end)
+                        .speed(BOUNDARY_DRAG_SPEED)
+                        .range(0.0..=f64::MAX)
+                        .suffix("s")
+                        .max_decimals(2),
+                );
+                if start_drag.changed() || end_drag.changed() {
+                    pending_edit = Some(TranscriptEdit::SetBounds {
+                        index,
+                        start: Duration::from_secs_f64(start),
+                        end: Duration::from_secs_f64(end.max(start)),
+                    });
+                }
+
+                let split_at = self
+                    .split_cursor
+                    .and_then(|(cursor_index, at_char)| (cursor_index == index).then_some(at_char));
+                if ui
+                    .add_enabled(split_at.is_some(), egui::Button::new("Split"))
+                    .on_hover_text("Split the segment at the text cursor.")
+                    .on_disabled_hover_text("Place the text cursor where the segment should split.")
+                    .on_hover_cursor(egui::CursorIcon::Default)
+                    .clicked()
+                    && let Some(at_char) = split_at
+                {
+                    pending_edit = Some(TranscriptEdit::Split { index, at_char });
+                }
+
+                if ui
+                    .add_enabled(index + 1 < segments.len(), egui::Button::new("Merge ⏷"))
+                    .on_hover_text("Merge with the next segment.")
+                    .on_hover_cursor(egui::CursorIcon::Default)
+                    .clicked()
+                {
+                    pending_edit = Some(TranscriptEdit::MergeWithNext { index });
+                }
+            });
+
+            let output = egui::TextEdit::multiline(buffer)
+                .desired_rows(1)
+                .desired_width(f32::INFINITY)
+                .hint_text("Empty segments are removed.")
+                .show(ui);
+
+            if output.response.has_focus() {
+                self.split_cursor = output
+                    .cursor_range
+                    .map(|cursor_range| (index, cursor_range.primary.index));
+            }
+
+            // Commit the text once the user is done with it.
+            if output.response.lost_focus() && buffer.trim() != segment.text().trim() {
+                pending_edit = Some(TranscriptEdit::SetText {
+                    index,
+                    text: buffer.clone(),
+                });
+            }
+            ui.separator();
+        }
+
+        if let Some(edit) = pending_edit
+            && let Err(e) = controller.edit_transcript(&edit)
+        {
+            log::warn!("Failed to edit transcript: {e}");
+            let mut toast = Toast::error(e.to_string());
+            toast.duration(Some(DEFAULT_TOAST_DURATION));
+            controller.send_toast(toast);
+        }
+    }
+}
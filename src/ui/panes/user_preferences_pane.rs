@@ -3,7 +3,13 @@ use crate::controller::{MAX_NUM_CONSOLE_MESSAGES, MIN_NUM_CONSOLE_MESSAGES};
 use crate::ui::panes::PaneView;
 use crate::ui::panes::ribble_pane::RibblePaneId;
 use crate::ui::{GRID_ROW_SPACING_COEFF, PANE_INNER_MARGIN};
+use crate::utils::control_api_configs::{ControlApiConfigs, MIN_CONTROL_API_PORT};
 use crate::utils::preferences::RibbleAppTheme;
+use crate::utils::transcript_formats::{
+    MAX_SUBTITLE_CUE_DURATION, MAX_SUBTITLE_LINE_LENGTH, MAX_SUBTITLE_LINES_PER_CUE,
+    MIN_SUBTITLE_CUE_DURATION, MIN_SUBTITLE_LINE_LENGTH, MIN_SUBTITLE_LINES_PER_CUE,
+    SubtitleConfigs,
+};
 use strum::IntoEnumIterator;
 
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
@@ -15,6 +21,14 @@ pub(crate) struct UserPreferencesPane {
     #[serde(skip)]
     #[serde(default)]
     update_num_console_messages: bool,
+    // Cached to avoid writing on every slider tick.
+    #[serde(skip)]
+    #[serde(default)]
+    subtitle_configs: Option<SubtitleConfigs>,
+    // Cached so that the server isn't restarted on every drag tick.
+    #[serde(skip)]
+    #[serde(default)]
+    control_api_configs: Option<ControlApiConfigs>,
 }
 
 impl PaneView for UserPreferencesPane {
@@ -45,6 +59,27 @@ impl PaneView for UserPreferencesPane {
 
         let mut theme = prefs.system_theme();
 
+        if self.subtitle_configs.is_none() {
+            self.subtitle_configs = Some(*controller.read_subtitle_configs());
+        }
+
+        let subtitle_configs = self
+            .subtitle_configs
+            .expect("Subtitle configs can only be None at construction time");
+        let mut max_line_length = subtitle_configs.max_line_length();
+        let mut max_lines_per_cue = subtitle_configs.max_lines_per_cue();
+        let mut max_cue_duration = subtitle_configs.max_cue_duration();
+
+        if self.control_api_configs.is_none() {
+            self.control_api_configs = Some(*controller.read_control_api_configs());
+        }
+
+        let control_api_configs = self
+            .control_api_configs
+            .expect("Control API configs can only be None at construction time");
+        let mut control_api_enabled = control_api_configs.enabled();
+        let mut control_api_port = control_api_configs.port();
+
         let pane_id = egui::Id::new("user_prefs_pane");
         let resp = ui
             .interact(ui.max_rect(), pane_id, egui::Sense::click_and_drag())
@@ -118,6 +153,100 @@ impl PaneView for UserPreferencesPane {
                             }
 
                             ui.end_row();
+
+                            // SUBTITLE EXPORT
+                            // Like the console slider, these write on drag-finished.
+                            ui.label("Subtitle line length:").on_hover_text(
+                                "Set the maximum number of characters per line in SRT/WebVTT exports.",
+                            );
+                            let slider = ui.add(egui::Slider::new(
+                                &mut max_line_length,
+                                MIN_SUBTITLE_LINE_LENGTH..=MAX_SUBTITLE_LINE_LENGTH,
+                            ));
+                            if slider.changed() {
+                                self.subtitle_configs =
+                                    Some(subtitle_configs.with_max_line_length(max_line_length));
+                            }
+                            if slider.drag_stopped() || slider.lost_focus() {
+                                controller.write_subtitle_configs(
+                                    subtitle_configs.with_max_line_length(max_line_length),
+                                );
+                            }
+                            ui.end_row();
+
+                            ui.label("Subtitle lines per cue:").on_hover_text(
+                                "Set the maximum number of lines shown at once in SRT/WebVTT exports.",
+                            );
+                            let slider = ui.add(egui::Slider::new(
+                                &mut max_lines_per_cue,
+                                MIN_SUBTITLE_LINES_PER_CUE..=MAX_SUBTITLE_LINES_PER_CUE,
+                            ));
+                            if slider.changed() {
+                                self.subtitle_configs =
+                                    Some(subtitle_configs.with_max_lines_per_cue(max_lines_per_cue));
+                            }
+                            if slider.drag_stopped() || slider.lost_focus() {
+                                controller.write_subtitle_configs(
+                                    subtitle_configs.with_max_lines_per_cue(max_lines_per_cue),
+                                );
+                            }
+                            ui.end_row();
+
+                            ui.label("Subtitle cue duration:").on_hover_text(
+                                "Set the maximum time (in seconds) a subtitle cue stays on screen.",
+                            );
+                            let slider = ui.add(
+                                egui::Slider::new(
+                                    &mut max_cue_duration,
+                                    MIN_SUBTITLE_CUE_DURATION..=MAX_SUBTITLE_CUE_DURATION,
+                                )
+                                .suffix(" s"),
+                            );
+                            if slider.changed() {
+                                self.subtitle_configs =
+                                    Some(subtitle_configs.with_max_cue_duration(max_cue_duration));
+                            }
+                            if slider.drag_stopped() || slider.lost_focus() {
+                                controller.write_subtitle_configs(
+                                    subtitle_configs.with_max_cue_duration(max_cue_duration),
+                                );
+                            }
+                            ui.end_row();
+
+                            // CONTROL API
+                            ui.label("Control API:").on_hover_text(
+                                "Let other programs on this computer control Ribble over a local socket.\n\
+                                Clients authenticate with the token in the control_api_token file,\n\
+                                in Ribble's data folder. It changes every time the API starts.",
+                            );
+                            if ui
+                                .add(egui::Checkbox::without_text(&mut control_api_enabled))
+                                .on_hover_cursor(egui::CursorIcon::Default)
+                                .clicked()
+                            {
+                                let new_configs =
+                                    control_api_configs.with_enabled(control_api_enabled);
+                                self.control_api_configs = Some(new_configs);
+                                controller.write_control_api_configs(new_configs);
+                            }
+                            ui.end_row();
+
+                            ui.label("Control API port:").on_hover_text(
+                                "The port to listen on. Only connections from this computer are accepted.",
+                            );
+                            let drag = ui.add(egui::DragValue::new(&mut control_api_port).range(
+                                MIN_CONTROL_API_PORT..=u16::MAX,
+                            ));
+                            if drag.changed() {
+                                self.control_api_configs =
+                                    Some(control_api_configs.with_port(control_api_port));
+                            }
+                            if drag.drag_stopped() || drag.lost_focus() {
+                                controller.write_control_api_configs(
+                                    control_api_configs.with_port(control_api_port),
+                                );
+                            }
+                            ui.end_row();
                         });
                 });
             });
@@ -0,0 +1,71 @@
+use crate::controller::audio_backend_proxy::AudioBackendKind;
+use crate::controller::ribble_controller::RibbleController;
+use egui::{Button, ComboBox, CursorIcon, Response, Ui};
+use std::sync::Arc;
+
+const SYSTEM_DEFAULT_DEVICE: &str = "System default";
+const WARNING_ICON: &str = "⚠";
+
+// The recorder and the realtime transcriber share the input device; picking one here sets it for
+// both.
+pub(in crate::ui) fn input_device_combobox(
+    ui: &mut Ui,
+    id_salt: &str,
+    controller: &RibbleController,
+) -> Response {
+    // Audio is replayed from a file; there's nothing to choose.
+    if let AudioBackendKind::File(path) = controller.audio_backend_kind() {
+        let file_name = path.file_name().unwrap_or(path.as_os_str());
+        return ui.label(format!("Audio file: {}", file_name.to_string_lossy()));
+    }
+
+    let configs = controller.read_recorder_configs();
+    let input_devices = controller.read_input_devices();
+    let selected = configs.input_device();
+    let disconnected = selected.is_some_and(|name| !input_devices.contains(name));
+
+    let selected_text = match selected {
+        Some(name) if disconnected => format!("{WARNING_ICON} {name}"),
+        Some(name) => name.to_string(),
+        None => SYSTEM_DEFAULT_DEVICE.to_string(),
+    };
+
+    let response = ComboBox::from_id_salt(id_salt)
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            // None: no change, Some(None): the system default.
+            let mut new_device: Option<Option<Arc<str>>> = None;
+            if ui
+                .add(Button::selectable(
+                    selected.is_none(),
+                    SYSTEM_DEFAULT_DEVICE,
+                ))
+                .clicked()
+            {
+                new_device = Some(None);
+            }
+            for name in input_devices.iter() {
+                if ui
+                    .add(Button::selectable(selected == Some(name), name.as_ref()))
+                    .clicked()
+                {
+                    new_device = Some(Some(Arc::clone(name)));
+                }
+            }
+
+            if let Some(new_device) = new_device {
+                let new_configs = (*configs).clone().with_input_device(new_device);
+                controller.write_recorder_configs(new_configs);
+            }
+        })
+        .response
+        .on_hover_cursor(CursorIcon::Default);
+
+    if disconnected {
+        response.on_hover_text(
+            "This device is not connected. Reconnect it, or choose another input device.",
+        )
+    } else {
+        response
+    }
+}
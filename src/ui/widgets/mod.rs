@@ -3,3 +3,4 @@ pub(super) mod recording_icon;
 pub(super) mod soundbar;
 pub(super) mod toggle_switch;
 pub(super) mod recording_modal;
+pub(super) mod input_device_combobox;
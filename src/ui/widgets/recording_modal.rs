@@ -1,6 +1,7 @@
 use crate::controller::ribble_controller::RibbleController;
 use crate::controller::CompletedRecordingJobs;
 use crate::ui::{GRID_ROW_SPACING_COEFF, MODAL_HEIGHT_PROPORTION, PANE_INNER_MARGIN};
+use crate::utils::transcript_formats::format_rfc3339;
 use egui::{Align, Frame, Grid, Id, Layout, Modal, ScrollArea, Sense, Ui, UiBuilder, Vec2};
 use std::sync::Arc;
 
@@ -41,7 +42,10 @@ where
                     .show(ui, |ui| {
                         let len = recordings.len();
                         for (i, (file_name, recording)) in recordings.iter().enumerate() {
-                            let heading_text = format!("Recording {}", len - i);
+                            let heading_text = match recording.label() {
+                                Some(label) => label.to_string(),
+                                None => format!("Recording {}", len - i),
+                            };
                             let body_text = {
                                 let secs = recording.total_duration().as_secs();
                                 let seconds = secs % 60;
@@ -55,7 +59,18 @@ where
                                     unit_prefix::NumberPrefix::Prefixed(prefix, number) => format!("{number:.2} {prefix}B"),
                                 };
 
-                                format!("Total time: {hours}:{minutes}:{seconds} | Approx size: {size_text}")
+                                let created_at = format_rfc3339(recording.created_at());
+                                let mut body_text = format!("Total time: {hours}:{minutes}:{seconds} | Approx size: {size_text}\nRecorded: {created_at}");
+                                if let Some(file_name) = recording.transcript_path().and_then(|path| path.file_name()) {
+                                    body_text.push_str(&format!("\nTranscript: {}", file_name.to_string_lossy()));
+                                }
+                                // NOTE: recordings too long to join back into one wav file are
+                                // kept in parts. Exports include every part; loading the
+                                // recording (playback, transcription) only uses the first.
+                                if recording.num_segments() > 1 {
+                                    body_text.push_str(&format!(" | {} parts", recording.num_segments()));
+                                }
+                                body_text
                             };
 
                             let tile_resp = ui.scope_builder(
@@ -3,7 +3,9 @@ use core::f32;
 // to worry about the application.
 //
 // Maybe this should be a 30 db reference; not sure?
+use crate::utils::loudness::{AgcConfigs, AutomaticGainControl, PeakLimiter};
 use std::borrow::Borrow;
+use strum::{AsRefStr, Display, EnumIter, EnumString, IntoStaticStr};
 
 // This is in decibels
 pub const MAX_AUDIO_GAIN_DB: f32 = 20.0;
@@ -14,6 +16,10 @@ const DECIBEL: f32 = 10.0;
 pub(crate) struct AudioGainConfigs {
     db: f32,
     use_offline: bool,
+    #[serde(default)]
+    mode: AudioGainMode,
+    #[serde(default)]
+    agc_configs: AgcConfigs,
 }
 
 impl AudioGainConfigs {
@@ -31,6 +37,16 @@ impl AudioGainConfigs {
         self
     }
 
+    pub(crate) fn with_mode(mut self, mode: AudioGainMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub(crate) fn with_agc_configs(mut self, agc_configs: AgcConfigs) -> Self {
+        self.agc_configs = agc_configs;
+        self
+    }
+
     pub(crate) fn db(&self) -> f32 {
         self.db
     }
@@ -39,13 +55,89 @@ impl AudioGainConfigs {
         self.use_offline
     }
 
+    pub(crate) fn mode(&self) -> AudioGainMode {
+        self.mode
+    }
+
+    pub(crate) fn agc_configs(&self) -> &AgcConfigs {
+        &self.agc_configs
+    }
+
     pub(crate) fn no_gain(&self) -> bool {
-        (self.db - 0.0) <= f32::EPSILON
+        match self.mode {
+            AudioGainMode::Fixed => (self.db - 0.0) <= f32::EPSILON,
+            AudioGainMode::Automatic => false,
+        }
     }
 
     pub(crate) fn build_audio_gain(&self) -> AudioGain {
         AudioGain::from_db(self.db)
     }
+
+    pub(crate) fn build_realtime_gain(&self, sample_rate: f32) -> RealtimeGain {
+        match self.mode {
+            AudioGainMode::Fixed => RealtimeGain::Fixed(
+                self.build_audio_gain(),
+                PeakLimiter::new(sample_rate as f64),
+            ),
+            AudioGainMode::Automatic => {
+                RealtimeGain::Automatic(self.agc_configs.build_agc(sample_rate))
+            }
+        }
+    }
+}
+
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    EnumString,
+    EnumIter,
+    IntoStaticStr,
+    AsRefStr,
+    Display,
+)]
+pub(crate) enum AudioGainMode {
+    #[default]
+    Fixed,
+    Automatic,
+}
+
+impl AudioGainMode {
+    pub(crate) fn tooltip(&self) -> &'static str {
+        match self {
+            AudioGainMode::Fixed => "Boost the audio by a fixed amount.",
+            AudioGainMode::Automatic => {
+                "Continuously adjust the gain to keep speech at the target level.\n\
+                Quiet and loud speakers are both brought to the same level."
+            }
+        }
+    }
+}
+
+// The gain stage for realtime audio. Both modes end in a limiter, so the output stays in range.
+pub(crate) enum RealtimeGain {
+    Fixed(AudioGain, PeakLimiter),
+    Automatic(AutomaticGainControl),
+}
+
+impl RealtimeGain {
+    pub(crate) fn process(&mut self, samples: &mut [f32]) {
+        match self {
+            RealtimeGain::Fixed(gain, limiter) => {
+                gain.apply_gain(samples.iter_mut());
+                samples
+                    .iter_mut()
+                    .for_each(|sample| *sample = limiter.process(*sample));
+            }
+            RealtimeGain::Automatic(agc) => agc.process(samples),
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -0,0 +1,114 @@
+use crate::utils::transcript_formats::TranscriptFormat;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub(crate) struct BatchConfigs {
+    export_format: TranscriptFormat,
+    // If this is None, transcripts are written next to their audio files.
+    output_directory: Option<PathBuf>,
+}
+
+impl BatchConfigs {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn with_export_format(mut self, export_format: TranscriptFormat) -> Self {
+        self.export_format = export_format;
+        self
+    }
+    pub(crate) fn with_output_directory(mut self, output_directory: Option<PathBuf>) -> Self {
+        self.output_directory = output_directory;
+        self
+    }
+
+    pub(crate) fn export_format(&self) -> TranscriptFormat {
+        self.export_format
+    }
+    pub(crate) fn output_directory(&self) -> Option<&Path> {
+        self.output_directory.as_deref()
+    }
+
+    // Picks a transcript path for the audio file that does not clobber an existing file:
+    // e.g. interview.wav -> interview.txt, interview_1.txt, interview_2.txt...
+    // If an output directory is passed in, it takes precedence over the configured one.
+    pub(crate) fn output_path_for(
+        &self,
+        audio_path: &Path,
+        output_directory: Option<&Path>,
+    ) -> PathBuf {
+        let extension = self.export_format.extension();
+        let directory = output_directory
+            .or(self.output_directory.as_deref())
+            .or(audio_path.parent())
+            .unwrap_or(Path::new(""));
+        let stem = audio_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "transcript".to_string());
+
+        let mut out_path = directory.join(format!("{stem}.{extension}"));
+        let mut suffix = 1usize;
+        while out_path.exists() {
+            out_path = directory.join(format!("{stem}_{suffix}.{extension}"));
+            suffix += 1;
+        }
+        out_path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn transcripts_go_next_to_the_audio_by_default() {
+        let configs = BatchConfigs::new();
+        let out_path = configs.output_path_for(Path::new("/recordings/interview.wav"), None);
+        assert_eq!(out_path, Path::new("/recordings/interview.txt"));
+    }
+
+    #[test]
+    fn an_output_directory_overrides_the_configured_one() {
+        let configs = BatchConfigs::new()
+            .with_export_format(TranscriptFormat::Srt)
+            .with_output_directory(Some(PathBuf::from("/transcripts")));
+        let audio_path = Path::new("/recordings/interview.wav");
+
+        assert_eq!(
+            configs.output_path_for(audio_path, None),
+            Path::new("/transcripts/interview.srt")
+        );
+        assert_eq!(
+            configs.output_path_for(audio_path, Some(Path::new("/outbox"))),
+            Path::new("/outbox/interview.srt")
+        );
+    }
+
+    #[test]
+    fn existing_transcripts_are_not_clobbered() {
+        let directory = TempDir::new().unwrap();
+        let configs = BatchConfigs::new();
+        let audio_path = directory.path().join("interview.wav");
+
+        let mut out_paths = vec![];
+        for _ in 0..3 {
+            let out_path = configs.output_path_for(&audio_path, None);
+            std::fs::write(&out_path, "Transcript.").unwrap();
+            out_paths.push(out_path);
+        }
+        let expected: Vec<PathBuf> = ["interview.txt", "interview_1.txt", "interview_2.txt"]
+            .into_iter()
+            .map(|name| directory.path().join(name))
+            .collect();
+        assert_eq!(out_paths, expected);
+
+        // The first free name is used, so a removed transcript's name is reused.
+        std::fs::remove_file(&expected[1]).unwrap();
+        assert_eq!(
+            configs.output_path_for(&audio_path, None),
+            directory.path().join("interview_1.txt")
+        );
+    }
+}
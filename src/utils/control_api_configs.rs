@@ -0,0 +1,47 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+pub(crate) const DEFAULT_CONTROL_API_PORT: u16 = 47_800;
+// Stay clear of the privileged ports.
+pub(crate) const MIN_CONTROL_API_PORT: u16 = 1024;
+
+// NOTE: the control API only ever binds to the loopback interface; it is not meant to be
+// reachable from other machines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct ControlApiConfigs {
+    enabled: bool,
+    port: u16,
+}
+
+impl ControlApiConfigs {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_CONTROL_API_PORT,
+        }
+    }
+
+    pub(crate) fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    pub(crate) fn with_port(mut self, port: u16) -> Self {
+        self.port = port.max(MIN_CONTROL_API_PORT);
+        self
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+    pub(crate) fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.port))
+    }
+}
+
+impl Default for ControlApiConfigs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+pub(crate) const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 2;
+pub(crate) const MAX_CONCURRENT_DOWNLOADS: usize = 8;
+pub(crate) const DEFAULT_MAX_DOWNLOAD_RETRIES: u32 = 3;
+pub(crate) const MAX_DOWNLOAD_RETRIES: u32 = 10;
+
+// Downloads past the concurrency limit wait in the queue.
+// Transient failures (dropped connections, timeouts, server errors) are retried with an
+// exponential backoff; a retry resumes the download where it left off.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct DownloadConfigs {
+    max_concurrent_downloads: usize,
+    max_retries: u32,
+    // A local directory (or catalog file) to install models from, for machines that can't
+    // download them.
+    #[serde(default)]
+    model_catalog: Option<PathBuf>,
+}
+
+impl DownloadConfigs {
+    pub(crate) fn new() -> Self {
+        Self {
+            max_concurrent_downloads: DEFAULT_MAX_CONCURRENT_DOWNLOADS,
+            max_retries: DEFAULT_MAX_DOWNLOAD_RETRIES,
+            model_catalog: None,
+        }
+    }
+
+    pub(crate) fn with_max_concurrent_downloads(mut self, max_concurrent_downloads: usize) -> Self {
+        self.max_concurrent_downloads = max_concurrent_downloads.clamp(1, MAX_CONCURRENT_DOWNLOADS);
+        self
+    }
+    pub(crate) fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries.min(MAX_DOWNLOAD_RETRIES);
+        self
+    }
+    pub(crate) fn with_model_catalog(mut self, model_catalog: Option<PathBuf>) -> Self {
+        self.model_catalog = model_catalog;
+        self
+    }
+
+    pub(crate) fn max_concurrent_downloads(&self) -> usize {
+        // NOTE: a hand-edited config could set this to 0, which would stall the queue.
+        self.max_concurrent_downloads.max(1)
+    }
+    pub(crate) fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+    pub(crate) fn model_catalog(&self) -> Option<&Path> {
+        self.model_catalog.as_deref()
+    }
+}
+
+impl Default for DownloadConfigs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -37,4 +37,12 @@ pub(crate) enum RibbleError {
     CrashHandler(#[from] crash_handler::Error),
     #[error("Conversion Error: {0}")]
     ConversionError(&'static str),
+    #[error("Json Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Opus: {0}")]
+    Opus(#[from] opus::Error),
+    #[error("Http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Input Device: {0}")]
+    InputDevice(String),
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use strum::{AsRefStr, Display, EnumIter};
+
+pub(crate) const DEFAULT_LIVE_SINK_PORT: u16 = 47_801;
+// Stay clear of the privileged ports.
+pub(crate) const MIN_LIVE_SINK_PORT: u16 = 1024;
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Display,
+    AsRefStr,
+    EnumIter,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub(crate) enum LiveSinkTarget {
+    #[default]
+    Stdout,
+    // A regular file (appended to) or a named pipe (FIFO).
+    File,
+    // Connects to a program listening on 127.0.0.1:<port>.
+    #[strum(serialize = "Local socket")]
+    Socket,
+}
+
+// Streams the real-time transcript as newline-delimited JSON while it's being transcribed.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct LiveSinkConfigs {
+    enabled: bool,
+    target: LiveSinkTarget,
+    file_path: Option<PathBuf>,
+    port: u16,
+    // Also stream the in-progress (not yet confirmed) text, which may still be revised.
+    include_partial: bool,
+}
+
+impl LiveSinkConfigs {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            target: LiveSinkTarget::default(),
+            file_path: None,
+            port: DEFAULT_LIVE_SINK_PORT,
+            include_partial: false,
+        }
+    }
+
+    pub(crate) fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    pub(crate) fn with_target(mut self, target: LiveSinkTarget) -> Self {
+        self.target = target;
+        self
+    }
+    pub(crate) fn with_file_path(mut self, file_path: Option<PathBuf>) -> Self {
+        self.file_path = file_path;
+        self
+    }
+    pub(crate) fn with_port(mut self, port: u16) -> Self {
+        self.port = port.max(MIN_LIVE_SINK_PORT);
+        self
+    }
+    pub(crate) fn with_include_partial(mut self, include_partial: bool) -> Self {
+        self.include_partial = include_partial;
+        self
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub(crate) fn target(&self) -> LiveSinkTarget {
+        self.target
+    }
+    pub(crate) fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+    pub(crate) fn include_partial(&self) -> bool {
+        self.include_partial
+    }
+    pub(crate) fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::LOCALHOST, self.port))
+    }
+}
+
+impl Default for LiveSinkConfigs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,454 @@
+// Loudness measurement (ITU-R BS.1770) and automatic level control.
+//
+// Levels are in LUFS: the K-weighted mean-square level (roughly, how loud it sounds), in dB
+// relative to full scale. Comfortable speech sits somewhere around -23 to -16 LUFS.
+
+pub(crate) const DEFAULT_TARGET_LUFS: f32 = -20.0;
+pub(crate) const MIN_TARGET_LUFS: f32 = -40.0;
+pub(crate) const MAX_TARGET_LUFS: f32 = -10.0;
+pub(crate) const DEFAULT_AGC_MAX_GAIN_DB: f32 = 24.0;
+pub(crate) const MAX_AGC_GAIN_DB: f32 = 40.0;
+pub(crate) const DEFAULT_ATTACK_MS: f32 = 20.0;
+pub(crate) const MIN_ATTACK_MS: f32 = 1.0;
+pub(crate) const MAX_ATTACK_MS: f32 = 500.0;
+pub(crate) const DEFAULT_RELEASE_MS: f32 = 1000.0;
+pub(crate) const MIN_RELEASE_MS: f32 = 50.0;
+pub(crate) const MAX_RELEASE_MS: f32 = 5000.0;
+pub(crate) const DEFAULT_GATE_LUFS: f32 = -50.0;
+pub(crate) const MIN_GATE_LUFS: f32 = -80.0;
+pub(crate) const MAX_GATE_LUFS: f32 = -20.0;
+
+// Loud passages are turned down by at most this much.
+const MAX_CUT_DB: f64 = 20.0;
+// The AGC measures the level over roughly this window.
+const DETECTOR_MS: f64 = 100.0;
+// The limiter keeps peaks just under full scale.
+const LIMITER_CEILING_DB: f32 = -1.0;
+const LIMITER_RELEASE_MS: f64 = 50.0;
+
+// BS.1770 gating: 400ms blocks, overlapping by 75%.
+const GATING_STEP_SECONDS: f64 = 0.1;
+const STEPS_PER_BLOCK: usize = 4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct AgcConfigs {
+    target_lufs: f32,
+    max_gain_db: f32,
+    attack_ms: f32,
+    release_ms: f32,
+    // Below this level, the gain is held instead of boosting the silence/background noise.
+    gate_lufs: f32,
+}
+
+impl AgcConfigs {
+    pub(crate) fn new() -> Self {
+        Self {
+            target_lufs: DEFAULT_TARGET_LUFS,
+            max_gain_db: DEFAULT_AGC_MAX_GAIN_DB,
+            attack_ms: DEFAULT_ATTACK_MS,
+            release_ms: DEFAULT_RELEASE_MS,
+            gate_lufs: DEFAULT_GATE_LUFS,
+        }
+    }
+
+    pub(crate) fn with_target_lufs(mut self, target_lufs: f32) -> Self {
+        self.target_lufs = target_lufs.clamp(MIN_TARGET_LUFS, MAX_TARGET_LUFS);
+        self
+    }
+    pub(crate) fn with_max_gain_db(mut self, max_gain_db: f32) -> Self {
+        self.max_gain_db = max_gain_db.clamp(0.0, MAX_AGC_GAIN_DB);
+        self
+    }
+    pub(crate) fn with_attack_ms(mut self, attack_ms: f32) -> Self {
+        self.attack_ms = attack_ms.clamp(MIN_ATTACK_MS, MAX_ATTACK_MS);
+        self
+    }
+    pub(crate) fn with_release_ms(mut self, release_ms: f32) -> Self {
+        self.release_ms = release_ms.clamp(MIN_RELEASE_MS, MAX_RELEASE_MS);
+        self
+    }
+    pub(crate) fn with_gate_lufs(mut self, gate_lufs: f32) -> Self {
+        self.gate_lufs = gate_lufs.clamp(MIN_GATE_LUFS, MAX_GATE_LUFS);
+        self
+    }
+
+    pub(crate) fn target_lufs(&self) -> f32 {
+        self.target_lufs
+    }
+    pub(crate) fn max_gain_db(&self) -> f32 {
+        self.max_gain_db
+    }
+    pub(crate) fn attack_ms(&self) -> f32 {
+        self.attack_ms
+    }
+    pub(crate) fn release_ms(&self) -> f32 {
+        self.release_ms
+    }
+    pub(crate) fn gate_lufs(&self) -> f32 {
+        self.gate_lufs
+    }
+
+    pub(crate) fn build_agc(&self, sample_rate: f32) -> AutomaticGainControl {
+        AutomaticGainControl::new(self, sample_rate)
+    }
+}
+
+impl Default for AgcConfigs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Rides the gain toward the target level: attack is how quickly it turns loud audio down,
+// release is how quickly it brings quiet audio back up.
+pub(crate) struct AutomaticGainControl {
+    k_weighting: KWeighting,
+    // The smoothed, K-weighted power.
+    power: f64,
+    detector: f64,
+    attack: f64,
+    release: f64,
+    target_lufs: f64,
+    max_gain_db: f64,
+    gate_lufs: f64,
+    gain_db: f64,
+    limiter: PeakLimiter,
+}
+
+impl AutomaticGainControl {
+    pub(crate) fn new(configs: &AgcConfigs, sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+        Self {
+            k_weighting: KWeighting::new(sample_rate),
+            power: 0.0,
+            detector: smoothing_coefficient(DETECTOR_MS, sample_rate),
+            attack: smoothing_coefficient(configs.attack_ms as f64, sample_rate),
+            release: smoothing_coefficient(configs.release_ms as f64, sample_rate),
+            target_lufs: configs.target_lufs as f64,
+            max_gain_db: configs.max_gain_db as f64,
+            gate_lufs: configs.gate_lufs as f64,
+            gain_db: 0.0,
+            limiter: PeakLimiter::new(sample_rate),
+        }
+    }
+
+    // Mono only; the realtime audio is always mono.
+    pub(crate) fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            let weighted = self.k_weighting.process(*sample as f64);
+            self.power += self.detector * (weighted * weighted - self.power);
+
+            let level = loudness(self.power);
+            // NOTE: pauses, breaths and room noise sit under the gate; holding the gain keeps the
+            // AGC from pumping the noise floor up between sentences.
+            if level > self.gate_lufs {
+                let wanted_db = (self.target_lufs - level).clamp(-MAX_CUT_DB, self.max_gain_db);
+                let coefficient = if wanted_db < self.gain_db {
+                    self.attack
+                } else {
+                    self.release
+                };
+                self.gain_db += coefficient * (wanted_db - self.gain_db);
+            }
+
+            *sample = self
+                .limiter
+                .process(*sample * db_to_amplitude(self.gain_db as f32));
+        }
+    }
+}
+
+// Keeps peaks under the ceiling. It reacts instantly, so nothing gets through, and recovers
+// over LIMITER_RELEASE_MS.
+pub(crate) struct PeakLimiter {
+    ceiling: f32,
+    release: f32,
+    gain: f32,
+}
+
+impl PeakLimiter {
+    pub(crate) fn new(sample_rate: f64) -> Self {
+        Self {
+            ceiling: db_to_amplitude(LIMITER_CEILING_DB),
+            release: smoothing_coefficient(LIMITER_RELEASE_MS, sample_rate) as f32,
+            gain: 1.0,
+        }
+    }
+
+    pub(crate) fn process(&mut self, sample: f32) -> f32 {
+        let peak = sample.abs();
+        let needed = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+
+        if needed < self.gain {
+            self.gain = needed;
+        } else {
+            self.gain += self.release * (needed - self.gain);
+        }
+        (sample * self.gain).clamp(-1.0, 1.0)
+    }
+}
+
+// The gated, integrated loudness of a (mono) signal, per BS.1770.
+// Returns None if there's nothing above the absolute gate (i.e. silence).
+pub(crate) fn integrated_loudness(samples: &[f32], sample_rate: f32) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let step_len = ((GATING_STEP_SECONDS * sample_rate as f64) as usize).max(1);
+    let mut k_weighting = KWeighting::new(sample_rate as f64);
+
+    // The K-weighted energy (and length) of each 100ms step; blocks are made up of consecutive
+    // steps.
+    // NOTE: the last step is usually shorter than step_len, so each block is averaged over its
+    // actual length; otherwise the end of the signal reads quiet.
+    let step_energies: Vec<(f64, usize)> = samples
+        .chunks(step_len)
+        .map(|step| {
+            let energy = step
+                .iter()
+                .map(|sample| k_weighting.process(*sample as f64).powi(2))
+                .sum();
+            (energy, step.len())
+        })
+        .collect();
+
+    let block_powers: Vec<f64> = if step_energies.len() < STEPS_PER_BLOCK {
+        // Too short for a full block; measure the whole thing.
+        let energy: f64 = step_energies.iter().map(|(energy, _)| energy).sum();
+        vec![energy / samples.len() as f64]
+    } else {
+        step_energies
+            .windows(STEPS_PER_BLOCK)
+            .map(|block| {
+                let (energy, len) = block.iter().fold((0.0, 0), |(energy, len), step| {
+                    (energy + step.0, len + step.1)
+                });
+                energy / len as f64
+            })
+            .collect()
+    };
+
+    let above_absolute: Vec<f64> = block_powers
+        .into_iter()
+        .filter(|power| loudness(*power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if above_absolute.is_empty() {
+        return None;
+    }
+
+    let relative_gate = loudness(mean(&above_absolute)) + RELATIVE_GATE_LU;
+    // NOTE: the relative gate is under the mean, so at least the loudest block gets through.
+    let gated: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|power| loudness(*power) > relative_gate)
+        .collect();
+    Some(loudness(mean(&gated)))
+}
+
+// Two passes: measure the integrated loudness, then apply the gain that brings it to the target.
+// The limiter catches any peaks the gain pushes over.
+pub(crate) fn normalize_loudness(samples: &mut [f32], sample_rate: f32, configs: &AgcConfigs) {
+    let Some(measured) = integrated_loudness(samples, sample_rate) else {
+        return;
+    };
+
+    let gain_db =
+        (configs.target_lufs() as f64 - measured).clamp(-MAX_CUT_DB, configs.max_gain_db() as f64);
+    let gain = db_to_amplitude(gain_db as f32);
+    let mut limiter = PeakLimiter::new(sample_rate.into());
+    samples
+        .iter_mut()
+        .for_each(|sample| *sample = limiter.process(*sample * gain));
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+// One-pole smoothing coefficient for a time constant in milliseconds.
+fn smoothing_coefficient(time_ms: f64, sample_rate: f64) -> f64 {
+    1.0 - (-1.0 / (time_ms * 0.001 * sample_rate)).exp()
+}
+
+// The BS.1770 K-weighting: a high shelf (head effects) followed by a high-pass (RLB weighting).
+// The filters are specified at 48kHz; these are the same designs, re-derived for the sample rate.
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: f64) -> Self {
+        Self {
+            shelf: Self::shelf(sample_rate),
+            high_pass: Self::high_pass(sample_rate),
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+
+    fn shelf(sample_rate: f64) -> Biquad {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+
+    fn high_pass(sample_rate: f64) -> Biquad {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    }
+}
+
+// Transposed direct form II.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        let out = self.b[0] * sample + self.z1;
+        self.z1 = self.b[1] * sample - self.a[0] * out + self.z2;
+        self.z2 = self.b[2] * sample - self.a[1] * out;
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1kHz sine with a peak at the given level.
+    fn sine(level_dbfs: f32, seconds: f32, sample_rate: f32) -> Vec<f32> {
+        let amplitude = db_to_amplitude(level_dbfs);
+        (0..(seconds * sample_rate) as usize)
+            .map(|n| {
+                let t = n as f32 / sample_rate;
+                amplitude * (2.0 * std::f32::consts::PI * 1000.0 * t).sin()
+            })
+            .collect()
+    }
+
+    // BS.1770's reference: a 0dBFS, 1kHz sine reads -3.01 LUFS, so a -20dBFS one reads -23.01.
+    const SINE_LUFS: f64 = -23.01;
+
+    #[test]
+    fn a_1khz_sine_measures_to_the_reference_level() {
+        for sample_rate in [48000.0, 16000.0] {
+            let measured = integrated_loudness(&sine(-20.0, 3.0, sample_rate), sample_rate)
+                .expect("The sine is above the gate.");
+            assert!(
+                (measured - SINE_LUFS).abs() < 0.1,
+                "Measured {measured} LUFS at {sample_rate}Hz."
+            );
+        }
+    }
+
+    #[test]
+    fn a_partial_last_step_does_not_bias_the_level_low() {
+        // 4.5 steps: the last block ends on half a step.
+        let sample_rate = 48000.0;
+        let measured = integrated_loudness(&sine(-20.0, 0.45, sample_rate), sample_rate)
+            .expect("The sine is above the gate.");
+        assert!(
+            (measured - SINE_LUFS).abs() < 0.1,
+            "Measured {measured} LUFS."
+        );
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        assert_eq!(integrated_loudness(&[], 16000.0), None);
+        assert_eq!(integrated_loudness(&[0.0; 16000 * 2], 16000.0), None);
+        // Under the absolute gate.
+        assert_eq!(
+            integrated_loudness(&sine(-80.0, 2.0, 16000.0), 16000.0),
+            None
+        );
+    }
+
+    #[test]
+    fn the_limiter_never_exceeds_the_ceiling() {
+        let sample_rate = 16000.0;
+        let ceiling = db_to_amplitude(LIMITER_CEILING_DB);
+        let mut limiter = PeakLimiter::new(sample_rate.into());
+
+        // Quiet, then far too loud, then quiet again while the limiter recovers.
+        let loud = sine(12.0, 0.5, sample_rate);
+        let quiet = sine(-6.0, 0.5, sample_rate);
+        let signal = quiet.iter().chain(&loud).chain(&quiet).chain(&loud);
+        for sample in signal {
+            let limited = limiter.process(*sample);
+            assert!(
+                limited.abs() <= ceiling + 1e-6,
+                "{limited} is over the ceiling."
+            );
+        }
+    }
+
+    #[test]
+    fn normalization_lands_on_the_target() {
+        let sample_rate = 16000.0;
+        for (level, target) in [(-40.0, -20.0), (-6.0, -23.0), (-30.0, -16.0)] {
+            let configs = AgcConfigs::new().with_target_lufs(target);
+            let mut samples = sine(level, 3.0, sample_rate);
+            normalize_loudness(&mut samples, sample_rate, &configs);
+
+            let measured = integrated_loudness(&samples, sample_rate).unwrap();
+            assert!(
+                (measured - target as f64).abs() <= 1.0,
+                "Normalized a {level}dBFS sine to {measured} LUFS, not {target}."
+            );
+        }
+    }
+}
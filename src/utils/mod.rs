@@ -2,11 +2,21 @@ pub(crate) mod dc_block;
 pub(crate) mod errors;
 
 pub(crate) mod audio_gain;
+pub(crate) mod batch_configs;
+pub(crate) mod control_api_configs;
+pub(crate) mod download_configs;
+pub(crate) mod live_sink_configs;
+pub(crate) mod loudness;
 pub(crate) mod crash_handler;
 pub(crate) mod migration;
+pub(crate) mod noise_suppression;
 pub(crate) mod pcm_f32;
 pub(crate) mod preferences;
 pub(crate) mod realtime_settings;
 pub(crate) mod recorder_configs;
+pub(crate) mod recording_library_configs;
+pub(crate) mod resampler;
+pub(crate) mod transcript_formats;
 pub(crate) mod vad_configs;
+pub(crate) mod watch_folder_configs;
 pub(crate) mod buffering_strategy;
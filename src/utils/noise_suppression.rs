@@ -0,0 +1,483 @@
+use realfft::num_complex::Complex;
+use realfft::{ComplexToReal, FftError, RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use strum::{AsRefStr, Display, EnumIter, EnumString, IntoStaticStr};
+
+// Spectral subtraction: the noise floor is tracked per frequency band while listening, and each
+// band is turned down by how much of it is (estimated to be) noise.
+// This is aimed at steady background noise (fans, air conditioning, hum); it won't do much for
+// chatter or other speech, which is best left to the VAD.
+#[derive(Default, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct NoiseSuppressionConfigs {
+    strength: NoiseSuppressionStrength,
+    use_offline: bool,
+    use_recording: bool,
+}
+
+impl NoiseSuppressionConfigs {
+    pub(crate) fn with_strength(mut self, strength: NoiseSuppressionStrength) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    pub(crate) fn with_use_offline(mut self, use_offline: bool) -> Self {
+        self.use_offline = use_offline;
+        self
+    }
+
+    pub(crate) fn with_use_recording(mut self, use_recording: bool) -> Self {
+        self.use_recording = use_recording;
+        self
+    }
+
+    pub(crate) fn strength(&self) -> NoiseSuppressionStrength {
+        self.strength
+    }
+
+    pub(crate) fn use_offline(&self) -> bool {
+        self.use_offline
+    }
+
+    // Whether the saved recording is denoised too; otherwise it keeps the audio as captured.
+    pub(crate) fn use_recording(&self) -> bool {
+        self.use_recording
+    }
+
+    pub(crate) fn no_suppression(&self) -> bool {
+        self.strength == NoiseSuppressionStrength::Off
+    }
+
+    pub(crate) fn build_noise_suppressor(
+        &self,
+        sample_rate: usize,
+        channels: usize,
+    ) -> NoiseSuppressor {
+        NoiseSuppressor::new(self.strength, sample_rate, channels)
+    }
+}
+
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    EnumString,
+    EnumIter,
+    IntoStaticStr,
+    AsRefStr,
+    Display,
+)]
+pub(crate) enum NoiseSuppressionStrength {
+    #[default]
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl NoiseSuppressionStrength {
+    pub(crate) fn tooltip(&self) -> &'static str {
+        match self {
+            NoiseSuppressionStrength::Off => "Leave the audio as-is.",
+            NoiseSuppressionStrength::Low => {
+                "Lightly reduce background noise; keeps speech natural."
+            }
+            NoiseSuppressionStrength::Medium => "Reduce steady background noise, e.g. fans or hum.",
+            NoiseSuppressionStrength::High => {
+                "Aggressively reduce background noise.\nMay make speech sound thin or watery."
+            }
+        }
+    }
+
+    // (Oversubtraction, spectral floor).
+    // Oversubtraction: how many times the noise estimate is taken out of each band.
+    // Floor: the most a band is turned down (as a gain); leaving some noise in sounds far more
+    // natural than gating it, and whisper copes better with it.
+    fn parameters(&self) -> (f32, f32) {
+        match self {
+            NoiseSuppressionStrength::Off => (0.0, 1.0),
+            NoiseSuppressionStrength::Low => (1.0, 0.35),
+            NoiseSuppressionStrength::Medium => (1.5, 0.2),
+            NoiseSuppressionStrength::High => (2.0, 0.1),
+        }
+    }
+}
+
+// A streaming (frame-by-frame) denoiser for interleaved audio.
+// NOTE: output lags input by latency() frames, and comes out a hop at a time; call flush() at the
+// end of a stream to get the remainder.
+pub(crate) struct NoiseSuppressor {
+    oversubtraction: f32,
+    floor: f32,
+    frame_size: usize,
+    hop_size: usize,
+    // sqrt-Hann: applied on the way in and on the way out, which overlap-adds back to unity at 50%
+    // overlap.
+    window: Arc<[f32]>,
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    channels: Vec<ChannelSuppressor>,
+    // Per channel, in frames.
+    num_in: usize,
+    num_out: usize,
+}
+
+impl NoiseSuppressor {
+    // The analysis frame length, rounded up to the next power of two for the FFT.
+    const FRAME_SECONDS: f32 = 0.032;
+    const MIN_FRAME_SIZE: usize = 64;
+    // How quickly the band power follows the signal; the noise floor is tracked from this.
+    const POWER_SMOOTHING: f32 = 0.7;
+    // The noise floor follows quieter bands immediately but only creeps up (~3dB/s at a 16ms hop),
+    // so that speech isn't mistaken for noise.
+    const NOISE_RISE: f32 = 1.011;
+    // Gains can drop at most this much per hop; sudden drops in single bands are what cause the
+    // "musical noise" warble.
+    const GAIN_RELEASE: f32 = 0.6;
+    const EPSILON: f32 = 1e-10;
+
+    pub(crate) fn new(
+        strength: NoiseSuppressionStrength,
+        sample_rate: usize,
+        channels: usize,
+    ) -> Self {
+        let (oversubtraction, floor) = strength.parameters();
+        let frame_size = ((sample_rate as f32 * Self::FRAME_SECONDS) as usize)
+            .next_power_of_two()
+            .max(Self::MIN_FRAME_SIZE);
+        let hop_size = frame_size / 2;
+        let window = (0..frame_size)
+            .map(|n| (PI * n as f32 / frame_size as f32).sin())
+            .collect();
+
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(frame_size);
+        let inverse = planner.plan_fft_inverse(frame_size);
+        let channels = (0..channels.max(1))
+            .map(|_| ChannelSuppressor::new(frame_size, forward.as_ref(), inverse.as_ref()))
+            .collect();
+
+        Self {
+            oversubtraction,
+            floor,
+            frame_size,
+            hop_size,
+            window,
+            forward,
+            inverse,
+            channels,
+            num_in: 0,
+            num_out: 0,
+        }
+    }
+
+    // In frames (i.e. per channel).
+    pub(crate) fn latency(&self) -> usize {
+        self.frame_size - self.hop_size
+    }
+
+    // Interleaved in, interleaved out. The output can be shorter (or longer) than the input, by up
+    // to a hop; it may be empty.
+    pub(crate) fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let num_channels = self.channels.len();
+        self.num_in += samples.len() / num_channels;
+        for (i, channel) in self.channels.iter_mut().enumerate() {
+            channel
+                .pending
+                .extend(samples.iter().skip(i).step_by(num_channels));
+        }
+
+        let mut num_frames = usize::MAX;
+        for channel in self.channels.iter_mut() {
+            while channel.pending.len() >= self.hop_size {
+                channel.process_hop(
+                    self.hop_size,
+                    &self.window,
+                    self.forward.as_ref(),
+                    self.inverse.as_ref(),
+                    self.oversubtraction,
+                    self.floor,
+                );
+            }
+            num_frames = num_frames.min(channel.ready.len());
+        }
+
+        self.take_ready(num_frames)
+    }
+
+    // Pushes the audio still in the pipeline out, so that (counting everything returned from
+    // process) exactly latency() frames more have come out than went in.
+    pub(crate) fn flush(&mut self) -> Vec<f32> {
+        let target = self.num_in + self.latency();
+        let num_silent = target.saturating_sub(self.num_out) + self.hop_size;
+        let num_in = self.num_in;
+        let mut out = self.process(&vec![0.0; num_silent * self.channels.len()]);
+        self.num_in = num_in;
+
+        let overshoot = self.num_out.saturating_sub(target);
+        out.truncate(out.len() - overshoot * self.channels.len());
+        self.num_out = target;
+        out
+    }
+
+    // Denoises a complete signal (e.g. a file), compensating for the latency: the output lines up
+    // with (and is the same length as) the input.
+    pub(crate) fn process_all(mut self, samples: &[f32]) -> Vec<f32> {
+        let mut out = self.process(samples);
+        out.extend(self.flush());
+        out.drain(..self.latency() * self.channels.len());
+        out
+    }
+
+    fn take_ready(&mut self, num_frames: usize) -> Vec<f32> {
+        let mut out = Vec::with_capacity(num_frames * self.channels.len());
+        for frame in 0..num_frames {
+            // NOTE: overlap-add can overshoot slightly on transients; keep the output in range.
+            out.extend(
+                self.channels
+                    .iter()
+                    .map(|channel| channel.ready[frame].clamp(-1.0, 1.0)),
+            );
+        }
+        for channel in self.channels.iter_mut() {
+            channel.ready.drain(..num_frames);
+        }
+        self.num_out += num_frames;
+        out
+    }
+}
+
+struct ChannelSuppressor {
+    // Input not yet processed.
+    pending: Vec<f32>,
+    // The last frame_size input samples.
+    frame: Vec<f32>,
+    // The tail of the last synthesized frame, waiting to be overlap-added.
+    overlap: Vec<f32>,
+    // Output, waiting for the other channels.
+    ready: Vec<f32>,
+    // Per band:
+    smoothed_power: Vec<f32>,
+    noise_power: Vec<f32>,
+    gains: Vec<f32>,
+    // Whether the band powers have been seeded from a first frame.
+    primed: bool,
+    // FFT buffers
+    fft_input: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    fft_output: Vec<f32>,
+    forward_scratch: Vec<Complex<f32>>,
+    inverse_scratch: Vec<Complex<f32>>,
+}
+
+impl ChannelSuppressor {
+    fn new(
+        frame_size: usize,
+        forward: &dyn RealToComplex<f32>,
+        inverse: &dyn ComplexToReal<f32>,
+    ) -> Self {
+        let spectrum = forward.make_output_vec();
+        let num_bands = spectrum.len();
+        Self {
+            pending: vec![],
+            frame: vec![0.0; frame_size],
+            overlap: vec![0.0; frame_size / 2],
+            ready: vec![],
+            smoothed_power: vec![0.0; num_bands],
+            // Nothing is known about the noise yet; the first frame brings this down.
+            noise_power: vec![f32::MAX; num_bands],
+            gains: vec![1.0; num_bands],
+            primed: false,
+            fft_input: forward.make_input_vec(),
+            spectrum,
+            fft_output: inverse.make_output_vec(),
+            forward_scratch: forward.make_scratch_vec(),
+            inverse_scratch: inverse.make_scratch_vec(),
+        }
+    }
+
+    fn process_hop(
+        &mut self,
+        hop_size: usize,
+        window: &[f32],
+        forward: &dyn RealToComplex<f32>,
+        inverse: &dyn ComplexToReal<f32>,
+        oversubtraction: f32,
+        floor: f32,
+    ) {
+        let frame_size = self.frame.len();
+        self.frame.copy_within(hop_size.., 0);
+        self.frame[frame_size - hop_size..].copy_from_slice(&self.pending[..hop_size]);
+        self.pending.drain(..hop_size);
+
+        if let Err(e) = self.denoise_frame(window, forward, inverse, oversubtraction, floor) {
+            // This can only happen with mismatched buffer sizes; pass the audio through.
+            log::warn!("Noise suppression failed, passing audio through: {e}");
+            for ((output, sample), weight) in
+                self.fft_output.iter_mut().zip(&self.frame).zip(window)
+            {
+                *output = sample * weight;
+            }
+        }
+
+        for (sample, weight) in self.fft_output.iter_mut().zip(window) {
+            *sample *= weight;
+        }
+
+        let (head, tail) = self.fft_output.split_at(hop_size);
+        self.ready.extend(
+            head.iter()
+                .zip(&self.overlap)
+                .map(|(sample, overlap)| sample + overlap),
+        );
+        self.overlap.copy_from_slice(&tail[..hop_size]);
+    }
+
+    // Windowed frame -> spectrum -> gains -> fft_output (not yet windowed for synthesis).
+    fn denoise_frame(
+        &mut self,
+        window: &[f32],
+        forward: &dyn RealToComplex<f32>,
+        inverse: &dyn ComplexToReal<f32>,
+        oversubtraction: f32,
+        floor: f32,
+    ) -> Result<(), FftError> {
+        for ((input, sample), weight) in self.fft_input.iter_mut().zip(&self.frame).zip(window) {
+            *input = sample * weight;
+        }
+        forward.process_with_scratch(
+            &mut self.fft_input,
+            &mut self.spectrum,
+            &mut self.forward_scratch,
+        )?;
+
+        self.apply_gains(oversubtraction, floor);
+
+        // The imaginary parts of the DC and Nyquist bands must be zero for the inverse.
+        if let Some(dc) = self.spectrum.first_mut() {
+            dc.im = 0.0;
+        }
+        if let Some(nyquist) = self.spectrum.last_mut() {
+            nyquist.im = 0.0;
+        }
+        inverse.process_with_scratch(
+            &mut self.spectrum,
+            &mut self.fft_output,
+            &mut self.inverse_scratch,
+        )?;
+
+        // The inverse FFT is unnormalized.
+        let scale = 1.0 / self.frame.len() as f32;
+        self.fft_output
+            .iter_mut()
+            .for_each(|sample| *sample *= scale);
+        Ok(())
+    }
+
+    fn apply_gains(&mut self, oversubtraction: f32, floor: f32) {
+        if !self.primed {
+            self.smoothed_power
+                .iter_mut()
+                .zip(&self.spectrum)
+                .for_each(|(smoothed, band)| *smoothed = band.norm_sqr());
+            self.primed = true;
+        }
+
+        let bands = self
+            .spectrum
+            .iter_mut()
+            .zip(self.smoothed_power.iter_mut())
+            .zip(self.noise_power.iter_mut())
+            .zip(self.gains.iter_mut());
+
+        for (((band, smoothed), noise), gain) in bands {
+            let power = band.norm_sqr();
+            *smoothed = NoiseSuppressor::POWER_SMOOTHING * *smoothed
+                + (1.0 - NoiseSuppressor::POWER_SMOOTHING) * power;
+
+            *noise = if *smoothed < *noise {
+                *smoothed
+            } else {
+                (*noise * NoiseSuppressor::NOISE_RISE).min(*smoothed)
+            }
+            .max(NoiseSuppressor::EPSILON);
+
+            let subtracted =
+                1.0 - oversubtraction * (*noise / power.max(NoiseSuppressor::EPSILON)).sqrt();
+            *gain = subtracted
+                .max(floor)
+                .max(*gain * NoiseSuppressor::GAIN_RELEASE)
+                .min(1.0);
+            *band *= *gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 16000;
+
+    // Deterministic white noise in [-amplitude, amplitude).
+    fn white_noise(num_samples: usize, amplitude: f32) -> Vec<f32> {
+        let mut state: u32 = 12345;
+        (0..num_samples)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345) & 0x7FFF_FFFF;
+                amplitude * (state as f32 / (1u32 << 31) as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn stationary_noise_is_attenuated() {
+        let noise = white_noise(SAMPLE_RATE * 3, 0.1);
+        let suppressor = NoiseSuppressor::new(NoiseSuppressionStrength::Medium, SAMPLE_RATE, 1);
+        let denoised = suppressor.process_all(&noise);
+        assert_eq!(denoised.len(), noise.len());
+
+        // Give the noise floor the first couple of seconds to settle.
+        let last_second = noise.len() - SAMPLE_RATE..;
+        let ratio = rms(&denoised[last_second.clone()]) / rms(&noise[last_second]);
+        assert!(
+            ratio < 0.5,
+            "Noise was only attenuated to {ratio} of its level."
+        );
+    }
+
+    #[test]
+    fn silence_in_gives_silence_out() {
+        for strength in [
+            NoiseSuppressionStrength::Low,
+            NoiseSuppressionStrength::Medium,
+            NoiseSuppressionStrength::High,
+        ] {
+            let silence = vec![0.0; SAMPLE_RATE];
+            let denoised = NoiseSuppressor::new(strength, SAMPLE_RATE, 2).process_all(&silence);
+            assert_eq!(denoised.len(), silence.len());
+            assert!(denoised.iter().all(|sample| *sample == 0.0), "{strength}");
+        }
+    }
+
+    #[test]
+    fn streaming_output_is_latency_longer_than_the_input() {
+        let noise = white_noise(SAMPLE_RATE + 123, 0.1);
+        let mut suppressor = NoiseSuppressor::new(NoiseSuppressionStrength::High, SAMPLE_RATE, 1);
+        let mut out: Vec<f32> = noise
+            .chunks(1000)
+            .flat_map(|chunk| suppressor.process(chunk))
+            .collect();
+        out.extend(suppressor.flush());
+        assert_eq!(out.len(), noise.len() + suppressor.latency());
+    }
+}
@@ -2,8 +2,13 @@ use crate::utils::errors::RibbleError;
 use atomic_enum::atomic_enum;
 use ribble_whisper::audio::audio_backend::CaptureSpec;
 use ribble_whisper::audio::microphone::MicCapture;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use strum::{AsRefStr, Display, EnumIter, EnumString, IntoStaticStr};
 
+// Device names are never empty, so this can't collide with a real device.
+const DEFAULT_INPUT_DEVICE_KEY: &str = "";
+
 // NOTE: the From<_> implementations may not be the most logically sound.
 // However, to limit the granularity of settings and to reduce the amount of
 // typing (& excessive Traits that achieve the same thing), these members
@@ -28,6 +33,10 @@ pub(crate) enum RibbleExportFormat {
     // It's also much smaller and people don't really need floating point.
     #[default]
     I16,
+    // 24-bit, lossless.
+    Flac,
+    // Lossy, in an Ogg container. See: RibbleOpusBitrate.
+    Opus,
 }
 
 impl RibbleExportFormat {
@@ -38,13 +47,28 @@ impl RibbleExportFormat {
             }
 
             RibbleExportFormat::I16 => "16-bit signed integer format. Audio CD quality.",
+            RibbleExportFormat::Flac => {
+                "Lossless compression (24-bit). More range than I16, about half the size of F32."
+            }
+            RibbleExportFormat::Opus => {
+                "Lossy compression. By far the smallest files; well suited to long recordings of speech."
+            }
         }
     }
 
     pub(crate) fn bits_per_sample(&self) -> u16 {
         match self {
             RibbleExportFormat::F32 => 32,
-            RibbleExportFormat::I16 => 16,
+            RibbleExportFormat::I16 | RibbleExportFormat::Opus => 16,
+            RibbleExportFormat::Flac => 24,
+        }
+    }
+
+    pub(crate) fn file_extension(&self) -> &'static str {
+        match self {
+            RibbleExportFormat::F32 | RibbleExportFormat::I16 => "wav",
+            RibbleExportFormat::Flac => "flac",
+            RibbleExportFormat::Opus => "opus",
         }
     }
 }
@@ -58,11 +82,58 @@ impl From<hound::SampleFormat> for RibbleExportFormat {
     }
 }
 
+// NOTE: FLAC is encoded from 24-bit samples; Opus encodes the floating point samples directly.
 impl From<RibbleExportFormat> for hound::SampleFormat {
     fn from(data: RibbleExportFormat) -> Self {
         match data {
             RibbleExportFormat::F32 => hound::SampleFormat::Float,
-            RibbleExportFormat::I16 => hound::SampleFormat::Int,
+            RibbleExportFormat::I16 | RibbleExportFormat::Flac | RibbleExportFormat::Opus => {
+                hound::SampleFormat::Int
+            }
+        }
+    }
+}
+
+#[derive(
+    Default,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    EnumString,
+    Display,
+    EnumIter,
+    AsRefStr,
+    IntoStaticStr,
+)]
+#[atomic_enum]
+pub(crate) enum RibbleOpusBitrate {
+    #[strum(serialize = "24 kbps")]
+    Low,
+    #[default]
+    #[strum(serialize = "48 kbps")]
+    Medium,
+    #[strum(serialize = "96 kbps")]
+    High,
+    #[strum(serialize = "128 kbps")]
+    Highest,
+}
+
+impl RibbleOpusBitrate {
+    pub(crate) fn tooltip(&self) -> &str {
+        match self {
+            RibbleOpusBitrate::Low => "Smallest files. Fine for speech.",
+            RibbleOpusBitrate::Medium => "Good quality speech.",
+            RibbleOpusBitrate::High => "Good quality music.",
+            RibbleOpusBitrate::Highest => "Near-transparent quality.",
+        }
+    }
+
+    pub(crate) fn bits_per_second(&self) -> i32 {
+        match self {
+            RibbleOpusBitrate::Low => 24_000,
+            RibbleOpusBitrate::Medium => 48_000,
+            RibbleOpusBitrate::High => 96_000,
+            RibbleOpusBitrate::Highest => 128_000,
         }
     }
 }
@@ -220,11 +291,27 @@ impl From<RibblePeriod> for Option<usize> {
     }
 }
 
-#[derive(Default, Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+// The capture settings of an input device that isn't currently selected.
+#[derive(Default, Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct DeviceCaptureSettings {
+    sample_rate: RibbleSampleRate,
+    channel_configs: RibbleChannels,
+    period: RibblePeriod,
+}
+
+#[derive(Default, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct RibbleRecordingConfigs {
     sample_rate: RibbleSampleRate,
     channel_configs: RibbleChannels,
     period: RibblePeriod,
+    // The capture device, by name; None is the system default.
+    // NOTE: realtime transcription also captures from this device, but always with whisper's
+    // sample rate and channels.
+    #[serde(default)]
+    input_device: Option<Arc<str>>,
+    // The settings for the other devices, so that switching back to a device restores them.
+    #[serde(default)]
+    device_settings: BTreeMap<String, DeviceCaptureSettings>,
 }
 
 impl RibbleRecordingConfigs {
@@ -241,6 +328,7 @@ impl RibbleRecordingConfigs {
             sample_rate,
             channel_configs,
             period,
+            ..Default::default()
         }
     }
 
@@ -256,6 +344,35 @@ impl RibbleRecordingConfigs {
         self.period = period;
         self
     }
+    // Stashes the current device's settings and swaps in the new device's.
+    pub(crate) fn with_input_device(mut self, input_device: Option<Arc<str>>) -> Self {
+        if self.input_device == input_device {
+            return self;
+        }
+        let current_settings = DeviceCaptureSettings {
+            sample_rate: self.sample_rate,
+            channel_configs: self.channel_configs,
+            period: self.period,
+        };
+        let current_key = device_key(self.input_device.as_deref()).to_string();
+        self.device_settings.insert(current_key, current_settings);
+
+        let new_settings = self
+            .device_settings
+            .remove(device_key(input_device.as_deref()))
+            .unwrap_or_default();
+        self.sample_rate = new_settings.sample_rate;
+        self.channel_configs = new_settings.channel_configs;
+        self.period = new_settings.period;
+        self.input_device = input_device;
+        self
+    }
+    // Resets the current device's settings; the device (and the other devices' settings) are kept.
+    pub(crate) fn with_default_capture_settings(self) -> Self {
+        self.with_sample_rate(Default::default())
+            .with_num_channels(Default::default())
+            .with_period(Default::default())
+    }
 
     pub(crate) fn sample_rate(&self) -> RibbleSampleRate {
         self.sample_rate
@@ -266,6 +383,9 @@ impl RibbleRecordingConfigs {
     pub(crate) fn period(&self) -> RibblePeriod {
         self.period
     }
+    pub(crate) fn input_device(&self) -> Option<&Arc<str>> {
+        self.input_device.as_ref()
+    }
 
     pub(crate) fn into_wav_spec(
         self,
@@ -293,6 +413,10 @@ impl RibbleRecordingConfigs {
     }
 }
 
+fn device_key(input_device: Option<&str>) -> &str {
+    input_device.unwrap_or(DEFAULT_INPUT_DEVICE_KEY)
+}
+
 impl From<CaptureSpec> for RibbleRecordingConfigs {
     fn from(value: CaptureSpec) -> Self {
         let sample_rate = value.sample_rate().into();
@@ -313,3 +437,152 @@ impl From<RibbleRecordingConfigs> for CaptureSpec {
             .with_period(value.period.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configs_for(
+        sample_rate: RibbleSampleRate,
+        channels: RibbleChannels,
+        period: RibblePeriod,
+    ) -> RibbleRecordingConfigs {
+        RibbleRecordingConfigs::new()
+            .with_sample_rate(sample_rate)
+            .with_num_channels(channels)
+            .with_period(period)
+    }
+
+    fn capture_settings(configs: &RibbleRecordingConfigs) -> DeviceCaptureSettings {
+        DeviceCaptureSettings {
+            sample_rate: configs.sample_rate(),
+            channel_configs: configs.num_channels(),
+            period: configs.period(),
+        }
+    }
+
+    #[test]
+    fn switching_devices_restores_each_devices_settings() {
+        let usb: Arc<str> = Arc::from("USB Microphone");
+        let configs = configs_for(
+            RibbleSampleRate::High,
+            RibbleChannels::Stereo,
+            RibblePeriod::Large,
+        )
+        .with_input_device(Some(Arc::clone(&usb)))
+        .with_sample_rate(RibbleSampleRate::Low)
+        .with_num_channels(RibbleChannels::Mono);
+
+        assert_eq!(configs.input_device(), Some(&usb));
+        assert_eq!(configs.sample_rate(), RibbleSampleRate::Low);
+        assert_eq!(configs.num_channels(), RibbleChannels::Mono);
+
+        // Back to the system default: its settings come back, the USB device's are stashed.
+        let configs = configs.with_input_device(None);
+        assert_eq!(configs.input_device(), None);
+        assert_eq!(
+            capture_settings(&configs),
+            DeviceCaptureSettings {
+                sample_rate: RibbleSampleRate::High,
+                channel_configs: RibbleChannels::Stereo,
+                period: RibblePeriod::Large,
+            }
+        );
+
+        let configs = configs.with_input_device(Some(Arc::clone(&usb)));
+        assert_eq!(
+            capture_settings(&configs),
+            DeviceCaptureSettings {
+                sample_rate: RibbleSampleRate::Low,
+                channel_configs: RibbleChannels::Mono,
+                period: RibblePeriod::Auto,
+            }
+        );
+        // The current device's settings live in the top-level fields, not in the map.
+        assert!(!configs.device_settings.contains_key("USB Microphone"));
+        assert!(
+            configs
+                .device_settings
+                .contains_key(DEFAULT_INPUT_DEVICE_KEY)
+        );
+    }
+
+    #[test]
+    fn selecting_the_same_device_keeps_the_settings() {
+        let configs = configs_for(
+            RibbleSampleRate::Medium,
+            RibbleChannels::Mono,
+            RibblePeriod::Small,
+        )
+        .with_input_device(Some(Arc::from("Headset")))
+        .with_sample_rate(RibbleSampleRate::Highest)
+        .with_input_device(Some(Arc::from("Headset")));
+
+        assert_eq!(configs.sample_rate(), RibbleSampleRate::Highest);
+        assert_eq!(configs.device_settings.len(), 1);
+    }
+
+    #[test]
+    fn a_device_without_saved_settings_falls_back_to_defaults() {
+        let configs = configs_for(
+            RibbleSampleRate::High,
+            RibbleChannels::Stereo,
+            RibblePeriod::Huge,
+        )
+        .with_input_device(Some(Arc::from("Never seen before")));
+
+        assert_eq!(capture_settings(&configs), DeviceCaptureSettings::default());
+    }
+
+    #[test]
+    fn resetting_keeps_the_device_and_the_other_devices_settings() {
+        let configs = configs_for(
+            RibbleSampleRate::High,
+            RibbleChannels::Stereo,
+            RibblePeriod::Huge,
+        )
+        .with_input_device(Some(Arc::from("Headset")))
+        .with_sample_rate(RibbleSampleRate::Low)
+        .with_default_capture_settings();
+
+        assert_eq!(configs.input_device().map(AsRef::as_ref), Some("Headset"));
+        assert_eq!(capture_settings(&configs), DeviceCaptureSettings::default());
+
+        let configs = configs.with_input_device(None);
+        assert_eq!(configs.sample_rate(), RibbleSampleRate::High);
+        assert_eq!(configs.period(), RibblePeriod::Huge);
+    }
+
+    #[test]
+    fn the_device_settings_map_survives_serialization() {
+        let configs = configs_for(
+            RibbleSampleRate::High,
+            RibbleChannels::Stereo,
+            RibblePeriod::Huge,
+        )
+        .with_input_device(Some(Arc::from("Headset")))
+        .with_num_channels(RibbleChannels::Mono);
+
+        let serialized = ron::to_string(&configs).unwrap();
+        let restored: RibbleRecordingConfigs = ron::from_str(&serialized).unwrap();
+
+        assert_eq!(restored.input_device().map(AsRef::as_ref), Some("Headset"));
+        assert_eq!(restored.num_channels(), RibbleChannels::Mono);
+        assert_eq!(restored.device_settings, configs.device_settings);
+
+        let restored = restored.with_input_device(None);
+        assert_eq!(restored.sample_rate(), RibbleSampleRate::High);
+        assert_eq!(restored.num_channels(), RibbleChannels::Stereo);
+    }
+
+    #[test]
+    fn configs_saved_before_device_selection_use_the_system_default() {
+        let saved = "(sample_rate: Medium, channel_configs: Mono, period: Auto)";
+        let configs: RibbleRecordingConfigs = ron::from_str(saved).unwrap();
+
+        assert_eq!(configs.input_device(), None);
+        assert!(configs.device_settings.is_empty());
+        assert_eq!(configs.sample_rate(), RibbleSampleRate::Medium);
+        assert_eq!(configs.num_channels(), RibbleChannels::Mono);
+    }
+}
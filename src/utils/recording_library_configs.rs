@@ -0,0 +1,59 @@
+pub(crate) const DEFAULT_LIBRARY_KEEP_LAST: usize = 20;
+pub(crate) const DEFAULT_LIBRARY_MAX_SIZE_MB: u64 = 2048;
+pub(crate) const MIN_LIBRARY_MAX_SIZE_MB: u64 = 1;
+
+// When enabled, recordings are kept across restarts (instead of being cleared on exit) and
+// indexed alongside the recordings.
+// The retention rules are applied on startup and whenever a new recording is added; the oldest
+// recordings are removed first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct RecordingLibraryConfigs {
+    enabled: bool,
+    // None: keep every recording.
+    keep_last: Option<usize>,
+    // None: no size limit.
+    max_total_size_mb: Option<u64>,
+}
+
+impl RecordingLibraryConfigs {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            keep_last: Some(DEFAULT_LIBRARY_KEEP_LAST),
+            max_total_size_mb: Some(DEFAULT_LIBRARY_MAX_SIZE_MB),
+        }
+    }
+
+    pub(crate) fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    pub(crate) fn with_keep_last(mut self, keep_last: Option<usize>) -> Self {
+        // Keeping zero recordings would delete every recording as soon as it's written.
+        self.keep_last = keep_last.map(|keep_last| keep_last.max(1));
+        self
+    }
+    pub(crate) fn with_max_total_size_mb(mut self, max_total_size_mb: Option<u64>) -> Self {
+        self.max_total_size_mb = max_total_size_mb.map(|size| size.max(MIN_LIBRARY_MAX_SIZE_MB));
+        self
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub(crate) fn keep_last(&self) -> Option<usize> {
+        self.keep_last
+    }
+    pub(crate) fn max_total_size_mb(&self) -> Option<u64> {
+        self.max_total_size_mb
+    }
+    pub(crate) fn max_total_size_bytes(&self) -> Option<u64> {
+        self.max_total_size_mb.map(|size| size * 1024 * 1024)
+    }
+}
+
+impl Default for RecordingLibraryConfigs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,146 @@
+// Offline resampling of interleaved f32 audio.
+// Shared by the compressed exports (opus only takes a handful of rates) and the file-fed capture
+// backend (which replays a file at whatever rate the capture asks for).
+use crate::utils::errors::RibbleError;
+use rubato::{FftFixedIn, Resampler};
+
+pub(crate) const RESAMPLER_CHUNK_SIZE: usize = 1024;
+
+// Interleaved in, interleaved out.
+pub(crate) fn resample(
+    samples: &[f32],
+    channels: usize,
+    from_rate: usize,
+    to_rate: usize,
+) -> Result<Vec<f32>, RibbleError> {
+    let num_frames = samples.len() / channels;
+    let mut resampler = ChunkResampler::new(channels, from_rate, to_rate, num_frames)?;
+    let mut output = Vec::with_capacity(resampler.output_len() * channels);
+
+    let mut chunks = samples[..num_frames * channels].chunks_exact(RESAMPLER_CHUNK_SIZE * channels);
+    for chunk in chunks.by_ref() {
+        resampler.process(chunk, &mut output)?;
+    }
+    // The remainder (even if it's empty) is the last chunk.
+    resampler.process(chunks.remainder(), &mut output)?;
+    Ok(output)
+}
+
+// Resamples interleaved audio a chunk (RESAMPLER_CHUNK_SIZE frames) at a time.
+// The output is trimmed to line up with the input: the resampler's delay is dropped from the front,
+// and the output stops (or is padded out) at the resampled length of the input.
+pub(crate) struct ChunkResampler {
+    resampler: FftFixedIn<f32>,
+    channels: usize,
+    deinterleaved: Vec<Vec<f32>>,
+    num_to_skip: usize,
+    num_to_keep: usize,
+    output_len: usize,
+}
+
+impl ChunkResampler {
+    pub(crate) fn new(
+        channels: usize,
+        from_rate: usize,
+        to_rate: usize,
+        num_input_frames: usize,
+    ) -> Result<Self, RibbleError> {
+        let resampler =
+            FftFixedIn::<f32>::new(from_rate, to_rate, RESAMPLER_CHUNK_SIZE, 2, channels)
+                .map_err(|e| resampler_error(&e))?;
+        let output_len = (num_input_frames as u64 * to_rate as u64 / from_rate as u64) as usize;
+        Ok(Self {
+            num_to_skip: resampler.output_delay(),
+            resampler,
+            channels,
+            deinterleaved: vec![Vec::with_capacity(RESAMPLER_CHUNK_SIZE); channels],
+            num_to_keep: output_len,
+            output_len,
+        })
+    }
+
+    // The number of (output) frames the whole input resamples to.
+    pub(crate) fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    // Anything shorter than a whole chunk is the last chunk; it also flushes the delay line.
+    pub(crate) fn process(
+        &mut self,
+        interleaved: &[f32],
+        output: &mut Vec<f32>,
+    ) -> Result<(), RibbleError> {
+        for (channel, samples) in self.deinterleaved.iter_mut().enumerate() {
+            samples.clear();
+            samples.extend(interleaved.iter().skip(channel).step_by(self.channels));
+        }
+
+        if interleaved.len() == RESAMPLER_CHUNK_SIZE * self.channels {
+            let resampled = self
+                .resampler
+                .process(self.deinterleaved.as_slice(), None)
+                .map_err(|e| resampler_error(&e))?;
+            self.push_output(&resampled, output);
+            return Ok(());
+        }
+
+        let resampled = self
+            .resampler
+            .process_partial(Some(self.deinterleaved.as_slice()), None)
+            .map_err(|e| resampler_error(&e))?;
+        self.push_output(&resampled, output);
+        let flushed = self
+            .resampler
+            .process_partial::<&[f32]>(None, None)
+            .map_err(|e| resampler_error(&e))?;
+        self.push_output(&flushed, output);
+
+        // Anything the resampler came up short on is padded out with silence.
+        output.resize(output.len() + self.num_to_keep * self.channels, 0.0);
+        self.num_to_keep = 0;
+        Ok(())
+    }
+
+    fn push_output(&mut self, resampled: &[Vec<f32>], output: &mut Vec<f32>) {
+        let num_frames = resampled.first().map_or(0, |channel| channel.len());
+        let skipped = self.num_to_skip.min(num_frames);
+        self.num_to_skip -= skipped;
+        let kept = self.num_to_keep.min(num_frames - skipped);
+        self.num_to_keep -= kept;
+
+        for frame in skipped..skipped + kept {
+            output.extend(resampled.iter().map(|channel| channel[frame]));
+        }
+    }
+}
+
+fn resampler_error(e: &dyn std::error::Error) -> RibbleError {
+    RibbleError::Core(format!("Failed to resample audio: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(num_frames: usize, channels: usize, sample_rate: usize) -> Vec<f32> {
+        (0..num_frames)
+            .flat_map(|frame| {
+                let t = frame as f32 / sample_rate as f32;
+                (0..channels).map(move |channel| {
+                    0.5 * (std::f32::consts::TAU * (440.0 + 110.0 * channel as f32) * t).sin()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resample_keeps_the_duration() {
+        let samples = sine(44100 + 17, 2, 44100);
+        let resampled = resample(&samples, 2, 44100, 16000).unwrap();
+        assert_eq!(resampled.len(), (44117 * 16000 / 44100) * 2);
+        let peak = resampled
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((0.4..=0.6).contains(&peak), "peak: {peak}");
+    }
+}
@@ -0,0 +1,681 @@
+use crate::controller::transcript::{Transcript, TranscriptionRunInfo, TranscriptionSource};
+use crate::controller::ModelFile;
+use crate::utils::audio_gain::AudioGainConfigs;
+use crate::utils::errors::RibbleError;
+use crate::utils::noise_suppression::NoiseSuppressionConfigs;
+use crate::utils::vad_configs::VadConfigs;
+use ribble_whisper::whisper::configs::WhisperRealtimeConfigs;
+use ribble_whisper::whisper::model::ModelId;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use strum::{AsRefStr, Display, EnumIter, EnumString, IntoStaticStr};
+
+pub(crate) const MIN_SUBTITLE_LINE_LENGTH: usize = 16;
+pub(crate) const MAX_SUBTITLE_LINE_LENGTH: usize = 80;
+pub(crate) const MIN_SUBTITLE_LINES_PER_CUE: usize = 1;
+pub(crate) const MAX_SUBTITLE_LINES_PER_CUE: usize = 4;
+// These are in seconds
+pub(crate) const MIN_SUBTITLE_CUE_DURATION: f32 = 1.0;
+pub(crate) const MAX_SUBTITLE_CUE_DURATION: f32 = 15.0;
+
+#[derive(
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    EnumString,
+    Display,
+    EnumIter,
+    AsRefStr,
+    IntoStaticStr,
+)]
+pub(crate) enum TranscriptFormat {
+    #[default]
+    #[strum(to_string = "Plain Text")]
+    PlainText,
+    #[strum(to_string = "SRT")]
+    Srt,
+    #[strum(to_string = "WebVTT")]
+    WebVtt,
+    #[strum(to_string = "JSON")]
+    Json,
+}
+
+impl TranscriptFormat {
+    pub(crate) fn tooltip(&self) -> &str {
+        match self {
+            TranscriptFormat::PlainText => "The transcription as plain text, without timestamps.",
+            TranscriptFormat::Srt => "SubRip subtitles. Supported by most video players/editors.",
+            TranscriptFormat::WebVtt => "WebVTT subtitles. Used for HTML5 video.",
+            TranscriptFormat::Json => {
+                "Timed segments, along with the model and settings used to produce them."
+            }
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::PlainText => "txt",
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::WebVtt => "vtt",
+            TranscriptFormat::Json => "json",
+        }
+    }
+
+    // Returns None if the path doesn't have a known transcript extension.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    // Case-insensitive, without the leading dot; e.g. "srt", "VTT".
+    pub(crate) fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "txt" => Some(TranscriptFormat::PlainText),
+            "srt" => Some(TranscriptFormat::Srt),
+            "vtt" => Some(TranscriptFormat::WebVtt),
+            "json" => Some(TranscriptFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct SubtitleConfigs {
+    max_line_length: usize,
+    max_lines_per_cue: usize,
+    // In seconds
+    max_cue_duration: f32,
+}
+
+impl SubtitleConfigs {
+    // Roughly the broadcast standard: 42 characters, 2 lines, ~7 seconds on screen.
+    const DEFAULT_LINE_LENGTH: usize = 42;
+    const DEFAULT_LINES_PER_CUE: usize = 2;
+    const DEFAULT_CUE_DURATION: f32 = 7.0;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            max_line_length: Self::DEFAULT_LINE_LENGTH,
+            max_lines_per_cue: Self::DEFAULT_LINES_PER_CUE,
+            max_cue_duration: Self::DEFAULT_CUE_DURATION,
+        }
+    }
+
+    pub(crate) fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length =
+            max_line_length.clamp(MIN_SUBTITLE_LINE_LENGTH, MAX_SUBTITLE_LINE_LENGTH);
+        self
+    }
+    pub(crate) fn with_max_lines_per_cue(mut self, max_lines_per_cue: usize) -> Self {
+        self.max_lines_per_cue =
+            max_lines_per_cue.clamp(MIN_SUBTITLE_LINES_PER_CUE, MAX_SUBTITLE_LINES_PER_CUE);
+        self
+    }
+    pub(crate) fn with_max_cue_duration(mut self, max_cue_duration: f32) -> Self {
+        self.max_cue_duration =
+            max_cue_duration.clamp(MIN_SUBTITLE_CUE_DURATION, MAX_SUBTITLE_CUE_DURATION);
+        self
+    }
+
+    pub(crate) fn max_line_length(&self) -> usize {
+        self.max_line_length
+    }
+    pub(crate) fn max_lines_per_cue(&self) -> usize {
+        self.max_lines_per_cue
+    }
+    pub(crate) fn max_cue_duration(&self) -> f32 {
+        self.max_cue_duration
+    }
+}
+
+impl Default for SubtitleConfigs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct SubtitleCue {
+    start: Duration,
+    end: Duration,
+    lines: Vec<String>,
+}
+
+pub(crate) fn format_srt(transcript: &Transcript, configs: &SubtitleConfigs) -> String {
+    let mut out = String::new();
+    for (index, cue) in build_cues(transcript, configs).iter().enumerate() {
+        // NOTE: writing into a String is infallible.
+        let _ = writeln!(out, "{}", index + 1);
+        let _ = writeln!(
+            out,
+            "{} --> {}",
+            format_timestamp(cue.start, ','),
+            format_timestamp(cue.end, ',')
+        );
+        for line in cue.lines.iter() {
+            let _ = writeln!(out, "{line}");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) fn format_webvtt(transcript: &Transcript, configs: &SubtitleConfigs) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in build_cues(transcript, configs).iter() {
+        let _ = writeln!(
+            out,
+            "{} --> {}",
+            format_timestamp(cue.start, '.'),
+            format_timestamp(cue.end, '.')
+        );
+        for line in cue.lines.iter() {
+            // "-->" is not allowed in a WebVTT cue payload.
+            let _ = writeln!(out, "{}", line.replace("-->", "->"));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Bump this whenever the JSON layout changes in a way that could break consumers.
+const JSON_TRANSCRIPT_VERSION: u32 = 1;
+
+#[derive(serde::Serialize)]
+struct JsonTranscript<'a> {
+    version: u32,
+    app_version: &'static str,
+    created_at: String,
+    started_at: Option<String>,
+    source: &'static str,
+    source_audio_path: Option<&'a Path>,
+    model: Option<JsonModel<'a>>,
+    settings: Option<JsonSettings<'a>>,
+    // Whether the segment timestamps are estimated (real-time), rather than whisper's own.
+    // See: controller::transcript
+    estimated_timestamps: bool,
+    duration_secs: f64,
+    text: &'a str,
+    segments: Vec<JsonSegment<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonModel<'a> {
+    id: ModelId,
+    file_name: Option<&'a str>,
+    packed: bool,
+}
+
+#[derive(serde::Serialize)]
+struct JsonSettings<'a> {
+    transcription: &'a WhisperRealtimeConfigs,
+    vad: &'a VadConfigs,
+    audio_gain: &'a AudioGainConfigs,
+    noise_suppression: &'a NoiseSuppressionConfigs,
+}
+
+#[derive(serde::Serialize)]
+struct JsonSegment<'a> {
+    start_secs: f64,
+    end_secs: f64,
+    text: &'a str,
+    confirmed: bool,
+}
+
+// NOTE: the model file is resolved by the caller, since only the model bank can map the model id
+// in the run info to a file.
+pub(crate) fn format_json(
+    transcript: &Transcript,
+    text: &str,
+    run_info: Option<&TranscriptionRunInfo>,
+    model_file: Option<&ModelFile>,
+) -> Result<String, RibbleError> {
+    let (source, source_audio_path) = match run_info.map(|info| info.source()) {
+        Some(TranscriptionSource::Microphone) => ("microphone", None),
+        Some(TranscriptionSource::AudioFile(path)) => ("audio_file", Some(path.as_path())),
+        None => ("unknown", None),
+    };
+
+    let model = run_info
+        .and_then(|info| info.model_id())
+        .map(|id| JsonModel {
+            id,
+            file_name: model_file.map(|file| file.file_name()),
+            packed: model_file.is_some_and(|file| file.is_packed()),
+        });
+
+    let settings = run_info.map(|info| JsonSettings {
+        transcription: info.transcription_configs(),
+        vad: info.vad_configs(),
+        audio_gain: info.audio_gain_configs(),
+        noise_suppression: info.noise_suppression_configs(),
+    });
+
+    let segments = transcript
+        .segments()
+        .iter()
+        .filter(|segment| !segment.text().trim().is_empty())
+        .map(|segment| JsonSegment {
+            start_secs: segment.start().as_secs_f64(),
+            end_secs: segment.end().as_secs_f64(),
+            text: segment.text().trim(),
+            confirmed: segment.is_confirmed(),
+        })
+        .collect();
+
+    let json_transcript = JsonTranscript {
+        version: JSON_TRANSCRIPT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION"),
+        created_at: format_rfc3339(SystemTime::now()),
+        started_at: run_info.map(|info| format_rfc3339(info.started_at())),
+        source,
+        source_audio_path,
+        model,
+        settings,
+        estimated_timestamps: !transcript.has_whisper_timestamps(),
+        duration_secs: transcript.duration().as_secs_f64(),
+        text: text.trim(),
+        segments,
+    };
+
+    Ok(serde_json::to_string_pretty(&json_transcript)?)
+}
+
+// YYYY-MM-DDTHH:MM:SSZ (UTC); times before the unix epoch are clamped to the epoch.
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let total_secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let days = total_secs.div_euclid(86_400);
+    let secs_of_day = total_secs.rem_euclid(86_400);
+
+    // Civil-from-days: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let hours = secs_of_day / 3600;
+    let minutes = (secs_of_day / 60) % 60;
+    let seconds = secs_of_day % 60;
+    format!("{year:04}-{month:02}-{day:02}T{hours:02}:{minutes:02}:{seconds:02}Z")
+}
+
+// HH:MM:SS<sep>mmm; SRT uses a comma, WebVTT uses a period.
+fn format_timestamp(timestamp: Duration, millis_separator: char) -> String {
+    let total_millis = timestamp.as_millis();
+    let millis = total_millis % 1000;
+    let total_secs = total_millis / 1000;
+    let seconds = total_secs % 60;
+    let minutes = (total_secs / 60) % 60;
+    let hours = total_secs / 3600;
+    format!("{hours:02}:{minutes:02}:{seconds:02}{millis_separator}{millis:03}")
+}
+
+// Anything shorter flashes by too quickly to read; this is 20 frames at 24 fps.
+const MIN_CUE_DURATION: Duration = Duration::from_millis(833);
+
+// Splits each transcript segment into cues that respect the line-length, line-count and
+// duration limits. Time within a segment is divided proportionally by character count.
+fn build_cues(transcript: &Transcript, configs: &SubtitleConfigs) -> Vec<SubtitleCue> {
+    let max_cue_duration = Duration::from_secs_f32(configs.max_cue_duration());
+    let max_line_length = configs.max_line_length();
+    let max_cue_chars = max_line_length * configs.max_lines_per_cue();
+    let mut cues: Vec<SubtitleCue> = vec![];
+
+    for segment in transcript.segments() {
+        let words: Vec<&str> = segment.text().split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let word_lengths: Vec<usize> = words.iter().map(|word| word.chars().count()).collect();
+
+        // Each word carries its trailing space.
+        let total_chars = word_lengths.iter().map(|length| length + 1).sum::<usize>();
+        // If the segment is too long to show in a single cue, spread the text so that each cue
+        // carries about the same amount.
+        let num_timed_cues = (segment.duration().as_secs_f64() / max_cue_duration.as_secs_f64())
+            .ceil()
+            .max(1.0) as usize;
+        let target_cue_chars = total_chars.div_ceil(num_timed_cues).min(max_cue_chars);
+
+        // Chunks are (start, end) word index ranges, along with their character weight.
+        // NOTE: this tracks the greedy wrap of the current chunk as it grows (only the last line
+        // can change when a word is appended), so that it matches wrap_words() without
+        // re-wrapping the chunk for every word.
+        let mut chunks: Vec<(usize, usize, usize)> = vec![];
+        let mut chunk_start = 0;
+        let mut chunk_chars = 0;
+        let mut num_lines = 0;
+        let mut last_line_length = 0;
+        for (index, &length) in word_lengths.iter().enumerate() {
+            let (candidate_lines, candidate_line_length) = if num_lines == 0 {
+                (1, length)
+            } else if last_line_length + 1 + length > max_line_length {
+                (num_lines + 1, length)
+            } else {
+                (num_lines, last_line_length + 1 + length)
+            };
+            let fits = candidate_lines <= configs.max_lines_per_cue()
+                && chunk_chars + length + 1 <= target_cue_chars;
+
+            if fits || index == chunk_start {
+                num_lines = candidate_lines;
+                last_line_length = candidate_line_length;
+            } else {
+                chunks.push((chunk_start, index, chunk_chars));
+                chunk_start = index;
+                chunk_chars = 0;
+                num_lines = 1;
+                last_line_length = length;
+            }
+            chunk_chars += length + 1;
+        }
+        chunks.push((chunk_start, words.len(), chunk_chars));
+
+        let span = segment.duration();
+        let mut accumulated = 0usize;
+        let mut cue_start = segment.start();
+        for (start, end, weight) in chunks {
+            accumulated += weight;
+            let cue_end =
+                segment.start() + span.mul_f64(accumulated as f64 / total_chars.max(1) as f64);
+            cues.push(SubtitleCue {
+                start: cue_start,
+                end: cue_end.min(cue_start + max_cue_duration),
+                lines: wrap_words(&words[start..end], max_line_length),
+            });
+            cue_start = cue_end;
+        }
+    }
+
+    // Stretch cues that are too short to read, pushing the following cues back as needed so
+    // that they never overlap.
+    let mut previous_end = Duration::ZERO;
+    for cue in cues.iter_mut() {
+        cue.start = cue.start.max(previous_end);
+        cue.end = cue.end.max(cue.start + MIN_CUE_DURATION);
+        previous_end = cue.end;
+    }
+    cues
+}
+
+// Greedy word-wrap. Words longer than the line get a line to themselves.
+fn wrap_words(words: &[&str], max_line_length: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in words {
+        let line_chars = line.chars().count();
+        if !line.is_empty() && line_chars + 1 + word.chars().count() > max_line_length {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::transcript::{SegmentState, TranscriptSegment};
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn transcript(segments: &[(f64, f64, &str)]) -> Transcript {
+        Transcript::from_whisper_segments(
+            segments
+                .iter()
+                .map(|&(start, end, text)| {
+                    TranscriptSegment::new(secs(start), secs(end), text, SegmentState::Confirmed)
+                })
+                .collect(),
+        )
+    }
+
+    fn single_line_configs() -> SubtitleConfigs {
+        SubtitleConfigs::new()
+            .with_max_line_length(16)
+            .with_max_lines_per_cue(1)
+    }
+
+    #[test]
+    fn rfc3339_timestamps() {
+        let at = |secs: u64| format_rfc3339(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        // Leap day, and a century that isn't a leap year.
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(4_107_542_400), "2100-03-01T00:00:00Z");
+        assert_eq!(at(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(at(253_402_300_799), "9999-12-31T23:59:59Z");
+        // Sub-second precision is dropped, and times before the epoch are clamped.
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH + Duration::from_millis(1_999)),
+            "1970-01-01T00:00:01Z"
+        );
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH - Duration::from_secs(86_400)),
+            "1970-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn subtitle_timestamps() {
+        let millis = |millis: u64| Duration::from_millis(millis);
+        assert_eq!(format_timestamp(Duration::ZERO, ','), "00:00:00,000");
+        assert_eq!(format_timestamp(millis(999), ','), "00:00:00,999");
+        assert_eq!(format_timestamp(millis(1_000), ','), "00:00:01,000");
+        assert_eq!(format_timestamp(millis(59_999), '.'), "00:00:59.999");
+        assert_eq!(format_timestamp(millis(60_000), '.'), "00:01:00.000");
+        assert_eq!(format_timestamp(millis(3_599_999), ','), "00:59:59,999");
+        assert_eq!(format_timestamp(millis(3_600_000), ','), "01:00:00,000");
+        // Hours don't wrap.
+        assert_eq!(format_timestamp(millis(360_000_000), ','), "100:00:00,000");
+        assert_eq!(
+            format_timestamp(millis(3_599_999_999), '.'),
+            "999:59:59.999"
+        );
+        // Sub-millisecond precision is truncated, not rounded.
+        assert_eq!(
+            format_timestamp(Duration::from_micros(1_999), ','),
+            "00:00:00,001"
+        );
+    }
+
+    #[test]
+    fn srt_golden() {
+        let transcript = transcript(&[
+            (0.0, 1.5, " Hello there. "),
+            (2.0, 2.0, "   "),
+            (3599.5, 3601.25, "General Kenobi."),
+        ]);
+        let expected = "\
+1
+00:00:00,000 --> 00:00:01,500
+Hello there.
+
+2
+00:59:59,500 --> 01:00:01,250
+General Kenobi.
+
+";
+        assert_eq!(format_srt(&transcript, &SubtitleConfigs::new()), expected);
+    }
+
+    #[test]
+    fn webvtt_golden() {
+        let transcript = transcript(&[(0.0, 1.5, "Hello there."), (3599.5, 3601.25, "A --> B")]);
+        let expected = "\
+WEBVTT
+
+00:00:00.000 --> 00:00:01.500
+Hello there.
+
+00:59:59.500 --> 01:00:01.250
+A -> B
+
+";
+        assert_eq!(
+            format_webvtt(&transcript, &SubtitleConfigs::new()),
+            expected
+        );
+    }
+
+    #[test]
+    fn long_segments_are_split_by_character_count() {
+        let transcript = transcript(&[(0.0, 6.0, "The quick brown fox jumps over the lazy dog")]);
+        let expected = "\
+1
+00:00:00,000 --> 00:00:02,181
+The quick brown
+
+2
+00:00:02,181 --> 00:00:04,227
+fox jumps over
+
+3
+00:00:04,227 --> 00:00:06,000
+the lazy dog
+
+";
+        assert_eq!(format_srt(&transcript, &single_line_configs()), expected);
+    }
+
+    #[test]
+    fn cues_wrap_onto_multiple_lines() {
+        let transcript = transcript(&[(0.0, 6.0, "The quick brown fox jumps over the lazy dog")]);
+        let configs = single_line_configs().with_max_lines_per_cue(2);
+        let cues = build_cues(&transcript, &configs);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].lines, ["The quick brown", "fox jumps over"]);
+        assert_eq!(cues[1].lines, ["the lazy dog"]);
+    }
+
+    #[test]
+    fn cues_respect_the_maximum_duration() {
+        let transcript = transcript(&[(0.0, 20.0, "Short.")]);
+        let cues = build_cues(&transcript, &SubtitleConfigs::new());
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].start, Duration::ZERO);
+        assert_eq!(cues[0].end, secs(7.0));
+    }
+
+    #[test]
+    fn short_cues_are_stretched_without_overlapping() {
+        let transcript = transcript(&[(1.0, 1.0, "Hi."), (1.0, 1.2, "There."), (5.0, 6.0, "Bye.")]);
+        let cues = build_cues(&transcript, &SubtitleConfigs::new());
+        assert_eq!(cues.len(), 3);
+        assert_eq!(
+            (cues[0].start, cues[0].end),
+            (secs(1.0), secs(1.0) + MIN_CUE_DURATION)
+        );
+        assert_eq!(
+            (cues[1].start, cues[1].end),
+            (cues[0].end, cues[0].end + MIN_CUE_DURATION)
+        );
+        // There's room before the last cue, so it's left alone.
+        assert_eq!((cues[2].start, cues[2].end), (secs(5.0), secs(6.0)));
+    }
+
+    #[test]
+    fn line_wrapping() {
+        assert_eq!(wrap_words(&["aaa", "bbb", "ccc"], 7), ["aaa bbb", "ccc"]);
+        assert_eq!(wrap_words(&["aaa", "bbb", "ccc"], 11), ["aaa bbb ccc"]);
+        // Overlong words get a line to themselves.
+        assert_eq!(
+            wrap_words(&["a", "abcdefghijk", "b"], 5),
+            ["a", "abcdefghijk", "b"]
+        );
+        // Lengths are in characters, not bytes.
+        assert_eq!(wrap_words(&["ééé", "ààà"], 7), ["ééé ààà"]);
+        assert!(wrap_words(&[], 7).is_empty());
+    }
+
+    #[test]
+    fn long_words_are_never_dropped() {
+        let text = "a supercalifragilisticexpialidocious word";
+        let transcript = transcript(&[(0.0, 3.0, text)]);
+        let cues = build_cues(&transcript, &single_line_configs());
+        let lines: Vec<&str> = cues
+            .iter()
+            .flat_map(|cue| cue.lines.iter())
+            .map(String::as_str)
+            .collect();
+        assert_eq!(lines.join(" "), text);
+        assert!(cues.iter().all(|cue| cue.lines.len() == 1));
+    }
+
+    #[test]
+    fn json_shape() {
+        let transcript = transcript(&[
+            (0.0, 1.5, " Hello there. "),
+            (1.5, 1.5, " "),
+            (2.0, 3.25, "Bye."),
+        ]);
+        let json = format_json(&transcript, " Hello there. Bye. ", None, None).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        let mut keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut expected_keys = [
+            "version",
+            "app_version",
+            "created_at",
+            "started_at",
+            "source",
+            "source_audio_path",
+            "model",
+            "settings",
+            "estimated_timestamps",
+            "duration_secs",
+            "text",
+            "segments",
+        ];
+        expected_keys.sort_unstable();
+        keys.sort_unstable();
+        assert_eq!(keys, expected_keys);
+
+        assert_eq!(json["version"], JSON_TRANSCRIPT_VERSION);
+        assert_eq!(json["app_version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["created_at"].as_str().unwrap().ends_with('Z'));
+        assert!(json["started_at"].is_null());
+        assert_eq!(json["source"], "unknown");
+        assert!(json["source_audio_path"].is_null());
+        assert!(json["model"].is_null());
+        assert!(json["settings"].is_null());
+        assert_eq!(json["estimated_timestamps"], false);
+        assert_eq!(json["duration_secs"], 3.25);
+        assert_eq!(json["text"], "Hello there. Bye.");
+
+        // Blank segments are left out, and the rest are trimmed.
+        assert_eq!(
+            json["segments"],
+            serde_json::json!([
+                {"start_secs": 0.0, "end_secs": 1.5, "text": "Hello there.", "confirmed": true},
+                {"start_secs": 2.0, "end_secs": 3.25, "text": "Bye.", "confirmed": true},
+            ])
+        );
+    }
+}
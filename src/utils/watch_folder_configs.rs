@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct WatchFolderConfigs {
+    enabled: bool,
+    inbox_directory: Option<PathBuf>,
+    // If this is None, transcripts are written next to their audio files (in the inbox).
+    outbox_directory: Option<PathBuf>,
+    // Native file-system events are not delivered for most network shares (SMB/NFS);
+    // polling is slower, but it works everywhere.
+    use_polling: bool,
+}
+
+impl WatchFolderConfigs {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    pub(crate) fn with_inbox_directory(mut self, inbox_directory: Option<PathBuf>) -> Self {
+        self.inbox_directory = inbox_directory;
+        self
+    }
+    pub(crate) fn with_outbox_directory(mut self, outbox_directory: Option<PathBuf>) -> Self {
+        self.outbox_directory = outbox_directory;
+        self
+    }
+    pub(crate) fn with_use_polling(mut self, use_polling: bool) -> Self {
+        self.use_polling = use_polling;
+        self
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub(crate) fn inbox_directory(&self) -> Option<&Path> {
+        self.inbox_directory.as_deref()
+    }
+    pub(crate) fn outbox_directory(&self) -> Option<&Path> {
+        self.outbox_directory.as_deref()
+    }
+    pub(crate) fn use_polling(&self) -> bool {
+        self.use_polling
+    }
+
+    // Only the inbox, polling and enabled flag affect the watcher itself; the outbox is read
+    // whenever files get queued.
+    pub(crate) fn needs_restart(&self, other: &Self) -> bool {
+        self.enabled != other.enabled
+            || self.inbox_directory != other.inbox_directory
+            || self.use_polling != other.use_polling
+    }
+}